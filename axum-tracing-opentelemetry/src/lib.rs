@@ -7,6 +7,7 @@
 
 #[allow(deprecated)]
 pub mod middleware;
+pub mod prelude;
 
 /// for basic backward compatibility and transition
 #[allow(deprecated)]
@@ -1,5 +1,13 @@
+mod otel_context;
 mod response_injector;
+mod rpc;
+mod tenant;
 mod trace_extractor;
+mod ws;
 
+pub use otel_context::CurrentOtelContext;
 pub use response_injector::*;
+pub use rpc::*;
+pub use tenant::{Enduser, EnduserExtractor, TenantInfo};
 pub use trace_extractor::*;
+pub use ws::{ws_message_span, WsDirection};
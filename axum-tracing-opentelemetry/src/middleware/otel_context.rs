@@ -0,0 +1,42 @@
+use axum::extract::FromRequestParts;
+use http::request::Parts;
+use std::convert::Infallible;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// The `OpenTelemetry` context of the request (with the span created by the [`super::OtelAxumLayer`] as active span),
+/// for the otel-native libraries (that don't use tracing).
+///
+/// It's inserted into the extensions of the request by the layer configured with
+/// [`super::OtelAxumLayer::with_context_extension`]. When used as extractor without the extension
+/// (eg the route is filtered), the context of the current tracing's span is returned.
+///
+/// ```
+/// use axum::{routing::get, Router};
+/// use axum_tracing_opentelemetry::middleware::{CurrentOtelContext, OtelAxumLayer};
+/// use opentelemetry::trace::TraceContextExt;
+///
+/// async fn handler(CurrentOtelContext(cx): CurrentOtelContext) -> String {
+///     cx.span().span_context().trace_id().to_string()
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/", get(handler))
+///     .layer(OtelAxumLayer::default().with_context_extension(true));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CurrentOtelContext(pub opentelemetry::Context);
+
+impl<S> FromRequestParts<S> for CurrentOtelContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<CurrentOtelContext>()
+            .cloned()
+            .unwrap_or_else(|| CurrentOtelContext(tracing::Span::current().context())))
+    }
+}
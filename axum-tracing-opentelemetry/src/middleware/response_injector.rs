@@ -1,5 +1,8 @@
 use futures_core::future::BoxFuture;
 use http::{Request, Response};
+use opentelemetry::propagation::{TextMapCompositePropagator, TextMapPropagator};
+use std::fmt;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use tower::{Layer, Service};
 use tracing_opentelemetry_instrumentation_sdk as otel;
@@ -11,23 +14,66 @@ use tracing_opentelemetry_instrumentation_sdk::http as otel_http;
 )]
 #[must_use]
 pub fn response_with_trace_layer() -> OtelInResponseLayer {
-    OtelInResponseLayer {}
+    OtelInResponseLayer::default()
 }
 
+/// layer/middleware for axum to inject the trace context of the current span into the headers of the response,
+/// with the global propagator (default) or with the propagators defined by [`OtelInResponseLayer::with_propagators`].
 #[derive(Default, Debug, Clone)]
-pub struct OtelInResponseLayer;
+pub struct OtelInResponseLayer {
+    propagator: Option<ResponsePropagator>,
+}
+
+impl OtelInResponseLayer {
+    /// Inject with the `propagators` (combined) instead of the global propagator (used to extract the context of
+    /// the requests), eg to extract W3C & B3 but to inject only B3 for legacy callers.
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::OtelInResponseLayer;
+    /// use opentelemetry_sdk::propagation::TraceContextPropagator;
+    ///
+    /// let layer = OtelInResponseLayer::default()
+    ///     .with_propagators(vec![Box::new(TraceContextPropagator::new())]);
+    /// ```
+    #[must_use]
+    pub fn with_propagators(
+        self,
+        propagators: Vec<Box<dyn TextMapPropagator + Send + Sync>>,
+    ) -> Self {
+        OtelInResponseLayer {
+            propagator: Some(ResponsePropagator(Arc::new(
+                TextMapCompositePropagator::new(propagators),
+            ))),
+        }
+    }
+}
 
 impl<S> Layer<S> for OtelInResponseLayer {
     type Service = OtelInResponseService<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        OtelInResponseService { inner }
+        OtelInResponseService {
+            inner,
+            propagator: self.propagator.clone(),
+        }
     }
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct OtelInResponseService<S> {
     inner: S,
+    propagator: Option<ResponsePropagator>,
+}
+
+#[derive(Clone)]
+struct ResponsePropagator(Arc<TextMapCompositePropagator>);
+
+impl fmt::Debug for ResponsePropagator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ResponsePropagator")
+            .field(&self.0.fields().collect::<Vec<_>>())
+            .finish()
+    }
 }
 
 impl<S, B, B2> Service<Request<B>> for OtelInResponseService<S>
@@ -47,12 +93,83 @@ where
     #[allow(unused_mut)]
     fn call(&mut self, mut request: Request<B>) -> Self::Future {
         let future = self.inner.call(request);
+        let propagator = self.propagator.clone();
 
         Box::pin(async move {
             let mut response = future.await?;
             // inject the trace context into the response (optional but useful for debugging and client)
-            otel_http::inject_context(&otel::find_current_context(), response.headers_mut());
+            let context = otel::find_current_context();
+            match propagator {
+                Some(ResponsePropagator(propagator)) => otel_http::inject_context_with_propagator(
+                    propagator.as_ref(),
+                    &context,
+                    response.headers_mut(),
+                ),
+                None => otel_http::inject_context(&context, response.headers_mut()),
+            }
             Ok(response)
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::OtelAxumLayer;
+    use axum::{body::Body, routing::get, Router};
+    use http::StatusCode;
+    use rstest::rstest;
+    use testing_tracing_opentelemetry::FakeEnvironment;
+
+    const TRACEPARENT: &str = "00-b2611246a58fd7ea623d2264c5a1e226-b2c9b811f2f424af-01";
+
+    #[rstest]
+    #[case::global(None, "traceparent", &["b3", "uber-trace-id"])]
+    #[case::b3(
+        Some(Box::new(opentelemetry_zipkin::Propagator::with_encoding(
+            opentelemetry_zipkin::B3Encoding::SingleHeader
+        )) as Box<dyn TextMapPropagator + Send + Sync>),
+        "b3",
+        &["traceparent", "uber-trace-id"]
+    )]
+    #[case::jaeger(
+        Some(Box::new(opentelemetry_jaeger_propagator::Propagator::new()) as Box<dyn TextMapPropagator + Send + Sync>),
+        "uber-trace-id",
+        &["traceparent", "b3"]
+    )]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn inject_into_response_with_propagators(
+        #[case] propagator: Option<Box<dyn TextMapPropagator + Send + Sync>>,
+        #[case] expected_header: &str,
+        #[case] unexpected_headers: &[&str],
+    ) {
+        let _fake_env = FakeEnvironment::setup().await;
+        let layer = match propagator {
+            Some(propagator) => OtelInResponseLayer::default().with_propagators(vec![propagator]),
+            None => OtelInResponseLayer::default(),
+        };
+        let mut svc = Router::new()
+            .route("/", get(|| async { StatusCode::OK }))
+            .layer(layer)
+            .layer(OtelAxumLayer::default());
+        let req = Request::builder()
+            .uri("/")
+            .header("traceparent", TRACEPARENT)
+            .body(Body::empty())
+            .unwrap();
+        let res = svc.call(req).await.unwrap();
+
+        let value = res
+            .headers()
+            .get(expected_header)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        assert!(
+            value.contains("b2611246a58fd7ea623d2264c5a1e226"),
+            "{expected_header}: {value}"
+        );
+        for header in unexpected_headers {
+            assert!(res.headers().get(*header).is_none(), "{header}");
+        }
+    }
+}
@@ -0,0 +1,439 @@
+//! Layer for RPC over http (JSON-RPC, Twirp) exposed by axum.
+//!
+//! The generic http span names everything `POST /twirp/...` or `POST /rpc` without status semantics,
+//! [`OtelRpcLayer`] names the span `{service}/{method}`, defines `rpc.system`
+//! and records the application-level errors from the JSON responses.
+
+use axum::body::{Body, Bytes};
+use futures_core::future::BoxFuture;
+use futures_util::StreamExt;
+use http::{Request, Response, StatusCode};
+use http_body::Body as _;
+use serde_json::Value;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_opentelemetry_instrumentation_sdk::http as otel_http;
+use tracing_opentelemetry_instrumentation_sdk::{
+    truncate_attribute_value, PrivacyMode, PrivacyPolicy,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcFlavor {
+    /// [JSON-RPC 2.0](https://www.jsonrpc.org/specification): the method is read from the request body,
+    /// the request & response bodies are buffered (up to the limit of [`OtelRpcLayer::with_max_body_size`]).
+    JsonRpc,
+    /// [Twirp](https://twitchtv.github.io/twirp/docs/spec_v7.html): the service & method are read from the url
+    /// (`/twirp/<package>.<Service>/<Method>`), the response body is only buffered on error.
+    Twirp,
+}
+
+impl RpcFlavor {
+    fn rpc_system(self) -> &'static str {
+        match self {
+            RpcFlavor::JsonRpc => "jsonrpc",
+            RpcFlavor::Twirp => "twirp",
+        }
+    }
+}
+
+/// layer/middleware for axum, like [`super::OtelAxumLayer`] but for RPC over http:
+///
+/// - propagate `OpenTelemetry` context (`trace_id`,...) to server
+/// - create a Span (`SpanKind::Server`) named `{service}/{method}` with `rpc.system`, `rpc.service`, `rpc.method`
+/// - record the errors from the JSON response (`rpc.jsonrpc.error_code`, `rpc.twirp.error_code`, `exception.message`)
+///
+/// The bodies larger than [`DEFAULT_MAX_RPC_BODY_SIZE`] (see [`OtelRpcLayer::with_max_body_size`]) are not parsed,
+/// they are streamed untouched (the span keeps the generic name & attributes).
+///
+/// ```
+/// use axum::{Router, routing::post};
+/// use axum_tracing_opentelemetry::middleware::OtelRpcLayer;
+///
+/// let app: Router = Router::new()
+///     .route("/twirp/{*rpc}", post(|| async {}))
+///     .layer(OtelRpcLayer::twirp());
+/// ```
+/// The default maximum size of the bodies buffered by [`OtelRpcLayer`] (2 MiB)
+pub const DEFAULT_MAX_RPC_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct OtelRpcLayer {
+    flavor: RpcFlavor,
+    max_body_size: usize,
+    privacy: Arc<PrivacyPolicy>,
+}
+
+impl OtelRpcLayer {
+    #[must_use]
+    pub fn new(flavor: RpcFlavor) -> Self {
+        OtelRpcLayer {
+            flavor,
+            max_body_size: DEFAULT_MAX_RPC_BODY_SIZE,
+            privacy: Arc::default(),
+        }
+    }
+
+    /// Buffer (to read the method & the errors) only the bodies of at most `max_body_size` bytes,
+    /// the larger ones are streamed untouched (default: [`DEFAULT_MAX_RPC_BODY_SIZE`])
+    #[must_use]
+    pub fn with_max_body_size(self, max_body_size: usize) -> Self {
+        OtelRpcLayer {
+            max_body_size,
+            ..self
+        }
+    }
+
+    /// Protect the attributes identifying a person (eg the user agent) recorded by this layer,
+    /// like [`super::OtelAxumLayer::with_privacy_mode`].
+    #[must_use]
+    pub fn with_privacy_mode(self, mode: PrivacyMode) -> Self {
+        OtelRpcLayer {
+            privacy: Arc::new(PrivacyPolicy::new(mode)),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn json_rpc() -> Self {
+        Self::new(RpcFlavor::JsonRpc)
+    }
+
+    #[must_use]
+    pub fn twirp() -> Self {
+        Self::new(RpcFlavor::Twirp)
+    }
+}
+
+impl<S> Layer<S> for OtelRpcLayer {
+    type Service = OtelRpcService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        OtelRpcService {
+            inner,
+            flavor: self.flavor,
+            max_body_size: self.max_body_size,
+            privacy: self.privacy.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OtelRpcService<S> {
+    inner: S,
+    flavor: RpcFlavor,
+    max_body_size: usize,
+    privacy: Arc<PrivacyPolicy>,
+}
+
+impl<S> Service<Request<Body>> for OtelRpcService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        // take the service that is ready, and leave a clone in place
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let flavor = self.flavor;
+        let max_body_size = self.max_body_size;
+        let privacy = self.privacy.clone();
+        Box::pin(async move {
+            let (req, request_payload) = match flavor {
+                RpcFlavor::JsonRpc => {
+                    let (parts, body) = req.into_parts();
+                    match buffer_body(body, max_body_size).await {
+                        Ok(LimitedBody::Buffered(bytes)) => (
+                            Request::from_parts(parts, Body::from(bytes.clone())),
+                            Some(bytes),
+                        ),
+                        Ok(LimitedBody::Streamed(body)) => (Request::from_parts(parts, body), None),
+                        Err(err) => {
+                            tracing::warn!(error = %err, "failed to read the body of the JSON-RPC request");
+                            return Ok(status_response(StatusCode::BAD_REQUEST));
+                        }
+                    }
+                }
+                RpcFlavor::Twirp => (req, None),
+            };
+            let span = otel_http::grpc_server::make_span_from_request_with_rpc_system(
+                &req,
+                flavor.rpc_system(),
+                &privacy,
+            );
+            span.set_parent(otel_http::extract_context(req.headers()));
+            if let Some(payload) = request_payload {
+                record_jsonrpc_request(&span, &payload);
+            }
+
+            let response = inner.call(req).instrument(span.clone()).await?;
+
+            let buffer_response = match flavor {
+                RpcFlavor::JsonRpc => true,
+                RpcFlavor::Twirp => !response.status().is_success(),
+            };
+            if !buffer_response {
+                return Ok(response);
+            }
+            let (parts, body) = response.into_parts();
+            let bytes = match buffer_body(body, max_body_size).await {
+                Ok(LimitedBody::Buffered(bytes)) => bytes,
+                Ok(LimitedBody::Streamed(body)) => return Ok(Response::from_parts(parts, body)),
+                Err(err) => {
+                    span.record("otel.status_code", "ERROR");
+                    span.record(
+                        "exception.message",
+                        truncate_attribute_value(&err.to_string()).as_ref(),
+                    );
+                    return Ok(status_response(StatusCode::INTERNAL_SERVER_ERROR));
+                }
+            };
+            match flavor {
+                RpcFlavor::JsonRpc => record_jsonrpc_response(&span, &bytes),
+                RpcFlavor::Twirp => record_twirp_error(&span, parts.status, &bytes),
+            }
+            Ok(Response::from_parts(parts, Body::from(bytes)))
+        })
+    }
+}
+
+/// A body read by [`buffer_body`]
+enum LimitedBody {
+    /// the whole body (at most the limit)
+    Buffered(Bytes),
+    /// the body larger than the limit, untouched (the frames already read are replayed)
+    Streamed(Body),
+}
+
+/// Buffer the `body` if its size is at most `limit` bytes, without reading more than the limit
+async fn buffer_body(body: Body, limit: usize) -> Result<LimitedBody, axum::Error> {
+    if usize::try_from(body.size_hint().lower()).map_or(true, |size| size > limit) {
+        return Ok(LimitedBody::Streamed(body));
+    }
+    let mut stream = body.into_data_stream();
+    let mut chunks = Vec::new();
+    let mut size = 0;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        size += chunk.len();
+        chunks.push(chunk);
+        if size > limit {
+            let read = futures_util::stream::iter(chunks.into_iter().map(Ok));
+            return Ok(LimitedBody::Streamed(Body::from_stream(read.chain(stream))));
+        }
+    }
+    Ok(LimitedBody::Buffered(if chunks.len() == 1 {
+        chunks.swap_remove(0)
+    } else {
+        Bytes::from(chunks.concat())
+    }))
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// see [Semantic Conventions for JSON-RPC](https://opentelemetry.io/docs/specs/semconv/rpc/json-rpc/)
+fn record_jsonrpc_request(span: &tracing::Span, payload: &Bytes) {
+    let Ok(request) = serde_json::from_slice::<Value>(payload) else {
+        return;
+    };
+    // for batch, use the first request
+    let request = match &request {
+        Value::Array(requests) => requests.first().unwrap_or(&Value::Null),
+        other => other,
+    };
+    if let Some(method) = request.get("method").and_then(Value::as_str) {
+        span.record("otel.name", method);
+        span.record("rpc.method", method);
+        span.record("rpc.service", "");
+    }
+    if let Some(version) = request.get("jsonrpc").and_then(Value::as_str) {
+        span.set_attribute("rpc.jsonrpc.version", version.to_string());
+    }
+    match request.get("id") {
+        Some(Value::String(id)) => span.set_attribute("rpc.jsonrpc.request_id", id.clone()),
+        Some(Value::Number(id)) => span.set_attribute("rpc.jsonrpc.request_id", id.to_string()),
+        _ => {}
+    }
+}
+
+fn record_jsonrpc_response(span: &tracing::Span, payload: &Bytes) {
+    let Ok(response) = serde_json::from_slice::<Value>(payload) else {
+        return;
+    };
+    let response = match &response {
+        Value::Array(responses) => responses.first().unwrap_or(&Value::Null),
+        other => other,
+    };
+    let Some(error) = response.get("error") else {
+        return;
+    };
+    let code = error.get("code").and_then(Value::as_i64);
+    if let Some(code) = code {
+        span.set_attribute("rpc.jsonrpc.error_code", code);
+    }
+    if let Some(message) = error.get("message").and_then(Value::as_str) {
+        span.set_attribute(
+            "rpc.jsonrpc.error_message",
+            truncate_attribute_value(message).into_owned(),
+        );
+        span.record(
+            "exception.message",
+            truncate_attribute_value(message).as_ref(),
+        );
+    }
+    if code.map_or(true, jsonrpc_code_is_error) {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+/// The errors caused by the client (parse error, invalid request, method not found, invalid params)
+/// are not errors of the server
+fn jsonrpc_code_is_error(code: i64) -> bool {
+    !matches!(code, -32700 | -32600 | -32601 | -32602)
+}
+
+/// see [Twirp errors](https://twitchtv.github.io/twirp/docs/spec_v7.html#error-codes)
+fn record_twirp_error(span: &tracing::Span, status: StatusCode, payload: &Bytes) {
+    if let Ok(error) = serde_json::from_slice::<Value>(payload) {
+        if let Some(code) = error.get("code").and_then(Value::as_str) {
+            span.set_attribute("rpc.twirp.error_code", code.to_string());
+        }
+        if let Some(msg) = error.get("msg").and_then(Value::as_str) {
+            span.record("exception.message", truncate_attribute_value(msg).as_ref());
+        }
+    }
+    if status.is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Router};
+    use rstest::rstest;
+    use testing_tracing_opentelemetry::{assert_trace, FakeEnvironment};
+
+    #[rstest]
+    #[case(
+        "jsonrpc_ok",
+        RpcFlavor::JsonRpc,
+        "/rpc",
+        r#"{"jsonrpc":"2.0","method":"user.get","id":1}"#
+    )]
+    #[case(
+        "jsonrpc_error",
+        RpcFlavor::JsonRpc,
+        "/rpc",
+        r#"{"jsonrpc":"2.0","method":"fail","id":"a"}"#
+    )]
+    #[case(
+        "twirp_ok",
+        RpcFlavor::Twirp,
+        "/twirp/example.Haberdasher/MakeHat",
+        "{}"
+    )]
+    #[case(
+        "twirp_error",
+        RpcFlavor::Twirp,
+        "/twirp/example.Haberdasher/Fail",
+        "{}"
+    )]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_span_event(
+        #[case] name: &str,
+        #[case] flavor: RpcFlavor,
+        #[case] uri: &str,
+        #[case] body: &'static str,
+    ) {
+        let mut fake_env = FakeEnvironment::setup().await;
+        {
+            let mut svc = Router::new()
+                .route(
+                    "/rpc",
+                    post(|body: String| async move {
+                        if body.contains("fail") {
+                            r#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"boom"},"id":"a"}"#
+                        } else {
+                            r#"{"jsonrpc":"2.0","result":{"name":"me"},"id":1}"#
+                        }
+                    }),
+                )
+                .route(
+                    "/twirp/example.Haberdasher/MakeHat",
+                    post(|| async { r#"{"size":12}"# }),
+                )
+                .route(
+                    "/twirp/example.Haberdasher/Fail",
+                    post(|| async {
+                        (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            r#"{"code":"internal","msg":"boom"}"#,
+                        )
+                    }),
+                )
+                .layer(OtelRpcLayer::new(flavor));
+            let req = Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            let response = svc.call(req).await.unwrap();
+            // the body is still available for the client
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(!bytes.is_empty());
+        }
+        let (tracing_events, otel_spans) = fake_env.collect_traces().await;
+        assert_trace(&format!("rpc_{name}"), tracing_events, otel_spans, false);
+    }
+
+    #[rstest]
+    #[case(Body::from(r#"{"jsonrpc":"2.0","method":"user.get","id":1}"#))]
+    #[case(Body::from_stream(futures_util::stream::iter(
+        [r#"{"jsonrpc":"2.0","#, r#""method":"user.get","id":1}"#]
+            .map(Ok::<_, std::io::Error>)
+    )))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_the_body_larger_than_the_limit(#[case] body: Body) {
+        let mut fake_env = FakeEnvironment::setup().await;
+        {
+            let mut svc = Router::new()
+                .route("/rpc", post(|body: String| async move { body }))
+                .layer(OtelRpcLayer::json_rpc().with_max_body_size(24));
+            let req = Request::builder()
+                .method("POST")
+                .uri("/rpc")
+                .body(body)
+                .unwrap();
+            let response = svc.call(req).await.unwrap();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(bytes == r#"{"jsonrpc":"2.0","method":"user.get","id":1}"#);
+        }
+        let (_, otel_spans) = fake_env.collect_traces().await;
+        assert!(otel_spans.len() == 1);
+        // not parsed: the span keeps the generic name
+        assert!(otel_spans[0].name != "user.get");
+        assert!(!otel_spans[0]
+            .attributes
+            .contains_key("rpc.jsonrpc.request_id"));
+    }
+}
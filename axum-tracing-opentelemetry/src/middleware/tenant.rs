@@ -0,0 +1,113 @@
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_opentelemetry_instrumentation_sdk::{truncate_attribute_value, PrivacyPolicy};
+
+/// Information about the tenant & the user of a request, provided by a previous (auth) layer
+/// as an extension of the request, see [`super::OtelAxumLayer::with_tenant_info`].
+///
+/// ```
+/// use axum_tracing_opentelemetry::middleware::TenantInfo;
+///
+/// #[derive(Clone)]
+/// struct Claims {
+///     sub: String,
+///     org: String,
+/// }
+///
+/// impl TenantInfo for Claims {
+///     fn tenant_id(&self) -> Option<&str> {
+///         Some(&self.org)
+///     }
+///
+///     fn enduser_id(&self) -> Option<&str> {
+///         Some(&self.sub)
+///     }
+/// }
+/// ```
+pub trait TenantInfo {
+    /// recorded as `tenant.id`
+    fn tenant_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// recorded as `enduser.id`
+    fn enduser_id(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Function to record the tenant's attributes from the extensions of the request
+pub(crate) type RecordTenant = fn(&Span, &http::Extensions, &PrivacyPolicy);
+
+pub(crate) fn record_tenant_info<T>(
+    span: &Span,
+    extensions: &http::Extensions,
+    privacy: &PrivacyPolicy,
+) where
+    T: TenantInfo + Send + Sync + 'static,
+{
+    let Some(info) = extensions.get::<T>() else {
+        return;
+    };
+    if let Some(tenant_id) = info.tenant_id() {
+        span.set_attribute(
+            "tenant.id",
+            truncate_attribute_value(tenant_id).into_owned(),
+        );
+    }
+    if let Some(enduser_id) = info
+        .enduser_id()
+        .and_then(|v| privacy.protect("enduser.id", v))
+    {
+        span.set_attribute(
+            "enduser.id",
+            truncate_attribute_value(&enduser_id).into_owned(),
+        );
+    }
+}
+
+/// The end user of a request, recorded as `enduser.id`, `enduser.role` & `enduser.scope`
+/// (see [semantic-conventions/.../enduser](https://opentelemetry.io/docs/specs/semconv/attributes-registry/enduser/)).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Enduser {
+    /// the username or client id
+    pub id: Option<String>,
+    /// the roles (eg space separated)
+    pub role: Option<String>,
+    /// the scopes or granted authorities (eg the `scope` of an OAuth 2.0 access token)
+    pub scope: Option<String>,
+}
+
+/// Extract the [`Enduser`] from the response (status, headers, extensions), see [`super::OtelAxumLayer::with_enduser`].
+///
+/// It is implemented for the closures `Fn(&http::response::Parts) -> Option<Enduser>`.
+pub trait EnduserExtractor: Send + Sync {
+    fn extract(&self, parts: &http::response::Parts) -> Option<Enduser>;
+}
+
+impl<F> EnduserExtractor for F
+where
+    F: Fn(&http::response::Parts) -> Option<Enduser> + Send + Sync,
+{
+    fn extract(&self, parts: &http::response::Parts) -> Option<Enduser> {
+        self(parts)
+    }
+}
+
+impl std::fmt::Debug for dyn EnduserExtractor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EnduserExtractor")
+    }
+}
+
+pub(crate) fn record_enduser(span: &Span, enduser: &Enduser, privacy: &PrivacyPolicy) {
+    for (key, value) in [
+        ("enduser.id", &enduser.id),
+        ("enduser.role", &enduser.role),
+        ("enduser.scope", &enduser.scope),
+    ] {
+        if let Some(value) = value.as_deref().and_then(|v| privacy.protect(key, v)) {
+            span.set_attribute(key, truncate_attribute_value(&value).into_owned());
+        }
+    }
+}
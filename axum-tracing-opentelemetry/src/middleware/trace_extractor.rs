@@ -32,18 +32,34 @@
 //! ```
 //!
 
-use axum::extract::MatchedPath;
-use http::{Request, Response};
+use axum::extract::{ConnectInfo, MatchedPath, OriginalUri};
+use http::{HeaderMap, HeaderName, Method, Request, Response, StatusCode};
+use http_body::{Body, Frame, SizeHint};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{SpanKind, TraceContextExt};
+use opentelemetry::KeyValue;
 use pin_project_lite::pin_project;
 use std::{
-    error::Error,
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
     future::Future,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
+    time::{Duration, Instant, SystemTime},
 };
 use tower::{Layer, Service};
 use tracing::Span;
 use tracing_opentelemetry_instrumentation_sdk::http as otel_http;
+use tracing_opentelemetry_instrumentation_sdk::{
+    force_sampling, mark_span_for_recording_gate, truncate_attribute_value, DroppedSpanHook,
+    DroppedSpanReason, PrivacyMode, PrivacyPolicy, TRACING_LEVEL, TRACING_TARGET,
+};
+
+use super::tenant::{
+    record_enduser, record_tenant_info, EnduserExtractor, RecordTenant, TenantInfo,
+};
 
 #[deprecated(
     since = "0.12.0",
@@ -56,15 +72,165 @@ pub fn opentelemetry_tracing_layer() -> OtelAxumLayer {
 
 pub type Filter = fn(&str) -> bool;
 
+/// Function to define the `SpanKind` of the span from the route (`None` to keep `SpanKind::Server`)
+pub type SpanKindFor = fn(&str) -> Option<SpanKind>;
+
+/// Function to update the span from the response (status code, headers), see [`OtelAxumLayer::with_on_response`]
+pub type OnResponse = fn(&Span, StatusCode, &HeaderMap);
+
+/// Function to update the span from the error returned by the inner service, see [`OtelAxumLayer::with_on_failure`]
+///
+/// The error is only required to be displayable (not a `std::error::Error`), to support the error types of
+/// any tower stack (eg `BoxError`).
+pub type OnFailure = fn(&Span, &(dyn fmt::Display + 'static));
+
+/// Function to update the span when the processing of the request is cancelled, see [`OtelAxumLayer::with_on_cancellation`]
+pub type OnCancellation = fn(&Span);
+
+/// Function to format the route (template) before recording it into `http.route` and `otel.name`
+pub type RouteFormatter = fn(&str) -> Cow<'_, str>;
+
+/// [`RouteFormatter`] to convert the axum 0.8 templates (`/users/{id}`, `/files/{*path}`)
+/// into the "colon" convention (`/users/:id`, `/files/*path`) used by axum < 0.8.
+///
+/// Useful to keep the same `http.route` (grouping on the backend) across the upgrade.
+#[must_use]
+pub fn colon_route_formatter(route: &str) -> Cow<'_, str> {
+    if !route.contains('{') {
+        return Cow::Borrowed(route);
+    }
+    let mut formatted = String::with_capacity(route.len());
+    let mut rest = route;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let param = &rest[start + 1..start + len];
+        formatted.push_str(&rest[..start]);
+        if param.starts_with('*') {
+            formatted.push_str(param);
+        } else {
+            formatted.push(':');
+            formatted.push_str(param);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    formatted.push_str(rest);
+    Cow::Owned(formatted)
+}
+
+/// How to record the route of the requests processed by a nested router (`Router::nest`, `Router::nest_service`)
+/// when the route (`MatchedPath`) doesn't include the mount prefix (eg for the fallback of the nested router).
+///
+/// The mount prefix is detected from the difference between the `OriginalUri` and the uri of the request
+/// (so it's the concrete path of the prefix, not a template).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NestedRoutePolicy {
+    /// record the route as matched (default)
+    #[default]
+    MatchedPath,
+    /// prefix `http.route` (and the name of the span) with the mount prefix
+    Compose,
+    /// keep `http.route` as matched and record the prefixed route into `http.route.nested`
+    Attribute,
+}
+
+/// How to name the spans (`otel.name`), the method is always recorded into `http.request.method`.
+///
+/// Each method of a route is an operation for most of the backends: naming the spans by route only reduces
+/// the cardinality of the operations (eg for the APIs where every route supports 4+ methods).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpanNaming {
+    /// `{method} {route}` (default, as the semantic conventions)
+    #[default]
+    MethodAndRoute,
+    /// `{route}` for every method
+    Route,
+    /// `{route}` for the `HEAD` & `OPTIONS` requests (usually answered by the framework or the CORS layer),
+    /// `{method} {route}` for the other methods
+    RouteForHeadAndOptions,
+}
+
+impl SpanNaming {
+    fn span_name(self, method: &Method, http_method: &str, route: &str) -> String {
+        let with_method = match self {
+            SpanNaming::MethodAndRoute => true,
+            SpanNaming::Route => false,
+            SpanNaming::RouteForHeadAndOptions => {
+                *method != Method::HEAD && *method != Method::OPTIONS
+            }
+        };
+        if with_method {
+            format!(
+                "{} {route}",
+                otel_http::http_method_for_span_name(http_method)
+            )
+            .trim()
+            .to_string()
+        } else if route.is_empty() {
+            // no route (eg the fallback without `MatchedPath`), keep a non-empty name
+            otel_http::http_method_for_span_name(http_method).to_string()
+        } else {
+            route.to_string()
+        }
+    }
+}
+
+/// How to account for the time spent by the request before reaching the application (eg into the queue of
+/// the load balancer), from the header `X-Request-Start` (or `X-Queue-Start`),
+/// see [`otel_http::http_server::request_start_from_headers`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueueTimePolicy {
+    /// ignore the headers (default)
+    #[default]
+    Ignore,
+    /// record the duration (in seconds) into `http.server.queue_duration`
+    Attribute,
+    /// like `Attribute`, and add a child span `http.server.queue` (from the request start to the reception by the application),
+    /// created with the global tracer provider (so it's visible on the waterfall of the trace)
+    ChildSpan,
+}
+
 /// layer/middleware for axum:
 ///
 /// - propagate `OpenTelemetry` context (`trace_id`,...) to server
 /// - create a Span for `OpenTelemetry` (and tracing) on call
 ///
 /// `OpenTelemetry` context are extracted from tracing's span.
+///
+/// The `SpanKind` of the span is `Server` by default, it can be overridden (eg `Consumer` for webhook endpoints)
+/// - by a `SpanKind` inserted into the extensions of the request (by a previous layer)
+/// - by the function defined with [`OtelAxumLayer::with_span_kind_for`]
+#[allow(clippy::struct_excessive_bools)] // options of the builder
 #[derive(Default, Debug, Clone)]
 pub struct OtelAxumLayer {
     filter: Option<Filter>,
+    milestone_events: bool,
+    span_kind_for: Option<SpanKindFor>,
+    route_formatter: Option<RouteFormatter>,
+    sampling_rates: Arc<[(String, f64)]>,
+    extra_known_methods: Arc<[Method]>,
+    on_response: Option<OnResponse>,
+    on_failure: Option<OnFailure>,
+    on_cancellation: Option<OnCancellation>,
+    record_tenant: Option<RecordTenant>,
+    recording_gate: Option<Duration>,
+    nested_route_policy: NestedRoutePolicy,
+    span_naming: SpanNaming,
+    links_header: Option<HeaderName>,
+    enduser_extractor: Option<Arc<dyn EnduserExtractor>>,
+    interim_response_events: bool,
+    propagator: Option<Arc<dyn TextMapPropagator + Send + Sync>>,
+    queue_time_policy: QueueTimePolicy,
+    ignore_preflight: bool,
+    route_metadata: Arc<HashMap<String, Vec<KeyValue>>>,
+    record_connect_info: Option<RecordConnectInfo>,
+    context_extension: bool,
+    response_headers: Arc<[HeaderName]>,
+    force_sampling_for: Option<otel_http::ForceSamplingFor>,
+    force_trace: Option<otel_http::ForceTraceConfig>,
+    dropped_span_hook: Option<DroppedSpanHook>,
+    privacy: Arc<PrivacyPolicy>,
 }
 
 // add a builder like api
@@ -73,8 +239,599 @@ impl OtelAxumLayer {
     pub fn filter(self, filter: Filter) -> Self {
         OtelAxumLayer {
             filter: Some(filter),
+            ..self
+        }
+    }
+
+    /// Add span events marking the milestones of the request processing:
+    /// `request.received`, `response.first_byte` and `response.end_of_stream`.
+    ///
+    /// It provides a latency breakdown inside the server span without child spans.
+    /// When enabled, the span is closed when the response body is fully sent (or dropped)
+    /// instead of when the response headers are ready.
+    #[must_use]
+    pub fn with_milestone_events(self, enabled: bool) -> Self {
+        OtelAxumLayer {
+            milestone_events: enabled,
+            ..self
+        }
+    }
+
+    /// Define the `SpanKind` of the span from the route template as matched by the router
+    /// (without the prefix composed by [`NestedRoutePolicy::Compose`]),
+    /// a `SpanKind` set into the extensions of the request has priority.
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use opentelemetry::trace::SpanKind;
+    ///
+    /// let layer = OtelAxumLayer::default().with_span_kind_for(|route| {
+    ///     route.starts_with("/webhooks/").then_some(SpanKind::Consumer)
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_span_kind_for(self, span_kind_for: SpanKindFor) -> Self {
+        OtelAxumLayer {
+            span_kind_for: Some(span_kind_for),
+            ..self
+        }
+    }
+
+    /// Format the route (template) before recording it into `http.route` and `otel.name`.
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::{colon_route_formatter, OtelAxumLayer};
+    ///
+    /// let layer = OtelAxumLayer::default().with_route_formatter(colon_route_formatter);
+    /// ```
+    #[must_use]
+    pub fn with_route_formatter(self, route_formatter: RouteFormatter) -> Self {
+        OtelAxumLayer {
+            route_formatter: Some(route_formatter),
+            ..self
+        }
+    }
+
+    /// Only create the span for a ratio (`rate` between `0.0` and `1.0`) of the requests
+    /// with a route (fallback to the path if no route) matching `route_glob` (`*` matches any sequence of characters).
+    ///
+    /// The other requests are not traced at all (like for [`OtelAxumLayer::filter`]), it avoids
+    /// even the cost of span construction for very hot, uninteresting endpoints.
+    /// The decision is made by the layer, regardless of the sampling decision of the parent (if any).
+    /// The first matching rule is used.
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    ///
+    /// let layer = OtelAxumLayer::default()
+    ///     .with_sampling_rate_for("/health*", 0.01)
+    ///     .with_sampling_rate_for("/metrics", 0.0);
+    /// ```
+    #[must_use]
+    pub fn with_sampling_rate_for(self, route_glob: impl Into<String>, rate: f64) -> Self {
+        let mut sampling_rates = self.sampling_rates.to_vec();
+        sampling_rates.push((route_glob.into(), rate.clamp(0.0, 1.0)));
+        OtelAxumLayer {
+            sampling_rates: sampling_rates.into(),
+            ..self
+        }
+    }
+
+    /// Treat the `methods` (eg `PURGE`, `REPORT`) as known: they are recorded as is into `http.request.method`
+    /// and the name of the span.
+    ///
+    /// By default, the non-standard methods are recorded as `_OTHER`
+    /// (with the original value into `http.request.method_original`) to limit the cardinality.
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use http::Method;
+    ///
+    /// let layer = OtelAxumLayer::default()
+    ///     .with_extra_known_methods([Method::from_bytes(b"PURGE").unwrap()]);
+    /// ```
+    #[must_use]
+    pub fn with_extra_known_methods(self, methods: impl IntoIterator<Item = Method>) -> Self {
+        let mut extra_known_methods = self.extra_known_methods.to_vec();
+        extra_known_methods.extend(methods);
+        OtelAxumLayer {
+            extra_known_methods: extra_known_methods.into(),
+            ..self
+        }
+    }
+
+    /// Replace the default update of the span on response
+    /// ([`otel_http::http_server::update_span_from_status`]: record `http.response.status_code`, `ERROR` on 5xx),
+    /// eg to customize the classification of the responses.
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use axum_tracing_opentelemetry::tracing_opentelemetry_instrumentation_sdk::http as otel_http;
+    /// use http::StatusCode;
+    ///
+    /// let layer = OtelAxumLayer::default().with_on_response(|span, status, _headers| {
+    ///     otel_http::http_server::update_span_from_status(span, status);
+    ///     // for this service, a conflict is an error
+    ///     if status == StatusCode::CONFLICT {
+    ///         span.record("otel.status_code", "ERROR");
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_on_response(self, on_response: OnResponse) -> Self {
+        OtelAxumLayer {
+            on_response: Some(on_response),
+            ..self
+        }
+    }
+
+    /// Replace the default update of the span when the inner service returns an error
+    /// (status `ERROR` with the error as `exception.message`).
+    #[must_use]
+    pub fn with_on_failure(self, on_failure: OnFailure) -> Self {
+        OtelAxumLayer {
+            on_failure: Some(on_failure),
+            ..self
+        }
+    }
+
+    /// Replace the default update of the span when the processing of the request is cancelled
+    /// (the response future is dropped before completion, eg the client disconnects or a timeout fires):
+    /// [`otel_http::http_server::update_span_from_cancellation`] (`ERROR` with `error.type = "cancelled"`).
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    ///
+    /// // a cancellation by the client is not an error of the server
+    /// let layer = OtelAxumLayer::default().with_on_cancellation(|span| {
+    ///     span.record("error.type", "cancelled");
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_on_cancellation(self, on_cancellation: OnCancellation) -> Self {
+        OtelAxumLayer {
+            on_cancellation: Some(on_cancellation),
+            ..self
+        }
+    }
+
+    /// Record `tenant.id` & `enduser.id` from the extension `T` of the request (if present),
+    /// eg the claims inserted by an auth layer.
+    ///
+    /// The auth layer should process the request before the `OtelAxumLayer`
+    /// (so be added after it, with `.layer(...)`).
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, TenantInfo};
+    ///
+    /// #[derive(Clone)]
+    /// struct Claims {
+    ///     org: String,
+    /// }
+    ///
+    /// impl TenantInfo for Claims {
+    ///     fn tenant_id(&self) -> Option<&str> {
+    ///         Some(&self.org)
+    ///     }
+    /// }
+    ///
+    /// let layer = OtelAxumLayer::default().with_tenant_info::<Claims>();
+    /// ```
+    #[must_use]
+    pub fn with_tenant_info<T>(self) -> Self
+    where
+        T: TenantInfo + Send + Sync + 'static,
+    {
+        OtelAxumLayer {
+            record_tenant: Some(record_tenant_info::<T>),
+            ..self
+        }
+    }
+
+    /// Only export the traces of the requests that end with an error or last at least `latency_threshold`
+    /// (like a tail sampling, without collector).
+    ///
+    /// The span is always created (the context is propagated), but marked for the recording gate
+    /// ([`tracing_opentelemetry_instrumentation_sdk::RECORDING_GATE_ATTRIBUTE`]):
+    /// the decision is made when the span ends, by the `RecordingGateSpanProcessor` of `init-tracing-opentelemetry`
+    /// (enabled with `TracingConfig::with_recording_gate`, else the span is exported as usual, with the mark).
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use std::time::Duration;
+    ///
+    /// let layer = OtelAxumLayer::default().with_recording_gate(Duration::from_millis(500));
+    /// ```
+    #[must_use]
+    pub fn with_recording_gate(self, latency_threshold: Duration) -> Self {
+        OtelAxumLayer {
+            recording_gate: Some(latency_threshold),
+            ..self
+        }
+    }
+
+    /// Define how to record the route when the mount prefix of a nested router is missing from it
+    /// (default: [`NestedRoutePolicy::MatchedPath`]).
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::{NestedRoutePolicy, OtelAxumLayer};
+    ///
+    /// let layer = OtelAxumLayer::default().with_nested_route_policy(NestedRoutePolicy::Compose);
+    /// ```
+    #[must_use]
+    pub fn with_nested_route_policy(self, nested_route_policy: NestedRoutePolicy) -> Self {
+        OtelAxumLayer {
+            nested_route_policy,
+            ..self
+        }
+    }
+
+    /// Define how to name the spans (default: [`SpanNaming::MethodAndRoute`]), eg to collapse the methods of
+    /// a route into a single operation on the backend.
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, SpanNaming};
+    ///
+    /// let layer = OtelAxumLayer::default().with_span_naming(SpanNaming::Route);
+    /// ```
+    #[must_use]
+    pub fn with_span_naming(self, span_naming: SpanNaming) -> Self {
+        OtelAxumLayer {
+            span_naming,
+            ..self
+        }
+    }
+
+    /// Add a link to the span for each of the contexts listed into the header `header_name`
+    /// (eg the upstream requests forwarded as a batch by a gateway), see
+    /// [`otel_http::extract_contexts_multi`] for the format.
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use http::HeaderName;
+    ///
+    /// let layer =
+    ///     OtelAxumLayer::default().with_links_from_header(HeaderName::from_static("x-trace-links"));
+    /// ```
+    #[must_use]
+    pub fn with_links_from_header(self, header_name: HeaderName) -> Self {
+        OtelAxumLayer {
+            links_header: Some(header_name),
+            ..self
         }
     }
+
+    /// Record `enduser.id`, `enduser.role` & `enduser.scope` from the [`Enduser`](super::Enduser) returned by `enduser_extractor`,
+    /// called when the response is ready (so after the processing by the auth layers & handlers).
+    ///
+    /// The extensions of the request are not available at this stage, so the auth layer (or the handler)
+    /// should provide the information into the response (eg as an extension, with `axum::Extension` into the response's tuple).
+    /// `enduser.id` is protected by the privacy policy (see [`OtelAxumLayer::with_privacy_mode`]).
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::{Enduser, OtelAxumLayer};
+    ///
+    /// #[derive(Clone)]
+    /// struct Claims {
+    ///     sub: String,
+    ///     roles: Vec<String>,
+    /// }
+    ///
+    /// let layer = OtelAxumLayer::default().with_enduser(|parts: &http::response::Parts| {
+    ///     parts.extensions.get::<Claims>().map(|claims| Enduser {
+    ///         id: Some(claims.sub.clone()),
+    ///         role: Some(claims.roles.join(" ")),
+    ///         scope: None,
+    ///     })
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_enduser<E>(self, enduser_extractor: E) -> Self
+    where
+        E: EnduserExtractor + 'static,
+    {
+        OtelAxumLayer {
+            enduser_extractor: Some(Arc::new(enduser_extractor)),
+            ..self
+        }
+    }
+
+    /// Protect the attributes identifying a person (`enduser.id`, `user_agent.original`, see
+    /// [`tracing_opentelemetry_instrumentation_sdk::DEFAULT_PROTECTED_KEYS`]) recorded by this layer:
+    /// hashed with a secret salt ([`PrivacyMode::Hash`]) or not recorded ([`PrivacyMode::Drop`]).
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use axum_tracing_opentelemetry::tracing_opentelemetry_instrumentation_sdk::PrivacyMode;
+    ///
+    /// let layer = OtelAxumLayer::default().with_privacy_mode(PrivacyMode::Hash {
+    ///     salt: "a-secret-salt".to_string(),
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_privacy_mode(self, mode: PrivacyMode) -> Self {
+        self.with_privacy_policy(PrivacyPolicy::new(mode))
+    }
+
+    /// Like [`OtelAxumLayer::with_privacy_mode`] but with the keys of the protected attributes defined by `policy`.
+    #[must_use]
+    pub fn with_privacy_policy(self, policy: PrivacyPolicy) -> Self {
+        OtelAxumLayer {
+            privacy: Arc::new(policy),
+            ..self
+        }
+    }
+
+    /// Add a span event `http.response.interim` for each interim (`1xx`) response sent before the final one
+    /// (eg `103 Early Hints`), as listed by the extension [`otel_http::http_server::InterimResponses`] of the final response.
+    ///
+    /// `http.response.status_code` is always the status of the final response
+    /// (`101 Switching Protocols` is a final response, eg for websocket).
+    ///
+    /// ```
+    /// use axum::{routing::get, Extension, Router};
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use axum_tracing_opentelemetry::tracing_opentelemetry_instrumentation_sdk::http::http_server::InterimResponses;
+    /// use http::StatusCode;
+    ///
+    /// let app: Router = Router::new()
+    ///     .route(
+    ///         "/",
+    ///         get(|| async {
+    ///             // ... send the early hints (eg via the connection), then
+    ///             (Extension(InterimResponses(vec![StatusCode::EARLY_HINTS])), "hello")
+    ///         }),
+    ///     )
+    ///     .layer(OtelAxumLayer::default().with_interim_response_events(true));
+    /// ```
+    #[must_use]
+    pub fn with_interim_response_events(self, enabled: bool) -> Self {
+        OtelAxumLayer {
+            interim_response_events: enabled,
+            ..self
+        }
+    }
+
+    /// Extract the context of the requests with the `propagator` instead of the global one
+    /// (eg B3 for a public edge listener, W3C for the internal mesh, into the same process).
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use opentelemetry_sdk::propagation::TraceContextPropagator;
+    /// use std::sync::Arc;
+    ///
+    /// let layer = OtelAxumLayer::default().with_propagator(Arc::new(TraceContextPropagator::new()));
+    /// ```
+    #[must_use]
+    pub fn with_propagator(self, propagator: Arc<dyn TextMapPropagator + Send + Sync>) -> Self {
+        OtelAxumLayer {
+            propagator: Some(propagator),
+            ..self
+        }
+    }
+
+    /// Account for the time spent by the request before reaching the application (default: [`QueueTimePolicy::Ignore`]),
+    /// so the latency dashboards include it.
+    ///
+    /// Enable it only behind a proxy that sets (or removes) the header, and with synchronized clocks.
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, QueueTimePolicy};
+    ///
+    /// let layer = OtelAxumLayer::default().with_queue_time(QueueTimePolicy::Attribute);
+    /// ```
+    #[must_use]
+    pub fn with_queue_time(self, queue_time_policy: QueueTimePolicy) -> Self {
+        OtelAxumLayer {
+            queue_time_policy,
+            ..self
+        }
+    }
+
+    /// Don't create span for the CORS preflights (`OPTIONS` requests with the header `Access-Control-Request-Method`),
+    /// that double the number of spans of the APIs called by browsers (default: `false`).
+    ///
+    /// The actual requests (following the preflights) are traced as usual.
+    #[must_use]
+    pub fn ignore_preflight(self, ignore_preflight: bool) -> Self {
+        OtelAxumLayer {
+            ignore_preflight,
+            ..self
+        }
+    }
+
+    /// Record static attributes on the spans of the routes (eg `slo.tier`, `team`), to route the alerts
+    /// on the backend without touching every handler.
+    ///
+    /// The keys are the route templates as declared into the router (before the [`RouteFormatter`]
+    /// and without the prefix composed by [`NestedRoutePolicy::Compose`]).
+    ///
+    /// ```
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use opentelemetry::KeyValue;
+    /// use std::collections::HashMap;
+    ///
+    /// let layer = OtelAxumLayer::default().with_route_metadata(HashMap::from([(
+    ///     "/payments/{id}".to_string(),
+    ///     vec![KeyValue::new("slo.tier", "critical"), KeyValue::new("team", "payments")],
+    /// )]));
+    /// ```
+    #[must_use]
+    pub fn with_route_metadata(self, route_metadata: HashMap<String, Vec<KeyValue>>) -> Self {
+        OtelAxumLayer {
+            route_metadata: Arc::new(route_metadata),
+            ..self
+        }
+    }
+
+    /// Record `network.transport` (and `server.socket.path` for the Unix domain sockets) from the extension
+    /// `ConnectInfo<T>` of the request, inserted when the app is served with
+    /// `into_make_service_with_connect_info::<T>()`.
+    ///
+    /// `T` is `SocketAddr` for a `TcpListener`, or a type defined by the app for a `UnixListener`
+    /// (implementing [`otel_http::ConnectionInfo`], eg by wrapping an [`otel_http::UnixSocketInfo`]).
+    ///
+    /// ```
+    /// use axum::extract::connect_info::Connected;
+    /// use axum::serve::IncomingStream;
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use axum_tracing_opentelemetry::tracing_opentelemetry_instrumentation_sdk::http::{
+    ///     ConnectionInfo, UnixSocketInfo,
+    /// };
+    /// use std::borrow::Cow;
+    /// use tokio::net::UnixListener;
+    ///
+    /// #[derive(Clone)]
+    /// struct UdsConnectInfo(UnixSocketInfo);
+    ///
+    /// impl Connected<IncomingStream<'_, UnixListener>> for UdsConnectInfo {
+    ///     fn connect_info(stream: IncomingStream<'_, UnixListener>) -> Self {
+    ///         let path = stream.io().local_addr().ok().and_then(|addr| {
+    ///             addr.as_pathname().map(|path| path.display().to_string())
+    ///         });
+    ///         UdsConnectInfo(UnixSocketInfo { path })
+    ///     }
+    /// }
+    ///
+    /// impl ConnectionInfo for UdsConnectInfo {
+    ///     fn network_transport(&self) -> &str {
+    ///         self.0.network_transport()
+    ///     }
+    ///     fn socket_path(&self) -> Option<Cow<'_, str>> {
+    ///         self.0.socket_path()
+    ///     }
+    /// }
+    ///
+    /// let layer = OtelAxumLayer::default().with_connect_info::<UdsConnectInfo>();
+    /// ```
+    #[must_use]
+    pub fn with_connect_info<T>(self) -> Self
+    where
+        T: otel_http::ConnectionInfo + Send + Sync + 'static,
+    {
+        OtelAxumLayer {
+            record_connect_info: Some(record_connect_info::<T>),
+            ..self
+        }
+    }
+
+    /// Insert the `OpenTelemetry` context of the request (with the created span as active span)
+    /// into the extensions of the request, as [`CurrentOtelContext`](super::CurrentOtelContext)
+    /// (also an extractor), for the otel-native libraries (default: `false`).
+    #[must_use]
+    pub fn with_context_extension(self, enabled: bool) -> Self {
+        OtelAxumLayer {
+            context_extension: enabled,
+            ..self
+        }
+    }
+
+    /// Capture the values of the response headers `keys` as `http.response.header.<key>` attributes
+    /// (nothing is captured by default, the value of `set-cookie` is redacted).
+    ///
+    /// When enabled, the rate limiting is also recorded: on `429` & `503` responses, `http.server.throttled = true`
+    /// and `http.response.header.retry-after` (even if not listed into `keys`).
+    ///
+    /// ```rust
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use http::HeaderName;
+    ///
+    /// let layer = OtelAxumLayer::default()
+    ///     .with_response_headers([HeaderName::from_static("x-ratelimit-remaining")]);
+    /// ```
+    #[must_use]
+    pub fn with_response_headers(self, keys: impl IntoIterator<Item = HeaderName>) -> Self {
+        let mut response_headers = self.response_headers.to_vec();
+        response_headers.extend(keys);
+        OtelAxumLayer {
+            response_headers: response_headers.into(),
+            ..self
+        }
+    }
+
+    /// Force the sampling of the requests selected by `force_sampling_for` (eg with the header `x-debug-trace: 1`),
+    /// whatever the decision of the sampler of the sdk or of [`OtelAxumLayer::with_sampling_rate_for`],
+    /// to debug-trace a request on demand without changing the global sampling
+    /// (see [`tracing_opentelemetry_instrumentation_sdk::force_sampling`]).
+    ///
+    /// ```rust
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use tracing_opentelemetry_instrumentation_sdk::http::has_debug_trace_header;
+    ///
+    /// let layer = OtelAxumLayer::default().with_force_sampling_for(has_debug_trace_header);
+    /// ```
+    #[must_use]
+    pub fn with_force_sampling_for(self, force_sampling_for: otel_http::ForceSamplingFor) -> Self {
+        OtelAxumLayer {
+            force_sampling_for: Some(force_sampling_for),
+            ..self
+        }
+    }
+
+    /// Force the sampling of the requests with the force-trace header of `config` (with the secret),
+    /// like [`OtelAxumLayer::with_force_sampling_for`], and propagate the header to the downstream services
+    /// of the trace (see [`otel_http::ForceTraceConfig`]).
+    ///
+    /// ```rust
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use tracing_opentelemetry_instrumentation_sdk::http::ForceTraceConfig;
+    ///
+    /// let config = ForceTraceConfig::new("s3cr3t").expect("a valid header value");
+    /// let layer = OtelAxumLayer::default().with_force_trace(config);
+    /// ```
+    #[must_use]
+    pub fn with_force_trace(self, config: otel_http::ForceTraceConfig) -> Self {
+        OtelAxumLayer {
+            force_trace: Some(config),
+            ..self
+        }
+    }
+
+    /// Call the `hook` for each request without span (rejected by the [`OtelAxumLayer::filter`], not sampled by
+    /// [`OtelAxumLayer::with_sampling_rate_for`],...), eg to count them with the self-metrics of
+    /// `init-tracing-opentelemetry` (`self_metrics::dropped_span_hook`).
+    ///
+    /// ```rust
+    /// use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+    /// use tracing_opentelemetry_instrumentation_sdk::DroppedSpanHook;
+    ///
+    /// let layer = OtelAxumLayer::default().with_dropped_span_hook(DroppedSpanHook::new(|reason| {
+    ///     tracing::debug!(reason = reason.as_str(), "span dropped");
+    /// }));
+    /// ```
+    #[must_use]
+    pub fn with_dropped_span_hook(self, hook: DroppedSpanHook) -> Self {
+        OtelAxumLayer {
+            dropped_span_hook: Some(hook),
+            ..self
+        }
+    }
+}
+
+/// Middleware for [`axum::middleware::from_fn`], same as [`OtelAxumLayer::default()`]
+/// (creation of the span, propagation of the context,...) without constructing a layer.
+///
+/// Useful to instrument only some routes:
+///
+/// ```
+/// use axum::{middleware, routing::get, Router};
+/// use axum_tracing_opentelemetry::middleware::otel_from_fn_middleware;
+///
+/// let app: Router = Router::new()
+///     .route(
+///         "/checkout",
+///         get(|| async {}).route_layer(middleware::from_fn(otel_from_fn_middleware)),
+///     )
+///     .route("/health", get(|| async {}));
+/// ```
+pub async fn otel_from_fn_middleware(
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    match OtelAxumLayer::default().layer(next).call(req).await {
+        Ok(response) => response.map(axum::body::Body::new),
+        Err(never) => match never {},
+    }
 }
 
 impl<S> Layer<S> for OtelAxumLayer {
@@ -84,63 +841,247 @@ impl<S> Layer<S> for OtelAxumLayer {
         OtelAxumService {
             inner,
             filter: self.filter,
+            milestone_events: self.milestone_events,
+            span_kind_for: self.span_kind_for,
+            route_formatter: self.route_formatter,
+            sampling_rates: self.sampling_rates.clone(),
+            extra_known_methods: self.extra_known_methods.clone(),
+            on_response: self.on_response,
+            on_failure: self.on_failure,
+            on_cancellation: self.on_cancellation,
+            record_tenant: self.record_tenant,
+            recording_gate: self.recording_gate,
+            nested_route_policy: self.nested_route_policy,
+            span_naming: self.span_naming,
+            links_header: self.links_header.clone(),
+            enduser_extractor: self.enduser_extractor.clone(),
+            interim_response_events: self.interim_response_events,
+            propagator: self.propagator.clone(),
+            queue_time_policy: self.queue_time_policy,
+            ignore_preflight: self.ignore_preflight,
+            route_metadata: self.route_metadata.clone(),
+            record_connect_info: self.record_connect_info,
+            context_extension: self.context_extension,
+            response_headers: self.response_headers.clone(),
+            force_sampling_for: self.force_sampling_for,
+            force_trace: self.force_trace.clone(),
+            dropped_span_hook: self.dropped_span_hook.clone(),
+            privacy: self.privacy.clone(),
+            ready_wait_start: None,
         }
     }
 }
 
+#[allow(clippy::struct_excessive_bools)] // options of the builder
 #[derive(Debug, Clone)]
 pub struct OtelAxumService<S> {
     inner: S,
     filter: Option<Filter>,
+    milestone_events: bool,
+    span_kind_for: Option<SpanKindFor>,
+    route_formatter: Option<RouteFormatter>,
+    sampling_rates: Arc<[(String, f64)]>,
+    extra_known_methods: Arc<[Method]>,
+    on_response: Option<OnResponse>,
+    on_failure: Option<OnFailure>,
+    on_cancellation: Option<OnCancellation>,
+    record_tenant: Option<RecordTenant>,
+    recording_gate: Option<Duration>,
+    nested_route_policy: NestedRoutePolicy,
+    span_naming: SpanNaming,
+    links_header: Option<HeaderName>,
+    enduser_extractor: Option<Arc<dyn EnduserExtractor>>,
+    interim_response_events: bool,
+    propagator: Option<Arc<dyn TextMapPropagator + Send + Sync>>,
+    queue_time_policy: QueueTimePolicy,
+    ignore_preflight: bool,
+    route_metadata: Arc<HashMap<String, Vec<KeyValue>>>,
+    record_connect_info: Option<RecordConnectInfo>,
+    context_extension: bool,
+    response_headers: Arc<[HeaderName]>,
+    force_sampling_for: Option<otel_http::ForceSamplingFor>,
+    force_trace: Option<otel_http::ForceTraceConfig>,
+    dropped_span_hook: Option<DroppedSpanHook>,
+    privacy: Arc<PrivacyPolicy>,
+    /// since when the inner service is not ready (backpressure), for the next call
+    ready_wait_start: Option<Instant>,
 }
 
-impl<S, B, B2> Service<Request<B>> for OtelAxumService<S>
-where
-    S: Service<Request<B>, Response = Response<B2>> + Clone + Send + 'static,
-    S::Error: Error + 'static, //fmt::Display + 'static,
-    S::Future: Send + 'static,
-    B: Send + 'static,
-{
-    type Response = S::Response;
-    type Error = S::Error;
-    // #[allow(clippy::type_complexity)]
-    // type Future = futures_core::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
-    type Future = ResponseFuture<S::Future>;
-
-    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.inner.poll_ready(cx).map_err(Into::into)
+impl<S> OtelAxumService<S> {
+    fn record_dropped_span(&self, reason: DroppedSpanReason) {
+        if let Some(hook) = &self.dropped_span_hook {
+            hook.record(reason);
+        }
     }
 
-    fn call(&mut self, req: Request<B>) -> Self::Future {
+    fn make_span<B>(
+        &self,
+        req: &Request<B>,
+        forced_sampling: bool,
+        force_trace: Option<&otel_http::ForceTraceConfig>,
+    ) -> Span {
         use tracing_opentelemetry::OpenTelemetrySpanExt;
-        let req = req;
-        let span = if self.filter.map_or(true, |f| f(req.uri().path())) {
-            let span = otel_http::http_server::make_span_from_request(&req);
-            let route = http_route(&req);
-            let method = otel_http::http_method(req.method());
-            // let client_ip = parse_x_forwarded_for(req.headers())
-            //     .or_else(|| {
-            //         req.extensions()
-            //             .get::<ConnectInfo<SocketAddr>>()
-            //             .map(|ConnectInfo(client_ip)| Cow::from(client_ip.to_string()))
-            //     })
-            //     .unwrap_or_default();
-            span.record("http.route", route);
-            span.record("otel.name", format!("{method} {route}").trim());
-            // span.record("trace_id", find_trace_id_from_tracing(&span));
-            // span.record("client.address", client_ip);
-            span.set_parent(otel_http::extract_context(req.headers()));
-            span
-        } else {
-            tracing::Span::none()
+        let span = otel_http::http_server::make_span_from_request_with_extra_known_methods(
+            req,
+            &self.extra_known_methods,
+            &self.privacy,
+        );
+        let nested_route = match self.nested_route_policy {
+            NestedRoutePolicy::MatchedPath => None,
+            NestedRoutePolicy::Compose | NestedRoutePolicy::Attribute => nested_route(req),
         };
-        let future = {
-            let _enter = span.enter();
-            self.inner.call(req)
+        // the route template as matched by the router, the key of the lookups
+        let matched_route = http_route(req);
+        let route = match (self.nested_route_policy, &nested_route) {
+            (NestedRoutePolicy::Compose, Some(nested_route)) => nested_route.as_str(),
+            _ => matched_route,
         };
-        ResponseFuture {
+        let method =
+            otel_http::http_method_with_extra_known(req.method(), &self.extra_known_methods);
+        // let client_ip = parse_x_forwarded_for(req.headers())
+        //     .or_else(|| {
+        //         req.extensions()
+        //             .get::<ConnectInfo<SocketAddr>>()
+        //             .map(|ConnectInfo(client_ip)| Cow::from(client_ip.to_string()))
+        //     })
+        //     .unwrap_or_default();
+        let formatted_route = self
+            .route_formatter
+            .map_or(Cow::Borrowed(route), |f| f(route));
+        span.record("http.route", formatted_route.as_ref());
+        if let (NestedRoutePolicy::Attribute, Some(nested_route)) =
+            (self.nested_route_policy, &nested_route)
+        {
+            let formatted_nested_route = self
+                .route_formatter
+                .map_or(Cow::Borrowed(nested_route.as_str()), |f| f(nested_route));
+            span.set_attribute("http.route.nested", formatted_nested_route.into_owned());
+        }
+        span.record(
+            "otel.name",
+            self.span_naming
+                .span_name(req.method(), &method, &formatted_route),
+        );
+        if let Some(kind) = req
+            .extensions()
+            .get::<SpanKind>()
+            .cloned()
+            .or_else(|| self.span_kind_for.and_then(|f| f(matched_route)))
+        {
+            span.record("otel.kind", tracing::field::debug(kind));
+        }
+        // span.record("trace_id", find_trace_id_from_tracing(&span));
+        // span.record("client.address", client_ip);
+        for attribute in self.route_metadata.get(matched_route).into_iter().flatten() {
+            span.set_attribute(attribute.key.clone(), attribute.value.clone());
+        }
+        if let Some(record_tenant) = self.record_tenant {
+            record_tenant(&span, req.extensions(), &self.privacy);
+        }
+        if let Some(record_connect_info) = self.record_connect_info {
+            record_connect_info(&span, req.extensions());
+        }
+        if let Some(tls_info) = req.extensions().get::<otel_http::http_server::TlsInfo>() {
+            otel_http::http_server::record_tls_info(&span, tls_info);
+        }
+        span.set_parent(match &self.propagator {
+            Some(propagator) => {
+                otel_http::extract_context_with_propagator(propagator.as_ref(), req.headers())
+            }
+            None => otel_http::extract_context(req.headers()),
+        });
+        // before any use of the context of the span (the sampling decision is made on the first use)
+        if let Some(config) = force_trace {
+            otel_http::force_trace(&span, config);
+        } else if forced_sampling {
+            force_sampling(&span);
+        }
+        if self.queue_time_policy != QueueTimePolicy::Ignore {
+            record_queue_time(&span, req.headers(), self.queue_time_policy);
+        }
+        if let Some(links_header) = &self.links_header {
+            for context in otel_http::extract_contexts_multi(req.headers(), links_header) {
+                span.add_link(context.span().span_context().clone());
+            }
+        }
+        if let Some(latency_threshold) = self.recording_gate {
+            mark_span_for_recording_gate(&span, latency_threshold);
+        }
+        span
+    }
+}
+
+impl<S, B, B2> Service<Request<B>> for OtelAxumService<S>
+where
+    S: Service<Request<B>, Response = Response<B2>> + Clone + Send + 'static,
+    S::Error: fmt::Display + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = Response<ResponseBody<B2>>;
+    type Error = S::Error;
+    // #[allow(clippy::type_complexity)]
+    // type Future = futures_core::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let poll = self.inner.poll_ready(cx);
+        if poll.is_pending() {
+            self.ready_wait_start.get_or_insert_with(Instant::now);
+        }
+        poll.map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+        let mut req = req;
+        let force_trace = self
+            .force_trace
+            .as_ref()
+            .filter(|config| config.matches(req.headers()));
+        let forced_sampling = force_trace.is_some()
+            || self
+                .force_sampling_for
+                .is_some_and(|f| f(req.uri(), req.headers()));
+        let span = if !self.filter.map_or(true, |f| f(req.uri().path()))
+            || (self.ignore_preflight && is_cors_preflight(&req))
+        {
+            self.record_dropped_span(DroppedSpanReason::Filter);
+            tracing::Span::none()
+        } else if !(forced_sampling || is_sampled(&self.sampling_rates, &req)) {
+            self.record_dropped_span(DroppedSpanReason::Sampling);
+            tracing::Span::none()
+        } else {
+            let span = self.make_span(&req, forced_sampling, force_trace);
+            if self.context_extension {
+                req.extensions_mut()
+                    .insert(super::CurrentOtelContext(span.context()));
+            }
+            span
+        };
+        if let Some(ready_wait_start) = self.ready_wait_start.take() {
+            record_ready_wait(&span, ready_wait_start.elapsed());
+        }
+        let milestone_events = self.milestone_events && !span.is_disabled();
+        if milestone_events {
+            tracing::event!(target: TRACING_TARGET, parent: &span, TRACING_LEVEL, "request.received");
+        }
+        let future = {
+            let _enter = span.enter();
+            self.inner.call(req)
+        };
+        ResponseFuture {
             inner: future,
             span,
+            milestone_events,
+            on_response: self.on_response,
+            on_failure: self.on_failure,
+            on_cancellation: self.on_cancellation,
+            enduser_extractor: self.enduser_extractor.clone(),
+            privacy: self.privacy.clone(),
+            interim_response_events: self.interim_response_events,
+            response_headers: self.response_headers.clone(),
+            completed: false,
         }
     }
 }
@@ -153,24 +1094,254 @@ pin_project! {
         #[pin]
         pub(crate) inner: F,
         pub(crate) span: Span,
+        pub(crate) milestone_events: bool,
+        pub(crate) on_response: Option<OnResponse>,
+        pub(crate) on_failure: Option<OnFailure>,
+        pub(crate) on_cancellation: Option<OnCancellation>,
+        pub(crate) enduser_extractor: Option<Arc<dyn EnduserExtractor>>,
+        pub(crate) privacy: Arc<PrivacyPolicy>,
+        pub(crate) interim_response_events: bool,
+        pub(crate) response_headers: Arc<[HeaderName]>,
+        pub(crate) completed: bool,
         // pub(crate) start: Instant,
     }
+
+    impl<F> PinnedDrop for ResponseFuture<F> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if !*this.completed && !this.span.is_disabled() {
+                this.on_cancellation
+                    .unwrap_or(otel_http::http_server::update_span_from_cancellation)(this.span);
+            }
+        }
+    }
 }
 
 impl<Fut, ResBody, E> Future for ResponseFuture<Fut>
 where
     Fut: Future<Output = Result<Response<ResBody>, E>>,
-    E: std::error::Error + 'static,
+    E: fmt::Display + 'static,
 {
-    type Output = Result<Response<ResBody>, E>;
+    type Output = Result<Response<ResponseBody<ResBody>>, E>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
         let _guard = this.span.enter();
-        let result = futures_util::ready!(this.inner.poll(cx));
-        otel_http::http_server::update_span_from_response_or_error(this.span, &result);
+        let mut result = futures_util::ready!(this.inner.poll(cx));
+        *this.completed = true;
+        if let Some(enduser_extractor) = this.enduser_extractor.as_deref() {
+            if !this.span.is_disabled() {
+                result = result.map(|response| {
+                    record_enduser_from_response(
+                        this.span,
+                        enduser_extractor,
+                        this.privacy,
+                        response,
+                    )
+                });
+            }
+        }
+        if let (Ok(response), true) = (&result, *this.interim_response_events) {
+            if let Some(interim_responses) = response
+                .extensions()
+                .get::<otel_http::http_server::InterimResponses>()
+            {
+                otel_http::http_server::record_interim_responses(this.span, &interim_responses.0);
+            }
+        }
+        if let (Ok(response), false) = (&result, this.response_headers.is_empty()) {
+            otel_http::http_server::record_response_headers(
+                this.span,
+                response.status(),
+                response.headers(),
+                this.response_headers,
+            );
+        }
+        match (&result, *this.on_response, *this.on_failure) {
+            (Ok(response), Some(on_response), _) => {
+                on_response(this.span, response.status(), response.headers());
+            }
+            (Ok(response), None, _) => {
+                otel_http::http_server::update_span_from_response(this.span, response);
+            }
+            (Err(err), _, Some(on_failure)) => on_failure(this.span, err),
+            (Err(err), _, None) => update_span_from_error(this.span, err),
+        }
+        let span = this.milestone_events.then(|| this.span.clone());
+        Poll::Ready(result.map(|response| response.map(|body| ResponseBody::new(body, span))))
+    }
+}
+
+pin_project! {
+    /// Response body for [`OtelAxumService`].
+    ///
+    /// When milestone events are enabled, it holds the span (to keep it open) and records
+    /// the events `response.first_byte` and `response.end_of_stream`.
+    pub struct ResponseBody<B> {
+        #[pin]
+        inner: B,
+        span: Option<Span>,
+        first_byte_sent: bool,
+        end_of_stream_sent: bool,
+    }
+}
+
+impl<B> ResponseBody<B> {
+    fn new(inner: B, span: Option<Span>) -> Self {
+        ResponseBody {
+            inner,
+            span,
+            first_byte_sent: false,
+            end_of_stream_sent: false,
+        }
+    }
+}
+
+impl<B> Body for ResponseBody<B>
+where
+    B: Body,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let mut inner = this.inner;
+        let result = futures_util::ready!(inner.as_mut().poll_frame(cx));
+        if let Some(span) = this.span.as_ref() {
+            if !*this.first_byte_sent && matches!(&result, Some(Ok(frame)) if frame.is_data()) {
+                *this.first_byte_sent = true;
+                tracing::event!(target: TRACING_TARGET, parent: span, TRACING_LEVEL, "response.first_byte");
+            }
+            if !*this.end_of_stream_sent && (result.is_none() || inner.is_end_stream()) {
+                *this.end_of_stream_sent = true;
+                tracing::event!(target: TRACING_TARGET, parent: span, TRACING_LEVEL, "response.end_of_stream");
+            }
+        }
         Poll::Ready(result)
     }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Record the time spent waiting for the readiness of the inner service (eg a buffer or a concurrency limit is full)
+/// before the call, as `service.ready_wait` (in seconds).
+fn record_ready_wait(span: &Span, ready_wait: Duration) {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    if !span.is_disabled() {
+        span.set_attribute("service.ready_wait", ready_wait.as_secs_f64());
+    }
+}
+
+type RecordConnectInfo = fn(&Span, &http::Extensions);
+
+fn record_connect_info<T>(span: &Span, extensions: &http::Extensions)
+where
+    T: otel_http::ConnectionInfo + Send + Sync + 'static,
+{
+    if let Some(ConnectInfo(connection_info)) = extensions.get::<ConnectInfo<T>>() {
+        otel_http::record_connection_info(span, connection_info);
+    }
+}
+
+/// Same as [`otel_http::http_server::update_span_from_error`] but for any displayable error
+/// (the error of a tower stack is not always a `std::error::Error`, eg `BoxError`).
+fn update_span_from_error(span: &Span, error: &dyn fmt::Display) {
+    span.record("otel.status_code", "ERROR");
+    span.record(
+        "exception.message",
+        truncate_attribute_value(&error.to_string()).as_ref(),
+    );
+}
+
+fn record_queue_time(span: &Span, headers: &HeaderMap, queue_time_policy: QueueTimePolicy) {
+    use opentelemetry::trace::{Span as _, Tracer as _};
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let Some(request_start) = otel_http::http_server::request_start_from_headers(headers) else {
+        return;
+    };
+    let now = SystemTime::now();
+    // ignore the start in the future (clock skew)
+    let Ok(queue_duration) = now.duration_since(request_start) else {
+        return;
+    };
+    span.set_attribute("http.server.queue_duration", queue_duration.as_secs_f64());
+    if queue_time_policy == QueueTimePolicy::ChildSpan {
+        let tracer = opentelemetry::global::tracer(env!("CARGO_PKG_NAME"));
+        let mut queue_span = tracer
+            .span_builder("http.server.queue")
+            .with_kind(SpanKind::Internal)
+            .with_start_time(request_start)
+            .start_with_context(&tracer, &span.context());
+        queue_span.end_with_timestamp(now);
+    }
+}
+
+fn record_enduser_from_response<B>(
+    span: &Span,
+    enduser_extractor: &dyn EnduserExtractor,
+    privacy: &PrivacyPolicy,
+    response: Response<B>,
+) -> Response<B> {
+    let (parts, body) = response.into_parts();
+    if let Some(enduser) = enduser_extractor.extract(&parts) {
+        record_enduser(span, &enduser, privacy);
+    }
+    Response::from_parts(parts, body)
+}
+
+fn is_cors_preflight<B>(req: &Request<B>) -> bool {
+    req.method() == Method::OPTIONS
+        && req
+            .headers()
+            .contains_key(http::header::ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+fn is_sampled<B>(sampling_rates: &[(String, f64)], req: &Request<B>) -> bool {
+    if sampling_rates.is_empty() {
+        return true;
+    }
+    let route = Some(http_route(req))
+        .filter(|r| !r.is_empty())
+        .unwrap_or_else(|| req.uri().path());
+    sampling_rates
+        .iter()
+        .find(|(glob, _)| glob_match(glob, route))
+        .map_or(true, |(_, rate)| rand::random::<f64>() < *rate)
+}
+
+/// Match `text` against `pattern` where `*` matches any sequence of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(first) = parts.next() else {
+        return text.is_empty();
+    };
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middles)) = parts.split_last() else {
+        // no `*`
+        return rest.is_empty();
+    };
+    for middle in middles {
+        match rest.find(middle) {
+            Some(i) => rest = &rest[i + middle.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
 }
 
 #[inline]
@@ -180,15 +1351,71 @@ fn http_route<B>(req: &Request<B>) -> &str {
         .map_or_else(|| "", |mp| mp.as_str())
 }
 
+/// The route prefixed by the mount prefix of the nested router, `None` if not nested
+/// or if the route already includes the prefix
+fn nested_route<B>(req: &Request<B>) -> Option<String> {
+    let original_path = req.extensions().get::<OriginalUri>()?.path();
+    let prefix = original_path
+        .strip_suffix(req.uri().path())
+        .filter(|prefix| !prefix.is_empty())?;
+    let route = http_route(req);
+    let has_prefix = route
+        .strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+    (!has_prefix).then(|| format!("{prefix}{route}"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::middleware::Enduser;
     use axum::{body::Body, routing::get, Router};
+    use fake_opentelemetry_collector::{ExportedSpan, ExportedSpansExt};
     use http::{Request, StatusCode};
     use rstest::rstest;
-    use testing_tracing_opentelemetry::{assert_trace, FakeEnvironment};
+    use serde_json::Value;
+    use testing_tracing_opentelemetry::{assert_trace, otel_test};
     use tower::Service;
 
+    async fn call_router(uri: &str, headers: &[(&str, &str)]) {
+        let mut svc = Router::new()
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .route(
+                "/status/500",
+                get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+            )
+            .route(
+                "/with_child_span",
+                get(|| async {
+                    let span = tracing::span!(tracing::Level::INFO, "my child span");
+                    span.in_scope(|| {
+                        // Any trace events in this closure or code called by it will occur within
+                        // the span.
+                    });
+                    StatusCode::OK
+                }),
+            )
+            .nest(
+                "/nest",
+                Router::new()
+                    .route("/{nest_id}", get(|| async {}))
+                    .fallback(|| async { (StatusCode::NOT_FOUND, "inner fallback") }),
+            )
+            .fallback(|| async { (StatusCode::NOT_FOUND, "outer fallback") })
+            .layer(opentelemetry_tracing_layer());
+        let mut builder = Request::builder();
+        for (key, value) in headers {
+            builder = builder.header(*key, *value);
+        }
+        let req = builder.uri(uri).body(Body::empty()).unwrap();
+        let _res = svc.call(req).await.unwrap();
+
+        // while res.data().await.is_some() {}
+        // res.trailers().await.unwrap();
+        // drop(res);
+    }
+
+    #[otel_test(run = call_router(uri, headers))]
     #[rstest]
     #[case("filled_http_route_for_existing_route", "http://example.com/users/123", &[], false)]
     #[case("empty_http_route_for_nonexisting_route", "/idontexist/123", &[], false)]
@@ -202,52 +1429,1106 @@ mod tests {
     // - https://github.com/davidB/axum-tracing-opentelemetry/pull/54 (reverted)
     // - https://github.com/tokio-rs/axum/issues/1441#issuecomment-1272158039
     #[case("extract_route_from_nested", "/nest/123", &[], false)]
-    #[tokio::test(flavor = "multi_thread")]
     async fn check_span_event(
         #[case] name: &str,
         #[case] uri: &str,
         #[case] headers: &[(&str, &str)],
         #[case] is_trace_id_constant: bool,
+        tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
     ) {
-        let mut fake_env = FakeEnvironment::setup().await;
-        {
-            let mut svc = Router::new()
+        assert_trace(name, tracing_events, otel_spans, is_trace_id_constant);
+    }
+
+    async fn call_with_span_kind_for(uri: &str, span_kind: Option<SpanKind>) {
+        let mut svc = Router::new()
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .route("/webhooks/{source}", get(|| async { StatusCode::OK }))
+            .layer(OtelAxumLayer::default().with_span_kind_for(|route| {
+                route
+                    .starts_with("/webhooks/")
+                    .then_some(SpanKind::Consumer)
+            }));
+        let mut builder = Request::builder().uri(uri);
+        if let Some(span_kind) = span_kind {
+            builder = builder.extension(span_kind);
+        }
+        let req = builder.body(Body::empty()).unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_span_kind_for(uri, span_kind))]
+    #[rstest]
+    #[case("span_kind_for_route", "/webhooks/github", None)]
+    #[case("span_kind_from_extension", "/users/123", Some(SpanKind::Producer))]
+    async fn check_span_event_with_span_kind(
+        #[case] name: &str,
+        #[case] uri: &str,
+        #[case] span_kind: Option<SpanKind>,
+        tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_trace(name, tracing_events, otel_spans, false);
+    }
+
+    async fn call_with_route_formatter() {
+        let mut svc = Router::new()
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .layer(OtelAxumLayer::default().with_route_formatter(colon_route_formatter));
+        let req = Request::builder()
+            .uri("/users/123")
+            .body(Body::empty())
+            .unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_route_formatter)]
+    async fn check_span_event_with_route_formatter(
+        tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_trace("route_formatter", tracing_events, otel_spans, false);
+    }
+
+    #[rstest]
+    #[case("", "")]
+    #[case("/users", "/users")]
+    #[case("/users/{id}", "/users/:id")]
+    #[case("/users/{id}/posts/{post_id}", "/users/:id/posts/:post_id")]
+    #[case("/files/{*path}", "/files/*path")]
+    #[case("/broken/{id", "/broken/{id")]
+    fn test_colon_route_formatter(#[case] route: &str, #[case] expected: &str) {
+        assert_eq!(colon_route_formatter(route), expected);
+    }
+
+    #[rstest]
+    #[case("/health", "/health", true)]
+    #[case("/health", "/healthz", false)]
+    #[case("/health*", "/healthz", true)]
+    #[case("*", "/users/{id}", true)]
+    #[case("/users/*/posts", "/users/{id}/posts", true)]
+    #[case("/users/*/posts", "/users/{id}/comments", false)]
+    #[case("*/metrics", "/internal/metrics", true)]
+    #[case("/a*b*c", "/abc", true)]
+    #[case("/a*b*c", "/acb", false)]
+    fn test_glob_match(#[case] pattern: &str, #[case] text: &str, #[case] expected: bool) {
+        assert_eq!(glob_match(pattern, text), expected);
+    }
+
+    #[test]
+    fn sample_the_requests_with_the_rate_of_the_route() {
+        let sampling_rates = [("/health".to_string(), 0.5)];
+        let req = Request::builder()
+            .uri("/health")
+            .body(Body::empty())
+            .unwrap();
+        let sampled = (0..10_000)
+            .filter(|_| is_sampled(&sampling_rates, &req))
+            .count();
+        assert!((4_000..6_000).contains(&sampled), "sampled: {sampled}");
+    }
+
+    async fn call_with_sampling_rates() -> Vec<DroppedSpanReason> {
+        let dropped = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut svc = Router::new()
+            .route("/health", get(|| async { StatusCode::OK }))
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .layer(
+                OtelAxumLayer::default()
+                    .with_sampling_rate_for("/health", 0.0)
+                    .with_sampling_rate_for("/users/*", 1.0)
+                    .with_dropped_span_hook(DroppedSpanHook::new({
+                        let dropped = dropped.clone();
+                        move |reason| dropped.lock().unwrap().push(reason)
+                    })),
+            );
+        for uri in ["/health", "/users/123", "/health"] {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let _res = svc.call(req).await.unwrap();
+        }
+        let dropped = dropped.lock().unwrap().clone();
+        dropped
+    }
+
+    #[otel_test(run = call_with_sampling_rates)]
+    async fn check_no_span_when_not_sampled_by_route(
+        dropped: Vec<DroppedSpanReason>,
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(dropped, [DroppedSpanReason::Sampling; 2]);
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(otel_spans.spans_named("GET /users/{id}").len(), 1);
+    }
+
+    async fn call_with_cors_preflight() {
+        let mut svc = Router::new()
+            .route(
+                "/users/{id}",
+                get(|| async { StatusCode::OK }).options(|| async { StatusCode::NO_CONTENT }),
+            )
+            .layer(OtelAxumLayer::default().ignore_preflight(true));
+        let preflight = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/users/123")
+            .header("access-control-request-method", "GET")
+            .body(Body::empty())
+            .unwrap();
+        let options = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/users/123")
+            .body(Body::empty())
+            .unwrap();
+        let actual = Request::builder()
+            .uri("/users/123")
+            .body(Body::empty())
+            .unwrap();
+        for req in [preflight, options, actual] {
+            let _res = svc.call(req).await.unwrap();
+        }
+    }
+
+    #[otel_test(run = call_with_cors_preflight)]
+    async fn check_no_span_for_cors_preflight(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 2);
+        assert_eq!(otel_spans.spans_named("OPTIONS /users/{id}").len(), 1);
+        assert_eq!(otel_spans.spans_named("GET /users/{id}").len(), 1);
+    }
+
+    async fn call_with_custom_on_response() {
+        let mut svc = Router::new()
+            .route("/conflict", get(|| async { StatusCode::CONFLICT }))
+            .layer(
+                OtelAxumLayer::default().with_on_response(|span, status, _headers| {
+                    otel_http::http_server::update_span_from_status(span, status);
+                    if status == StatusCode::CONFLICT {
+                        span.record("otel.status_code", "ERROR");
+                    }
+                }),
+            );
+        let req = Request::builder()
+            .uri("/conflict")
+            .body(Body::empty())
+            .unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_custom_on_response)]
+    async fn check_span_status_with_custom_on_response(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(
+            otel_spans[0].attributes.get("http.response.status_code"),
+            Some(&"409".into())
+        );
+        assert_eq!(
+            otel_spans[0].status_code(),
+            fake_opentelemetry_collector::StatusCode::Error
+        );
+    }
+
+    async fn call_with_nonstandard_method(extra_known_methods: &[&str]) {
+        let mut svc = Router::new()
+            .route("/cache", axum::routing::any(|| async { StatusCode::OK }))
+            .layer(
+                OtelAxumLayer::default().with_extra_known_methods(
+                    extra_known_methods
+                        .iter()
+                        .map(|m| Method::from_bytes(m.as_bytes()).unwrap()),
+                ),
+            );
+        let req = Request::builder()
+            .method("PURGE")
+            .uri("/cache")
+            .body(Body::empty())
+            .unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_nonstandard_method(extra_known_methods))]
+    #[rstest]
+    #[case(&[], "_OTHER", Some("PURGE"), "HTTP /cache")]
+    #[case(&["PURGE"], "PURGE", None, "PURGE /cache")]
+    async fn check_span_for_nonstandard_method(
+        #[case] extra_known_methods: &[&str],
+        #[case] expected_method: &str,
+        #[case] expected_method_original: Option<&str>,
+        #[case] expected_name: &str,
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(otel_spans[0].name, expected_name);
+        assert_eq!(
+            otel_spans[0].attributes.get("http.request.method"),
+            Some(&expected_method.into())
+        );
+        assert_eq!(
+            otel_spans[0].attributes.get("http.request.method_original"),
+            expected_method_original.map(Into::into).as_ref()
+        );
+    }
+
+    async fn call_with_tenant_info() {
+        #[derive(Clone)]
+        struct Claims {
+            sub: &'static str,
+            org: &'static str,
+        }
+
+        impl TenantInfo for Claims {
+            fn tenant_id(&self) -> Option<&str> {
+                Some(self.org)
+            }
+
+            fn enduser_id(&self) -> Option<&str> {
+                Some(self.sub)
+            }
+        }
+
+        let mut svc = Router::new()
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .layer(OtelAxumLayer::default().with_tenant_info::<Claims>())
+            .layer(axum::Extension(Claims {
+                sub: "user-42",
+                org: "acme",
+            }));
+        let req = Request::builder()
+            .uri("/users/42")
+            .body(Body::empty())
+            .unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_tenant_info)]
+    async fn check_span_with_tenant_info(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(
+            otel_spans[0].attributes.get("tenant.id"),
+            Some(&"acme".into())
+        );
+        assert_eq!(
+            otel_spans[0].attributes.get("enduser.id"),
+            Some(&"user-42".into())
+        );
+    }
+
+    async fn call_with_tls_info() {
+        let mut svc = Router::new()
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .layer(OtelAxumLayer::default());
+        let mut req = Request::builder()
+            .uri("/users/42")
+            .body(Body::empty())
+            .unwrap();
+        // as inserted by the acceptor
+        req.extensions_mut().insert(
+            otel_http::http_server::TlsInfo::default()
+                .with_protocol_version("TLSv1_3")
+                .with_cipher("TLS13_AES_128_GCM_SHA256")
+                .with_alpn_protocol("h2"),
+        );
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_tls_info)]
+    async fn check_span_with_tls_info(_tracing_events: Vec<Value>, otel_spans: Vec<ExportedSpan>) {
+        assert_eq!(otel_spans.len(), 1);
+        let attributes = &otel_spans[0].attributes;
+        assert_eq!(attributes.get("tls.protocol.version"), Some(&"1.3".into()));
+        assert_eq!(
+            attributes.get("tls.cipher"),
+            Some(&"TLS13_AES_128_GCM_SHA256".into())
+        );
+        assert_eq!(attributes.get("tls.next_protocol"), Some(&"h2".into()));
+        assert_eq!(
+            attributes.get("network.protocol.name"),
+            Some(&"http".into())
+        );
+    }
+
+    async fn call_with_route_metadata() {
+        let mut svc = Router::new()
+            .route("/payments/{id}", get(|| async { StatusCode::OK }))
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .layer(
+                OtelAxumLayer::default().with_route_metadata(HashMap::from([(
+                    "/payments/{id}".to_string(),
+                    vec![
+                        KeyValue::new("slo.tier", "critical"),
+                        KeyValue::new("team", "payments"),
+                    ],
+                )])),
+            );
+        for uri in ["/payments/42", "/users/42"] {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let _res = svc.call(req).await.unwrap();
+        }
+    }
+
+    #[otel_test(run = call_with_route_metadata)]
+    async fn check_span_with_route_metadata(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 2);
+        for span in &otel_spans {
+            let attributes = &span.attributes;
+            if attributes.get("http.route") == Some(&"/payments/{id}".into()) {
+                assert_eq!(attributes.get("slo.tier"), Some(&"critical".into()));
+                assert_eq!(attributes.get("team"), Some(&"payments".into()));
+            } else {
+                assert_eq!(attributes.get("slo.tier"), None);
+            }
+        }
+    }
+
+    async fn call_with_unix_socket_connect_info() {
+        let mut svc = Router::new()
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .layer(OtelAxumLayer::default().with_connect_info::<otel_http::UnixSocketInfo>());
+        let mut req = Request::builder()
+            .uri("/users/42")
+            .body(Body::empty())
+            .unwrap();
+        // as inserted by `into_make_service_with_connect_info`
+        req.extensions_mut()
+            .insert(ConnectInfo(otel_http::UnixSocketInfo::new("/run/app.sock")));
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_unix_socket_connect_info)]
+    async fn check_span_with_unix_socket_connect_info(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        let attributes = &otel_spans[0].attributes;
+        assert_eq!(attributes.get("network.transport"), Some(&"unix".into()));
+        assert_eq!(
+            attributes.get("server.socket.path"),
+            Some(&"/run/app.sock".into())
+        );
+    }
+
+    async fn call_with_context_extension() -> axum::body::Bytes {
+        use crate::middleware::CurrentOtelContext;
+
+        let mut svc = Router::new()
+            .route(
+                "/users/{id}",
+                get(|CurrentOtelContext(cx): CurrentOtelContext| async move {
+                    cx.span().span_context().trace_id().to_string()
+                }),
+            )
+            .layer(OtelAxumLayer::default().with_context_extension(true));
+        let req = Request::builder()
+            .uri("/users/42")
+            .body(Body::empty())
+            .unwrap();
+        let response = svc.call(req).await.unwrap();
+        axum::body::to_bytes(Body::new(response.into_body()), usize::MAX)
+            .await
+            .unwrap()
+    }
+
+    #[otel_test(run = call_with_context_extension)]
+    async fn check_context_extension(
+        body: axum::body::Bytes,
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(body, otel_spans[0].trace_id.as_bytes());
+    }
+
+    async fn call_with_from_fn_middleware() {
+        let mut svc = Router::new()
+            .route(
+                "/users/{id}",
+                get(|| async { StatusCode::OK })
+                    .route_layer(axum::middleware::from_fn(super::otel_from_fn_middleware)),
+            )
+            .route("/health", get(|| async { StatusCode::OK }));
+        for uri in ["/users/42", "/health"] {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let _res = svc.call(req).await.unwrap();
+        }
+    }
+
+    #[otel_test(run = call_with_from_fn_middleware)]
+    async fn check_span_with_from_fn_middleware(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(otel_spans[0].name, "GET /users/{id}");
+        assert_eq!(
+            otel_spans[0].attributes.get("http.route"),
+            Some(&"/users/{id}".into())
+        );
+    }
+
+    async fn call_with_enduser(privacy_mode: PrivacyMode) {
+        #[derive(Clone)]
+        struct Claims {
+            sub: &'static str,
+            role: &'static str,
+        }
+
+        let mut svc = Router::new()
+            .route(
+                "/users/{id}",
+                get(|| async {
+                    // eg set by the auth layer, after the verification of the token
+                    let claims = Claims {
+                        sub: "user-42",
+                        role: "admin",
+                    };
+                    (axum::Extension(claims), StatusCode::OK)
+                }),
+            )
+            .layer(
+                OtelAxumLayer::default()
+                    .with_enduser(|parts: &http::response::Parts| {
+                        parts.extensions.get::<Claims>().map(|claims| Enduser {
+                            id: Some(claims.sub.to_string()),
+                            role: Some(claims.role.to_string()),
+                            scope: None,
+                        })
+                    })
+                    .with_privacy_mode(privacy_mode),
+            );
+        let req = Request::builder()
+            .uri("/users/42")
+            .header("user-agent", "tests")
+            .body(Body::empty())
+            .unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_enduser(privacy_mode.clone()))]
+    #[rstest]
+    #[case(PrivacyMode::Off)]
+    #[case(PrivacyMode::Drop)]
+    #[case(PrivacyMode::Hash { salt: "salt".to_string() })]
+    async fn check_span_with_enduser(
+        #[case] privacy_mode: PrivacyMode,
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        let policy = PrivacyPolicy::new(privacy_mode);
+        let attribute = |key| otel_spans[0].attributes.get(key).and_then(|v| v.as_str());
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(
+            attribute("enduser.id"),
+            policy.protect("enduser.id", "user-42").as_deref()
+        );
+        assert_eq!(
+            attribute("user_agent.original"),
+            policy.protect("user_agent.original", "tests").as_deref()
+        );
+        // not an identifier, never protected
+        assert_eq!(
+            otel_spans[0].attributes.get("enduser.role"),
+            Some(&"admin".into())
+        );
+        assert_eq!(otel_spans[0].attributes.get("enduser.scope"), None);
+    }
+
+    async fn call_with_interim_response_events() {
+        use otel_http::http_server::InterimResponses;
+
+        let mut svc = Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    let interim = vec![StatusCode::EARLY_HINTS];
+                    (axum::Extension(InterimResponses(interim)), StatusCode::OK)
+                }),
+            )
+            .route("/ws", get(|| async { StatusCode::SWITCHING_PROTOCOLS }))
+            .layer(OtelAxumLayer::default().with_interim_response_events(true));
+        for uri in ["/", "/ws"] {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let _res = svc.call(req).await.unwrap();
+        }
+    }
+
+    #[otel_test(run = call_with_interim_response_events)]
+    async fn check_span_with_interim_response_events(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 2);
+        let span_for = |route: &str| {
+            otel_spans
+                .iter()
+                .find(|span| span.attributes.get("http.route") == Some(&route.into()))
+                .unwrap()
+        };
+        let span = span_for("/");
+        assert_eq!(
+            span.attributes.get("http.response.status_code"),
+            Some(&"200".into())
+        );
+        let interim_events = span
+            .events
+            .iter()
+            .filter(|event| event.name == "http.response.interim")
+            .collect::<Vec<_>>();
+        assert_eq!(interim_events.len(), 1);
+        assert_eq!(
+            interim_events[0]
+                .attributes
+                .get("http.response.status_code"),
+            Some(&"103".into())
+        );
+        let span = span_for("/ws");
+        assert_eq!(
+            span.attributes.get("http.response.status_code"),
+            Some(&"101".into())
+        );
+        assert_eq!(
+            span.status_code(),
+            fake_opentelemetry_collector::StatusCode::Unset
+        );
+        assert!(span.events.is_empty());
+    }
+
+    async fn call_with_propagator(
+        propagator: Arc<dyn TextMapPropagator + Send + Sync>,
+        header: (&str, &str),
+    ) {
+        let mut svc = Router::new()
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .layer(OtelAxumLayer::default().with_propagator(propagator));
+        let req = Request::builder()
+            .uri("/users/42")
+            .header(header.0, header.1)
+            .body(Body::empty())
+            .unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_propagator(propagator.clone(), header))]
+    #[rstest]
+    #[case(
+        Arc::new(opentelemetry_zipkin::Propagator::with_encoding(opentelemetry_zipkin::B3Encoding::SingleHeader)),
+        ("b3", "b2611246a58fd7ea623d2264c5a1e226-b2c9b811f2f424af-1")
+    )]
+    #[case(
+        Arc::new(opentelemetry_jaeger_propagator::Propagator::new()),
+        ("uber-trace-id", "b2611246a58fd7ea623d2264c5a1e226:b2c9b811f2f424af:0:1")
+    )]
+    async fn check_span_parent_with_propagator(
+        #[case] propagator: Arc<dyn TextMapPropagator + Send + Sync>,
+        #[case] header: (&str, &str),
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(otel_spans[0].trace_id, "b2611246a58fd7ea623d2264c5a1e226");
+        assert_eq!(otel_spans[0].parent_span_id, "b2c9b811f2f424af");
+    }
+
+    async fn call_with_queue_duration() {
+        let mut svc = Router::new()
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .layer(OtelAxumLayer::default().with_queue_time(QueueTimePolicy::Attribute));
+        let request_start = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            - Duration::from_millis(250);
+        let req = Request::builder()
+            .uri("/users/42")
+            .header(
+                "x-request-start",
+                format!("t={}", request_start.as_millis()),
+            )
+            .body(Body::empty())
+            .unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_queue_duration)]
+    async fn check_span_with_queue_duration(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        let queue_duration = otel_spans[0].attributes.get("http.server.queue_duration");
+        assert!(
+            matches!(queue_duration, Some(fake_opentelemetry_collector::AttrValue::Double(v)) if *v >= 0.25 && *v < 10.0),
+            "{queue_duration:?}"
+        );
+    }
+
+    async fn call_with_span_naming(span_naming: SpanNaming, method: &str) {
+        let mut svc = Router::new()
+            .route(
+                "/users/{id}",
+                get(|| async { StatusCode::OK })
+                    .post(|| async { StatusCode::OK })
+                    .options(|| async { StatusCode::OK }),
+            )
+            .layer(OtelAxumLayer::default().with_span_naming(span_naming));
+        let req = Request::builder()
+            .method(method)
+            .uri("/users/42")
+            .body(Body::empty())
+            .unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_span_naming(span_naming, method))]
+    #[rstest]
+    #[case(SpanNaming::MethodAndRoute, "POST", "POST /users/{id}")]
+    #[case(SpanNaming::MethodAndRoute, "HEAD", "HEAD /users/{id}")]
+    #[case(SpanNaming::Route, "POST", "/users/{id}")]
+    #[case(SpanNaming::RouteForHeadAndOptions, "POST", "POST /users/{id}")]
+    #[case(SpanNaming::RouteForHeadAndOptions, "HEAD", "/users/{id}")]
+    #[case(SpanNaming::RouteForHeadAndOptions, "OPTIONS", "/users/{id}")]
+    async fn check_span_name_with_span_naming(
+        #[case] span_naming: SpanNaming,
+        #[case] method: &str,
+        #[case] expected_name: &str,
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(otel_spans[0].name, expected_name);
+        assert_eq!(
+            otel_spans[0].attributes.get("http.request.method"),
+            Some(&method.into())
+        );
+    }
+
+    async fn call_with_nested_route_policy(policy: NestedRoutePolicy, uri: &str) {
+        let mut svc = Router::new().nest(
+            "/api",
+            Router::new()
+                .route("/users/{id}", get(|| async { StatusCode::OK }))
+                .fallback(|| async { StatusCode::NOT_FOUND })
+                .layer(OtelAxumLayer::default().with_nested_route_policy(policy)),
+        );
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_nested_route_policy(policy, uri))]
+    #[rstest]
+    #[case(NestedRoutePolicy::MatchedPath, "/api/other", "", None)]
+    #[case(NestedRoutePolicy::Compose, "/api/other", "/api", None)]
+    #[case(NestedRoutePolicy::Attribute, "/api/other", "", Some("/api"))]
+    #[case(NestedRoutePolicy::Compose, "/api/users/1", "/api/users/{id}", None)]
+    #[case(NestedRoutePolicy::Attribute, "/api/users/1", "/api/users/{id}", None)]
+    async fn check_span_route_with_nested_route_policy(
+        #[case] policy: NestedRoutePolicy,
+        #[case] uri: &str,
+        #[case] expected_route: &str,
+        #[case] expected_nested_route: Option<&str>,
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(
+            otel_spans[0].attributes.get("http.route"),
+            Some(&expected_route.into())
+        );
+        assert_eq!(
+            otel_spans[0].attributes.get("http.route.nested"),
+            expected_nested_route.map(Into::into).as_ref()
+        );
+    }
+
+    async fn call_with_composed_nested_route() {
+        let mut svc = Router::new().nest(
+            "/api",
+            Router::new()
                 .route("/users/{id}", get(|| async { StatusCode::OK }))
-                .route(
-                    "/status/500",
-                    get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
-                )
-                .route(
-                    "/with_child_span",
-                    get(|| async {
-                        let span = tracing::span!(tracing::Level::INFO, "my child span");
-                        span.in_scope(|| {
-                            // Any trace events in this closure or code called by it will occur within
-                            // the span.
-                        });
-                        StatusCode::OK
-                    }),
-                )
-                .nest(
-                    "/nest",
-                    Router::new()
-                        .route("/{nest_id}", get(|| async {}))
-                        .fallback(|| async { (StatusCode::NOT_FOUND, "inner fallback") }),
-                )
-                .fallback(|| async { (StatusCode::NOT_FOUND, "outer fallback") })
-                .layer(opentelemetry_tracing_layer());
-            let mut builder = Request::builder();
-            for (key, value) in headers {
-                builder = builder.header(*key, *value);
-            }
-            let req = builder.uri(uri).body(Body::empty()).unwrap();
+                .fallback(|| async { StatusCode::NOT_FOUND })
+                .layer(
+                    OtelAxumLayer::default()
+                        .with_nested_route_policy(NestedRoutePolicy::Compose)
+                        .with_route_metadata(HashMap::from([
+                            ("/api".to_string(), vec![KeyValue::new("team", "api")]),
+                            (
+                                "/api/users/{id}".to_string(),
+                                vec![KeyValue::new("team", "users")],
+                            ),
+                        ]))
+                        .with_span_kind_for(|route| {
+                            (route == "/api").then_some(SpanKind::Consumer)
+                        }),
+                ),
+        );
+        for uri in ["/api/users/1", "/api/other"] {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
             let _res = svc.call(req).await.unwrap();
+        }
+    }
 
-            // while res.data().await.is_some() {}
-            // res.trailers().await.unwrap();
-            // drop(res);
+    #[otel_test(run = call_with_composed_nested_route)]
+    async fn check_span_route_metadata_and_kind_with_composed_nested_route(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 2);
+        // the composed route of the fallback ("/api") is not the matched route template
+        assert_eq!(otel_spans.spans_with_kind(SpanKind::Consumer).len(), 0);
+        for span in &otel_spans {
+            let attributes = &span.attributes;
+            if attributes.get("http.route") == Some(&"/api/users/{id}".into()) {
+                assert_eq!(attributes.get("team"), Some(&"users".into()));
+            } else {
+                assert_eq!(attributes.get("http.route"), Some(&"/api".into()));
+                assert_eq!(attributes.get("team"), None);
+            }
         }
-        let (tracing_events, otel_spans) = fake_env.collect_traces().await;
-        assert_trace(name, tracing_events, otel_spans, is_trace_id_constant);
+    }
+
+    async fn call_with_recording_gate() {
+        // the fake collector exports the mark (no `RecordingGateSpanProcessor` to consume it)
+        let mut svc = Router::new()
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .layer(OtelAxumLayer::default().with_recording_gate(Duration::from_millis(500)));
+        let req = Request::builder()
+            .uri("/users/42")
+            .body(Body::empty())
+            .unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_recording_gate)]
+    async fn check_span_marked_for_recording_gate(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(
+            otel_spans[0]
+                .attributes
+                .get("otel.recording_gate.latency_threshold_ms"),
+            Some(&500i64.into())
+        );
+    }
+
+    async fn call_with_links_header() {
+        let mut svc = Router::new()
+            .route("/batch", get(|| async { StatusCode::OK }))
+            .layer(
+                OtelAxumLayer::default()
+                    .with_links_from_header(HeaderName::from_static("x-trace-links")),
+            );
+        let req = Request::builder()
+            .uri("/batch")
+            .header(
+                "x-trace-links",
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            )
+            .header(
+                "x-trace-links",
+                "00-b2611246a58fd7ea623d2264c5a1e226-b2c9b811f2f424af-01, not-a-context",
+            )
+            .body(Body::empty())
+            .unwrap();
+        let _res = svc.call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_links_header)]
+    async fn check_span_links_from_header(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        let links = otel_spans[0]
+            .links
+            .iter()
+            .map(|link| (link.trace_id.as_str(), link.span_id.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            vec![
+                ("0af7651916cd43dd8448eb211c80319c", "b7ad6b7169203331"),
+                ("b2611246a58fd7ea623d2264c5a1e226", "b2c9b811f2f424af"),
+            ]
+        );
+    }
+
+    async fn call_cancelled() {
+        let mut svc = Router::new()
+            .route("/slow", get(std::future::pending::<StatusCode>))
+            .layer(OtelAxumLayer::default());
+        let req = Request::builder().uri("/slow").body(Body::empty()).unwrap();
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(10), svc.call(req)).await;
+        assert!(result.is_err());
+    }
+
+    #[otel_test(run = call_cancelled)]
+    async fn check_span_status_on_cancellation(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(
+            otel_spans[0].attributes.get("error.type"),
+            Some(&"cancelled".into())
+        );
+        assert_eq!(
+            otel_spans[0].status_code(),
+            fake_opentelemetry_collector::StatusCode::Error
+        );
+    }
+
+    async fn call_with_box_error() {
+        // a tower stack where the error is not a `std::error::Error`
+        let mut svc =
+            OtelAxumLayer::default().layer(tower::service_fn(|_req: Request<Body>| async {
+                Err::<Response<Body>, tower::BoxError>("upstream unavailable".into())
+            }));
+        let req = Request::builder()
+            .uri("/users")
+            .body(Body::empty())
+            .unwrap();
+        let result = svc.call(req).await;
+        assert!(result.is_err());
+    }
+
+    #[otel_test(run = call_with_box_error)]
+    async fn check_span_status_on_box_error(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(
+            otel_spans[0].attributes.get("exception.message"),
+            Some(&"upstream unavailable".into())
+        );
+        assert_eq!(
+            otel_spans[0].status_code(),
+            fake_opentelemetry_collector::StatusCode::Error
+        );
+    }
+
+    async fn call_with_ready_wait() {
+        use std::time::Instant;
+        use tower::ServiceExt;
+
+        /// a service not ready until `ready_at` (eg backpressure)
+        #[derive(Clone)]
+        struct SlowReady {
+            ready_at: Instant,
+        }
+
+        impl Service<Request<Body>> for SlowReady {
+            type Response = Response<Body>;
+            type Error = std::convert::Infallible;
+            type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+            fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                if Instant::now() >= self.ready_at {
+                    return Poll::Ready(Ok(()));
+                }
+                let waker = cx.waker().clone();
+                let ready_at = self.ready_at;
+                tokio::spawn(async move {
+                    tokio::time::sleep_until(ready_at.into()).await;
+                    waker.wake();
+                });
+                Poll::Pending
+            }
+
+            fn call(&mut self, _req: Request<Body>) -> Self::Future {
+                std::future::ready(Ok(Response::new(Body::empty())))
+            }
+        }
+
+        let mut svc = OtelAxumLayer::default().layer(SlowReady {
+            ready_at: Instant::now() + Duration::from_millis(50),
+        });
+        let req = Request::builder()
+            .uri("/users")
+            .body(Body::empty())
+            .unwrap();
+        let _res = svc.ready().await.unwrap().call(req).await.unwrap();
+    }
+
+    #[otel_test(run = call_with_ready_wait)]
+    async fn check_span_with_ready_wait(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        let ready_wait = otel_spans[0].attributes.get("service.ready_wait");
+        assert!(
+            matches!(ready_wait, Some(fake_opentelemetry_collector::AttrValue::Double(v)) if *v >= 0.05 && *v < 10.0),
+            "{ready_wait:?}"
+        );
+    }
+
+    async fn call_with_response_headers_and_throttling() {
+        let mut svc = Router::new()
+            .route(
+                "/limited",
+                get(|| async {
+                    (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        [("retry-after", "30"), ("x-ratelimit-remaining", "0")],
+                    )
+                }),
+            )
+            .route(
+                "/ok",
+                get(|| async { ([("x-ratelimit-remaining", "9")], "hello") }),
+            )
+            .layer(
+                OtelAxumLayer::default()
+                    .with_response_headers([HeaderName::from_static("x-ratelimit-remaining")]),
+            );
+        for uri in ["/limited", "/ok"] {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let _res = svc.call(req).await.unwrap();
+        }
+    }
+
+    #[otel_test(run = call_with_response_headers_and_throttling)]
+    async fn check_span_with_response_headers_and_throttling(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 2);
+        let limited = otel_spans.spans_named("GET /limited")[0];
+        assert_eq!(
+            limited.attributes.get("http.server.throttled"),
+            Some(&true.into())
+        );
+        assert_eq!(
+            limited.attributes.get("http.response.header.retry-after"),
+            Some(&fake_opentelemetry_collector::AttrValue::Array(vec![
+                "30".into()
+            ]))
+        );
+        assert_eq!(
+            limited
+                .attributes
+                .get("http.response.header.x-ratelimit-remaining"),
+            Some(&fake_opentelemetry_collector::AttrValue::Array(vec![
+                "0".into()
+            ]))
+        );
+        let ok = otel_spans.spans_named("GET /ok")[0];
+        assert_eq!(ok.attributes.get("http.server.throttled"), None);
+        assert_eq!(
+            ok.attributes
+                .get("http.response.header.x-ratelimit-remaining"),
+            Some(&fake_opentelemetry_collector::AttrValue::Array(vec![
+                "9".into()
+            ]))
+        );
+    }
+
+    async fn call_with_force_sampling() {
+        let mut svc = Router::new()
+            .route("/users/{id}", get(|| async { StatusCode::OK }))
+            .layer(
+                OtelAxumLayer::default()
+                    .with_sampling_rate_for("/users/*", 0.0)
+                    .with_force_sampling_for(otel_http::has_debug_trace_header),
+            );
+        for debug in ["0", "1"] {
+            let req = Request::builder()
+                .uri("/users/42")
+                .header("x-debug-trace", debug)
+                .body(Body::empty())
+                .unwrap();
+            let _res = svc.call(req).await.unwrap();
+        }
+    }
+
+    #[otel_test(run = call_with_force_sampling)]
+    async fn check_span_with_force_sampling(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(
+            otel_spans[0].attributes.get("sampling.priority"),
+            Some(&1i64.into())
+        );
+    }
+
+    async fn call_with_force_trace_header() {
+        let config = otel_http::ForceTraceConfig::new("s3cr3t").unwrap();
+        let mut svc = Router::new()
+            .route(
+                "/orders/{id}",
+                get(|| async {
+                    use tracing_opentelemetry::OpenTelemetrySpanExt;
+                    // the downstream calls of the forced trace carry the header
+                    let mut headers = http::HeaderMap::new();
+                    otel_http::inject_context(&tracing::Span::current().context(), &mut headers);
+                    match headers.get(otel_http::DEFAULT_FORCE_TRACE_HEADER) {
+                        Some(secret) if secret == "s3cr3t" => StatusCode::OK,
+                        _ => StatusCode::IM_A_TEAPOT,
+                    }
+                }),
+            )
+            .layer(
+                OtelAxumLayer::default()
+                    .with_sampling_rate_for("/orders/*", 0.0)
+                    .with_force_trace(config),
+            );
+        for secret in ["guess", "s3cr3t"] {
+            let req = Request::builder()
+                .uri("/orders/42")
+                .header(otel_http::DEFAULT_FORCE_TRACE_HEADER, secret)
+                .body(Body::empty())
+                .unwrap();
+            let _res = svc.call(req).await.unwrap();
+        }
+    }
+
+    #[otel_test(run = call_with_force_trace_header)]
+    async fn check_span_with_force_trace_header(
+        _tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_eq!(otel_spans.len(), 1);
+        assert_eq!(
+            otel_spans[0].attributes.get("http.response.status_code"),
+            Some(&"200".into())
+        );
+    }
+
+    async fn call_with_milestone_events() {
+        let mut svc = Router::new()
+            .route("/users/{id}", get(|| async { "hello" }))
+            .layer(OtelAxumLayer::default().with_milestone_events(true));
+        let req = Request::builder()
+            .uri("/users/123")
+            .body(Body::empty())
+            .unwrap();
+        let response = svc.call(req).await.unwrap();
+        let _body = axum::body::to_bytes(Body::new(response.into_body()), usize::MAX)
+            .await
+            .unwrap();
+    }
+
+    #[otel_test(run = call_with_milestone_events)]
+    async fn check_span_event_with_milestone_events(
+        tracing_events: Vec<Value>,
+        otel_spans: Vec<ExportedSpan>,
+    ) {
+        assert_trace("milestone_events", tracing_events, otel_spans, false);
     }
 }
@@ -0,0 +1,124 @@
+//! Spans for the messages of a websocket connection.
+//!
+//! The layers only cover the HTTP upgrade request, the messages exchanged after the upgrade are out of the
+//! request/response cycle. [`ws_message_span`] creates a span per message, child of the current span: instrument
+//! the connection task with the span of the upgrade request (or a connection span linked to it) to keep the
+//! messages in the trace of the connection.
+//!
+//! ```
+//! use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+//! use axum::response::Response;
+//! use axum_tracing_opentelemetry::middleware::{ws_message_span, WsDirection};
+//! use tracing::Instrument;
+//!
+//! async fn handler(ws: WebSocketUpgrade) -> Response {
+//!     // the span of the upgrade request (created by `OtelAxumLayer`)
+//!     let span = tracing::Span::current();
+//!     ws.on_upgrade(move |socket| handle_socket(socket).instrument(span))
+//! }
+//!
+//! async fn handle_socket(mut socket: WebSocket) {
+//!     while let Some(Ok(Message::Text(text))) = socket.recv().await {
+//!         let reply = ws_message_span(WsDirection::Received, text.len())
+//!             .in_scope(|| text.to_uppercase());
+//!         let size = reply.len();
+//!         if socket
+//!             .send(Message::Text(reply.into()))
+//!             .instrument(ws_message_span(WsDirection::Sent, size))
+//!             .await
+//!             .is_err()
+//!         {
+//!             break;
+//!         }
+//!     }
+//! }
+//! ```
+
+use tracing::Span;
+use tracing_opentelemetry_instrumentation_sdk::otel_trace_span;
+
+/// The direction of a websocket message, from the point of view of the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WsDirection {
+    /// from the client (span kind `Consumer`)
+    Received,
+    /// to the client (span kind `Producer`)
+    Sent,
+}
+
+impl WsDirection {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WsDirection::Received => "received",
+            WsDirection::Sent => "sent",
+        }
+    }
+}
+
+/// Create a span (child of the current span) for a websocket message of `size` bytes,
+/// named `WS received` | `WS sent` with the attributes `websocket.message.direction` & `websocket.message.size`.
+#[must_use]
+pub fn ws_message_span(direction: WsDirection, size: usize) -> Span {
+    let (name, kind) = match direction {
+        WsDirection::Received => ("WS received", "consumer"),
+        WsDirection::Sent => ("WS sent", "producer"),
+    };
+    otel_trace_span!(
+        "WS message",
+        otel.name = name,
+        otel.kind = kind,
+        websocket.message.direction = direction.as_str(),
+        websocket.message.size = i64::try_from(size).unwrap_or(i64::MAX),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::OtelAxumLayer;
+    use axum::{body::Body, routing::get, Router};
+    use fake_opentelemetry_collector::ExportedSpansExt;
+    use http::{Request, StatusCode};
+    use testing_tracing_opentelemetry::FakeEnvironment;
+    use tower::Service;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_message_spans_are_children_of_the_current_span() {
+        let mut fake_env = FakeEnvironment::setup().await;
+        {
+            let mut svc = Router::new()
+                .route(
+                    "/ws",
+                    get(|| async {
+                        ws_message_span(WsDirection::Received, 5).in_scope(|| {});
+                        ws_message_span(WsDirection::Sent, 12).in_scope(|| {});
+                        StatusCode::OK
+                    }),
+                )
+                .layer(OtelAxumLayer::default());
+            let req = Request::builder().uri("/ws").body(Body::empty()).unwrap();
+            let _res = svc.call(req).await.unwrap();
+        }
+        let (_tracing_events, otel_spans) = fake_env.collect_traces().await;
+        assert_eq!(otel_spans.len(), 3);
+        let server = otel_spans.spans_named("GET /ws")[0];
+        let messages = otel_spans.children_of(&server.span_id);
+        assert_eq!(messages.len(), 2);
+        let received = otel_spans.spans_named("WS received")[0];
+        assert_eq!(received.parent_span_id, server.span_id);
+        assert_eq!(
+            received.attributes.get("websocket.message.direction"),
+            Some(&"received".into())
+        );
+        assert_eq!(
+            received.attributes.get("websocket.message.size"),
+            Some(&5i64.into())
+        );
+        let sent = otel_spans.spans_named("WS sent")[0];
+        assert_eq!(
+            sent.attributes.get("websocket.message.size"),
+            Some(&12i64.into())
+        );
+    }
+}
@@ -0,0 +1,26 @@
+//! Re-export of the items commonly combined to instrument an axum application, for a single import line.
+//!
+//! ```rust
+//! use axum::{routing::get, Router};
+//! use axum_tracing_opentelemetry::prelude::*;
+//!
+//! let app: Router = Router::new()
+//!     .route("/", get(|| async { find_current_trace_id().unwrap_or_default() }))
+//!     .layer(OtelLayers::new(OtelInResponseLayer::default(), OtelAxumLayer::default()));
+//! ```
+
+use tower::layer::util::Stack;
+
+pub use crate::middleware::{
+    otel_from_fn_middleware, NestedRoutePolicy, OtelAxumLayer, OtelAxumService,
+    OtelInResponseLayer, OtelInResponseService, TenantInfo,
+};
+pub use tracing_opentelemetry_instrumentation_sdk::{find_current_context, find_current_trace_id};
+
+/// [`OtelAxumLayer`] wrapping [`OtelInResponseLayer`], like
+/// `.layer(OtelInResponseLayer::default()).layer(OtelAxumLayer::default())` on a `Router`
+/// (the trace context is injected into the response once the span is created).
+pub type OtelLayers = Stack<OtelInResponseLayer, OtelAxumLayer>;
+
+/// The service built by [`OtelLayers`] around `S`.
+pub type OtelServices<S> = OtelAxumService<OtelInResponseService<S>>;
@@ -1,17 +1,27 @@
 #![allow(clippy::let_with_type_underscore)]
 #![allow(clippy::default_constructed_unit_structs)] // warning since 1.71
 
-use axum::extract::Path;
+use axum::body::Body;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Request, State};
+use axum::http::StatusCode;
+use axum::response::Response;
 use axum::{response::IntoResponse, routing::get, BoxError, Router};
-use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
+use axum_tracing_opentelemetry::middleware::{
+    ws_message_span, OtelAxumLayer, OtelInResponseLayer, WsDirection,
+};
+use init_tracing_opentelemetry::shutdown::{shutdown_signal, with_telemetry_shutdown};
 use serde_json::json;
 use std::net::SocketAddr;
+use tracing::Instrument;
 use tracing_opentelemetry_instrumentation_sdk::find_current_trace_id;
+use tracing_opentelemetry_instrumentation_sdk::http::{http_client, QueryRedaction};
 
 #[tokio::main]
 async fn main() -> Result<(), BoxError> {
     // very opinionated init of tracing, look as is source to make your own
-    let _guard = init_tracing_opentelemetry::tracing_subscriber_ext::init_subscribers()?;
+    let guard = init_tracing_opentelemetry::tracing_subscriber_ext::init_subscribers()?;
+    let (shutdown_signal, telemetry) = with_telemetry_shutdown(shutdown_signal(), guard);
 
     let app = app();
     // run it
@@ -19,24 +29,45 @@ async fn main() -> Result<(), BoxError> {
     tracing::warn!("listening on {}", addr);
     tracing::info!("try to call `curl -i http://127.0.0.1:3003/` (with trace)"); //Devskim: ignore DS137138
     tracing::info!("try to call `curl -i http://127.0.0.1:3003/health` (with NO trace)"); //Devskim: ignore DS137138
+    tracing::info!("try to call `curl -i http://127.0.0.1:3003/proxy/127.0.0.1:3003/health` (with trace propagated to the proxied service)"); //Devskim: ignore DS137138
+    tracing::info!("try to call `websocat ws://127.0.0.1:3003/ws` (with a span per message, in the trace of the upgrade request)"); //Devskim: ignore DS137138
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app.into_make_service()).await?;
+    axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(shutdown_signal)
+        .await?;
+    telemetry.shutdown()?;
     Ok(())
 }
 
+/// The headers of a connection (hop-by-hop), not forwarded by the proxy
+/// (`host` is the one of the proxied service, set from the url)
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "host",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
 fn app() -> Router {
     // build our application with a route
     Router::new()
         .route(
-            "/proxy/:service/*path",
+            "/proxy/{service}/{*path}",
             get(proxy_handler).post(proxy_handler),
         )
         .route("/", get(index)) // request processed inside span
+        .route("/ws", get(ws_handler)) // upgrade request processed inside span, messages inside child spans
         // include trace context as header into the response
         .layer(OtelInResponseLayer::default())
         //start OpenTelemetry trace on incoming request
         .layer(OtelAxumLayer::default())
         .route("/health", get(health)) // request processed without span / trace
+        // the client (& its pool of connections) shared by the calls of the proxy
+        .with_state(reqwest::Client::new())
 }
 
 async fn health() -> impl IntoResponse {
@@ -51,11 +82,77 @@ async fn index() -> impl IntoResponse {
     axum::Json(json!({ "my_trace_id": trace_id }))
 }
 
-async fn proxy_handler(Path((service, path)): Path<(String, String)>) -> impl IntoResponse {
+async fn ws_handler(ws: WebSocketUpgrade) -> Response {
+    // keep the span of the upgrade request as parent of the messages (the connection outlives the request)
+    let span = tracing::Span::current();
+    ws.on_upgrade(move |socket| echo(socket).instrument(span))
+}
+
+async fn echo(mut socket: WebSocket) {
+    while let Some(Ok(msg)) = socket.recv().await {
+        let reply = match msg {
+            Message::Text(text) => {
+                let _span = ws_message_span(WsDirection::Received, text.len()).entered();
+                tracing::info!(%text, "received");
+                Message::Text(text)
+            }
+            Message::Binary(bytes) => {
+                let _span = ws_message_span(WsDirection::Received, bytes.len()).entered();
+                Message::Binary(bytes)
+            }
+            Message::Close(_) => break,
+            Message::Ping(_) | Message::Pong(_) => continue,
+        };
+        let size = match &reply {
+            Message::Text(text) => text.len(),
+            Message::Binary(bytes) => bytes.len(),
+            _ => 0,
+        };
+        if socket
+            .send(reply)
+            .instrument(ws_message_span(WsDirection::Sent, size))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+async fn proxy_handler(
+    State(client): State<reqwest::Client>,
+    Path((service, path)): Path<(String, String)>,
+    req: Request,
+) -> Result<Response, (StatusCode, String)> {
     // Overwrite the otel.name of the span
     tracing::Span::current().record("otel.name", format!("proxy {service}"));
-    let trace_id = find_current_trace_id();
-    axum::Json(
-        json!({ "my_trace_id": trace_id, "fake_proxy": { "service": service, "path": path } }),
-    )
+    let bad_gateway = |err: &dyn std::error::Error| (StatusCode::BAD_GATEWAY, err.to_string());
+
+    // stream the request to the proxied service (without buffering the body)
+    let (parts, body) = req.into_parts();
+    let mut outgoing = axum::http::Request::builder()
+        .method(parts.method)
+        .uri(format!("http://{service}/{path}")) //Devskim: ignore DS137138
+        .body(reqwest::Body::wrap_stream(body.into_data_stream()))
+        .map_err(|err| bad_gateway(&err))?;
+    let mut headers = parts.headers;
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(name);
+    }
+    *outgoing.headers_mut() = headers;
+
+    // create the client span (child of the server span) & inject its context into the headers
+    // (replacing the context of the caller)
+    let span = http_client::make_span_and_inject_context(&mut outgoing, &QueryRedaction::default());
+    let outgoing = reqwest::Request::try_from(outgoing).map_err(|err| bad_gateway(&err))?;
+    let result = client
+        .execute(outgoing)
+        .instrument(span.clone())
+        .await
+        .map(axum::http::Response::<reqwest::Body>::from);
+    http_client::update_span_from_response_or_error(&span, &result);
+
+    // stream the response back
+    let response = result.map_err(|err| bad_gateway(&err))?;
+    Ok(response.map(Body::new))
 }
@@ -0,0 +1,67 @@
+use axum::{response::IntoResponse, routing::get, BoxError, Router};
+use axum_tracing_opentelemetry::middleware::OtelAxumLayer;
+use serde_json::json;
+use std::net::SocketAddr;
+use tonic::transport::Server;
+use tonic_tracing_opentelemetry::middleware::{filters, server::OtelGrpcLayer};
+use tracing_opentelemetry_instrumentation_sdk::find_current_trace_id;
+
+/// The paths not traced, for both protocols (the http & grpc health checks)
+fn is_traced(path: &str) -> bool {
+    path != "/health" && filters::reject_healthcheck(path)
+}
+
+/// The server layers for the HTTP & gRPC listeners, configured consistently.
+///
+/// It's a pattern to copy (and adapt: filters, privacy, recording gate,...) into your service, not a function
+/// provided by the crates: each protocol keeps its own layer, built from the same settings.
+///
+/// The layers don't hold the tracer provider (nor the resource): they use the global one,
+/// registered once by the init of the tracing.
+fn shared_layers() -> (OtelAxumLayer, OtelGrpcLayer) {
+    (
+        OtelAxumLayer::default().filter(is_traced),
+        OtelGrpcLayer::default().filter(is_traced),
+    )
+}
+
+#[tokio::main]
+async fn main() -> Result<(), BoxError> {
+    // init once for the whole process (both listeners), the guard flush the traces on drop
+    let _guard = init_tracing_opentelemetry::tracing_subscriber_ext::init_subscribers()?;
+    let (http_layer, grpc_layer) = shared_layers();
+
+    let http_addr = "0.0.0.0:3003".parse::<SocketAddr>()?;
+    let grpc_addr = "0.0.0.0:50051".parse::<SocketAddr>()?;
+    tracing::warn!("listening on {http_addr} (http) & {grpc_addr} (grpc)");
+
+    let app = Router::new()
+        .route("/", get(index))
+        .route("/health", get(health))
+        .layer(http_layer);
+    let listener = tokio::net::TcpListener::bind(http_addr).await?;
+    let http_server = axum::serve(listener, app.into_make_service());
+
+    let (_, health_service) = tonic_health::server::health_reporter();
+    let grpc_server = Server::builder()
+        .layer(grpc_layer)
+        .add_service(health_service)
+        .serve(grpc_addr);
+
+    tokio::select! {
+        result = http_server => result?,
+        result = grpc_server => result?,
+        result = tokio::signal::ctrl_c() => result?,
+    }
+    Ok(())
+}
+
+async fn health() -> impl IntoResponse {
+    axum::Json(json!({ "status" : "UP" }))
+}
+
+#[tracing::instrument]
+async fn index() -> impl IntoResponse {
+    let trace_id = find_current_trace_id();
+    axum::Json(json!({ "my_trace_id": trace_id }))
+}
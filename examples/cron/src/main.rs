@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use tokio_cron_scheduler::{Job, JobScheduler};
+use tracing::Instrument;
+use tracing_opentelemetry_instrumentation_sdk::job;
+
+const SCHEDULE: &str = "1/10 * * * * *";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // very opinionated init of tracing, look as is source to make your own
+    let _guard = init_tracing_opentelemetry::tracing_subscriber_ext::init_subscribers()?;
+
+    let scheduler = JobScheduler::new().await?;
+    scheduler
+        .add(Job::new_async(SCHEDULE, |run_id, _scheduler| {
+            Box::pin(async move {
+                let span = job::make_scheduled_job_span("purge_sessions", SCHEDULE);
+                span.record("job.run_id", run_id.to_string());
+                let result = purge_sessions().instrument(span.clone()).await;
+                job::update_span_from_job_result(&span, &result);
+            })
+        })?)
+        .await?;
+    scheduler.start().await?;
+
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+#[tracing::instrument]
+async fn purge_sessions() -> Result<u64, std::io::Error> {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    tracing::info!("sessions purged");
+    Ok(3)
+}
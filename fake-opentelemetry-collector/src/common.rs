@@ -1,10 +1,249 @@
+use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, ArrayValue, KeyValueList};
+use serde::Serialize;
 use std::collections::BTreeMap;
+use std::fmt;
 
+/// Typed value of an attribute (or of the body of a log),
+/// converted from `opentelemetry_proto::tonic::common::v1::AnyValue` to be easy to assert and to serialize.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum AttrValue {
+    String(String),
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    Array(Vec<AttrValue>),
+    KvList(BTreeMap<String, AttrValue>),
+    #[serde(serialize_with = "serialize_bytes_as_hex")]
+    Bytes(Vec<u8>),
+}
+
+fn serialize_bytes_as_hex<S: serde::Serializer>(
+    value: &[u8],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(value))
+}
+
+impl AttrValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AttrValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            AttrValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            AttrValue::Double(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            AttrValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// The value formatted like before the introduction of `AttrValue`
+    /// (eg `Some(AnyValue { value: Some(StringValue("bar")) })`), to ease the migration of existing assertions.
+    pub fn to_legacy_string(&self) -> String {
+        format!("{:?}", Some(AnyValue::from(self)))
+    }
+}
+
+// Doubles are compared by their bits, to be able to implement `Eq` (and to compare `NaN`)
+impl PartialEq for AttrValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (AttrValue::String(a), AttrValue::String(b)) => a == b,
+            (AttrValue::Bool(a), AttrValue::Bool(b)) => a == b,
+            (AttrValue::Int(a), AttrValue::Int(b)) => a == b,
+            (AttrValue::Double(a), AttrValue::Double(b)) => a.to_bits() == b.to_bits(),
+            (AttrValue::Array(a), AttrValue::Array(b)) => a == b,
+            (AttrValue::KvList(a), AttrValue::KvList(b)) => a == b,
+            (AttrValue::Bytes(a), AttrValue::Bytes(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for AttrValue {}
+
+impl PartialEq<&str> for AttrValue {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == Some(*other)
+    }
+}
+
+impl fmt::Display for AttrValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttrValue::String(v) => f.write_str(v),
+            AttrValue::Bool(v) => write!(f, "{v}"),
+            AttrValue::Int(v) => write!(f, "{v}"),
+            AttrValue::Double(v) => write!(f, "{v}"),
+            AttrValue::Array(v) => {
+                f.write_str("[")?;
+                for (i, item) in v.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str("]")
+            }
+            AttrValue::KvList(v) => {
+                f.write_str("{")?;
+                for (i, (key, item)) in v.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{key}: {item}")?;
+                }
+                f.write_str("}")
+            }
+            AttrValue::Bytes(v) => f.write_str(&hex::encode(v)),
+        }
+    }
+}
+
+impl From<&str> for AttrValue {
+    fn from(value: &str) -> Self {
+        AttrValue::String(value.to_string())
+    }
+}
+
+impl From<i64> for AttrValue {
+    fn from(value: i64) -> Self {
+        AttrValue::Int(value)
+    }
+}
+
+impl From<f64> for AttrValue {
+    fn from(value: f64) -> Self {
+        AttrValue::Double(value)
+    }
+}
+
+impl From<bool> for AttrValue {
+    fn from(value: bool) -> Self {
+        AttrValue::Bool(value)
+    }
+}
+
+impl AttrValue {
+    /// `None` if the `AnyValue` has no value
+    pub(crate) fn from_any_value(value: &AnyValue) -> Option<Self> {
+        let value = match value.value.as_ref()? {
+            any_value::Value::StringValue(v) => AttrValue::String(v.clone()),
+            any_value::Value::BoolValue(v) => AttrValue::Bool(*v),
+            any_value::Value::IntValue(v) => AttrValue::Int(*v),
+            any_value::Value::DoubleValue(v) => AttrValue::Double(*v),
+            any_value::Value::ArrayValue(v) => {
+                AttrValue::Array(v.values.iter().filter_map(Self::from_any_value).collect())
+            }
+            any_value::Value::KvlistValue(v) => AttrValue::KvList(cnv_attributes(&v.values)),
+            any_value::Value::BytesValue(v) => AttrValue::Bytes(v.clone()),
+        };
+        Some(value)
+    }
+}
+
+impl From<&AttrValue> for AnyValue {
+    fn from(value: &AttrValue) -> Self {
+        let value = match value {
+            AttrValue::String(v) => any_value::Value::StringValue(v.clone()),
+            AttrValue::Bool(v) => any_value::Value::BoolValue(*v),
+            AttrValue::Int(v) => any_value::Value::IntValue(*v),
+            AttrValue::Double(v) => any_value::Value::DoubleValue(*v),
+            AttrValue::Array(v) => any_value::Value::ArrayValue(ArrayValue {
+                values: v.iter().map(AnyValue::from).collect(),
+            }),
+            AttrValue::KvList(v) => any_value::Value::KvlistValue(KeyValueList {
+                values: v
+                    .iter()
+                    .map(
+                        |(key, value)| opentelemetry_proto::tonic::common::v1::KeyValue {
+                            key: key.clone(),
+                            value: Some(AnyValue::from(value)),
+                        },
+                    )
+                    .collect(),
+            }),
+            AttrValue::Bytes(v) => any_value::Value::BytesValue(v.clone()),
+        };
+        AnyValue { value: Some(value) }
+    }
+}
+
+/// The attributes without value are ignored
 pub(crate) fn cnv_attributes(
     attributes: &[opentelemetry_proto::tonic::common::v1::KeyValue],
-) -> BTreeMap<String, String> {
+) -> BTreeMap<String, AttrValue> {
     attributes
         .iter()
-        .map(|kv| (kv.key.to_string(), format!("{:?}", kv.value)))
-        .collect::<BTreeMap<String, String>>()
+        .filter_map(|kv| {
+            let value = AttrValue::from_any_value(kv.value.as_ref()?)?;
+            Some((kv.key.to_string(), value))
+        })
+        .collect::<BTreeMap<String, AttrValue>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn convert_any_value_into_typed_value_and_back() {
+        let any_value = AnyValue {
+            value: Some(any_value::Value::KvlistValue(KeyValueList {
+                values: vec![
+                    opentelemetry_proto::tonic::common::v1::KeyValue {
+                        key: "a".to_string(),
+                        value: Some(AnyValue {
+                            value: Some(any_value::Value::IntValue(1)),
+                        }),
+                    },
+                    opentelemetry_proto::tonic::common::v1::KeyValue {
+                        key: "b".to_string(),
+                        value: Some(AnyValue {
+                            value: Some(any_value::Value::ArrayValue(ArrayValue {
+                                values: vec![AnyValue {
+                                    value: Some(any_value::Value::StringValue("x".to_string())),
+                                }],
+                            })),
+                        }),
+                    },
+                ],
+            })),
+        };
+        let value = AttrValue::from_any_value(&any_value);
+        assert!(
+            value
+                == Some(AttrValue::KvList(BTreeMap::from([
+                    ("a".to_string(), AttrValue::Int(1)),
+                    ("b".to_string(), AttrValue::Array(vec!["x".into()])),
+                ])))
+        );
+        assert!(value.map(|v| AnyValue::from(&v)) == Some(any_value));
+    }
+
+    #[test]
+    fn format_legacy_string() {
+        assert!(
+            AttrValue::from("bar").to_legacy_string()
+                == r#"Some(AnyValue { value: Some(StringValue("bar")) })"#
+        );
+    }
 }
@@ -0,0 +1,144 @@
+//! OTLP/HTTP receiver, for the SDKs configured with `http/protobuf` or `http/json`
+//! (see [OTLP/HTTP](https://opentelemetry.io/docs/specs/otlp/#otlphttp)).
+use crate::logs::send_logs;
+use crate::metrics::send_metrics;
+use crate::trace::send_spans;
+use crate::{ExportedLog, ExportedMetric, ExportedSpan};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use opentelemetry_proto::tonic::collector::logs::v1::{
+    ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use opentelemetry_proto::tonic::collector::metrics::v1::{
+    ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+};
+use opentelemetry_proto::tonic::collector::trace::v1::{
+    ExportTraceServiceRequest, ExportTraceServiceResponse,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::mpsc;
+
+#[derive(Clone)]
+struct Senders {
+    spans: mpsc::Sender<ExportedSpan>,
+    logs: mpsc::Sender<ExportedLog>,
+    metrics: mpsc::Sender<ExportedMetric>,
+}
+
+pub(crate) fn router(
+    spans: mpsc::Sender<ExportedSpan>,
+    logs: mpsc::Sender<ExportedLog>,
+    metrics: mpsc::Sender<ExportedMetric>,
+) -> Router {
+    Router::new()
+        .route("/v1/traces", post(export_traces))
+        .route("/v1/logs", post(export_logs))
+        .route("/v1/metrics", post(export_metrics))
+        .with_state(Senders {
+            spans,
+            logs,
+            metrics,
+        })
+}
+
+async fn export_traces(
+    State(senders): State<Senders>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let encoding = Encoding::from_headers(&headers);
+    let request = match encoding.decode::<ExportTraceServiceRequest>(&body) {
+        Ok(request) => request,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    if let Err(err) = send_spans(&senders.spans, request).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response();
+    }
+    encoding.encode(&ExportTraceServiceResponse {
+        partial_success: None,
+    })
+}
+
+async fn export_logs(State(senders): State<Senders>, headers: HeaderMap, body: Bytes) -> Response {
+    let encoding = Encoding::from_headers(&headers);
+    let request = match encoding.decode::<ExportLogsServiceRequest>(&body) {
+        Ok(request) => request,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    if let Err(err) = send_logs(&senders.logs, request).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response();
+    }
+    encoding.encode(&ExportLogsServiceResponse {
+        partial_success: None,
+    })
+}
+
+async fn export_metrics(
+    State(senders): State<Senders>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let encoding = Encoding::from_headers(&headers);
+    let request = match encoding.decode::<ExportMetricsServiceRequest>(&body) {
+        Ok(request) => request,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    if let Err(err) = send_metrics(&senders.metrics, request).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response();
+    }
+    encoding.encode(&ExportMetricsServiceResponse {
+        partial_success: None,
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Json,
+    Protobuf,
+}
+
+impl Encoding {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let is_json = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/json"));
+        if is_json {
+            Encoding::Json
+        } else {
+            Encoding::Protobuf
+        }
+    }
+
+    fn decode<T>(self, body: &[u8]) -> Result<T, String>
+    where
+        T: prost::Message + DeserializeOwned + Default,
+    {
+        match self {
+            Encoding::Json => serde_json::from_slice(body).map_err(|err| err.to_string()),
+            Encoding::Protobuf => T::decode(body).map_err(|err| err.to_string()),
+        }
+    }
+
+    fn encode<T>(self, response: &T) -> Response
+    where
+        T: prost::Message + Serialize,
+    {
+        match self {
+            Encoding::Json => (
+                [(header::CONTENT_TYPE, "application/json")],
+                serde_json::to_vec(response).unwrap_or_default(),
+            )
+                .into_response(),
+            Encoding::Protobuf => (
+                [(header::CONTENT_TYPE, "application/x-protobuf")],
+                response.encode_to_vec(),
+            )
+                .into_response(),
+        }
+    }
+}
@@ -1,18 +1,24 @@
 mod common;
+mod http;
 mod logs;
+mod metrics;
 mod trace;
+pub use common::AttrValue;
 pub use logs::ExportedLog;
-pub use trace::ExportedSpan;
+pub use metrics::{DataPoint, ExportedMetric, ExportedMetricsExt, MetricValue};
+pub use trace::{ExportedSpan, ExportedSpansExt, SpanKind, Status, StatusCode};
 
 use logs::*;
+use metrics::*;
 use trace::*;
 
 use std::net::SocketAddr;
 use std::time::{Duration, Instant};
 
 use futures::StreamExt;
-use opentelemetry_otlp::{LogExporter, SpanExporter, WithExportConfig};
+use opentelemetry_otlp::{LogExporter, MetricExporter, SpanExporter, WithExportConfig};
 use opentelemetry_proto::tonic::collector::logs::v1::logs_service_server::LogsServiceServer;
+use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_server::MetricsServiceServer;
 use opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::TraceServiceServer;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Receiver;
@@ -23,6 +29,7 @@ pub struct FakeCollectorServer {
     address: SocketAddr,
     req_rx: mpsc::Receiver<ExportedSpan>,
     log_rx: mpsc::Receiver<ExportedLog>,
+    metric_rx: mpsc::Receiver<ExportedMetric>,
     handle: tokio::task::JoinHandle<()>,
 }
 
@@ -40,13 +47,16 @@ impl FakeCollectorServer {
 
         let (req_tx, req_rx) = mpsc::channel::<ExportedSpan>(64);
         let (log_tx, log_rx) = mpsc::channel::<ExportedLog>(64);
+        let (metric_tx, metric_rx) = mpsc::channel::<ExportedMetric>(64);
         let trace_service = TraceServiceServer::new(FakeTraceService::new(req_tx));
         let logs_service = LogsServiceServer::new(FakeLogsService::new(log_tx));
+        let metrics_service = MetricsServiceServer::new(FakeMetricsService::new(metric_tx));
         let handle = tokio::task::spawn(async move {
             debug!("start FakeCollectorServer http://{addr}"); //Devskim: ignore DS137138)
             tonic::transport::Server::builder()
                 .add_service(trace_service)
                 .add_service(logs_service)
+                .add_service(metrics_service)
                 .serve_with_incoming(stream)
                 .await
                 .expect("Server failed");
@@ -56,6 +66,34 @@ impl FakeCollectorServer {
             address: addr,
             req_rx,
             log_rx,
+            metric_rx,
+            handle,
+        })
+    }
+
+    /// Start the collector with an OTLP/HTTP receiver (`/v1/traces`, `/v1/logs`, `/v1/metrics`),
+    /// accepting `http/protobuf` and `http/json` payloads (based on the `content-type`).
+    ///
+    /// The collected spans, logs & metrics are the same as with the grpc receiver (see [`FakeCollectorServer::start`]).
+    pub async fn start_http() -> Result<Self, Box<dyn std::error::Error>> {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+
+        let (req_tx, req_rx) = mpsc::channel::<ExportedSpan>(64);
+        let (log_tx, log_rx) = mpsc::channel::<ExportedLog>(64);
+        let (metric_tx, metric_rx) = mpsc::channel::<ExportedMetric>(64);
+        let app = http::router(req_tx, log_tx, metric_tx);
+        let handle = tokio::task::spawn(async move {
+            debug!("start FakeCollectorServer (http) http://{addr}"); //Devskim: ignore DS137138)
+            axum::serve(listener, app).await.expect("Server failed");
+            debug!("stop FakeCollectorServer (http)");
+        });
+        Ok(Self {
+            address: addr,
+            req_rx,
+            log_rx,
+            metric_rx,
             handle,
         })
     }
@@ -80,6 +118,16 @@ impl FakeCollectorServer {
         recv_many(&mut self.log_rx, at_least, timeout).await
     }
 
+    /// The metrics of the exports received (one `ExportedMetric` per metric per export),
+    /// see [`ExportedMetricsExt`] to compare the cumulative values between exports.
+    pub async fn exported_metrics(
+        &mut self,
+        at_least: usize,
+        timeout: Duration,
+    ) -> Vec<ExportedMetric> {
+        recv_many(&mut self.metric_rx, at_least, timeout).await
+    }
+
     pub fn abort(self) {
         self.handle.abort()
     }
@@ -126,3 +174,23 @@ pub async fn setup_logger_provider(
         )
         .build()
 }
+
+/// A meter provider exporting to the `fake_server`, call `force_flush` to export
+/// (the periodic export is too slow for the tests).
+pub async fn setup_meter_provider(
+    fake_server: &FakeCollectorServer,
+) -> opentelemetry_sdk::metrics::SdkMeterProvider {
+    opentelemetry_sdk::metrics::SdkMeterProvider::builder()
+        .with_reader(
+            opentelemetry_sdk::metrics::PeriodicReader::builder(
+                MetricExporter::builder()
+                    .with_tonic()
+                    .with_endpoint(fake_server.endpoint())
+                    .build()
+                    .expect("failed to install metrics"),
+                opentelemetry_sdk::runtime::Tokio,
+            )
+            .build(),
+        )
+        .build()
+}
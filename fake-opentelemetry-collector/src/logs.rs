@@ -1,4 +1,4 @@
-use crate::common::cnv_attributes;
+use crate::common::{cnv_attributes, AttrValue};
 use opentelemetry_proto::tonic::collector::logs::v1::{
     logs_service_server::LogsService, ExportLogsServiceRequest, ExportLogsServiceResponse,
 };
@@ -14,8 +14,8 @@ pub struct ExportedLog {
     pub observed_time_unix_nano: u64,
     pub severity_number: i32,
     pub severity_text: String,
-    pub body: Option<String>,
-    pub attributes: BTreeMap<String, String>,
+    pub body: Option<AttrValue>,
+    pub attributes: BTreeMap<String, AttrValue>,
     pub dropped_attributes_count: u32,
     pub flags: u32,
 }
@@ -28,7 +28,7 @@ impl From<opentelemetry_proto::tonic::logs::v1::LogRecord> for ExportedLog {
             observed_time_unix_nano: value.observed_time_unix_nano,
             severity_number: value.severity_number,
             severity_text: value.severity_text,
-            body: value.body.map(|value| format!("{:?}", value)),
+            body: value.body.as_ref().and_then(AttrValue::from_any_value),
             attributes: cnv_attributes(&value.attributes),
             dropped_attributes_count: value.dropped_attributes_count,
             flags: value.flags,
@@ -52,24 +52,32 @@ impl LogsService for FakeLogsService {
         &self,
         request: tonic::Request<ExportLogsServiceRequest>,
     ) -> Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
-        let sender = self.tx.clone();
-        for el in request
-            .into_inner()
-            .resource_logs
-            .into_iter()
-            .flat_map(|rl| rl.scope_logs)
-            .flat_map(|sl| sl.log_records)
-            .map(ExportedLog::from)
-        {
-            sender
-                .send(el)
-                .await
-                .inspect_err(|e| eprintln!("failed to send to channel: {e}"))
-                .map_err(|err| tonic::Status::from_error(Box::new(err)))?;
-        }
+        send_logs(&self.tx, request.into_inner())
+            .await
+            .map_err(|err| tonic::Status::from_error(Box::new(err)))?;
 
         Ok(tonic::Response::new(ExportLogsServiceResponse {
             partial_success: None,
         }))
     }
 }
+
+/// Send the logs of the request (received via grpc or http) into the channel
+pub(crate) async fn send_logs(
+    sender: &mpsc::Sender<ExportedLog>,
+    request: ExportLogsServiceRequest,
+) -> Result<(), mpsc::error::SendError<ExportedLog>> {
+    for el in request
+        .resource_logs
+        .into_iter()
+        .flat_map(|rl| rl.scope_logs)
+        .flat_map(|sl| sl.log_records)
+        .map(ExportedLog::from)
+    {
+        sender
+            .send(el)
+            .await
+            .inspect_err(|e| eprintln!("failed to send to channel: {e}"))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,341 @@
+use crate::common::{cnv_attributes, AttrValue};
+use opentelemetry_proto::tonic::collector::metrics::v1::{
+    metrics_service_server::MetricsService, ExportMetricsServiceRequest,
+    ExportMetricsServiceResponse,
+};
+use opentelemetry_proto::tonic::metrics::v1::{metric, number_data_point, NumberDataPoint};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tokio::sync::mpsc;
+
+/// A metric of an export, flattened to be easy to assert and to serialize (eg for insta).
+///
+/// The SDKs export (by default) the cumulative values: every export contains the total since the start,
+/// use [`ExportedMetric::delta`] (or [`ExportedMetricsExt::counter_increase`]) to check what happened between 2 exports.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportedMetric {
+    pub name: String,
+    pub description: String,
+    pub unit: String,
+    pub data_points: Vec<DataPoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DataPoint {
+    pub attributes: BTreeMap<String, AttrValue>,
+    pub time_unix_nano: u64,
+    pub value: MetricValue,
+}
+
+/// The value of a data point: a number (counter, up-down counter, gauge) or the summary of an histogram
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum MetricValue {
+    Int(i64),
+    Double(f64),
+    Histogram { count: u64, sum: f64 },
+}
+
+impl MetricValue {
+    /// The value as a number (the `sum` for an histogram)
+    #[allow(clippy::cast_precision_loss)]
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            MetricValue::Int(v) => *v as f64,
+            MetricValue::Double(v) => *v,
+            MetricValue::Histogram { sum, .. } => *sum,
+        }
+    }
+
+    fn minus(&self, earlier: &MetricValue) -> MetricValue {
+        match (self, earlier) {
+            (MetricValue::Int(v), MetricValue::Int(e)) => MetricValue::Int(v - e),
+            (
+                MetricValue::Histogram { count, sum },
+                MetricValue::Histogram {
+                    count: e_count,
+                    sum: e_sum,
+                },
+            ) => MetricValue::Histogram {
+                count: count.saturating_sub(*e_count),
+                sum: sum - e_sum,
+            },
+            (v, e) => MetricValue::Double(v.as_f64() - e.as_f64()),
+        }
+    }
+}
+
+impl ExportedMetric {
+    /// The data point with exactly the `attributes`
+    pub fn data_point(&self, attributes: &[(&str, AttrValue)]) -> Option<&DataPoint> {
+        self.data_points
+            .iter()
+            .find(|dp| same_attributes(&dp.attributes, attributes))
+    }
+
+    /// The difference with an `earlier` export of the (cumulative) metric, per data point
+    /// (the data points not present into `earlier` are kept as is).
+    #[must_use]
+    pub fn delta(&self, earlier: &ExportedMetric) -> ExportedMetric {
+        let data_points = self
+            .data_points
+            .iter()
+            .map(|dp| {
+                let value = earlier
+                    .data_points
+                    .iter()
+                    .find(|e| e.attributes == dp.attributes)
+                    .map_or(dp.value, |e| dp.value.minus(&e.value));
+                DataPoint {
+                    value,
+                    ..dp.clone()
+                }
+            })
+            .collect();
+        ExportedMetric {
+            data_points,
+            ..self.clone()
+        }
+    }
+}
+
+fn same_attributes(actual: &BTreeMap<String, AttrValue>, expected: &[(&str, AttrValue)]) -> bool {
+    actual.len() == expected.len()
+        && expected
+            .iter()
+            .all(|(key, value)| actual.get(*key) == Some(value))
+}
+
+/// Helpers to query the collected metrics (the result of [`crate::FakeCollectorServer::exported_metrics`])
+///
+/// ```rust
+/// use fake_opentelemetry_collector::{ExportedMetric, ExportedMetricsExt};
+///
+/// fn check(before: &[ExportedMetric], after: &[ExportedMetric]) {
+///     after.assert_counter_increase(before, "http.server.requests", &[("http.route", "/users".into())], 1.0);
+/// }
+/// ```
+pub trait ExportedMetricsExt {
+    /// The last export of the metric `name` (the most up to date for a cumulative metric)
+    fn last_metric_named(&self, name: &str) -> Option<&ExportedMetric>;
+    /// The last value of the data point of the metric `name` with exactly the `attributes`
+    fn last_value(&self, name: &str, attributes: &[(&str, AttrValue)]) -> Option<f64>;
+    /// The increase of the data point (with exactly the `attributes`) of the metric `name`
+    /// between the `earlier` exports and `self` (a missing value counts as 0)
+    fn counter_increase(
+        &self,
+        earlier: &[ExportedMetric],
+        name: &str,
+        attributes: &[(&str, AttrValue)],
+    ) -> f64;
+    /// Panic if the increase (see [`ExportedMetricsExt::counter_increase`]) is lower than `at_least`
+    fn assert_counter_increase(
+        &self,
+        earlier: &[ExportedMetric],
+        name: &str,
+        attributes: &[(&str, AttrValue)],
+        at_least: f64,
+    );
+}
+
+impl ExportedMetricsExt for [ExportedMetric] {
+    fn last_metric_named(&self, name: &str) -> Option<&ExportedMetric> {
+        self.iter().rev().find(|metric| metric.name == name)
+    }
+
+    fn last_value(&self, name: &str, attributes: &[(&str, AttrValue)]) -> Option<f64> {
+        self.iter()
+            .rev()
+            .filter(|metric| metric.name == name)
+            .find_map(|metric| metric.data_point(attributes))
+            .map(|dp| dp.value.as_f64())
+    }
+
+    fn counter_increase(
+        &self,
+        earlier: &[ExportedMetric],
+        name: &str,
+        attributes: &[(&str, AttrValue)],
+    ) -> f64 {
+        self.last_value(name, attributes).unwrap_or(0.0)
+            - earlier.last_value(name, attributes).unwrap_or(0.0)
+    }
+
+    fn assert_counter_increase(
+        &self,
+        earlier: &[ExportedMetric],
+        name: &str,
+        attributes: &[(&str, AttrValue)],
+        at_least: f64,
+    ) {
+        let increase = self.counter_increase(earlier, name, attributes);
+        assert!(
+            increase >= at_least,
+            "expected an increase of at least {at_least} for metric '{name}' with attributes {attributes:?}, got {increase} (last value: {:?})",
+            self.last_value(name, attributes),
+        );
+    }
+}
+
+impl From<opentelemetry_proto::tonic::metrics::v1::Metric> for ExportedMetric {
+    fn from(value: opentelemetry_proto::tonic::metrics::v1::Metric) -> Self {
+        let data_points = match value.data {
+            Some(metric::Data::Sum(sum)) => cnv_number_data_points(&sum.data_points),
+            Some(metric::Data::Gauge(gauge)) => cnv_number_data_points(&gauge.data_points),
+            Some(metric::Data::Histogram(histogram)) => histogram
+                .data_points
+                .iter()
+                .map(|dp| DataPoint {
+                    attributes: cnv_attributes(&dp.attributes),
+                    time_unix_nano: dp.time_unix_nano,
+                    value: MetricValue::Histogram {
+                        count: dp.count,
+                        sum: dp.sum.unwrap_or_default(),
+                    },
+                })
+                .collect(),
+            Some(metric::Data::ExponentialHistogram(histogram)) => histogram
+                .data_points
+                .iter()
+                .map(|dp| DataPoint {
+                    attributes: cnv_attributes(&dp.attributes),
+                    time_unix_nano: dp.time_unix_nano,
+                    value: MetricValue::Histogram {
+                        count: dp.count,
+                        sum: dp.sum.unwrap_or_default(),
+                    },
+                })
+                .collect(),
+            Some(metric::Data::Summary(summary)) => summary
+                .data_points
+                .iter()
+                .map(|dp| DataPoint {
+                    attributes: cnv_attributes(&dp.attributes),
+                    time_unix_nano: dp.time_unix_nano,
+                    value: MetricValue::Histogram {
+                        count: dp.count,
+                        sum: dp.sum,
+                    },
+                })
+                .collect(),
+            None => vec![],
+        };
+        Self {
+            name: value.name,
+            description: value.description,
+            unit: value.unit,
+            data_points,
+        }
+    }
+}
+
+fn cnv_number_data_points(data_points: &[NumberDataPoint]) -> Vec<DataPoint> {
+    data_points
+        .iter()
+        .filter_map(|dp| {
+            let value = match dp.value? {
+                number_data_point::Value::AsInt(v) => MetricValue::Int(v),
+                number_data_point::Value::AsDouble(v) => MetricValue::Double(v),
+            };
+            Some(DataPoint {
+                attributes: cnv_attributes(&dp.attributes),
+                time_unix_nano: dp.time_unix_nano,
+                value,
+            })
+        })
+        .collect()
+}
+
+pub(crate) struct FakeMetricsService {
+    tx: mpsc::Sender<ExportedMetric>,
+}
+
+impl FakeMetricsService {
+    pub fn new(tx: mpsc::Sender<ExportedMetric>) -> Self {
+        Self { tx }
+    }
+}
+
+#[tonic::async_trait]
+impl MetricsService for FakeMetricsService {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportMetricsServiceRequest>,
+    ) -> Result<tonic::Response<ExportMetricsServiceResponse>, tonic::Status> {
+        send_metrics(&self.tx, request.into_inner())
+            .await
+            .map_err(|err| tonic::Status::from_error(Box::new(err)))?;
+
+        Ok(tonic::Response::new(ExportMetricsServiceResponse {
+            partial_success: None,
+        }))
+    }
+}
+
+/// Send the metrics of the request (received via grpc or http) into the channel
+pub(crate) async fn send_metrics(
+    sender: &mpsc::Sender<ExportedMetric>,
+    request: ExportMetricsServiceRequest,
+) -> Result<(), mpsc::error::SendError<ExportedMetric>> {
+    for em in request
+        .resource_metrics
+        .into_iter()
+        .flat_map(|rm| rm.scope_metrics)
+        .flat_map(|sm| sm.metrics)
+        .map(ExportedMetric::from)
+    {
+        sender
+            .send(em)
+            .await
+            .inspect_err(|e| eprintln!("failed to send to channel: {e}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    fn counter(values: &[(&str, i64)]) -> ExportedMetric {
+        ExportedMetric {
+            name: "calls".to_string(),
+            description: String::new(),
+            unit: String::new(),
+            data_points: values
+                .iter()
+                .map(|(route, value)| DataPoint {
+                    attributes: BTreeMap::from([("route".to_string(), AttrValue::from(*route))]),
+                    time_unix_nano: 0,
+                    value: MetricValue::Int(*value),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn delta_per_data_point() {
+        let earlier = counter(&[("/a", 3)]);
+        let later = counter(&[("/a", 5), ("/b", 2)]);
+        let delta = later.delta(&earlier);
+        assert!(delta.data_points[0].value == MetricValue::Int(2));
+        assert!(delta.data_points[1].value == MetricValue::Int(2));
+    }
+
+    #[test]
+    fn counter_increase_between_exports() {
+        let earlier = [counter(&[("/a", 3)])];
+        let later = [counter(&[("/a", 4)]), counter(&[("/a", 7), ("/b", 1)])];
+        assert!(later.counter_increase(&earlier, "calls", &[("route", "/a".into())]) == 4.0);
+        assert!(later.counter_increase(&earlier, "calls", &[("route", "/b".into())]) == 1.0);
+        assert!(later.counter_increase(&earlier, "calls", &[]) == 0.0);
+        later.assert_counter_increase(&earlier, "calls", &[("route", "/a".into())], 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected an increase of at least 1 for metric 'calls'")]
+    fn assert_counter_increase_fails_without_increase() {
+        let earlier = [counter(&[("/a", 3)])];
+        earlier.assert_counter_increase(&earlier, "calls", &[("route", "/a".into())], 1.0);
+    }
+}
@@ -1,8 +1,11 @@
 //! based on https://github.com/open-telemetry/opentelemetry-rust/blob/main/opentelemetry-otlp/tests/smoke.rs
-use crate::common::cnv_attributes;
+use crate::common::{cnv_attributes, AttrValue};
 use opentelemetry_proto::tonic::collector::trace::v1::{
     trace_service_server::TraceService, ExportTraceServiceRequest, ExportTraceServiceResponse,
 };
+use opentelemetry_proto::tonic::trace::v1::{
+    span::SpanKind as ProtoSpanKind, status::StatusCode as ProtoStatusCode,
+};
 use serde::Serialize;
 use std::collections::BTreeMap;
 use tokio::sync::mpsc;
@@ -18,10 +21,10 @@ pub struct ExportedSpan {
     pub trace_state: String,
     pub parent_span_id: String,
     pub name: String,
-    pub kind: String, //SpanKind,
+    pub kind: SpanKind,
     pub start_time_unix_nano: u64,
     pub end_time_unix_nano: u64,
-    pub attributes: BTreeMap<String, String>,
+    pub attributes: BTreeMap<String, AttrValue>,
     pub dropped_attributes_count: u32,
     pub events: Vec<Event>,
     pub dropped_events_count: u32,
@@ -38,7 +41,7 @@ impl From<opentelemetry_proto::tonic::trace::v1::Span> for ExportedSpan {
             trace_state: value.trace_state.clone(),
             parent_span_id: hex::encode(&value.parent_span_id),
             name: value.name.clone(),
-            kind: value.kind().as_str_name().to_owned(),
+            kind: SpanKind::from(value.kind()),
             start_time_unix_nano: value.start_time_unix_nano,
             end_time_unix_nano: value.end_time_unix_nano,
             attributes: cnv_attributes(&value.attributes),
@@ -52,18 +55,134 @@ impl From<opentelemetry_proto::tonic::trace::v1::Span> for ExportedSpan {
     }
 }
 
+/// The kind of the span, serialized with the name used by OTLP (eg `SPAN_KIND_SERVER`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum SpanKind {
+    #[serde(rename = "SPAN_KIND_UNSPECIFIED")]
+    Unspecified,
+    #[serde(rename = "SPAN_KIND_INTERNAL")]
+    Internal,
+    #[serde(rename = "SPAN_KIND_SERVER")]
+    Server,
+    #[serde(rename = "SPAN_KIND_CLIENT")]
+    Client,
+    #[serde(rename = "SPAN_KIND_PRODUCER")]
+    Producer,
+    #[serde(rename = "SPAN_KIND_CONSUMER")]
+    Consumer,
+}
+
+impl From<ProtoSpanKind> for SpanKind {
+    fn from(value: ProtoSpanKind) -> Self {
+        match value {
+            ProtoSpanKind::Unspecified => SpanKind::Unspecified,
+            ProtoSpanKind::Internal => SpanKind::Internal,
+            ProtoSpanKind::Server => SpanKind::Server,
+            ProtoSpanKind::Client => SpanKind::Client,
+            ProtoSpanKind::Producer => SpanKind::Producer,
+            ProtoSpanKind::Consumer => SpanKind::Consumer,
+        }
+    }
+}
+
+impl From<opentelemetry::trace::SpanKind> for SpanKind {
+    fn from(value: opentelemetry::trace::SpanKind) -> Self {
+        match value {
+            opentelemetry::trace::SpanKind::Internal => SpanKind::Internal,
+            opentelemetry::trace::SpanKind::Server => SpanKind::Server,
+            opentelemetry::trace::SpanKind::Client => SpanKind::Client,
+            opentelemetry::trace::SpanKind::Producer => SpanKind::Producer,
+            opentelemetry::trace::SpanKind::Consumer => SpanKind::Consumer,
+        }
+    }
+}
+
+/// The status code of the span, serialized with the name used by OTLP (eg `STATUS_CODE_ERROR`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum StatusCode {
+    #[serde(rename = "STATUS_CODE_UNSET")]
+    Unset,
+    #[serde(rename = "STATUS_CODE_OK")]
+    Ok,
+    #[serde(rename = "STATUS_CODE_ERROR")]
+    Error,
+}
+
+impl From<ProtoStatusCode> for StatusCode {
+    fn from(value: ProtoStatusCode) -> Self {
+        match value {
+            ProtoStatusCode::Unset => StatusCode::Unset,
+            ProtoStatusCode::Ok => StatusCode::Ok,
+            ProtoStatusCode::Error => StatusCode::Error,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Serialize)]
 pub struct Status {
     pub message: String,
-    pub code: String,
+    pub code: StatusCode,
 }
 
 impl From<opentelemetry_proto::tonic::trace::v1::Status> for Status {
     fn from(value: opentelemetry_proto::tonic::trace::v1::Status) -> Self {
         Self {
             message: value.message.clone(),
-            code: value.code().as_str_name().to_string(),
+            code: StatusCode::from(value.code()),
+        }
+    }
+}
+
+impl ExportedSpan {
+    /// The status code of the span (`Unset` if no status)
+    pub fn status_code(&self) -> StatusCode {
+        self.status.as_ref().map_or(StatusCode::Unset, |s| s.code)
+    }
+}
+
+/// Queries on the collected spans, to shorten the assertions in tests
+///
+/// ```rust
+/// use fake_opentelemetry_collector::{ExportedSpan, ExportedSpansExt, SpanKind};
+///
+/// fn check(otel_spans: &[ExportedSpan]) {
+///     assert_eq!(otel_spans.spans_with_kind(SpanKind::Server).len(), 1);
+///     assert_eq!(otel_spans.trace_ids().len(), 1);
+/// }
+/// ```
+pub trait ExportedSpansExt {
+    fn spans_with_kind(&self, kind: impl Into<SpanKind>) -> Vec<&ExportedSpan>;
+    fn spans_named(&self, name: &str) -> Vec<&ExportedSpan>;
+    /// The distinct trace ids (in order of first appearance)
+    fn trace_ids(&self) -> Vec<&str>;
+    /// The spans with `parent_span_id` as parent
+    fn children_of(&self, parent_span_id: &str) -> Vec<&ExportedSpan>;
+}
+
+impl ExportedSpansExt for [ExportedSpan] {
+    fn spans_with_kind(&self, kind: impl Into<SpanKind>) -> Vec<&ExportedSpan> {
+        let kind = kind.into();
+        self.iter().filter(|span| span.kind == kind).collect()
+    }
+
+    fn spans_named(&self, name: &str) -> Vec<&ExportedSpan> {
+        self.iter().filter(|span| span.name == name).collect()
+    }
+
+    fn trace_ids(&self) -> Vec<&str> {
+        let mut trace_ids = Vec::new();
+        for span in self {
+            if !trace_ids.contains(&span.trace_id.as_str()) {
+                trace_ids.push(span.trace_id.as_str());
+            }
         }
+        trace_ids
+    }
+
+    fn children_of(&self, parent_span_id: &str) -> Vec<&ExportedSpan> {
+        self.iter()
+            .filter(|span| span.parent_span_id == parent_span_id)
+            .collect()
     }
 }
 
@@ -72,7 +191,7 @@ pub struct Link {
     pub trace_id: String,
     pub span_id: String,
     pub trace_state: String,
-    pub attributes: BTreeMap<String, String>,
+    pub attributes: BTreeMap<String, AttrValue>,
     pub dropped_attributes_count: u32,
 }
 
@@ -92,7 +211,7 @@ impl From<&opentelemetry_proto::tonic::trace::v1::span::Link> for Link {
 pub struct Event {
     pub time_unix_nano: u64,
     pub name: String,
-    pub attributes: BTreeMap<String, String>,
+    pub attributes: BTreeMap<String, AttrValue>,
     pub dropped_attributes_count: u32,
 }
 
@@ -124,23 +243,31 @@ impl TraceService for FakeTraceService {
         request: tonic::Request<ExportTraceServiceRequest>,
     ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
         debug!("Sending request into channel...");
-        let sender = self.tx.clone();
-        for es in request
-            .into_inner()
-            .resource_spans
-            .into_iter()
-            .flat_map(|rs| rs.scope_spans)
-            .flat_map(|ss| ss.spans)
-            .map(ExportedSpan::from)
-        {
-            sender
-                .send(es)
-                .await
-                .inspect_err(|e| eprintln!("failed to send to channel: {e}"))
-                .map_err(|err| tonic::Status::from_error(Box::new(err)))?;
-        }
+        send_spans(&self.tx, request.into_inner())
+            .await
+            .map_err(|err| tonic::Status::from_error(Box::new(err)))?;
         Ok(tonic::Response::new(ExportTraceServiceResponse {
             partial_success: None,
         }))
     }
 }
+
+/// Send the spans of the request (received via grpc or http) into the channel
+pub(crate) async fn send_spans(
+    sender: &mpsc::Sender<ExportedSpan>,
+    request: ExportTraceServiceRequest,
+) -> Result<(), mpsc::error::SendError<ExportedSpan>> {
+    for es in request
+        .resource_spans
+        .into_iter()
+        .flat_map(|rs| rs.scope_spans)
+        .flat_map(|ss| ss.spans)
+        .map(ExportedSpan::from)
+    {
+        sender
+            .send(es)
+            .await
+            .inspect_err(|e| eprintln!("failed to send to channel: {e}"))?;
+    }
+    Ok(())
+}
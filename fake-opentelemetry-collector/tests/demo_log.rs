@@ -46,6 +46,6 @@ async fn demo_fake_logger_and_collector() {
         "[].observed_time_unix_nano" => "[timestamp]",
         "[].severity_number" => 9,
         "[].severity_text" => "info",
-        "[].body" => "AnyValue { value: Some(StringValue(\"This is information\")) }",
+        "[].body" => "This is information",
     });
 }
@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use fake_opentelemetry_collector::{setup_meter_provider, ExportedMetricsExt, FakeCollectorServer};
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry::KeyValue;
+use tracing::debug;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn demo_fake_meter_and_collector() {
+    debug!("Start the fake collector");
+    let mut fake_collector = FakeCollectorServer::start()
+        .await
+        .expect("fake collector setup and started");
+
+    debug!("Init the 'application' & meter provider");
+    let meter_provider = setup_meter_provider(&fake_collector).await;
+    let counter = meter_provider.meter("test").u64_counter("requests").build();
+    let attributes = [KeyValue::new("http.route", "/users")];
+
+    debug!("Run the 'application' & export a first snapshot");
+    counter.add(3, &attributes);
+    meter_provider.force_flush().expect("flush the metrics");
+    let before = fake_collector
+        .exported_metrics(1, Duration::from_millis(500))
+        .await;
+
+    debug!("Run the 'application' & export a second (cumulative) snapshot");
+    counter.add(2, &attributes);
+    meter_provider.force_flush().expect("flush the metrics");
+    let after = fake_collector
+        .exported_metrics(1, Duration::from_millis(500))
+        .await;
+    meter_provider.shutdown().expect("no error during shutdown");
+
+    let route = [("http.route", "/users".into())];
+    assert_eq!(after.last_value("requests", &route), Some(5.0));
+    after.assert_counter_increase(&before, "requests", &route, 2.0);
+    let delta = after
+        .last_metric_named("requests")
+        .map(|later| later.delta(before.last_metric_named("requests").unwrap()));
+    assert_eq!(
+        delta.and_then(|d| d.data_point(&route).map(|dp| dp.value.as_f64())),
+        Some(2.0)
+    );
+}
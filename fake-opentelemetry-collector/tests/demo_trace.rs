@@ -1,6 +1,8 @@
 use std::time::Duration;
 
-use fake_opentelemetry_collector::{setup_tracer_provider, FakeCollectorServer};
+use fake_opentelemetry_collector::{
+    setup_tracer_provider, ExportedSpansExt, FakeCollectorServer, StatusCode,
+};
 use opentelemetry::trace::TracerProvider;
 use opentelemetry::trace::{Span, SpanKind, Tracer};
 use tracing::debug;
@@ -35,6 +37,10 @@ async fn demo_fake_tracer_and_collector() {
     let otel_spans = fake_collector
         .exported_spans(1, Duration::from_secs(20))
         .await;
+    assert_eq!(otel_spans.spans_with_kind(SpanKind::Server).len(), 1);
+    assert_eq!(otel_spans.spans_named("my-test-span").len(), 1);
+    assert_eq!(otel_spans.trace_ids().len(), 1);
+    assert_eq!(otel_spans[0].status_code(), StatusCode::Unset);
     //insta::assert_debug_snapshot!(otel_spans);
     insta::assert_yaml_snapshot!(otel_spans, {
         "[].start_time_unix_nano" => "[timestamp]",
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use fake_opentelemetry_collector::{
+    AttrValue, ExportedSpan, ExportedSpansExt, FakeCollectorServer,
+};
+use opentelemetry::trace::{Span, SpanKind, Tracer, TracerProvider};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{Protocol, WithExportConfig};
+
+async fn export_spans_via_http(protocol: Protocol) -> Vec<ExportedSpan> {
+    let mut fake_collector = FakeCollectorServer::start_http()
+        .await
+        .expect("fake collector setup and started");
+
+    let tracer_provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(
+            opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_protocol(protocol)
+                .with_endpoint(format!("{}/v1/traces", fake_collector.endpoint()))
+                .build()
+                .expect("failed to build the exporter"),
+            opentelemetry_sdk::runtime::Tokio,
+        )
+        .build();
+    let tracer = tracer_provider.tracer("test");
+    let mut span = tracer
+        .span_builder("my-test-span")
+        .with_kind(SpanKind::Server)
+        .with_attributes([
+            KeyValue::new("http.request.method", "GET"),
+            KeyValue::new("http.response.status_code", 200),
+        ])
+        .start(&tracer);
+    span.add_event("my-test-event", vec![]);
+    span.end();
+
+    let _ = tracer_provider.force_flush();
+    tracer_provider
+        .shutdown()
+        .expect("no error during shutdown");
+    drop(tracer_provider);
+
+    fake_collector
+        .exported_spans(1, Duration::from_secs(20))
+        .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn collect_spans_sent_with_http_json_like_with_http_protobuf() {
+    let json_spans = export_spans_via_http(Protocol::HttpJson).await;
+    let protobuf_spans = export_spans_via_http(Protocol::HttpBinary).await;
+
+    for otel_spans in [&json_spans, &protobuf_spans] {
+        assert_eq!(otel_spans.spans_with_kind(SpanKind::Server).len(), 1);
+        let span = &otel_spans[0];
+        assert_eq!(span.name, "my-test-span");
+        assert_eq!(span.trace_id.len(), 32);
+        assert_eq!(span.events.len(), 1);
+        assert_eq!(
+            span.attributes.get("http.request.method"),
+            Some(&AttrValue::from("GET"))
+        );
+        assert_eq!(
+            span.attributes.get("http.response.status_code"),
+            Some(&AttrValue::Int(200))
+        );
+    }
+}
@@ -0,0 +1,13 @@
+fn main() {
+    // version of rustc, used for the resource attribute `process.runtime.version`
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = std::process::Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .and_then(|v| v.split_whitespace().nth(1).map(ToString::to_string))
+        .unwrap_or_default();
+    println!("cargo:rustc-env=INIT_TRACING_RUSTC_VERSION={version}");
+    println!("cargo:rerun-if-env-changed=RUSTC");
+}
@@ -0,0 +1,85 @@
+use futures_core::future::BoxFuture;
+use opentelemetry::{KeyValue, StringValue, Value};
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use tracing_opentelemetry_instrumentation_sdk::truncate_to;
+
+/// Wrap a `SpanExporter` to truncate the string values of the attributes (of span, events and links)
+/// to `max_attribute_len` bytes before export.
+///
+/// `opentelemetry_sdk` only supports limits on the count of attributes, events, links
+/// (via `OTEL_SPAN_ATTRIBUTE_COUNT_LIMIT`, `OTEL_SPAN_EVENT_COUNT_LIMIT`,...), not on the length of values.
+#[derive(Debug)]
+pub struct TruncateAttributeValueExporter<E> {
+    inner: E,
+    max_attribute_len: Option<usize>,
+}
+
+impl<E> TruncateAttributeValueExporter<E> {
+    /// Use `None` to export attributes as is.
+    pub fn new(inner: E, max_attribute_len: Option<usize>) -> Self {
+        Self {
+            inner,
+            max_attribute_len,
+        }
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for TruncateAttributeValueExporter<E> {
+    fn export(&mut self, mut batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        if let Some(max_len) = self.max_attribute_len {
+            for span in &mut batch {
+                truncate_attributes(&mut span.attributes, max_len);
+                for event in &mut span.events.events {
+                    truncate_attributes(&mut event.attributes, max_len);
+                }
+                for link in &mut span.links.links {
+                    truncate_attributes(&mut link.attributes, max_len);
+                }
+            }
+        }
+        self.inner.export(batch)
+    }
+
+    fn shutdown(&mut self) {
+        self.inner.shutdown();
+    }
+
+    fn force_flush(&mut self) -> BoxFuture<'static, ExportResult> {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+fn truncate_attributes(attributes: &mut [KeyValue], max_len: usize) {
+    for kv in attributes {
+        if let Value::String(s) = &kv.value {
+            if s.as_str().len() > max_len {
+                let truncated = truncate_to(s.as_str(), max_len).into_owned();
+                kv.value = Value::String(StringValue::from(truncated));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn truncate_only_long_string_values() {
+        let mut attributes = vec![
+            KeyValue::new("short", "abc"),
+            KeyValue::new("long", "abcdefgh"),
+            KeyValue::new("number", 123_456_789_i64),
+        ];
+        truncate_attributes(&mut attributes, 4);
+        assert!(attributes[0].value == Value::from("abc"));
+        assert!(attributes[1].value == Value::from("abcd"));
+        assert!(attributes[2].value == Value::I64(123_456_789));
+    }
+}
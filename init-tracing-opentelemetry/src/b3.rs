@@ -0,0 +1,249 @@
+//! [B3](https://github.com/openzipkin/b3-propagation) propagator, without the dependencies of the zipkin exporter
+//! (for the services that only need the compatibility of the headers).
+//!
+//! - single header: `b3: {trace_id}-{span_id}-{sampling_state}-{parent_span_id}`
+//! - multiple headers: `x-b3-traceid`, `x-b3-spanid`, `x-b3-sampled`, `x-b3-flags` (`x-b3-parentspanid` is ignored)
+//!
+//! The extraction accepts both formats (the single header first), the injection uses the format of the [`B3Encoding`].
+use opentelemetry::{
+    propagation::{text_map_propagator::FieldIter, Extractor, Injector, TextMapPropagator},
+    trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+    Context,
+};
+
+const B3_SINGLE_HEADER: &str = "b3";
+// lower case, as used by HTTP/2 & gRPC (the lookup of the http headers is case-insensitive)
+const B3_TRACE_ID_HEADER: &str = "x-b3-traceid";
+const B3_SPAN_ID_HEADER: &str = "x-b3-spanid";
+const B3_SAMPLED_HEADER: &str = "x-b3-sampled";
+const B3_DEBUG_FLAG_HEADER: &str = "x-b3-flags";
+
+/// the sampling decision is deferred to the receiver (no sampling state into the headers)
+const TRACE_FLAG_DEFERRED: TraceFlags = TraceFlags::new(0x02);
+const TRACE_FLAG_DEBUG: TraceFlags = TraceFlags::new(0x04);
+
+/// The format of the injected headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum B3Encoding {
+    /// the header `b3` (`OTEL_PROPAGATORS=b3`)
+    SingleHeader,
+    /// the headers `x-b3-*` (`OTEL_PROPAGATORS=b3multi`)
+    MultipleHeader,
+}
+
+/// Extract & inject the context with the B3 headers.
+#[derive(Debug, Clone)]
+pub struct B3Propagator {
+    encoding: B3Encoding,
+    fields: Vec<String>,
+}
+
+impl B3Propagator {
+    #[must_use]
+    pub fn with_encoding(encoding: B3Encoding) -> Self {
+        let fields = match encoding {
+            B3Encoding::SingleHeader => vec![B3_SINGLE_HEADER],
+            B3Encoding::MultipleHeader => vec![
+                B3_TRACE_ID_HEADER,
+                B3_SPAN_ID_HEADER,
+                B3_SAMPLED_HEADER,
+                B3_DEBUG_FLAG_HEADER,
+            ],
+        };
+        B3Propagator {
+            encoding,
+            fields: fields.into_iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+fn parse_trace_id(value: &str) -> Option<TraceId> {
+    // only the lower case hex (64 or 128 bits)
+    ((value.len() == 16 || value.len() == 32) && !value.bytes().any(|b| b.is_ascii_uppercase()))
+        .then(|| TraceId::from_hex(value).ok())
+        .flatten()
+}
+
+fn parse_span_id(value: &str) -> Option<SpanId> {
+    (value.len() == 16 && !value.bytes().any(|b| b.is_ascii_uppercase()))
+        .then(|| SpanId::from_hex(value).ok())
+        .flatten()
+}
+
+fn parse_sampling_state(value: &str) -> Option<TraceFlags> {
+    match value {
+        "0" | "false" => Some(TraceFlags::default()),
+        "1" | "true" => Some(TraceFlags::SAMPLED),
+        // debug implies sampled
+        "d" => Some(TRACE_FLAG_DEBUG | TraceFlags::SAMPLED),
+        _ => None,
+    }
+}
+
+fn remote_span_context(
+    trace_id: TraceId,
+    span_id: SpanId,
+    trace_flags: TraceFlags,
+) -> Option<SpanContext> {
+    Some(SpanContext::new(
+        trace_id,
+        span_id,
+        trace_flags,
+        true,
+        TraceState::default(),
+    ))
+    .filter(SpanContext::is_valid)
+}
+
+fn extract_single_header(extractor: &dyn Extractor) -> Option<SpanContext> {
+    let parts = extractor
+        .get(B3_SINGLE_HEADER)?
+        .split_terminator('-')
+        .collect::<Vec<_>>();
+    if !(2..=4).contains(&parts.len()) {
+        return None;
+    }
+    let trace_flags = match parts.get(2) {
+        Some(sampling_state) => parse_sampling_state(sampling_state)?,
+        None => TRACE_FLAG_DEFERRED,
+    };
+    if let Some(parent_span_id) = parts.get(3) {
+        parse_span_id(parent_span_id)?;
+    }
+    remote_span_context(
+        parse_trace_id(parts[0])?,
+        parse_span_id(parts[1])?,
+        trace_flags,
+    )
+}
+
+fn extract_multiple_header(extractor: &dyn Extractor) -> Option<SpanContext> {
+    let trace_id = parse_trace_id(extractor.get(B3_TRACE_ID_HEADER)?)?;
+    let span_id = parse_span_id(extractor.get(B3_SPAN_ID_HEADER)?)?;
+    let trace_flags = if extractor.get(B3_DEBUG_FLAG_HEADER) == Some("1") {
+        TRACE_FLAG_DEBUG | TraceFlags::SAMPLED
+    } else if let Some(sampled) = extractor.get(B3_SAMPLED_HEADER) {
+        parse_sampling_state(sampled)?
+    } else {
+        TRACE_FLAG_DEFERRED
+    };
+    remote_span_context(trace_id, span_id, trace_flags)
+}
+
+impl TextMapPropagator for B3Propagator {
+    fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        let is_debug = span_context.trace_flags() & TRACE_FLAG_DEBUG == TRACE_FLAG_DEBUG;
+        let is_deferred = span_context.trace_flags() & TRACE_FLAG_DEFERRED == TRACE_FLAG_DEFERRED;
+        let sampled = if span_context.is_sampled() { "1" } else { "0" };
+        match (self.encoding, span_context.is_valid()) {
+            (B3Encoding::SingleHeader, true) => {
+                let mut value = format!("{}-{}", span_context.trace_id(), span_context.span_id());
+                if is_debug {
+                    value.push_str("-d");
+                } else if !is_deferred {
+                    value.push('-');
+                    value.push_str(sampled);
+                }
+                injector.set(B3_SINGLE_HEADER, value);
+            }
+            (B3Encoding::MultipleHeader, true) => {
+                injector.set(B3_TRACE_ID_HEADER, span_context.trace_id().to_string());
+                injector.set(B3_SPAN_ID_HEADER, span_context.span_id().to_string());
+                if is_debug {
+                    injector.set(B3_DEBUG_FLAG_HEADER, "1".to_string());
+                } else if !is_deferred {
+                    injector.set(B3_SAMPLED_HEADER, sampled.to_string());
+                }
+            }
+            // only the sampling decision
+            (B3Encoding::SingleHeader, false) => {
+                injector.set(B3_SINGLE_HEADER, sampled.to_string());
+            }
+            (B3Encoding::MultipleHeader, false) => {
+                injector.set(B3_SAMPLED_HEADER, sampled.to_string());
+            }
+        }
+    }
+
+    fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+        match extract_single_header(extractor).or_else(|| extract_multiple_header(extractor)) {
+            Some(span_context) => cx.with_remote_span_context(span_context),
+            None => cx.clone(),
+        }
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        FieldIter::new(&self.fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use rstest::rstest;
+    use std::collections::HashMap;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    fn extract(headers: &[(&str, &str)]) -> SpanContext {
+        let headers = headers
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect::<HashMap<_, _>>();
+        B3Propagator::with_encoding(B3Encoding::SingleHeader)
+            .extract(&headers)
+            .span()
+            .span_context()
+            .clone()
+    }
+
+    #[rstest]
+    #[case(&[("b3", "4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1")], Some(TraceFlags::SAMPLED))]
+    #[case(&[("b3", "4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-0-00f067aa0ba902b8")], Some(TraceFlags::default()))]
+    #[case(&[("b3", "4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7")], Some(TRACE_FLAG_DEFERRED))]
+    #[case(&[("b3", "4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-d")], Some(TRACE_FLAG_DEBUG | TraceFlags::SAMPLED))]
+    #[case(&[("b3", "4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-1")], None)]
+    #[case(&[("b3", "1")], None)]
+    #[case(&[("x-b3-traceid", TRACE_ID), ("x-b3-spanid", SPAN_ID), ("x-b3-sampled", "1")], Some(TraceFlags::SAMPLED))]
+    #[case(&[("x-b3-traceid", TRACE_ID), ("x-b3-spanid", SPAN_ID), ("x-b3-flags", "1")], Some(TRACE_FLAG_DEBUG | TraceFlags::SAMPLED))]
+    #[case(&[("x-b3-traceid", TRACE_ID), ("x-b3-sampled", "1")], None)]
+    fn test_extract(#[case] headers: &[(&str, &str)], #[case] expected: Option<TraceFlags>) {
+        let span_context = extract(headers);
+        match expected {
+            Some(trace_flags) => {
+                assert!(span_context.is_remote());
+                assert!(span_context.trace_id() == TraceId::from_hex(TRACE_ID).unwrap());
+                assert!(span_context.span_id() == SpanId::from_hex(SPAN_ID).unwrap());
+                assert!(span_context.trace_flags() == trace_flags);
+            }
+            None => assert!(!span_context.is_valid()),
+        }
+    }
+
+    #[rstest]
+    #[case(B3Encoding::SingleHeader, &[("b3", "4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1")])]
+    #[case(B3Encoding::MultipleHeader, &[("x-b3-traceid", TRACE_ID), ("x-b3-spanid", SPAN_ID), ("x-b3-sampled", "1")])]
+    fn test_inject(#[case] encoding: B3Encoding, #[case] expected: &[(&str, &str)]) {
+        let span_context = SpanContext::new(
+            TraceId::from_hex(TRACE_ID).unwrap(),
+            SpanId::from_hex(SPAN_ID).unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let mut headers = HashMap::new();
+        B3Propagator::with_encoding(encoding).inject_context(
+            &Context::new().with_remote_span_context(span_context),
+            &mut headers,
+        );
+        let expected = expected
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect::<HashMap<_, _>>();
+        assert!(headers == expected);
+    }
+}
@@ -0,0 +1,114 @@
+use opentelemetry::baggage::BaggageExt;
+use opentelemetry::trace::{Span as _, TraceResult};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_sdk::export::trace::SpanData;
+use opentelemetry_sdk::trace::{Span, SpanProcessor};
+
+/// `SpanProcessor` that copies the entries of the baggage (of the parent context) into the attributes
+/// of every new span.
+///
+/// By default, every entry is copied, use [`BaggageSpanProcessor::with_allowlist`]
+/// or [`BaggageSpanProcessor::with_prefix`] to restrict the keys.
+///
+/// ```rust
+/// use init_tracing_opentelemetry::BaggageSpanProcessor;
+///
+/// let processor = BaggageSpanProcessor::default().with_prefix("app.");
+/// // register it via the `transform` of `otlp::init_tracerprovider`:
+/// // `|builder| builder.with_span_processor(processor)`
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BaggageSpanProcessor {
+    allowlist: Vec<String>,
+    prefixes: Vec<String>,
+}
+
+impl BaggageSpanProcessor {
+    /// Copy the entries with the given keys
+    #[must_use]
+    pub fn with_allowlist<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowlist.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Copy the entries with a key starting with `prefix`
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes.push(prefix.into());
+        self
+    }
+
+    fn is_allowed(&self, key: &str) -> bool {
+        (self.allowlist.is_empty() && self.prefixes.is_empty())
+            || self.allowlist.iter().any(|k| k == key)
+            || self.prefixes.iter().any(|p| key.starts_with(p.as_str()))
+    }
+}
+
+impl SpanProcessor for BaggageSpanProcessor {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        for (key, (value, _metadata)) in cx.baggage() {
+            if self.is_allowed(key.as_str()) {
+                span.set_attribute(KeyValue::new(key.clone(), value.clone()));
+            }
+        }
+    }
+
+    fn on_end(&self, _span: SpanData) {}
+
+    fn force_flush(&self) -> TraceResult<()> {
+        Ok(())
+    }
+
+    fn shutdown(&self) -> TraceResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::trace::{Tracer, TracerProvider as _};
+    use opentelemetry::Value;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use opentelemetry_sdk::trace::TracerProvider;
+
+    #[test]
+    fn copy_allowed_baggage_entries_into_span_attributes() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_span_processor(
+                BaggageSpanProcessor::default()
+                    .with_allowlist(["user.id"])
+                    .with_prefix("app."),
+            )
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let cx = Context::new().with_baggage(vec![
+            KeyValue::new("user.id", "42"),
+            KeyValue::new("app.tenant", "acme"),
+            KeyValue::new("secret", "xxx"),
+        ]);
+        provider
+            .tracer("test")
+            .start_with_context("span", &cx)
+            .end();
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        let_assert!([span] = spans.as_slice());
+        let attribute = |key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.clone())
+        };
+        assert!(attribute("user.id") == Some(Value::from("42")));
+        assert!(attribute("app.tenant") == Some(Value::from("acme")));
+        assert!(attribute("secret").is_none());
+    }
+}
@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use crate::Error;
+
+/// The default maximum number of spans buffered by the batch processor (same as the sdk)
+#[cfg(feature = "otlp")]
+const DEFAULT_MAX_QUEUE_SIZE: usize = 2048;
+
+/// The settings of the batch processor that export the spans, the unset values use the environment variables
+/// [`OTEL_BSP_*`](https://opentelemetry.io/docs/specs/otel/configuration/sdk-environment-variables/#batch-span-processor)
+/// or the default of the sdk.
+///
+/// The environment variables keep the priority (same as with the configuration file), so the settings can be tuned
+/// at deployment without recompilation.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use init_tracing_opentelemetry::BatchConfig;
+///
+/// // for a high-throughput service
+/// let batch_config = BatchConfig::default()
+///     .with_max_queue_size(65_536)
+///     .with_max_export_batch_size(4_096)
+///     .with_scheduled_delay(Duration::from_millis(500));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchConfig {
+    /// the maximum number of spans buffered (the spans are dropped when the queue is full)
+    /// (`OTEL_BSP_MAX_QUEUE_SIZE`, default: 2048)
+    pub max_queue_size: Option<usize>,
+    /// the delay between two consecutive exports (`OTEL_BSP_SCHEDULE_DELAY` in milliseconds, default: 5s)
+    pub scheduled_delay: Option<Duration>,
+    /// the maximum number of spans exported in a batch, should be less or equal to `max_queue_size`
+    /// (`OTEL_BSP_MAX_EXPORT_BATCH_SIZE`, default: 512)
+    pub max_export_batch_size: Option<usize>,
+}
+
+impl BatchConfig {
+    #[must_use]
+    pub fn with_max_queue_size(self, max_queue_size: usize) -> Self {
+        BatchConfig {
+            max_queue_size: Some(max_queue_size),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_scheduled_delay(self, scheduled_delay: Duration) -> Self {
+        BatchConfig {
+            scheduled_delay: Some(scheduled_delay),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_max_export_batch_size(self, max_export_batch_size: usize) -> Self {
+        BatchConfig {
+            max_export_batch_size: Some(max_export_batch_size),
+            ..self
+        }
+    }
+
+    /// Read the settings from the environment variables `OTEL_BSP_MAX_QUEUE_SIZE`, `OTEL_BSP_SCHEDULE_DELAY`
+    /// & `OTEL_BSP_MAX_EXPORT_BATCH_SIZE`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error::InvalidEnv` if a variable is not a positive integer
+    /// (the sdk silently ignores it).
+    pub fn from_env() -> Result<Self, Error> {
+        Ok(BatchConfig {
+            max_queue_size: read_positive_env("OTEL_BSP_MAX_QUEUE_SIZE")?,
+            scheduled_delay: read_positive_env("OTEL_BSP_SCHEDULE_DELAY")?
+                .map(|millis| Duration::from_millis(millis as u64)),
+            max_export_batch_size: read_positive_env("OTEL_BSP_MAX_EXPORT_BATCH_SIZE")?,
+        })
+    }
+
+    /// The settings of `self`, completed by the ones of `other` for the unset values.
+    #[must_use]
+    pub fn or(self, other: BatchConfig) -> Self {
+        BatchConfig {
+            max_queue_size: self.max_queue_size.or(other.max_queue_size),
+            scheduled_delay: self.scheduled_delay.or(other.scheduled_delay),
+            max_export_batch_size: self.max_export_batch_size.or(other.max_export_batch_size),
+        }
+    }
+
+    /// Resolve the settings (the environment variables override `self`) into the configuration of the sdk
+    /// and the maximum size of the queue (not readable from the configuration of the sdk).
+    #[cfg(feature = "otlp")]
+    pub(crate) fn resolve(self) -> Result<(opentelemetry_sdk::trace::BatchConfig, usize), Error> {
+        let config = Self::from_env()?.or(self);
+        let max_queue_size = config.max_queue_size.unwrap_or(DEFAULT_MAX_QUEUE_SIZE);
+        let mut builder = opentelemetry_sdk::trace::BatchConfigBuilder::default()
+            .with_max_queue_size(max_queue_size);
+        if let Some(scheduled_delay) = config.scheduled_delay {
+            builder = builder.with_scheduled_delay(scheduled_delay);
+        }
+        if let Some(max_export_batch_size) = config.max_export_batch_size {
+            builder = builder.with_max_export_batch_size(max_export_batch_size);
+        }
+        Ok((builder.build(), max_queue_size))
+    }
+}
+
+pub(crate) fn read_positive_env(name: &str) -> Result<Option<usize>, Error> {
+    std::env::var(name)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(|value| {
+            parse_positive(&value).map_err(|reason| Error::InvalidEnv {
+                name: name.to_string(),
+                value: value.clone(),
+                reason: reason.to_string(),
+            })
+        })
+        .transpose()
+}
+
+fn parse_positive(value: &str) -> Result<usize, &'static str> {
+    match value.trim().parse::<usize>() {
+        Ok(0) => Err("should be greater than 0"),
+        Ok(v) => Ok(v),
+        Err(_) => Err("should be a positive integer"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("2048", Ok(2048))]
+    #[case(" 512 ", Ok(512))]
+    #[case("0", Err("should be greater than 0"))]
+    #[case("-1", Err("should be a positive integer"))]
+    #[case("5s", Err("should be a positive integer"))]
+    fn test_parse_positive(#[case] input: &str, #[case] expected: Result<usize, &'static str>) {
+        assert!(parse_positive(input) == expected);
+    }
+
+    #[test]
+    fn or_completes_the_unset_values() {
+        let config = BatchConfig::default()
+            .with_max_queue_size(10)
+            .or(BatchConfig::default()
+                .with_max_queue_size(20)
+                .with_max_export_batch_size(5));
+        assert!(config.max_queue_size == Some(10));
+        assert!(config.max_export_batch_size == Some(5));
+        assert!(config.scheduled_delay.is_none());
+    }
+}
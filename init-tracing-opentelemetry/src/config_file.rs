@@ -0,0 +1,779 @@
+//! Configuration from a file (TOML or YAML), to tune the telemetry without recompilation.
+//!
+//! ```toml
+//! [log]
+//! # same syntax as `RUST_LOG`
+//! directives = "info,axum_tracing_opentelemetry=debug"
+//! # "auto" (default: "pretty" for the debug builds, "json" for the release builds), "full", "compact", "pretty" or "json"
+//! format = "json"
+//! # "stdout" (default) or "stderr"
+//! writer = "stdout"
+//!
+//! [otel]
+//! # `false` to not export traces (same as `OTEL_TRACES_EXPORTER=none`)
+//! enabled = true
+//! service_name = "my-service"
+//! traces_exporter = "otlp"
+//! endpoint = "http://localhost:4317"
+//! protocol = "grpc"
+//! # the collector of the "zipkin" exporter (require feature `zipkin`)
+//! zipkin_endpoint = "http://localhost:9411/api/v2/spans"
+//! # "gzip" (require feature `gzip`) or "zstd" (require feature `zstd`), only for "grpc"
+//! compression = "gzip"
+//! sampler = "parentbased_traceidratio"
+//! sampler_arg = "0.1"
+//! propagators = ["tracecontext", "baggage"]
+//! # where the tracing's events are sent: "span_events" (default), "logs" or "both"
+//! event_destination = "span_events"
+//! # `true` to continue without export if the setup of the exporter fails (default: `false`)
+//! fail_open = false
+//! # the runtime of the exporter: "tokio" (default, the runtime of the application) or "own_thread" (eg for a CLI)
+//! runtime = "tokio"
+//! # `true` to count the spans & the exports `otel.sdk.*` (require feature `self_metrics`, default: `false`)
+//! self_metrics = false
+//! # `true` to apply the recording gate of the layers (eg `OtelAxumLayer::with_recording_gate`, default: `false`)
+//! recording_gate = false
+//!
+//! # the batch processor of the exporter (the `OTEL_BSP_*` env variables keep the priority)
+//! [otel.batch]
+//! max_queue_size = 2048
+//! # in milliseconds
+//! schedule_delay = 5000
+//! max_export_batch_size = 512
+//!
+//! # the tracing's events recorded as span events (used by `init_subscribers_with_config`)
+//! [otel.span_events]
+//! # the most verbose level recorded: "error", "warn", "info", "debug" or "trace" (default: all)
+//! max_level = "info"
+//! # the targets (and their sub-modules) recorded (default: all)
+//! target_allowlist = ["my_app", "axum_tracing_opentelemetry"]
+//! max_per_span = 128
+//!
+//! # the export of the metrics (require feature `metrics`, the `OTEL_METRIC_EXPORT_*` env variables keep the priority)
+//! [otel.metrics]
+//! # in milliseconds
+//! export_interval = 60000
+//! export_timeout = 30000
+//!
+//! # the attributes kept on the metrics, per instrument name (glob), to limit the cardinality
+//! [otel.metrics.attribute_allowlist]
+//! "http.server.*" = ["http.route", "http.request.method", "http.response.status_code"]
+//!
+//! [otel.resource]
+//! "deployment.environment.name" = "production"
+//! ```
+//!
+//! The same keys are supported into a YAML file (extension `.yaml` or `.yml`), the unknown keys are rejected.
+//!
+//! The configuration is applied by defining the environment variables (used by the rest of the crate)
+//! that are not already defined, so the environment variables keep the priority over the file.
+use std::collections::BTreeMap;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{
+    BatchConfig, Error, EventDestination, LogFormat, LogWriter, RuntimeMode, SpanEventsConfig,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TracingConfig {
+    /// log directives (`RUST_LOG`)
+    pub log_directives: Option<String>,
+    /// the format of the logs (not applied to the env, used by `tracing_subscriber_ext::init_subscribers_with_config`)
+    pub log_format: Option<LogFormat>,
+    /// where the logs are written (not applied to the env, used by `tracing_subscriber_ext::init_subscribers_with_config`)
+    pub log_writer: Option<LogWriter>,
+    /// `Some(false)` to disable the export of traces (`OTEL_TRACES_EXPORTER=none`)
+    pub otel_enabled: Option<bool>,
+    /// `OTEL_SERVICE_NAME`
+    pub service_name: Option<String>,
+    /// `OTEL_TRACES_EXPORTER`
+    pub traces_exporter: Option<String>,
+    /// `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`
+    pub endpoint: Option<String>,
+    /// `OTEL_EXPORTER_ZIPKIN_ENDPOINT`
+    pub zipkin_endpoint: Option<String>,
+    /// `OTEL_EXPORTER_OTLP_TRACES_PROTOCOL`
+    pub protocol: Option<String>,
+    /// `OTEL_EXPORTER_OTLP_COMPRESSION` (for every signal)
+    pub compression: Option<String>,
+    /// `OTEL_TRACES_SAMPLER`
+    pub sampler: Option<String>,
+    /// `OTEL_TRACES_SAMPLER_ARG`
+    pub sampler_arg: Option<String>,
+    /// `OTEL_PROPAGATORS`
+    pub propagators: Option<Vec<String>>,
+    /// `OTEL_RESOURCE_ATTRIBUTES` (the attributes already defined into the env variable keep the priority)
+    pub resource_attributes: BTreeMap<String, String>,
+    /// where the tracing's events are sent (not applied to the env, use
+    /// `EventDestination::span_events_filter` & `EventDestination::logs_filter` on the layers)
+    pub event_destination: Option<EventDestination>,
+    /// continue without export (instead of failing) if the setup of the exporter fails (not applied to the env,
+    /// used by `tracing_subscriber_ext::init_subscribers_with_config`)
+    pub fail_open: Option<bool>,
+    /// the runtime of the exporter (not applied to the env, used by `tracing_subscriber_ext::init_subscribers_with_config`)
+    pub runtime: Option<RuntimeMode>,
+    /// count the spans & the exports (`otel.sdk.*`, require the feature `self_metrics`) (not applied to the env,
+    /// used by `tracing_subscriber_ext::init_subscribers_with_config`)
+    pub self_metrics: Option<bool>,
+    /// apply the recording gate of the layers with the `RecordingGateSpanProcessor` (not applied to the env,
+    /// used by `tracing_subscriber_ext::init_subscribers_with_config`)
+    pub recording_gate: Option<bool>,
+    /// the settings of the batch processor of the exporter (not applied to the env, used by
+    /// `tracing_subscriber_ext::init_subscribers_with_config`, the `OTEL_BSP_*` env variables keep the priority)
+    pub batch: BatchConfig,
+    /// the tracing's events recorded as span events (level, targets, maximum per span) (not applied to the env,
+    /// used by `tracing_subscriber_ext::init_subscribers_with_config`)
+    pub span_events: SpanEventsConfig,
+    /// the delay between two exports of the metrics (not applied to the env, used by
+    /// `tracing_subscriber_ext::init_subscribers_with_config` with the feature `metrics`,
+    /// the `OTEL_METRIC_EXPORT_INTERVAL` env variable keeps the priority)
+    pub metric_export_interval: Option<Duration>,
+    /// the maximum duration of an export of the metrics (like `metric_export_interval`,
+    /// the `OTEL_METRIC_EXPORT_TIMEOUT` env variable keeps the priority)
+    pub metric_timeout: Option<Duration>,
+    /// the attributes kept on the metrics, per instrument name (glob) (not applied to the env, used by
+    /// `tracing_subscriber_ext::init_subscribers_with_config` with the feature `metrics`)
+    pub metric_attribute_allowlists: BTreeMap<String, Vec<String>>,
+}
+
+impl TracingConfig {
+    /// Read the configuration from a file, YAML if the extension is `.yaml` or `.yml`, else TOML
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let is_yaml = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"));
+        std::fs::read_to_string(path)
+            .map_err(Error::from)
+            .and_then(|content| {
+                if is_yaml {
+                    Self::from_yaml(&content)
+                } else {
+                    Self::from_toml(&content)
+                }
+            })
+            .map_err(|source| Error::ConfigFile {
+                path: path.to_path_buf(),
+                source: Box::new(source),
+            })
+    }
+
+    /// Read the configuration from the content of a TOML file (like `str::parse`)
+    pub fn from_toml(content: &str) -> Result<Self, Error> {
+        let model: FileModel =
+            toml::from_str(content).map_err(|err| Error::InvalidConfig(err.to_string()))?;
+        model.try_into()
+    }
+
+    /// Read the configuration from the content of a YAML file
+    pub fn from_yaml(content: &str) -> Result<Self, Error> {
+        // an empty document is `null`, not an empty mapping
+        if content.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        let model: FileModel =
+            serde_yaml::from_str(content).map_err(|err| Error::InvalidConfig(err.to_string()))?;
+        model.try_into()
+    }
+
+    /// Read the configuration from the file and apply it (see [`TracingConfig::apply_to_env`]).
+    ///
+    /// Should be called at the start of the `main` (before starting threads & before the initialization of the subscribers).
+    pub fn from_env_and_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let config = Self::from_file(path)?;
+        config.apply_to_env();
+        Ok(config)
+    }
+
+    /// Write the logs with the format `log_format`
+    #[must_use]
+    pub fn with_log_format(mut self, log_format: LogFormat) -> Self {
+        self.log_format = Some(log_format);
+        self
+    }
+
+    /// The format of the logs (default: `LogFormat::Auto`)
+    #[must_use]
+    pub fn log_format(&self) -> LogFormat {
+        self.log_format.unwrap_or_default()
+    }
+
+    /// Write the logs to `log_writer`, eg `LogWriter::Stderr` for a CLI
+    #[must_use]
+    pub fn with_log_writer(mut self, log_writer: LogWriter) -> Self {
+        self.log_writer = Some(log_writer);
+        self
+    }
+
+    /// Where the logs are written (default: `LogWriter::Stdout`)
+    #[must_use]
+    pub fn log_writer(&self) -> LogWriter {
+        self.log_writer.unwrap_or_default()
+    }
+
+    #[must_use]
+    pub fn with_event_destination(mut self, event_destination: EventDestination) -> Self {
+        self.event_destination = Some(event_destination);
+        self
+    }
+
+    /// The destination of the tracing's events (default: `EventDestination::SpanEvents`)
+    #[must_use]
+    pub fn event_destination(&self) -> EventDestination {
+        self.event_destination.unwrap_or_default()
+    }
+
+    /// Don't export the traces but keep the propagation of the context (`OTEL_TRACES_EXPORTER=none`, like
+    /// `enabled = false` into the file): the layers still extract & inject the context and the spans get a
+    /// `trace_id` for the logs, eg for a lightweight edge service that only passes the context through
+    #[must_use]
+    pub fn with_propagation_only(mut self, propagation_only: bool) -> Self {
+        self.otel_enabled = Some(!propagation_only);
+        self
+    }
+
+    /// `true` if the traces are not exported, only propagated (default: `false`)
+    #[must_use]
+    pub fn propagation_only(&self) -> bool {
+        self.otel_enabled == Some(false)
+    }
+
+    /// Continue without export (log the error, install the log layers, disable the failed signal)
+    /// instead of failing if the setup of an exporter fails
+    #[must_use]
+    pub fn with_fail_open(mut self, fail_open: bool) -> Self {
+        self.fail_open = Some(fail_open);
+        self
+    }
+
+    /// `true` to continue without export if the setup of an exporter fails (default: `false`)
+    #[must_use]
+    pub fn fail_open(&self) -> bool {
+        self.fail_open.unwrap_or(false)
+    }
+
+    /// Run the exporter on the runtime `runtime`, eg [`RuntimeMode::OwnThread`] to initialize the tracing
+    /// outside of a Tokio runtime (CLI)
+    #[must_use]
+    pub fn with_runtime(mut self, runtime: RuntimeMode) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// The runtime of the exporter (default: `RuntimeMode::Tokio`)
+    #[must_use]
+    pub fn runtime(&self) -> RuntimeMode {
+        self.runtime.unwrap_or_default()
+    }
+
+    /// Count the spans (started, ended, dropped by the sampler or the full queue) & the exports
+    /// (`otel.sdk.*`, see `init_tracing_opentelemetry::self_metrics`) on the meter provider, require the feature
+    /// `self_metrics` (the spans dropped by the filters of the layers are counted by the layers configured with
+    /// `self_metrics::dropped_span_hook`)
+    #[must_use]
+    pub fn with_self_metrics(mut self, self_metrics: bool) -> Self {
+        self.self_metrics = Some(self_metrics);
+        self
+    }
+
+    /// `true` if the self-metrics are enabled (default: `false`)
+    #[must_use]
+    pub fn self_metrics(&self) -> bool {
+        self.self_metrics.unwrap_or(false)
+    }
+
+    /// Apply the recording gate of the layers (eg `OtelAxumLayer::with_recording_gate`): the batch processor of the
+    /// exporter is wrapped by the [`crate::RecordingGateSpanProcessor`]
+    #[must_use]
+    pub fn with_recording_gate(mut self, recording_gate: bool) -> Self {
+        self.recording_gate = Some(recording_gate);
+        self
+    }
+
+    /// `true` if the recording gate is applied (default: `false`)
+    #[must_use]
+    pub fn recording_gate(&self) -> bool {
+        self.recording_gate.unwrap_or(false)
+    }
+
+    /// Compress the exports via OTLP/grpc with `compression` (`gzip` or `zstd`, require the feature of the same name),
+    /// the env variables `OTEL_EXPORTER_OTLP_*COMPRESSION` keep the priority
+    #[must_use]
+    pub fn with_compression(mut self, compression: impl Into<String>) -> Self {
+        self.compression = Some(compression.into());
+        self
+    }
+
+    /// Tune the batch processor of the exporter (queue size, delay & size of the batches), eg for high-throughput
+    #[must_use]
+    pub fn with_batch_config(mut self, batch: BatchConfig) -> Self {
+        self.batch = batch;
+        self
+    }
+
+    /// Select the tracing's events recorded as span events, to not bloat the exported spans with noisy events
+    #[must_use]
+    pub fn with_span_events(mut self, span_events: SpanEventsConfig) -> Self {
+        self.span_events = span_events;
+        self
+    }
+
+    /// Export the metrics every `metric_export_interval` (default: 60s)
+    #[must_use]
+    pub fn with_metric_export_interval(mut self, metric_export_interval: Duration) -> Self {
+        self.metric_export_interval = Some(metric_export_interval);
+        self
+    }
+
+    /// Abandon an export of the metrics after `metric_timeout` (default: 30s)
+    #[must_use]
+    pub fn with_metric_timeout(mut self, metric_timeout: Duration) -> Self {
+        self.metric_timeout = Some(metric_timeout);
+        self
+    }
+
+    /// Keep only the attributes `keys` on the metrics of the instruments matching `instrument_glob`
+    /// (`*` matches any sequence of characters), to limit the cardinality
+    #[must_use]
+    pub fn with_metric_attribute_allowlist<K>(
+        mut self,
+        instrument_glob: impl Into<String>,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Self
+    where
+        K: Into<String>,
+    {
+        self.metric_attribute_allowlists.insert(
+            instrument_glob.into(),
+            keys.into_iter().map(Into::into).collect(),
+        );
+        self
+    }
+
+    /// Define the environment variables (not already defined) from the configuration.
+    pub fn apply_to_env(&self) {
+        set_env_if_absent("RUST_LOG", self.log_directives.as_deref());
+        set_env_if_absent("OTEL_SERVICE_NAME", self.service_name.as_deref());
+        let traces_exporter = if self.otel_enabled == Some(false) {
+            Some("none")
+        } else {
+            self.traces_exporter.as_deref()
+        };
+        set_env_if_absent("OTEL_TRACES_EXPORTER", traces_exporter);
+        set_env_if_absent(
+            "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+            self.endpoint.as_deref(),
+        );
+        set_env_if_absent(
+            "OTEL_EXPORTER_ZIPKIN_ENDPOINT",
+            self.zipkin_endpoint.as_deref(),
+        );
+        set_env_if_absent(
+            "OTEL_EXPORTER_OTLP_TRACES_PROTOCOL",
+            self.protocol.as_deref(),
+        );
+        set_env_if_absent(
+            "OTEL_EXPORTER_OTLP_COMPRESSION",
+            self.compression.as_deref(),
+        );
+        set_env_if_absent("OTEL_TRACES_SAMPLER", self.sampler.as_deref());
+        set_env_if_absent("OTEL_TRACES_SAMPLER_ARG", self.sampler_arg.as_deref());
+        set_env_if_absent(
+            "OTEL_PROPAGATORS",
+            self.propagators.as_ref().map(|v| v.join(",")).as_deref(),
+        );
+        if !self.resource_attributes.is_empty() {
+            // the last value of a key wins, so values from the env are appended
+            let mut attributes = self
+                .resource_attributes
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect::<Vec<_>>();
+            if let Ok(existing) = std::env::var("OTEL_RESOURCE_ATTRIBUTES") {
+                attributes.push(existing);
+            }
+            std::env::set_var("OTEL_RESOURCE_ATTRIBUTES", attributes.join(","));
+        }
+    }
+}
+
+impl std::str::FromStr for TracingConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_toml(s)
+    }
+}
+
+/// The model of the file (TOML or YAML)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileModel {
+    log: LogModel,
+    otel: OtelModel,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LogModel {
+    directives: Option<String>,
+    format: Option<String>,
+    writer: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct OtelModel {
+    enabled: Option<bool>,
+    service_name: Option<String>,
+    traces_exporter: Option<String>,
+    endpoint: Option<String>,
+    zipkin_endpoint: Option<String>,
+    protocol: Option<String>,
+    compression: Option<String>,
+    sampler: Option<String>,
+    sampler_arg: Option<Scalar>,
+    propagators: Option<Vec<String>>,
+    event_destination: Option<String>,
+    fail_open: Option<bool>,
+    runtime: Option<String>,
+    self_metrics: Option<bool>,
+    recording_gate: Option<bool>,
+    batch: BatchModel,
+    span_events: SpanEventsModel,
+    metrics: MetricsModel,
+    resource: BTreeMap<String, Scalar>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BatchModel {
+    max_queue_size: Option<NonZeroUsize>,
+    /// in milliseconds
+    schedule_delay: Option<NonZeroU64>,
+    max_export_batch_size: Option<NonZeroUsize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SpanEventsModel {
+    max_level: Option<String>,
+    target_allowlist: Vec<String>,
+    max_per_span: Option<NonZeroUsize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MetricsModel {
+    /// in milliseconds
+    export_interval: Option<NonZeroU64>,
+    /// in milliseconds
+    export_timeout: Option<NonZeroU64>,
+    attribute_allowlist: BTreeMap<String, Vec<String>>,
+}
+
+/// A value converted into a string (eg `sampler_arg = 0.1`, the values of the resource attributes)
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl From<Scalar> for String {
+    fn from(value: Scalar) -> Self {
+        match value {
+            Scalar::String(v) => v,
+            Scalar::Integer(v) => v.to_string(),
+            Scalar::Float(v) => v.to_string(),
+            Scalar::Boolean(v) => v.to_string(),
+        }
+    }
+}
+
+impl TryFrom<FileModel> for TracingConfig {
+    type Error = Error;
+
+    fn try_from(model: FileModel) -> Result<Self, Self::Error> {
+        let FileModel { log, otel } = model;
+        let millis = |v: Option<NonZeroU64>| v.map(|millis| Duration::from_millis(millis.get()));
+        Ok(TracingConfig {
+            log_directives: log.directives,
+            log_format: log.format.map(|v| v.parse()).transpose()?,
+            log_writer: log.writer.map(|v| v.parse()).transpose()?,
+            otel_enabled: otel.enabled,
+            service_name: otel.service_name,
+            traces_exporter: otel.traces_exporter,
+            endpoint: otel.endpoint,
+            zipkin_endpoint: otel.zipkin_endpoint,
+            protocol: otel.protocol,
+            compression: otel.compression,
+            sampler: otel.sampler,
+            sampler_arg: otel.sampler_arg.map(String::from),
+            propagators: otel.propagators,
+            resource_attributes: otel
+                .resource
+                .into_iter()
+                .map(|(k, v)| (k, String::from(v)))
+                .collect(),
+            event_destination: otel.event_destination.map(|v| v.parse()).transpose()?,
+            fail_open: otel.fail_open,
+            runtime: otel.runtime.map(|v| v.parse()).transpose()?,
+            self_metrics: otel.self_metrics,
+            recording_gate: otel.recording_gate,
+            batch: BatchConfig {
+                max_queue_size: otel.batch.max_queue_size.map(NonZeroUsize::get),
+                scheduled_delay: millis(otel.batch.schedule_delay),
+                max_export_batch_size: otel.batch.max_export_batch_size.map(NonZeroUsize::get),
+            },
+            span_events: SpanEventsConfig {
+                max_level: otel
+                    .span_events
+                    .max_level
+                    .map(|v| parse_level(&v))
+                    .transpose()?,
+                target_allowlist: otel.span_events.target_allowlist,
+                max_events_per_span: otel.span_events.max_per_span.map(NonZeroUsize::get),
+            },
+            metric_export_interval: millis(otel.metrics.export_interval),
+            metric_timeout: millis(otel.metrics.export_timeout),
+            metric_attribute_allowlists: otel.metrics.attribute_allowlist,
+        })
+    }
+}
+
+/// Parse the level of the events (`error`, `warn`, `info`, `debug`, `trace`)
+fn parse_level(value: &str) -> Result<tracing::Level, Error> {
+    value.trim().parse::<tracing::Level>().map_err(|_| {
+        Error::InvalidConfig(format!(
+            "unsupported level '{value}', expected 'error', 'warn', 'info', 'debug' or 'trace'"
+        ))
+    })
+}
+
+fn set_env_if_absent(key: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        if std::env::var_os(key).is_none() {
+            std::env::set_var(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use rstest::rstest;
+
+    #[test]
+    fn parse_config() {
+        let_assert!(
+            Ok(config) = r#"
+            [log]
+            directives = "info,otel=debug"
+            format = "compact"
+            writer = "stderr"
+
+            [otel]
+            enabled = false
+            service_name = "my-service"
+            endpoint = "http://localhost:4317"
+            compression = "gzip"
+            sampler = "parentbased_traceidratio"
+            sampler_arg = 0.1
+            propagators = ["tracecontext", "b3"]
+            event_destination = "both"
+            fail_open = true
+            runtime = "own_thread"
+            self_metrics = true
+            recording_gate = true
+
+            [otel.batch]
+            max_queue_size = 8192
+            schedule_delay = 500
+
+            [otel.span_events]
+            max_level = "info"
+            target_allowlist = ["my_app"]
+            max_per_span = 64
+
+            [otel.metrics]
+            export_interval = 10000
+
+            [otel.metrics.attribute_allowlist]
+            "http.server.*" = ["http.route", "http.request.method"]
+
+            [otel.resource]
+            "deployment.environment.name" = "production"
+            "service.namespace" = "shop"
+            "#
+            .parse::<TracingConfig>()
+        );
+        assert!(config.log_directives.as_deref() == Some("info,otel=debug"));
+        assert!(config.log_format() == LogFormat::Compact);
+        assert!(config.log_writer() == LogWriter::Stderr);
+        assert!(config.otel_enabled == Some(false));
+        assert!(config.service_name.as_deref() == Some("my-service"));
+        assert!(config.endpoint.as_deref() == Some("http://localhost:4317"));
+        assert!(config.protocol.is_none());
+        assert!(config.compression.as_deref() == Some("gzip"));
+        assert!(config.sampler_arg.as_deref() == Some("0.1"));
+        assert!(config.propagators == Some(vec!["tracecontext".to_string(), "b3".to_string()]));
+        assert!(config.event_destination() == EventDestination::Both);
+        assert!(config.fail_open());
+        assert!(config.runtime() == RuntimeMode::OwnThread);
+        assert!(config.self_metrics());
+        assert!(config.recording_gate());
+        assert!(config.batch.max_queue_size == Some(8192));
+        assert!(config.batch.scheduled_delay == Some(Duration::from_millis(500)));
+        assert!(config.batch.max_export_batch_size.is_none());
+        assert!(config.span_events.max_level == Some(tracing::Level::INFO));
+        assert!(config.span_events.target_allowlist == vec!["my_app".to_string()]);
+        assert!(config.span_events.max_events_per_span == Some(64));
+        assert!(config.metric_export_interval == Some(Duration::from_secs(10)));
+        assert!(config.metric_timeout.is_none());
+        assert!(
+            config.metric_attribute_allowlists.get("http.server.*")
+                == Some(&vec![
+                    "http.route".to_string(),
+                    "http.request.method".to_string()
+                ])
+        );
+        assert!(config.resource_attributes.len() == 2);
+        assert!(
+            config
+                .resource_attributes
+                .get("service.namespace")
+                .map(String::as_str)
+                == Some("shop")
+        );
+    }
+
+    #[test]
+    fn parse_yaml_config() {
+        let_assert!(
+            Ok(config) = TracingConfig::from_yaml(
+                r"
+log:
+  directives: info
+  format: json
+otel:
+  service_name: my-service
+  sampler_arg: 0.5
+  propagators: [tracecontext, baggage]
+  batch:
+    schedule_delay: 1000
+  resource:
+    service.version: 1
+"
+            )
+        );
+        assert!(config.log_directives.as_deref() == Some("info"));
+        assert!(config.log_format() == LogFormat::Json);
+        assert!(config.log_writer() == LogWriter::Stdout);
+        assert!(config.service_name.as_deref() == Some("my-service"));
+        assert!(config.sampler_arg.as_deref() == Some("0.5"));
+        assert!(
+            config.propagators == Some(vec!["tracecontext".to_string(), "baggage".to_string()])
+        );
+        assert!(config.batch.scheduled_delay == Some(Duration::from_secs(1)));
+        assert!(
+            config
+                .resource_attributes
+                .get("service.version")
+                .map(String::as_str)
+                == Some("1")
+        );
+        let_assert!(Ok(config) = TracingConfig::from_yaml(""));
+        assert!(config == TracingConfig::default());
+    }
+
+    #[test]
+    fn read_the_format_from_the_extension() {
+        let dir = std::env::temp_dir().join(format!("config_file_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let yaml = dir.join("telemetry.yml");
+        std::fs::write(&yaml, "otel:\n  service_name: from-yaml\n").unwrap();
+        let toml = dir.join("telemetry.toml");
+        std::fs::write(&toml, "[otel]\nservice_name = \"from-toml\"\n").unwrap();
+        let_assert!(Ok(config) = TracingConfig::from_file(&yaml));
+        assert!(config.service_name.as_deref() == Some("from-yaml"));
+        let_assert!(Ok(config) = TracingConfig::from_file(&toml));
+        assert!(config.service_name.as_deref() == Some("from-toml"));
+        let_assert!(
+            Err(Error::ConfigFile { .. }) = TracingConfig::from_file(dir.join("missing.toml"))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[rstest]
+    #[case("[otel]\nendpont = \"http://localhost:4317\"")]
+    #[case("[log]\nlevel = \"info\"")]
+    #[case("[otel.batch]\nmax_queue_size = 0")]
+    #[case("[log]\nformat = \"xml\"")]
+    fn reject_the_invalid_config(#[case] content: &str) {
+        let_assert!(Err(Error::InvalidConfig(_)) = content.parse::<TracingConfig>());
+    }
+
+    #[test]
+    fn default_event_destination() {
+        let config = TracingConfig::default();
+        assert!(config.event_destination() == EventDestination::SpanEvents);
+        let config = config.with_event_destination(EventDestination::Logs);
+        assert!(config.event_destination() == EventDestination::Logs);
+    }
+
+    #[test]
+    fn parse_invalid_level() {
+        let_assert!(Ok(tracing::Level::DEBUG) = parse_level(" debug "));
+        let_assert!(Err(Error::InvalidConfig(_)) = parse_level("verbose"));
+    }
+
+    #[test]
+    fn propagation_only_disables_the_exporter() {
+        let config = TracingConfig::default();
+        assert!(!config.propagation_only());
+        let config = config.with_propagation_only(true);
+        assert!(config.propagation_only());
+        assert!(config.otel_enabled == Some(false));
+        assert!(!config.with_propagation_only(false).propagation_only());
+    }
+
+    #[test]
+    fn default_fail_open() {
+        let config = TracingConfig::default();
+        assert!(!config.fail_open());
+        assert!(config.with_fail_open(true).fail_open());
+    }
+
+    #[test]
+    fn default_self_metrics() {
+        let config = TracingConfig::default();
+        assert!(!config.self_metrics());
+        assert!(config.with_self_metrics(true).self_metrics());
+    }
+
+    #[test]
+    fn default_runtime() {
+        let config = TracingConfig::default();
+        assert!(config.runtime() == RuntimeMode::Tokio);
+        assert!(config.with_runtime(RuntimeMode::OwnThread).runtime() == RuntimeMode::OwnThread);
+    }
+
+    #[test]
+    fn parse_config_with_invalid_type() {
+        let_assert!(
+            Err(Error::InvalidConfig(_)) = r#"
+            [otel]
+            enabled = "yes"
+            "#
+            .parse::<TracingConfig>()
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! A span exporter for the local development, without Jaeger or collector: the spans are rendered into
+//! a self-contained HTML file, as a waterfall (Gantt-style) per trace.
+//!
+//! Open the file into a browser, and reload it to see the new traces.
+//!
+//! ```rust,no_run
+//! use init_tracing_opentelemetry::dev_ui::HtmlFileExporter;
+//! use opentelemetry::trace::TracerProvider as _;
+//!
+//! let tracerprovider = opentelemetry_sdk::trace::TracerProvider::builder()
+//!     .with_simple_exporter(HtmlFileExporter::new("target/traces.html"))
+//!     .build();
+//! let tracer = tracerprovider.tracer("dev");
+//! ```
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use futures_core::future::BoxFuture;
+use opentelemetry::trace::{SpanId, Status, TraceError, TraceId};
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+
+/// The default number of spans kept (the oldest are dropped)
+pub const DEFAULT_MAX_SPANS: usize = 2_000;
+
+/// Write the last exported spans into the HTML file `path` (rewritten after every export).
+#[derive(Debug)]
+pub struct HtmlFileExporter {
+    path: PathBuf,
+    max_spans: usize,
+    spans: VecDeque<SpanData>,
+}
+
+impl HtmlFileExporter {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        HtmlFileExporter {
+            path: path.into(),
+            max_spans: DEFAULT_MAX_SPANS,
+            spans: VecDeque::new(),
+        }
+    }
+
+    /// Keep only the last `max_spans` spans into the file (default: [`DEFAULT_MAX_SPANS`])
+    #[must_use]
+    pub fn with_max_spans(self, max_spans: usize) -> Self {
+        HtmlFileExporter { max_spans, ..self }
+    }
+}
+
+impl SpanExporter for HtmlFileExporter {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        self.spans.extend(batch);
+        let overflow = self.spans.len().saturating_sub(self.max_spans);
+        self.spans.drain(..overflow);
+        let result = std::fs::write(&self.path, render_html(self.spans.iter())).map_err(|err| {
+            TraceError::from(format!("failed to write {}: {err}", self.path.display()))
+        });
+        Box::pin(std::future::ready(result))
+    }
+}
+
+/// Render the spans as a self-contained HTML page, one waterfall per trace (the most recent trace first).
+#[must_use]
+pub fn render_html<'a>(spans: impl IntoIterator<Item = &'a SpanData>) -> String {
+    let mut traces: HashMap<TraceId, Vec<&SpanData>> = HashMap::new();
+    for span in spans {
+        traces
+            .entry(span.span_context.trace_id())
+            .or_default()
+            .push(span);
+    }
+    let mut traces = traces.into_values().collect::<Vec<_>>();
+    traces.sort_by_key(|spans| std::cmp::Reverse(trace_bounds(spans).0));
+
+    let mut html = String::from(HTML_HEAD);
+    for spans in &traces {
+        render_trace(&mut html, spans);
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn trace_bounds(spans: &[&SpanData]) -> (SystemTime, SystemTime) {
+    let start = spans
+        .iter()
+        .map(|s| s.start_time)
+        .min()
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let end = spans.iter().map(|s| s.end_time).max().unwrap_or(start);
+    (start, end)
+}
+
+fn render_trace(html: &mut String, spans: &[&SpanData]) {
+    let (start, end) = trace_bounds(spans);
+    let total = end.duration_since(start).unwrap_or_default();
+    let rows = ordered_with_depth(spans);
+    let title = rows.first().map_or("", |(span, _)| span.name.as_ref());
+    let trace_id = spans
+        .first()
+        .map_or(TraceId::INVALID, |s| s.span_context.trace_id());
+    let _ = writeln!(
+        html,
+        "<section>\n<h2>{} <small>{trace_id} &middot; {} spans &middot; {}</small></h2>\n<table>",
+        escape(title),
+        spans.len(),
+        format_duration(total),
+    );
+    for (span, depth) in rows {
+        let offset = span.start_time.duration_since(start).unwrap_or_default();
+        let duration = span
+            .end_time
+            .duration_since(span.start_time)
+            .unwrap_or_default();
+        let left = percent(offset, total);
+        let width = percent(duration, total).max(0.2).min(100.0 - left);
+        let class = if matches!(span.status, Status::Error { .. }) {
+            "bar error"
+        } else {
+            "bar"
+        };
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"name\" style=\"padding-left:{}em\">{}</td><td class=\"duration\">{}</td>\
+             <td class=\"timeline\"><div class=\"{class}\" style=\"margin-left:{left:.2}%;width:{width:.2}%\" title=\"{}\"></div></td></tr>",
+            depth + 1,
+            escape(&span.name),
+            format_duration(duration),
+            escape(&describe(span)),
+        );
+    }
+    html.push_str("</table>\n</section>\n");
+}
+
+/// The spans of a trace in the order of the waterfall (each child after its parent, by start time),
+/// with their depth into the tree.
+fn ordered_with_depth<'a>(spans: &[&'a SpanData]) -> Vec<(&'a SpanData, usize)> {
+    let known = spans
+        .iter()
+        .map(|s| s.span_context.span_id())
+        .collect::<HashSet<_>>();
+    let mut children: HashMap<SpanId, Vec<&SpanData>> = HashMap::new();
+    let mut roots = Vec::new();
+    for span in spans {
+        if span.parent_span_id != SpanId::INVALID && known.contains(&span.parent_span_id) {
+            children.entry(span.parent_span_id).or_default().push(span);
+        } else {
+            roots.push(*span);
+        }
+    }
+    roots.sort_by_key(|s| s.start_time);
+    for siblings in children.values_mut() {
+        siblings.sort_by_key(|s| s.start_time);
+    }
+
+    let mut ordered = Vec::with_capacity(spans.len());
+    let mut stack = roots.into_iter().rev().map(|s| (s, 0)).collect::<Vec<_>>();
+    while let Some((span, depth)) = stack.pop() {
+        ordered.push((span, depth));
+        if let Some(siblings) = children.remove(&span.span_context.span_id()) {
+            stack.extend(siblings.into_iter().rev().map(|s| (s, depth + 1)));
+        }
+    }
+    ordered
+}
+
+/// The details of the span (displayed on hover)
+fn describe(span: &SpanData) -> String {
+    let mut text = format!("{:?} {}", span.span_kind, span.span_context.span_id());
+    if let Status::Error { description } = &span.status {
+        let _ = write!(text, "\nerror: {description}");
+    }
+    for kv in &span.attributes {
+        let _ = write!(text, "\n{} = {}", kv.key, kv.value);
+    }
+    for event in &span.events.events {
+        let _ = write!(text, "\nevent: {}", event.name);
+    }
+    text
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn percent(part: Duration, total: Duration) -> f64 {
+    if total.is_zero() {
+        0.0
+    } else {
+        part.as_nanos() as f64 * 100.0 / total.as_nanos() as f64
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    if duration.as_secs() > 0 {
+        format!("{:.2}s", duration.as_secs_f64())
+    } else {
+        format!("{:.3}ms", duration.as_secs_f64() * 1_000.0)
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>traces</title>
+<style>
+body { font-family: sans-serif; margin: 1em; }
+h2 { font-size: 1em; margin: 1.5em 0 0.3em; }
+h2 small { color: #666; font-weight: normal; }
+table { width: 100%; border-collapse: collapse; font-size: 0.85em; }
+tr:hover { background: #f0f0f0; }
+td { padding: 2px 4px; white-space: nowrap; }
+td.name { width: 25%; overflow: hidden; text-overflow: ellipsis; max-width: 30em; }
+td.duration { width: 6em; text-align: right; color: #666; }
+td.timeline { width: 100%; }
+.bar { height: 0.9em; background: #4a90d9; border-radius: 2px; }
+.bar.error { background: #d9534f; }
+</style>
+</head>
+<body>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::trace::{TraceContextExt as _, Tracer as _, TracerProvider as _};
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use opentelemetry_sdk::trace::TracerProvider;
+
+    fn collect_spans() -> Vec<SpanData> {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let tracer = provider.tracer("test");
+        tracer.in_span("GET /users/<id>", |cx| {
+            tracer.in_span("db query", |_| {});
+            cx.span().set_status(Status::error("boom"));
+        });
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        spans
+    }
+
+    #[test]
+    fn children_are_after_their_parent() {
+        let spans = collect_spans();
+        let spans = spans.iter().collect::<Vec<_>>();
+        let ordered = ordered_with_depth(&spans);
+        let names = ordered
+            .iter()
+            .map(|(span, depth)| (span.name.as_ref(), *depth))
+            .collect::<Vec<_>>();
+        assert!(names == vec![("GET /users/<id>", 0), ("db query", 1)]);
+    }
+
+    #[test]
+    fn render_escaped_names_and_errors() {
+        let html = render_html(&collect_spans());
+        assert!(html.contains("GET /users/&lt;id&gt;"));
+        assert!(!html.contains("<id>"));
+        assert!(html.contains("class=\"bar error\""));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[tokio::test]
+    async fn keep_only_the_last_spans() {
+        let path = std::env::temp_dir().join(format!("dev_ui_{}.html", std::process::id()));
+        let mut exporter = HtmlFileExporter::new(&path).with_max_spans(1);
+        let_assert!(Ok(()) = exporter.export(collect_spans()).await);
+        assert!(exporter.spans.len() == 1);
+        let_assert!(Ok(html) = std::fs::read_to_string(&path));
+        assert!(html.contains("GET /users/&lt;id&gt;"));
+        let _ = std::fs::remove_file(&path);
+    }
+}
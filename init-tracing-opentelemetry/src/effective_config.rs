@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+
+use opentelemetry_sdk::Resource;
+
+/// Snapshot of the configuration resolved during the setup (from the environment variables, the detected resource,...).
+///
+/// Useful to debug "why no trace?" without piecing together the `otel::setup` logs.
+/// It is also logged at level `debug` on target `otel::setup`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EffectiveConfig {
+    /// value of `OTEL_TRACES_EXPORTER` (default: `otlp`)
+    pub traces_exporter: String,
+    /// protocol of the OTLP exporter for traces (explicit or inferred)
+    pub traces_protocol: Option<String>,
+    /// endpoint of the OTLP exporter for traces
+    pub traces_endpoint: Option<String>,
+    /// value of `OTEL_TRACES_SAMPLER` (& `OTEL_TRACES_SAMPLER_ARG`) (default: `parentbased_always_on`)
+    pub sampler: String,
+    /// names of the propagators (from `OTEL_PROPAGATORS`)
+    pub propagators: Vec<String>,
+    /// attributes of the resource
+    pub resource: BTreeMap<String, String>,
+    /// directives of the log filter (from `RUST_LOG`)
+    pub log_filter: Option<String>,
+}
+
+impl EffectiveConfig {
+    /// Resolve the configuration from the environment variables and the resource
+    #[must_use]
+    pub fn from_env(resource: &Resource) -> Self {
+        #[allow(unused_mut)]
+        let mut config = EffectiveConfig {
+            traces_exporter: std::env::var("OTEL_TRACES_EXPORTER")
+                .map_or_else(|_| "otlp".to_string(), |v| v.trim().to_lowercase()),
+            sampler: read_sampler_from_env(),
+            propagators: crate::read_propagators_from_env(),
+            resource: resource
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            log_filter: std::env::var("RUST_LOG").ok(),
+            ..Default::default()
+        };
+        #[cfg(feature = "otlp")]
+        {
+            config.traces_exporter = crate::otlp::read_traces_exporter_from_env();
+            if let Ok((protocol, endpoint)) =
+                crate::otlp::read_protocol_and_endpoint_from_env(crate::otlp::Signal::Traces)
+            {
+                config.traces_protocol =
+                    crate::otlp::infer_protocol(protocol.as_deref(), endpoint.as_deref());
+                config.traces_endpoint = endpoint;
+            }
+        }
+        config
+    }
+
+    /// Log the configuration at level `debug` on target `otel::setup`
+    pub fn log(&self) {
+        tracing::debug!(target: "otel::setup", effective_config = ?self);
+    }
+}
+
+fn read_sampler_from_env() -> String {
+    let sampler = std::env::var("OTEL_TRACES_SAMPLER")
+        .unwrap_or_else(|_| "parentbased_always_on".to_string());
+    match std::env::var("OTEL_TRACES_SAMPLER_ARG") {
+        Ok(arg) => format!("{sampler}({arg})"),
+        Err(_) => sampler,
+    }
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error(transparent)]
@@ -8,4 +10,105 @@ pub enum Error {
 
     #[error(transparent)]
     TraceError(#[from] opentelemetry::trace::TraceError),
+
+    #[cfg(feature = "metrics")]
+    #[error(transparent)]
+    MetricError(#[from] opentelemetry_sdk::metrics::MetricError),
+
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    /// an environment variable has an invalid value
+    #[error("invalid value '{value}' for env {name}: {reason}")]
+    InvalidEnv {
+        name: String,
+        value: String,
+        reason: String,
+    },
+
+    /// the propagator (from `OTEL_PROPAGATORS`) is unknown or requires a disabled compile feature
+    #[error("unsupported propagator '{name}' from env OTEL_PROPAGATORS{}", feature_hint(*.required_feature))]
+    UnsupportedPropagator {
+        name: String,
+        required_feature: Option<&'static str>,
+    },
+
+    /// the exporter (from `OTEL_TRACES_EXPORTER`,...) is unknown or requires a disabled compile feature
+    #[error("unsupported {signal} exporter '{name}'{}", feature_hint(*.required_feature))]
+    UnsupportedExporter {
+        signal: &'static str,
+        name: String,
+        required_feature: Option<&'static str>,
+    },
+
+    /// the runtime (see `RuntimeMode`) requires a disabled compile feature
+    #[error("unsupported runtime '{name}'{}", feature_hint(*.required_feature))]
+    UnsupportedRuntime {
+        name: String,
+        required_feature: Option<&'static str>,
+    },
+
+    /// the creation of the exporter of a signal (`traces`, `metrics`, `logs`) failed
+    #[error("failed to build the {signal} exporter (endpoint: {})", .endpoint.as_deref().unwrap_or("default"))]
+    ExporterBuild {
+        signal: &'static str,
+        endpoint: Option<String>,
+        #[source]
+        source: opentelemetry::trace::TraceError,
+    },
+
+    /// the configuration file can not be read or is invalid
+    #[error("invalid configuration file '{}'", .path.display())]
+    ConfigFile {
+        path: PathBuf,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// The signal (`traces`, `metrics`, `logs`) of the exporter that failed, if the error is specific to a signal
+    /// (eg to continue without metrics if only the metrics exporter failed)
+    #[must_use]
+    pub fn signal(&self) -> Option<&'static str> {
+        match self {
+            Error::UnsupportedExporter { signal, .. } | Error::ExporterBuild { signal, .. } => {
+                Some(signal)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn feature_hint(required_feature: Option<&'static str>) -> String {
+    required_feature
+        .map(|feature| format!(", try to enable compile feature '{feature}'"))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn display_with_context() {
+        let err = Error::UnsupportedPropagator {
+            name: "b3".to_string(),
+            required_feature: Some("zipkin"),
+        };
+        assert!(
+            err.to_string()
+                == "unsupported propagator 'b3' from env OTEL_PROPAGATORS, try to enable compile feature 'zipkin'"
+        );
+        assert!(err.signal().is_none());
+
+        let err = Error::ExporterBuild {
+            signal: "traces",
+            endpoint: Some("http://localhost:4317".to_string()), //Devskim: ignore DS137138
+            source: opentelemetry::trace::TraceError::from("boom"),
+        };
+        assert!(err.signal() == Some("traces"));
+        assert!(std::error::Error::source(&err).is_some());
+    }
 }
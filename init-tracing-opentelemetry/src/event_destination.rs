@@ -0,0 +1,110 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Error;
+
+/// Where the tracing's events (`tracing::event!`, `info!`,...) are sent:
+/// as span events (by the `OpenTelemetryLayer`), as log records (by the logs bridge), or both.
+///
+/// The destination is applied by filtering the events on each layer:
+///
+/// ```rust,ignore
+/// let destination = EventDestination::Both;
+/// let subscriber = tracing_subscriber::registry()
+///     .with(otel_layer.with_filter(destination.span_events_filter()))
+///     .with(build_otel_logs_bridge_layer(&logger_provider).with_filter(destination.logs_filter()));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EventDestination {
+    /// as span events (the behavior of `tracing-opentelemetry`)
+    #[default]
+    SpanEvents,
+    /// as log records
+    Logs,
+    /// as span events and as log records
+    Both,
+}
+
+impl EventDestination {
+    #[must_use]
+    pub fn to_span_events(self) -> bool {
+        matches!(self, EventDestination::SpanEvents | EventDestination::Both)
+    }
+
+    #[must_use]
+    pub fn to_logs(self) -> bool {
+        matches!(self, EventDestination::Logs | EventDestination::Both)
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            EventDestination::SpanEvents => "span_events",
+            EventDestination::Logs => "logs",
+            EventDestination::Both => "both",
+        }
+    }
+}
+
+#[cfg(any(feature = "logs_bridge", feature = "tracing_subscriber_ext"))]
+impl EventDestination {
+    /// The filter for the `OpenTelemetryLayer` (the spans are always enabled)
+    #[must_use]
+    pub fn span_events_filter(
+        self,
+    ) -> tracing_subscriber::filter::FilterFn<impl Fn(&tracing::Metadata<'_>) -> bool> {
+        let enabled = self.to_span_events();
+        tracing_subscriber::filter::filter_fn(move |metadata| metadata.is_span() || enabled)
+    }
+
+    /// The filter for the logs bridge (the spans are always enabled, to retrieve the trace context of the events)
+    #[must_use]
+    pub fn logs_filter(
+        self,
+    ) -> tracing_subscriber::filter::FilterFn<impl Fn(&tracing::Metadata<'_>) -> bool> {
+        let enabled = self.to_logs();
+        tracing_subscriber::filter::filter_fn(move |metadata| metadata.is_span() || enabled)
+    }
+}
+
+impl fmt::Display for EventDestination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EventDestination {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "span_events" => Ok(EventDestination::SpanEvents),
+            "logs" => Ok(EventDestination::Logs),
+            "both" => Ok(EventDestination::Both),
+            _ => Err(Error::InvalidConfig(format!(
+                "unsupported event destination '{s}', expected 'span_events', 'logs' or 'both'"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("span_events", EventDestination::SpanEvents)]
+    #[case("logs", EventDestination::Logs)]
+    #[case(" Both ", EventDestination::Both)]
+    fn parse_event_destination(#[case] input: &str, #[case] expected: EventDestination) {
+        let_assert!(Ok(destination) = input.parse::<EventDestination>());
+        assert!(destination == expected);
+        assert!(destination.to_string().parse::<EventDestination>().ok() == Some(expected));
+    }
+
+    #[test]
+    fn parse_invalid_event_destination() {
+        let_assert!(Err(Error::InvalidConfig(_)) = "stdout".parse::<EventDestination>());
+    }
+}
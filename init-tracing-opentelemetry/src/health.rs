@@ -0,0 +1,336 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use futures_core::future::BoxFuture;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+#[cfg(feature = "metrics")]
+use opentelemetry_sdk::metrics::{
+    data::ResourceMetrics, exporter::PushMetricExporter, MetricResult, Temporality,
+};
+use opentelemetry_sdk::Resource;
+
+/// Outcome of the last exports of an exporter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExporterStatus {
+    pub last_success: Option<SystemTime>,
+    pub last_failure: Option<SystemTime>,
+    pub last_error: Option<String>,
+}
+
+impl ExporterStatus {
+    /// `false` if the last export failed, `true` otherwise (including when nothing was exported yet)
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        match (self.last_success, self.last_failure) {
+            (_, None) => true,
+            (None, Some(_)) => false,
+            (Some(success), Some(failure)) => success >= failure,
+        }
+    }
+}
+
+/// Status of the exporters, per signal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Health {
+    /// `None` if no span exporter was configured
+    pub traces: Option<ExporterStatus>,
+    /// `None` if no metric exporter was configured (require the feature `metrics`)
+    pub metrics: Option<ExporterStatus>,
+}
+
+impl Health {
+    /// `true` if every configured exporter is healthy
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        [&self.traces, &self.metrics]
+            .into_iter()
+            .flatten()
+            .all(ExporterStatus::is_healthy)
+    }
+}
+
+/// The minimal delay between two logs of the same export error
+const EXPORT_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Shared handle to the [`ExporterStatus`] updated by a [`HealthRecordingExporter`]
+#[derive(Debug, Clone, Default)]
+pub struct ExporterHealth(Arc<Mutex<HealthState>>);
+
+#[derive(Debug, Default)]
+struct HealthState {
+    status: ExporterStatus,
+    log_throttle: LogThrottle,
+}
+
+impl ExporterHealth {
+    #[must_use]
+    pub fn status(&self) -> ExporterStatus {
+        self.0.lock().map(|s| s.status.clone()).unwrap_or_default()
+    }
+
+    /// Record the outcome of an export of the `signal` (eg `spans`), and log the failures (and the recovery) via
+    /// `tracing` under the target `otel::exporter` (the same error is logged at most once per
+    /// [`EXPORT_ERROR_LOG_INTERVAL`]).
+    fn record<E: std::fmt::Display>(&self, signal: &str, result: &Result<(), E>) {
+        let log = match self.0.lock() {
+            Ok(mut state) => {
+                let now = SystemTime::now();
+                match result {
+                    Ok(()) => {
+                        state.status.last_success = Some(now);
+                        state.log_throttle.on_success().map(ExportLog::Recovered)
+                    }
+                    Err(err) => {
+                        let error = err.to_string();
+                        state.status.last_failure = Some(now);
+                        let log = state
+                            .log_throttle
+                            .on_failure(&error, Instant::now())
+                            .map(|suppressed| ExportLog::Failed(error.clone(), suppressed));
+                        state.status.last_error = Some(error);
+                        log
+                    }
+                }
+            }
+            Err(_) => None,
+        };
+        // log outside of the lock
+        match log {
+            Some(ExportLog::Failed(error, suppressed)) => {
+                tracing::error!(target: "otel::exporter", error, suppressed, "failed to export the {signal}");
+            }
+            Some(ExportLog::Recovered(failures)) => {
+                tracing::info!(target: "otel::exporter", failures, "export of the {signal} recovered");
+            }
+            None => {}
+        }
+    }
+}
+
+enum ExportLog {
+    /// the error and the number of failures not logged since the previous log
+    Failed(String, u64),
+    /// the number of failures before the recovery
+    Recovered(u64),
+}
+
+/// Rate-limit & deduplicate the logs of the export errors
+#[derive(Debug, Default)]
+struct LogThrottle {
+    last_logged: Option<(String, Instant)>,
+    suppressed: u64,
+    failures: u64,
+}
+
+impl LogThrottle {
+    /// `Some(number of failures not logged)` if the failure should be logged
+    fn on_failure(&mut self, error: &str, now: Instant) -> Option<u64> {
+        self.failures += 1;
+        let should_log = self.last_logged.as_ref().map_or(true, |(last_error, at)| {
+            last_error != error || now.duration_since(*at) >= EXPORT_ERROR_LOG_INTERVAL
+        });
+        if should_log {
+            self.last_logged = Some((error.to_string(), now));
+            Some(std::mem::take(&mut self.suppressed))
+        } else {
+            self.suppressed += 1;
+            None
+        }
+    }
+
+    /// `Some(number of failures)` if the success follows failures
+    fn on_success(&mut self) -> Option<u64> {
+        self.last_logged = None;
+        self.suppressed = 0;
+        Some(std::mem::take(&mut self.failures)).filter(|failures| *failures > 0)
+    }
+}
+
+/// Wrap a `SpanExporter` to record the outcome of each export into an [`ExporterHealth`].
+///
+/// Allow to expose a "telemetry degraded" status (eg on a readiness endpoint) without scraping logs.
+#[derive(Debug)]
+pub struct HealthRecordingExporter<E> {
+    inner: E,
+    health: ExporterHealth,
+}
+
+impl<E> HealthRecordingExporter<E> {
+    pub fn new(inner: E, health: ExporterHealth) -> Self {
+        Self { inner, health }
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for HealthRecordingExporter<E> {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        let health = self.health.clone();
+        let export = self.inner.export(batch);
+        Box::pin(async move {
+            let result = export.await;
+            health.record("spans", &result);
+            result
+        })
+    }
+
+    fn shutdown(&mut self) {
+        self.inner.shutdown();
+    }
+
+    fn force_flush(&mut self) -> BoxFuture<'static, ExportResult> {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+/// Wrap a `PushMetricExporter` to record the outcome of each export into an [`ExporterHealth`]
+/// (like [`HealthRecordingExporter`] for the spans).
+#[cfg(feature = "metrics")]
+#[derive(Debug)]
+pub struct HealthRecordingMetricExporter<E> {
+    inner: E,
+    health: ExporterHealth,
+}
+
+#[cfg(feature = "metrics")]
+impl<E> HealthRecordingMetricExporter<E> {
+    pub fn new(inner: E, health: ExporterHealth) -> Self {
+        Self { inner, health }
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[async_trait::async_trait]
+impl<E: PushMetricExporter> PushMetricExporter for HealthRecordingMetricExporter<E> {
+    async fn export(&self, metrics: &mut ResourceMetrics) -> MetricResult<()> {
+        let result = self.inner.export(metrics).await;
+        self.health.record("metrics", &result);
+        result
+    }
+
+    async fn force_flush(&self) -> MetricResult<()> {
+        self.inner.force_flush().await
+    }
+
+    fn shutdown(&self) -> MetricResult<()> {
+        self.inner.shutdown()
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.inner.temporality()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::trace::TraceError;
+
+    #[derive(Debug)]
+    struct FakeExporter(bool);
+
+    impl SpanExporter for FakeExporter {
+        fn export(&mut self, _batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            let success = self.0;
+            Box::pin(async move {
+                if success {
+                    Ok(())
+                } else {
+                    Err(TraceError::from("connection refused"))
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn record_success_then_failure() {
+        let health = ExporterHealth::default();
+        assert!(health.status().is_healthy());
+
+        let mut exporter = HealthRecordingExporter::new(FakeExporter(true), health.clone());
+        let_assert!(Ok(()) = exporter.export(vec![]).await);
+        let status = health.status();
+        assert!(status.last_success.is_some());
+        assert!(status.is_healthy());
+
+        let mut exporter = HealthRecordingExporter::new(FakeExporter(false), health.clone());
+        let_assert!(Err(_) = exporter.export(vec![]).await);
+        let status = health.status();
+        assert!(status.last_failure.is_some());
+        let_assert!(Some(error) = status.last_error.as_deref());
+        assert!(error.contains("connection refused"));
+        assert!(!status.is_healthy());
+        assert!(!(Health {
+            traces: Some(status),
+            metrics: None,
+        })
+        .is_healthy());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn record_the_exports_of_the_metrics() {
+        use opentelemetry_sdk::metrics::MetricError;
+
+        #[derive(Debug)]
+        struct FakeMetricExporter;
+
+        #[async_trait::async_trait]
+        impl PushMetricExporter for FakeMetricExporter {
+            async fn export(&self, _metrics: &mut ResourceMetrics) -> MetricResult<()> {
+                Err(MetricError::Other("connection refused".to_string()))
+            }
+
+            async fn force_flush(&self) -> MetricResult<()> {
+                Ok(())
+            }
+
+            fn shutdown(&self) -> MetricResult<()> {
+                Ok(())
+            }
+
+            fn temporality(&self) -> Temporality {
+                Temporality::Cumulative
+            }
+        }
+
+        let health = ExporterHealth::default();
+        let exporter = HealthRecordingMetricExporter::new(FakeMetricExporter, health.clone());
+        let mut metrics = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: vec![],
+        };
+        let_assert!(Err(_) = exporter.export(&mut metrics).await);
+        let status = health.status();
+        let_assert!(Some(error) = status.last_error.as_deref());
+        assert!(error.contains("connection refused"));
+        assert!(!(Health {
+            traces: None,
+            metrics: Some(status),
+        })
+        .is_healthy());
+    }
+
+    #[test]
+    fn throttle_the_logs_of_the_same_error() {
+        let start = Instant::now();
+        let mut throttle = LogThrottle::default();
+        assert!(throttle.on_failure("connection refused", start) == Some(0));
+        assert!(throttle.on_failure("connection refused", start + Duration::from_secs(1)) == None);
+        assert!(throttle.on_failure("connection refused", start + Duration::from_secs(2)) == None);
+        // a different error is logged immediately
+        assert!(throttle.on_failure("timeout", start + Duration::from_secs(3)) == Some(2));
+        assert!(
+            throttle.on_failure(
+                "timeout",
+                start + Duration::from_secs(3) + EXPORT_ERROR_LOG_INTERVAL
+            ) == Some(0)
+        );
+        assert!(throttle.on_success() == Some(5));
+        assert!(throttle.on_success() == None);
+        assert!(throttle.on_failure("timeout", start + Duration::from_secs(4)) == Some(0));
+    }
+}
@@ -0,0 +1,260 @@
+//! Heartbeat of the long-running spans (eg batch jobs).
+//!
+//! A span is exported when it ends, so nothing is visible while a job runs for hours.
+//! The spans that declare the field `otel.heartbeat` get a child span `heartbeat` exported periodically
+//! (with the attributes `heartbeat.span.name`, `heartbeat.count` and `heartbeat.elapsed_s`),
+//! so the progress is observable mid-flight.
+//!
+//! ```rust,ignore
+//! let (heartbeat_layer, heartbeat) = heartbeat::build_heartbeat(tracer, Duration::from_secs(60));
+//! let subscriber = tracing_subscriber::registry()
+//!     .with(otel_layer)
+//!     // after the `OpenTelemetryLayer` (to retrieve the otel context of the spans)
+//!     .with(heartbeat_layer);
+//! // stop the heartbeat with the guard
+//! let guard = guard.with_heartbeat(heartbeat);
+//!
+//! let span = tracing::info_span!("nightly import", otel.heartbeat = true);
+//! ```
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use opentelemetry::trace::{SpanContext, SpanKind, TraceContextExt, Tracer};
+use opentelemetry::KeyValue;
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_opentelemetry::{OtelData, PreSampledTracer};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The field to declare on a span to track it
+pub const HEARTBEAT_FIELD: &str = "otel.heartbeat";
+
+#[derive(Debug)]
+struct TrackedSpan {
+    name: &'static str,
+    span_context: SpanContext,
+    start: Instant,
+    count: u64,
+}
+
+type TrackedSpans = Arc<Mutex<HashMap<Id, TrackedSpan>>>;
+
+/// Create the layer to track the spans with the field `otel.heartbeat` (to register after the `OpenTelemetryLayer`),
+/// and the background task that emits the heartbeats every `interval` with the `tracer`.
+///
+/// The `tracer` also takes the sampling decision of the tracked spans (like the `OpenTelemetryLayer` does),
+/// so it should come from the same `TracerProvider`: the spans not sampled have no heartbeat.
+pub fn build_heartbeat<T>(tracer: T, interval: Duration) -> (HeartbeatLayer, Heartbeat)
+where
+    T: Tracer + PreSampledTracer + Clone + Send + Sync + 'static,
+{
+    let sampler: Arc<dyn PreSampledTracer + Send + Sync> = Arc::new(tracer.clone());
+    let spans = TrackedSpans::default();
+    let stop = Arc::new(AtomicBool::new(false));
+    let handle = {
+        let spans = spans.clone();
+        let stop = stop.clone();
+        std::thread::Builder::new()
+            .name("otel-heartbeat".to_string())
+            .spawn(move || loop {
+                std::thread::park_timeout(interval);
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                beat(&tracer, &spans, interval);
+            })
+            .ok()
+    };
+    (
+        HeartbeatLayer { spans, sampler },
+        Heartbeat { stop, handle },
+    )
+}
+
+fn beat<T: Tracer>(tracer: &T, spans: &TrackedSpans, interval: Duration) {
+    let Ok(mut spans) = spans.lock() else {
+        return;
+    };
+    for tracked in spans.values_mut() {
+        let elapsed = tracked.start.elapsed();
+        if elapsed < interval {
+            continue;
+        }
+        tracked.count += 1;
+        let parent_cx =
+            opentelemetry::Context::new().with_remote_span_context(tracked.span_context.clone());
+        let mut span = tracer
+            .span_builder("heartbeat")
+            .with_kind(SpanKind::Internal)
+            .with_attributes([
+                KeyValue::new("heartbeat.span.name", tracked.name),
+                KeyValue::new(
+                    "heartbeat.count",
+                    i64::try_from(tracked.count).unwrap_or(i64::MAX),
+                ),
+                KeyValue::new("heartbeat.elapsed_s", elapsed.as_secs_f64()),
+            ])
+            .start_with_context(tracer, &parent_cx);
+        opentelemetry::trace::Span::end(&mut span);
+    }
+}
+
+/// Layer to track the spans with the field `otel.heartbeat`, see [`build_heartbeat`].
+#[derive(Clone)]
+pub struct HeartbeatLayer {
+    spans: TrackedSpans,
+    sampler: Arc<dyn PreSampledTracer + Send + Sync>,
+}
+
+impl std::fmt::Debug for HeartbeatLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeartbeatLayer")
+            .field("spans", &self.spans)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for HeartbeatLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().fields().field(HEARTBEAT_FIELD).is_none() {
+            return;
+        }
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(otel_data) = extensions.get_mut::<OtelData>() else {
+            return;
+        };
+        if otel_data.builder.span_id.is_none() {
+            return;
+        }
+        // the sampling result is kept into the builder, so the span is exported with the same decision
+        let span_context = self
+            .sampler
+            .sampled_context(otel_data)
+            .span()
+            .span_context()
+            .clone();
+        if !span_context.is_sampled() {
+            return;
+        }
+        if let Ok(mut spans) = self.spans.lock() {
+            spans.insert(
+                id.clone(),
+                TrackedSpan {
+                    name: attrs.metadata().name(),
+                    span_context,
+                    start: Instant::now(),
+                    count: 0,
+                },
+            );
+        }
+    }
+
+    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+        if let Ok(mut spans) = self.spans.lock() {
+            spans.remove(&id);
+        }
+    }
+}
+
+/// The background task that emits the heartbeats, stopped when dropped
+/// (eg with the `TracingGuard`, see `TracingGuard::with_heartbeat`).
+#[derive(Debug)]
+pub struct Heartbeat {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use opentelemetry_sdk::trace::Sampler;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn emit_heartbeat_for_long_running_span() {
+        let exporter = InMemorySpanExporter::default();
+        let tracer_provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let (heartbeat_layer, heartbeat) = build_heartbeat(
+            tracer_provider.tracer("heartbeat"),
+            Duration::from_millis(10),
+        );
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("test")))
+            .with(heartbeat_layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("job", otel.heartbeat = true).in_scope(|| {
+                std::thread::sleep(Duration::from_millis(100));
+            });
+            tracing::info_span!("short").in_scope(|| {
+                std::thread::sleep(Duration::from_millis(30));
+            });
+        });
+        drop(heartbeat);
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        let_assert!(Some(job) = spans.iter().find(|s| s.name == "job"));
+        let heartbeats = spans
+            .iter()
+            .filter(|s| s.name == "heartbeat")
+            .collect::<Vec<_>>();
+        assert!(!heartbeats.is_empty());
+        for heartbeat in heartbeats {
+            assert!(heartbeat.parent_span_id == job.span_context.span_id());
+            assert!(heartbeat.span_context.trace_id() == job.span_context.trace_id());
+            // the heartbeats are exported before the end of the job
+            assert!(heartbeat.end_time <= job.end_time);
+        }
+    }
+
+    #[test]
+    fn no_heartbeat_for_span_not_sampled() {
+        let exporter = InMemorySpanExporter::default();
+        let tracer_provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::AlwaysOff)))
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let (heartbeat_layer, heartbeat) = build_heartbeat(
+            tracer_provider.tracer("heartbeat"),
+            Duration::from_millis(10),
+        );
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("test")))
+            .with(heartbeat_layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("job", otel.heartbeat = true).in_scope(|| {
+                std::thread::sleep(Duration::from_millis(100));
+            });
+        });
+        drop(heartbeat);
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        assert!(spans.is_empty());
+    }
+}
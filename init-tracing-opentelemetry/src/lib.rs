@@ -6,21 +6,75 @@
 #![allow(clippy::missing_errors_doc)]
 #![doc = include_str!("../README.md")]
 
+mod attribute_limit;
+mod baggage;
+mod batch_config;
+mod effective_config;
 mod error;
+mod event_destination;
+mod health;
+mod log_format;
+mod queue_overflow;
+mod recording_gate;
+mod runtime_mode;
+#[cfg(feature = "tracing_subscriber_ext")]
+mod setup_report;
+mod span_events;
+mod suppress;
+mod validate_env;
+pub use attribute_limit::TruncateAttributeValueExporter;
+pub use baggage::BaggageSpanProcessor;
+pub use batch_config::BatchConfig;
+pub use effective_config::EffectiveConfig;
 pub use error::Error;
+pub use event_destination::EventDestination;
+#[cfg(feature = "metrics")]
+pub use health::HealthRecordingMetricExporter;
+pub use health::{ExporterHealth, ExporterStatus, Health, HealthRecordingExporter};
+pub use log_format::{LogFormat, LogWriter};
+pub use queue_overflow::{QueueCapSpanProcessor, QueueDrainExporter, SpanQueueUsage};
+pub use recording_gate::{RecordingGateSpanProcessor, DEFAULT_MAX_BUFFERED_SPANS};
+pub use runtime_mode::RuntimeMode;
+#[cfg(feature = "tracing_subscriber_ext")]
+pub use setup_report::SetupReport;
+pub use span_events::SpanEventsConfig;
+#[cfg(any(feature = "logs_bridge", feature = "tracing_subscriber_ext"))]
+pub use span_events::SpanEventsFilter;
+pub use suppress::SuppressInstrumentationExporter;
+pub use validate_env::{validate_env, ConfigIssue};
 
 use opentelemetry::propagation::{TextMapCompositePropagator, TextMapPropagator};
-use opentelemetry::trace::TraceError;
 use opentelemetry_sdk::propagation::{BaggagePropagator, TraceContextPropagator};
 
+#[cfg(feature = "b3")]
+pub mod b3;
+#[cfg(feature = "config_file")]
+pub mod config_file;
+#[cfg(feature = "dev_ui")]
+pub mod dev_ui;
+#[cfg(feature = "heartbeat")]
+pub mod heartbeat;
+#[cfg(feature = "logs_bridge")]
+pub mod logs_bridge;
+#[cfg(feature = "otel_config_file")]
+pub mod otel_config_file;
 #[cfg(feature = "otlp")]
 pub mod otlp;
+pub mod prelude;
 #[cfg(feature = "tracer")]
 pub mod resource;
+#[cfg(feature = "self_metrics")]
+pub mod self_metrics;
+#[cfg(feature = "shutdown")]
+pub mod shutdown;
+#[cfg(feature = "span_metrics")]
+pub mod span_metrics;
 #[cfg(feature = "stdout")]
 pub mod stdio;
 #[cfg(feature = "tracing_subscriber_ext")]
 pub mod tracing_subscriber_ext;
+#[cfg(all(feature = "zipkin", feature = "tracer"))]
+pub mod zipkin;
 
 /// Configure the global propagator based on content of the env variable [OTEL_PROPAGATORS](https://opentelemetry.io/docs/concepts/sdk-configuration/general-sdk-configuration/#otel_propagators)
 /// Specifies Propagators to be used in a comma-separated list.
@@ -30,8 +84,8 @@ pub mod tracing_subscriber_ext;
 ///
 /// - "tracecontext": W3C Trace Context
 /// - "baggage": W3C Baggage
-/// - "b3": B3 Single (require feature "zipkin")
-/// - "b3multi": B3 Multi (require feature "zipkin")
+/// - "b3": B3 Single (require feature "b3", included into "zipkin")
+/// - "b3multi": B3 Multi (require feature "b3", included into "zipkin")
 /// - "jaeger": Jaeger (require feature "jaeger")
 /// - "xray": AWS X-Ray (require feature "xray")
 /// - "ottrace": OT Trace (third party) (not supported)
@@ -39,20 +93,16 @@ pub mod tracing_subscriber_ext;
 ///
 /// # Errors
 ///
-/// Will return `TraceError` if issue in reading or instanciate propagator.
-pub fn init_propagator() -> Result<(), TraceError> {
-    let value_from_env =
-        std::env::var("OTEL_PROPAGATORS").unwrap_or_else(|_| "tracecontext,baggage".to_string());
-    let propagators: Vec<(Box<dyn TextMapPropagator + Send + Sync>, String)> = value_from_env
-        .split(',')
-        .map(|s| {
-            let name = s.trim().to_lowercase();
-            propagator_from_string(&name).map(|o| o.map(|b| (b, name)))
-        })
-        .collect::<Result<Vec<_>, _>>()?
-        .into_iter()
-        .flatten()
-        .collect();
+/// Will return `Error::UnsupportedPropagator` if a propagator is unknown or requires a disabled feature.
+pub fn init_propagator() -> Result<(), Error> {
+    let propagators: Vec<(Box<dyn TextMapPropagator + Send + Sync>, String)> =
+        read_propagators_from_env()
+            .into_iter()
+            .map(|name| propagator_from_string(&name).map(|o| o.map(|b| (b, name))))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect();
     if !propagators.is_empty() {
         let (propagators_impl, propagators_name): (Vec<_>, Vec<_>) =
             propagators.into_iter().unzip();
@@ -63,41 +113,50 @@ pub fn init_propagator() -> Result<(), TraceError> {
     Ok(())
 }
 
+/// Names of the propagators listed into `OTEL_PROPAGATORS` (default: `"tracecontext,baggage"`)
+#[must_use]
+pub fn read_propagators_from_env() -> Vec<String> {
+    std::env::var("OTEL_PROPAGATORS")
+        .unwrap_or_else(|_| "tracecontext,baggage".to_string())
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .collect()
+}
+
 #[allow(clippy::box_default)]
 fn propagator_from_string(
     v: &str,
-) -> Result<Option<Box<dyn TextMapPropagator + Send + Sync>>, TraceError> {
+) -> Result<Option<Box<dyn TextMapPropagator + Send + Sync>>, Error> {
     match v {
         "tracecontext" => Ok(Some(Box::new(TraceContextPropagator::new()))),
         "baggage" => Ok(Some(Box::new(BaggagePropagator::new()))),
-        #[cfg(feature = "zipkin")]
-        "b3" => Ok(Some(Box::new(
-            opentelemetry_zipkin::Propagator::with_encoding(
-                opentelemetry_zipkin::B3Encoding::SingleHeader,
-            ),
-        ))),
-        #[cfg(not(feature = "zipkin"))]
-        "b3" => Err(TraceError::from(
-            "unsupported propagators form env OTEL_PROPAGATORS: 'b3', try to enable compile feature 'zipkin'"
-        )),
-        #[cfg(feature = "zipkin")]
-        "b3multi" => Ok(Some(Box::new(
-            opentelemetry_zipkin::Propagator::with_encoding(
-                opentelemetry_zipkin::B3Encoding::MultipleHeader,
-            ),
-        ))),
-        #[cfg(not(feature = "zipkin"))]
-        "b3multi" => Err(TraceError::from(
-            "unsupported propagators form env OTEL_PROPAGATORS: 'b3multi', try to enable compile feature 'zipkin'"
-        )),
+        #[cfg(feature = "b3")]
+        "b3" => Ok(Some(Box::new(b3::B3Propagator::with_encoding(
+            b3::B3Encoding::SingleHeader,
+        )))),
+        #[cfg(not(feature = "b3"))]
+        "b3" => Err(Error::UnsupportedPropagator {
+            name: v.to_string(),
+            required_feature: Some("b3"),
+        }),
+        #[cfg(feature = "b3")]
+        "b3multi" => Ok(Some(Box::new(b3::B3Propagator::with_encoding(
+            b3::B3Encoding::MultipleHeader,
+        )))),
+        #[cfg(not(feature = "b3"))]
+        "b3multi" => Err(Error::UnsupportedPropagator {
+            name: v.to_string(),
+            required_feature: Some("b3"),
+        }),
         #[cfg(feature = "jaeger")]
         "jaeger" => Ok(Some(Box::new(
-            opentelemetry_jaeger_propagator::Propagator::default()
+            opentelemetry_jaeger_propagator::Propagator::default(),
         ))),
         #[cfg(not(feature = "jaeger"))]
-        "jaeger" => Err(TraceError::from(
-            "unsupported propagators form env OTEL_PROPAGATORS: 'jaeger', try to enable compile feature 'jaeger'"
-        )),
+        "jaeger" => Err(Error::UnsupportedPropagator {
+            name: v.to_string(),
+            required_feature: Some("jaeger"),
+        }),
         //FIXME re-enable when opentelementry_aws available for the current version of opentelemetry
         // #[cfg(feature = "xray")]
         // "xray" => Ok(Some(Box::new(
@@ -108,9 +167,10 @@ fn propagator_from_string(
         //     "unsupported propagators form env OTEL_PROPAGATORS: 'xray', try to enable compile feature 'xray'"
         // )),
         "none" => Ok(None),
-        unknown => Err(TraceError::from(format!(
-            "unsupported propagators form env OTEL_PROPAGATORS: '{unknown}'"
-        ))),
+        unknown => Err(Error::UnsupportedPropagator {
+            name: unknown.to_string(),
+            required_feature: None,
+        }),
     }
 }
 
@@ -0,0 +1,127 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Error;
+
+/// The format of the logs (of the `fmt` layer built by `tracing_subscriber_ext::build_logger_text_with`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// `pretty` for the debug builds, `json` for the release builds (`logfmt` with the feature `logfmt`)
+    #[default]
+    Auto,
+    /// human readable, one line per event
+    Full,
+    /// like `Full` but shorter (the fields of the parent spans are not repeated)
+    Compact,
+    /// human readable, multi-lines, with the new & closed spans
+    Pretty,
+    /// one JSON object per event
+    Json,
+}
+
+impl LogFormat {
+    fn as_str(self) -> &'static str {
+        match self {
+            LogFormat::Auto => "auto",
+            LogFormat::Full => "full",
+            LogFormat::Compact => "compact",
+            LogFormat::Pretty => "pretty",
+            LogFormat::Json => "json",
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(LogFormat::Auto),
+            "full" => Ok(LogFormat::Full),
+            "compact" => Ok(LogFormat::Compact),
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(Error::InvalidConfig(format!(
+                "unsupported log format '{s}', expected 'auto', 'full', 'compact', 'pretty' or 'json'"
+            ))),
+        }
+    }
+}
+
+/// Where the logs are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogWriter {
+    #[default]
+    Stdout,
+    /// eg for a CLI, to keep the stdout for the output of the command
+    Stderr,
+}
+
+impl LogWriter {
+    fn as_str(self) -> &'static str {
+        match self {
+            LogWriter::Stdout => "stdout",
+            LogWriter::Stderr => "stderr",
+        }
+    }
+}
+
+impl fmt::Display for LogWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LogWriter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "stdout" => Ok(LogWriter::Stdout),
+            "stderr" => Ok(LogWriter::Stderr),
+            _ => Err(Error::InvalidConfig(format!(
+                "unsupported log writer '{s}', expected 'stdout' or 'stderr'"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("auto", LogFormat::Auto)]
+    #[case("full", LogFormat::Full)]
+    #[case("compact", LogFormat::Compact)]
+    #[case(" Pretty ", LogFormat::Pretty)]
+    #[case("json", LogFormat::Json)]
+    fn parse_log_format(#[case] input: &str, #[case] expected: LogFormat) {
+        let_assert!(Ok(format) = input.parse::<LogFormat>());
+        assert!(format == expected);
+        assert!(format.to_string().parse::<LogFormat>().ok() == Some(expected));
+    }
+
+    #[rstest]
+    #[case("stdout", LogWriter::Stdout)]
+    #[case("STDERR", LogWriter::Stderr)]
+    fn parse_log_writer(#[case] input: &str, #[case] expected: LogWriter) {
+        let_assert!(Ok(writer) = input.parse::<LogWriter>());
+        assert!(writer == expected);
+        assert!(writer.to_string().parse::<LogWriter>().ok() == Some(expected));
+    }
+
+    #[test]
+    fn parse_invalid_log_format_and_writer() {
+        let_assert!(Err(Error::InvalidConfig(_)) = "logfmt".parse::<LogFormat>());
+        let_assert!(Err(Error::InvalidConfig(_)) = "file".parse::<LogWriter>());
+    }
+}
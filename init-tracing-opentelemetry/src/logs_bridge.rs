@@ -0,0 +1,159 @@
+//! Bridge the tracing's events into OpenTelemetry logs (with `opentelemetry-appender-tracing`),
+//! without the events of the instrumentation & of the exporters.
+//!
+//! ```rust
+//! use init_tracing_opentelemetry::logs_bridge::build_otel_logs_bridge_layer;
+//! use opentelemetry_sdk::logs::LoggerProvider;
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! let logger_provider = LoggerProvider::builder().build();
+//! let subscriber = tracing_subscriber::registry()
+//!     .with(build_otel_logs_bridge_layer(&logger_provider));
+//! ```
+//!
+//! To choose where the events are sent (span events, log records or both),
+//! use the filters of [`crate::EventDestination`] on the `OpenTelemetryLayer` and on the bridge.
+//!
+//! The log records get the trace context of the current OpenTelemetry `Context` (eg attached with
+//! `tracing::Span::current().context().attach()`), entering a tracing's span does not attach it.
+use opentelemetry::logs::{Logger, LoggerProvider};
+use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+use tracing::{Metadata, Subscriber};
+use tracing_subscriber::layer::Layer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// The targets of the events not bridged (to prevent loops): the instrumentation & setup (`otel::*`)
+/// and the crates used to export (if they log while exporting, the export would generate logs to export,...).
+const EXCLUDED_TARGETS: &[&str] = &[
+    "otel",
+    "opentelemetry",
+    "opentelemetry_sdk",
+    "opentelemetry_otlp",
+    "opentelemetry_http",
+    "hyper",
+    "hyper_util",
+    "h2",
+    "tonic",
+    "tower",
+    "reqwest",
+];
+
+/// Create the layer that bridges the tracing's events into OpenTelemetry logs (`OpenTelemetryTracingBridge`),
+/// with a filter to exclude the events of the instrumentation & of the exporters (to prevent loops).
+pub fn build_otel_logs_bridge_layer<S, P, L>(logger_provider: &P) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    P: LoggerProvider<Logger = L> + Send + Sync + 'static,
+    L: Logger + Send + Sync + 'static,
+{
+    OpenTelemetryTracingBridge::new(logger_provider)
+        .with_filter(tracing_subscriber::filter::filter_fn(is_bridged))
+}
+
+// spans are not filtered, to be able to retrieve the trace context of the events
+fn is_bridged(metadata: &Metadata<'_>) -> bool {
+    metadata.is_span() || !is_excluded_target(metadata.target())
+}
+
+fn is_excluded_target(target: &str) -> bool {
+    EXCLUDED_TARGETS.iter().any(|excluded| {
+        target
+            .strip_prefix(excluded)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventDestination;
+    use assert2::{assert, let_assert};
+    use opentelemetry::logs::{AnyValue, Severity};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::logs::LoggerProvider as SdkLoggerProvider;
+    use opentelemetry_sdk::testing::logs::InMemoryLogExporter;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use rstest::rstest;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[rstest]
+    #[case("otel::setup", true)]
+    #[case("otel", true)]
+    #[case("opentelemetry_sdk::trace", true)]
+    #[case("hyper_util::client", true)]
+    #[case("hypermedia", false)]
+    #[case("my_app::handler", false)]
+    fn test_is_excluded_target(#[case] target: &str, #[case] expected: bool) {
+        assert!(is_excluded_target(target) == expected);
+    }
+
+    #[test]
+    fn bridge_events_with_trace_context() {
+        let exporter = InMemoryLogExporter::default();
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let tracer_provider = opentelemetry_sdk::trace::TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("test")))
+            .with(build_otel_logs_bridge_layer(&logger_provider));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request");
+            let _cx = span.context().attach();
+            span.in_scope(|| {
+                tracing::warn!(user_id = 42, "something happened");
+                tracing::info!(target: "otel::setup", "not bridged");
+            });
+        });
+
+        let_assert!(Ok(logs) = exporter.get_emitted_logs());
+        assert!(logs.len() == 1);
+        let record = &logs[0].record;
+        assert!(record.body == Some(AnyValue::from("something happened".to_string())));
+        assert!(record.severity_number == Some(Severity::Warn));
+        assert!(record.trace_context.is_some());
+    }
+
+    #[rstest]
+    #[case(EventDestination::SpanEvents, 1, 0)]
+    #[case(EventDestination::Logs, 0, 1)]
+    #[case(EventDestination::Both, 1, 1)]
+    fn dispatch_events_to_destination(
+        #[case] destination: EventDestination,
+        #[case] expected_span_events: usize,
+        #[case] expected_logs: usize,
+    ) {
+        let log_exporter = InMemoryLogExporter::default();
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_simple_exporter(log_exporter.clone())
+            .build();
+        let span_exporter = InMemorySpanExporter::default();
+        let tracer_provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_simple_exporter(span_exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(
+                tracing_opentelemetry::layer()
+                    .with_tracer(tracer_provider.tracer("test"))
+                    .with_filter(destination.span_events_filter()),
+            )
+            .with(
+                build_otel_logs_bridge_layer(&logger_provider)
+                    .with_filter(destination.logs_filter()),
+            );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request").in_scope(|| {
+                tracing::info!("something happened");
+            });
+        });
+
+        let_assert!(Ok(spans) = span_exporter.get_finished_spans());
+        assert!(spans.len() == 1);
+        assert!(spans[0].events.len() == expected_span_events);
+        let_assert!(Ok(logs) = log_exporter.get_emitted_logs());
+        assert!(logs.len() == expected_logs);
+    }
+}
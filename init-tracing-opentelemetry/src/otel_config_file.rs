@@ -0,0 +1,367 @@
+//! Experimental support of the [OpenTelemetry declarative configuration](https://github.com/open-telemetry/opentelemetry-configuration)
+//! (file defined by `OTEL_EXPERIMENTAL_CONFIG_FILE`).
+//!
+//! Only a subset of the model is supported, it is converted into a [`TracingConfig`]:
+//!
+//! - `disabled`
+//! - `resource.attributes` (list of `name`/`value` or map)
+//! - `tracer_provider.processors[].(batch|simple).exporter.(otlp|zipkin|console)` (`protocol`, `endpoint` & `compression`
+//!   of `otlp`, `endpoint` of `zipkin`)
+//! - `tracer_provider.sampler` (`always_on`, `always_off`, `trace_id_ratio_based`, `parent_based.root`)
+//! - `propagator.composite`
+//!
+//! The scalar values can reference environment variables with `${NAME}` (or `${env:NAME}`) and
+//! `${NAME:-default}`, `$$` is an escaped `$`. An undefined variable without default is replaced by an empty value.
+//!
+//! ```yaml
+//! file_format: "0.3"
+//! disabled: ${OTEL_SDK_DISABLED:-false}
+//! resource:
+//!   attributes:
+//!     - name: service.name
+//!       value: my-service
+//! tracer_provider:
+//!   processors:
+//!     - batch:
+//!         exporter:
+//!           otlp:
+//!             protocol: grpc
+//!             endpoint: ${OTEL_EXPORTER_OTLP_ENDPOINT:-http://localhost:4317}
+//!   sampler:
+//!     parent_based:
+//!       root:
+//!         trace_id_ratio_based:
+//!           ratio: 0.25
+//! propagator:
+//!   composite: [tracecontext, baggage]
+//! ```
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::config_file::TracingConfig;
+use crate::Error;
+
+impl TracingConfig {
+    /// Read the configuration from the file defined by `OTEL_EXPERIMENTAL_CONFIG_FILE` (if defined)
+    pub fn from_otel_config_env() -> Result<Option<Self>, Error> {
+        std::env::var_os("OTEL_EXPERIMENTAL_CONFIG_FILE")
+            .map(Self::from_otel_config_file)
+            .transpose()
+    }
+
+    /// Read the configuration from a declarative configuration file of OpenTelemetry
+    pub fn from_otel_config_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)?;
+        Self::from_otel_config_yaml(&content)
+    }
+
+    /// Read the configuration from the content (YAML) of a declarative configuration file of OpenTelemetry
+    pub fn from_otel_config_yaml(content: &str) -> Result<Self, Error> {
+        let mut model: Value = serde_yaml::from_str(content).map_err(|err| {
+            Error::InvalidConfig(format!(
+                "invalid YAML for the OpenTelemetry configuration file: {err}"
+            ))
+        })?;
+        substitute_env_vars(&mut model)?;
+        Self::from_otel_config(&model)
+    }
+
+    fn from_otel_config(model: &Value) -> Result<Self, Error> {
+        let mut config = TracingConfig {
+            otel_enabled: model.get("disabled").and_then(Value::as_bool).map(|d| !d),
+            ..Default::default()
+        };
+        match model.pointer("/resource/attributes") {
+            Some(Value::Array(attributes)) => {
+                for attribute in attributes {
+                    if let (Some(name), Some(value)) = (
+                        attribute.get("name").and_then(Value::as_str),
+                        attribute.get("value").and_then(scalar_to_string),
+                    ) {
+                        config.resource_attributes.insert(name.to_string(), value);
+                    }
+                }
+            }
+            Some(Value::Object(attributes)) => {
+                for (name, value) in attributes {
+                    if let Some(value) = scalar_to_string(value) {
+                        config.resource_attributes.insert(name.clone(), value);
+                    }
+                }
+            }
+            _ => {}
+        }
+        if let Some(service_name) = config.resource_attributes.remove("service.name") {
+            config.service_name = Some(service_name);
+        }
+        let processors = model
+            .pointer("/tracer_provider/processors")
+            .and_then(Value::as_array);
+        if let Some(exporter) = processors.and_then(|p| {
+            p.iter().find_map(|processor| {
+                processor
+                    .get("batch")
+                    .or_else(|| processor.get("simple"))
+                    .and_then(|p| p.get("exporter"))
+            })
+        }) {
+            if let Some(otlp) = exporter.get("otlp") {
+                config.traces_exporter = Some("otlp".to_string());
+                config.protocol = otlp
+                    .get("protocol")
+                    .and_then(Value::as_str)
+                    .map(ToString::to_string);
+                config.endpoint = otlp
+                    .get("endpoint")
+                    .and_then(Value::as_str)
+                    .map(ToString::to_string);
+                config.compression = otlp
+                    .get("compression")
+                    .and_then(Value::as_str)
+                    .map(ToString::to_string);
+            } else if let Some(zipkin) = exporter.get("zipkin") {
+                config.traces_exporter = Some("zipkin".to_string());
+                config.zipkin_endpoint = zipkin
+                    .get("endpoint")
+                    .and_then(Value::as_str)
+                    .map(ToString::to_string);
+            } else if exporter.get("console").is_some() {
+                return Err(Error::InvalidConfig(
+                    "unsupported exporter 'console' in tracer_provider.processors".to_string(),
+                ));
+            }
+        } else if processors.is_some() {
+            config.traces_exporter = Some("none".to_string());
+        }
+        if let Some(sampler) = model.pointer("/tracer_provider/sampler") {
+            let (name, arg) = sampler_name(sampler)?;
+            config.sampler = Some(name);
+            config.sampler_arg = arg;
+        }
+        if let Some(propagators) = model
+            .pointer("/propagator/composite")
+            .and_then(Value::as_array)
+        {
+            config.propagators = Some(
+                propagators
+                    .iter()
+                    .filter_map(|p| {
+                        // support `- tracecontext` and `- tracecontext: {}` (newer versions of the model)
+                        p.as_str()
+                            .map(ToString::to_string)
+                            .or_else(|| p.as_object().and_then(|o| o.keys().next().cloned()))
+                    })
+                    .collect(),
+            );
+        }
+        Ok(config)
+    }
+}
+
+/// Convert the sampler of the model into the name (& arg) used by `OTEL_TRACES_SAMPLER` (& `OTEL_TRACES_SAMPLER_ARG`)
+fn sampler_name(sampler: &Value) -> Result<(String, Option<String>), Error> {
+    if sampler.get("always_on").is_some() {
+        Ok(("always_on".to_string(), None))
+    } else if sampler.get("always_off").is_some() {
+        Ok(("always_off".to_string(), None))
+    } else if let Some(ratio_based) = sampler.get("trace_id_ratio_based") {
+        let ratio = ratio_based.get("ratio").and_then(scalar_to_string);
+        Ok(("traceidratio".to_string(), ratio))
+    } else if let Some(parent_based) = sampler.get("parent_based") {
+        let (root, arg) = match parent_based.get("root") {
+            Some(root) => sampler_name(root)?,
+            None => ("always_on".to_string(), None),
+        };
+        if root.starts_with("parentbased_") {
+            return Err(Error::InvalidConfig(
+                "unsupported nested parent_based sampler".to_string(),
+            ));
+        }
+        Ok((format!("parentbased_{root}"), arg))
+    } else {
+        Err(Error::InvalidConfig(format!(
+            "unsupported sampler in tracer_provider.sampler: {sampler}"
+        )))
+    }
+}
+
+/// Replace the references to the environment variables into the strings of the model. A string fully replaced is
+/// read as a YAML scalar (eg `disabled: ${OTEL_SDK_DISABLED}` is a boolean).
+fn substitute_env_vars(model: &mut Value) -> Result<(), Error> {
+    match model {
+        Value::String(s) if s.contains('$') => {
+            let substituted = substitute_env_vars_in(s, |name| std::env::var(name).ok())?;
+            let fully_replaced =
+                s.starts_with("${") && s.ends_with('}') && s.matches("${").count() == 1;
+            *model = match serde_yaml::from_str::<Value>(&substituted) {
+                Ok(value @ (Value::Bool(_) | Value::Number(_))) if fully_replaced => value,
+                _ => Value::String(substituted),
+            };
+        }
+        Value::Array(values) => {
+            for value in values {
+                substitute_env_vars(value)?;
+            }
+        }
+        Value::Object(values) => {
+            for value in values.values_mut() {
+                substitute_env_vars(value)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn substitute_env_vars_in(
+    s: &str,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> Result<String, Error> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after.find('}').ok_or_else(|| {
+                Error::InvalidConfig(format!("unclosed environment variable reference in '{s}'"))
+            })?;
+            let reference = &after[..end];
+            let (name, default) = match reference.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (reference, None),
+            };
+            let name = name.strip_prefix("env:").unwrap_or(name);
+            let valid_name = name
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid_name {
+                return Err(Error::InvalidConfig(format!(
+                    "invalid environment variable reference '${{{reference}}}'"
+                )));
+            }
+            match env_var(name).filter(|v| !v.is_empty()) {
+                Some(value) => result.push_str(&value),
+                None => result.push_str(default.unwrap_or_default()),
+            }
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use serde_json::json;
+
+    #[test]
+    fn convert_otel_config() {
+        let model = json!({
+            "file_format": "0.3",
+            "disabled": false,
+            "resource": {
+                "attributes": [
+                    {"name": "service.name", "value": "my-service"},
+                    {"name": "deployment.environment.name", "value": "production"}
+                ]
+            },
+            "tracer_provider": {
+                "processors": [
+                    {"batch": {"exporter": {"otlp": {"protocol": "grpc", "endpoint": "http://localhost:4317"}}}}
+                ],
+                "sampler": {"parent_based": {"root": {"trace_id_ratio_based": {"ratio": 0.25}}}}
+            },
+            "propagator": {"composite": ["tracecontext", "baggage"]}
+        });
+        let_assert!(Ok(config) = TracingConfig::from_otel_config(&model));
+        assert!(config.otel_enabled == Some(true));
+        assert!(config.service_name.as_deref() == Some("my-service"));
+        assert!(config.resource_attributes.len() == 1);
+        assert!(config.traces_exporter.as_deref() == Some("otlp"));
+        assert!(config.protocol.as_deref() == Some("grpc"));
+        assert!(config.endpoint.as_deref() == Some("http://localhost:4317"));
+        assert!(config.sampler.as_deref() == Some("parentbased_traceidratio"));
+        assert!(config.sampler_arg.as_deref() == Some("0.25"));
+        assert!(
+            config.propagators == Some(vec!["tracecontext".to_string(), "baggage".to_string()])
+        );
+    }
+
+    #[test]
+    fn convert_otel_config_yaml() {
+        let yaml = r"
+file_format: '0.3'
+disabled: ${OTEL_CONFIG_FILE_TEST_UNDEFINED:-true}
+resource:
+  attributes:
+    service.name: my-service
+tracer_provider:
+  processors:
+    - batch:
+        exporter:
+          zipkin:
+            endpoint: http://zipkin:9411/api/v2/spans
+  sampler:
+    always_off:
+propagator:
+  composite:
+    - tracecontext:
+    - b3:
+";
+        let_assert!(Ok(config) = TracingConfig::from_otel_config_yaml(yaml));
+        assert!(config.otel_enabled == Some(false));
+        assert!(config.service_name.as_deref() == Some("my-service"));
+        assert!(config.traces_exporter.as_deref() == Some("zipkin"));
+        assert!(config.zipkin_endpoint.as_deref() == Some("http://zipkin:9411/api/v2/spans"));
+        assert!(config.sampler.as_deref() == Some("always_off"));
+        assert!(config.propagators == Some(vec!["tracecontext".to_string(), "b3".to_string()]));
+    }
+
+    #[test]
+    fn substitute_the_env_vars() {
+        let env_var = |name: &str| (name == "HOST").then(|| "collector".to_string());
+        let_assert!(
+            Ok(value) = substitute_env_vars_in(
+                "http://${HOST}:${env:PORT:-4317}/$$path/${UNDEFINED}",
+                env_var
+            )
+        );
+        assert!(value == "http://collector:4317/$path/");
+        let_assert!(Err(Error::InvalidConfig(_)) = substitute_env_vars_in("${HOST", env_var));
+        let_assert!(Err(Error::InvalidConfig(_)) = substitute_env_vars_in("${1HOST}", env_var));
+    }
+
+    #[test]
+    fn convert_otel_config_without_exporter() {
+        let model = json!({"tracer_provider": {"processors": []}});
+        let_assert!(Ok(config) = TracingConfig::from_otel_config(&model));
+        assert!(config.traces_exporter.as_deref() == Some("none"));
+    }
+
+    #[test]
+    fn convert_otel_config_with_unsupported_sampler() {
+        let model = json!({"tracer_provider": {"sampler": {"jaeger_remote": {}}}});
+        let_assert!(Err(Error::InvalidConfig(_)) = TracingConfig::from_otel_config(&model));
+    }
+}
@@ -1,8 +1,16 @@
-use opentelemetry::trace::TraceError;
-use opentelemetry_otlp::SpanExporter;
+use opentelemetry_otlp::{Compression, SpanExporter, WithExportConfig, WithTonicConfig};
 use opentelemetry_sdk::{trace::TracerProvider, Resource};
 #[cfg(feature = "tls")]
-use {opentelemetry_otlp::WithTonicConfig, tonic::transport::ClientTlsConfig};
+use tonic::transport::ClientTlsConfig;
+
+use crate::{
+    BatchConfig, Error, ExporterHealth, HealthRecordingExporter, QueueCapSpanProcessor,
+    QueueDrainExporter, RecordingGateSpanProcessor, RuntimeMode, SpanQueueUsage,
+    SuppressInstrumentationExporter, TruncateAttributeValueExporter,
+};
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
 
 #[must_use]
 pub fn identity(v: opentelemetry_sdk::trace::Builder) -> opentelemetry_sdk::trace::Builder {
@@ -10,27 +18,229 @@ pub fn identity(v: opentelemetry_sdk::trace::Builder) -> opentelemetry_sdk::trac
 }
 
 // see https://opentelemetry.io/docs/reference/specification/protocol/exporter/
-pub fn init_tracerprovider<F>(
+pub fn init_tracerprovider<F>(resource: Resource, transform: F) -> Result<TracerProvider, Error>
+where
+    F: FnOnce(opentelemetry_sdk::trace::Builder) -> opentelemetry_sdk::trace::Builder,
+{
+    init_tracerprovider_with_health(resource, transform).map(|(provider, _)| provider)
+}
+
+/// Like [`init_tracerprovider`] but also return the [`ExporterHealth`] of the span exporter
+/// (`None` if no exporter was created).
+pub fn init_tracerprovider_with_health<F>(
     resource: Resource,
     transform: F,
-) -> Result<TracerProvider, TraceError>
+) -> Result<(TracerProvider, Option<ExporterHealth>), Error>
+where
+    F: FnOnce(opentelemetry_sdk::trace::Builder) -> opentelemetry_sdk::trace::Builder,
+{
+    init_tracerprovider_with_runtime(resource, RuntimeMode::Tokio, transform)
+}
+
+/// Like [`init_tracerprovider_with_health`] but with the exporter running on the runtime `runtime_mode`
+/// (eg [`RuntimeMode::OwnThread`] to not require a Tokio runtime).
+pub fn init_tracerprovider_with_runtime<F>(
+    resource: Resource,
+    runtime_mode: RuntimeMode,
+    transform: F,
+) -> Result<(TracerProvider, Option<ExporterHealth>), Error>
+where
+    F: FnOnce(opentelemetry_sdk::trace::Builder) -> opentelemetry_sdk::trace::Builder,
+{
+    init_tracerprovider_with_batch_config(resource, runtime_mode, BatchConfig::default(), transform)
+}
+
+/// Like [`init_tracerprovider_with_runtime`] but with the settings of the batch processor of the exporter
+/// (the environment variables `OTEL_BSP_*` keep the priority).
+pub fn init_tracerprovider_with_batch_config<F>(
+    resource: Resource,
+    runtime_mode: RuntimeMode,
+    batch_config: BatchConfig,
+    transform: F,
+) -> Result<(TracerProvider, Option<ExporterHealth>), Error>
+where
+    F: FnOnce(opentelemetry_sdk::trace::Builder) -> opentelemetry_sdk::trace::Builder,
+{
+    init_tracerprovider_with_pipeline(
+        resource,
+        runtime_mode,
+        batch_config,
+        &PipelineOptions::default(),
+        transform,
+    )
+}
+
+/// The optional processors around the batch processor of the exporter (see [`init_tracerprovider_with_pipeline`])
+#[derive(Debug, Clone, Default)]
+pub struct PipelineOptions {
+    #[cfg(feature = "self_metrics")]
+    self_metrics: Option<opentelemetry::metrics::Meter>,
+    recording_gate: bool,
+}
+
+impl PipelineOptions {
+    /// Wrap the batch processor with the [`RecordingGateSpanProcessor`], to consume the marks of the spans
+    /// by the layers (eg `OtelAxumLayer::with_recording_gate`)
+    #[must_use]
+    pub fn with_recording_gate(mut self, recording_gate: bool) -> Self {
+        self.recording_gate = recording_gate;
+        self
+    }
+
+    /// Count the spans (started, ended, dropped by the sampler or the full queue) & the exports with the
+    /// instruments of the `meter` (see [`crate::self_metrics`])
+    #[cfg(feature = "self_metrics")]
+    #[must_use]
+    pub fn with_self_metrics(self, meter: opentelemetry::metrics::Meter) -> Self {
+        PipelineOptions {
+            self_metrics: Some(meter),
+            ..self
+        }
+    }
+}
+
+/// Like [`init_tracerprovider_with_batch_config`] but with the optional processors of `pipeline`.
+pub fn init_tracerprovider_with_pipeline<F>(
+    resource: Resource,
+    runtime_mode: RuntimeMode,
+    batch_config: BatchConfig,
+    pipeline: &PipelineOptions,
+    transform: F,
+) -> Result<(TracerProvider, Option<ExporterHealth>), Error>
 where
     F: FnOnce(opentelemetry_sdk::trace::Builder) -> opentelemetry_sdk::trace::Builder,
 {
     debug_env();
-    let (maybe_protocol, maybe_endpoint) = read_protocol_and_endpoint_from_env();
+    let (batch_config, max_queue_size) = batch_config.resolve()?;
+    let _runtime = runtime_mode.enter()?;
+    let mut trace_provider: opentelemetry_sdk::trace::Builder = TracerProvider::builder();
+    let mut health = None;
+    match read_traces_exporter_from_env().as_str() {
+        "otlp" => {
+            if let Some(exporter) = build_span_exporter()? {
+                trace_provider = with_batch_exporter(
+                    trace_provider,
+                    exporter,
+                    batch_config,
+                    max_queue_size,
+                    pipeline,
+                    &mut health,
+                );
+            }
+        }
+        #[cfg(feature = "zipkin")]
+        "zipkin" => {
+            let exporter =
+                crate::zipkin::init_exporter(&resource).map_err(|source| Error::ExporterBuild {
+                    signal: "traces",
+                    endpoint: std::env::var("OTEL_EXPORTER_ZIPKIN_ENDPOINT").ok(),
+                    source,
+                })?;
+            trace_provider = with_batch_exporter(
+                trace_provider,
+                exporter,
+                batch_config,
+                max_queue_size,
+                pipeline,
+                &mut health,
+            );
+        }
+        #[cfg(not(feature = "zipkin"))]
+        "zipkin" => {
+            return Err(Error::UnsupportedExporter {
+                signal: "traces",
+                name: "zipkin".to_string(),
+                required_feature: Some("zipkin"),
+            });
+        }
+        "none" => {
+            tracing::debug!(target: "otel::setup", "OTEL_TRACES_EXPORTER is 'none'; no span exporter will be created");
+        }
+        unknown => {
+            return Err(Error::UnsupportedExporter {
+                signal: "traces",
+                name: unknown.to_string(),
+                required_feature: None,
+            });
+        }
+    }
+
+    trace_provider = transform(trace_provider.with_resource(resource));
+    Ok((trace_provider.build(), health))
+}
+
+/// Build the OTLP span exporter configured by the environment variables (protocol & endpoint, compression, TLS),
+/// without the provider & the processors: to compose a custom `TracerProvider` (own processors, several
+/// exporters,...) with the same handling of the environment as [`init_tracerprovider`].
+///
+/// Returns `None` (with a warning) if no protocol is set or inferred, or if the protocol is unknown.
+/// Should be called inside a Tokio runtime (the grpc client is created on it).
+///
+/// ```rust,no_run
+/// use init_tracing_opentelemetry::otlp;
+/// use opentelemetry_sdk::trace::TracerProvider;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), init_tracing_opentelemetry::Error> {
+/// let mut builder = TracerProvider::builder();
+/// if let Some(exporter) = otlp::build_span_exporter()? {
+///     builder = builder.with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio);
+/// }
+/// let tracer_provider = builder.build();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Will return `Err` if the environment variables are invalid or if the exporter can not be built.
+pub fn build_span_exporter() -> Result<Option<SpanExporter>, Error> {
+    let (maybe_protocol, maybe_endpoint) = read_protocol_and_endpoint_from_env(Signal::Traces)?;
     let protocol = infer_protocol(maybe_protocol.as_deref(), maybe_endpoint.as_deref());
+    let build_error = |source| Error::ExporterBuild {
+        signal: "traces",
+        endpoint: maybe_endpoint.clone(),
+        source,
+    };
+
+    let compression = read_compression_from_env(Signal::Traces)?;
 
     let exporter: Option<SpanExporter> = match protocol.as_deref() {
-        Some("http/protobuf") => Some(SpanExporter::builder().with_http().build()?),
+        Some("http/protobuf") => {
+            warn_compression_unsupported_by_http(compression);
+            Some(
+                with_endpoint(
+                    SpanExporter::builder().with_http(),
+                    maybe_endpoint.as_deref(),
+                )
+                .build()
+                .map_err(build_error)?,
+            )
+        }
         #[cfg(feature = "tls")]
         Some("grpc/tls") => Some(
-            SpanExporter::builder()
-                .with_tonic()
-                .with_tls_config(ClientTlsConfig::new().with_native_roots())
-                .build()?,
+            with_compression(
+                with_endpoint(
+                    SpanExporter::builder().with_tonic(),
+                    maybe_endpoint.as_deref(),
+                ),
+                compression,
+            )
+            .with_tls_config(ClientTlsConfig::new().with_native_roots())
+            .build()
+            .map_err(build_error)?,
+        ),
+        Some("grpc") => Some(
+            with_compression(
+                with_endpoint(
+                    SpanExporter::builder().with_tonic(),
+                    maybe_endpoint.as_deref(),
+                ),
+                compression,
+            )
+            .build()
+            .map_err(build_error)?,
         ),
-        Some("grpc") => Some(SpanExporter::builder().with_tonic().build()?),
         Some(x) => {
             tracing::warn!("unknown '{x}' env var set or infered for OTEL_EXPORTER_OTLP_TRACES_PROTOCOL or OTEL_EXPORTER_OTLP_PROTOCOL; no span exporter will be created");
             None
@@ -40,15 +250,111 @@ where
             None
         }
     };
-    let mut trace_provider: opentelemetry_sdk::trace::Builder =
-        TracerProvider::builder().with_resource(resource);
-    if let Some(exporter) = exporter {
-        trace_provider =
-            trace_provider.with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio);
+    Ok(exporter)
+}
+
+fn with_batch_exporter<E>(
+    trace_provider: opentelemetry_sdk::trace::Builder,
+    exporter: E,
+    batch_config: opentelemetry_sdk::trace::BatchConfig,
+    max_queue_size: usize,
+    pipeline: &PipelineOptions,
+    health: &mut Option<ExporterHealth>,
+) -> opentelemetry_sdk::trace::Builder
+where
+    E: opentelemetry_sdk::export::trace::SpanExporter + 'static,
+{
+    let exporter_health = ExporterHealth::default();
+    *health = Some(exporter_health.clone());
+    let exporter = HealthRecordingExporter::new(
+        TruncateAttributeValueExporter::new(
+            SuppressInstrumentationExporter::new(exporter),
+            tracing_opentelemetry_instrumentation_sdk::max_attribute_len(),
+        ),
+        exporter_health,
+    );
+    // account the spans dropped because the queue is full (the sdk only logs them on shutdown)
+    let queue_usage = SpanQueueUsage::default();
+    #[cfg(feature = "self_metrics")]
+    if let Some(meter) = &pipeline.self_metrics {
+        use crate::self_metrics::{
+            SelfMetricsExporter, SelfMetricsSampler, SelfMetricsSpanProcessor,
+        };
+        // the sampler of the env (like the default one of the builder)
+        #[allow(deprecated)]
+        let sampler = opentelemetry_sdk::trace::Config::default().sampler;
+        return with_gated_span_processor(
+            trace_provider
+                .with_sampler(SelfMetricsSampler::new(sampler, meter))
+                .with_span_processor(SelfMetricsSpanProcessor::new(meter)),
+            QueueCapSpanProcessor::new(
+                build_batch_processor(
+                    SelfMetricsExporter::new(
+                        QueueDrainExporter::new(exporter, queue_usage.clone()),
+                        meter,
+                    ),
+                    batch_config,
+                ),
+                max_queue_size,
+                queue_usage,
+            )
+            .with_self_metrics(meter),
+            pipeline.recording_gate,
+        );
     }
+    with_gated_span_processor(
+        trace_provider,
+        QueueCapSpanProcessor::new(
+            build_batch_processor(
+                QueueDrainExporter::new(exporter, queue_usage.clone()),
+                batch_config,
+            ),
+            max_queue_size,
+            queue_usage,
+        ),
+        pipeline.recording_gate,
+    )
+}
+
+/// Add the `processor` to the provider, behind the recording gate if `recording_gate`
+fn with_gated_span_processor<P>(
+    trace_provider: opentelemetry_sdk::trace::Builder,
+    processor: P,
+    recording_gate: bool,
+) -> opentelemetry_sdk::trace::Builder
+where
+    P: opentelemetry_sdk::trace::SpanProcessor + 'static,
+{
+    if recording_gate {
+        trace_provider.with_span_processor(RecordingGateSpanProcessor::new(processor))
+    } else {
+        trace_provider.with_span_processor(processor)
+    }
+}
+
+fn build_batch_processor<E>(
+    exporter: E,
+    batch_config: opentelemetry_sdk::trace::BatchConfig,
+) -> opentelemetry_sdk::trace::BatchSpanProcessor<opentelemetry_sdk::runtime::Tokio>
+where
+    E: opentelemetry_sdk::export::trace::SpanExporter + 'static,
+{
+    opentelemetry_sdk::trace::BatchSpanProcessor::builder(
+        exporter,
+        opentelemetry_sdk::runtime::Tokio,
+    )
+    .with_batch_config(batch_config)
+    .build()
+}
 
-    trace_provider = transform(trace_provider);
-    Ok(trace_provider.build())
+/// Read the exporter to use from [`OTEL_TRACES_EXPORTER`](https://opentelemetry.io/docs/specs/otel/configuration/sdk-environment-variables/#exporter-selection)
+/// Accepted values: "otlp" (default), "zipkin" (require feature "zipkin"), "none"
+pub(crate) fn read_traces_exporter_from_env() -> String {
+    std::env::var("OTEL_TRACES_EXPORTER")
+        .ok()
+        .map(|v| v.trim().to_lowercase())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "otlp".to_string())
 }
 
 pub fn debug_env() {
@@ -57,25 +363,187 @@ pub fn debug_env() {
         .for_each(|(k, v)| tracing::debug!(target: "otel::setup::env", key = %k, value = %v));
 }
 
-fn read_protocol_and_endpoint_from_env() -> (Option<String>, Option<String>) {
-    let maybe_protocol = std::env::var("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL")
-        .or_else(|_| std::env::var("OTEL_EXPORTER_OTLP_PROTOCOL"))
-        .ok();
-    let maybe_endpoint = std::env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT")
-        .or_else(|_| {
-            std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").map(|endpoint| match &maybe_protocol {
-                Some(protocol) if protocol.contains("http") => {
-                    format!("{endpoint}/v1/traces")
-                }
-                _ => endpoint,
-            })
+/// The signals exported via OTLP, used to resolve the per-signal configuration
+/// (`OTEL_EXPORTER_OTLP_{SIGNAL}_ENDPOINT`, `OTEL_EXPORTER_OTLP_{SIGNAL}_PROTOCOL`, ...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Traces,
+    Metrics,
+    Logs,
+}
+
+impl Signal {
+    fn env_name(self) -> &'static str {
+        match self {
+            Signal::Traces => "TRACES",
+            Signal::Metrics => "METRICS",
+            Signal::Logs => "LOGS",
+        }
+    }
+
+    fn http_path(self) -> &'static str {
+        match self {
+            Signal::Traces => "v1/traces",
+            Signal::Metrics => "v1/metrics",
+            Signal::Logs => "v1/logs",
+        }
+    }
+}
+
+/// Read the protocol and the endpoint of the `signal` from the environment variables
+/// (see [`resolve_endpoint`])
+pub fn read_protocol_and_endpoint_from_env(
+    signal: Signal,
+) -> Result<(Option<String>, Option<String>), Error> {
+    let read_env = |suffix: &str| {
+        std::env::var(format!("OTEL_EXPORTER_OTLP_{}_{suffix}", signal.env_name()))
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+    };
+    let maybe_protocol =
+        read_env("PROTOCOL").or_else(|| std::env::var("OTEL_EXPORTER_OTLP_PROTOCOL").ok());
+    let signal_endpoint = read_env("ENDPOINT");
+    let base_endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .filter(|v| !v.trim().is_empty());
+    let protocol = infer_protocol(
+        maybe_protocol.as_deref(),
+        signal_endpoint.as_deref().or(base_endpoint.as_deref()),
+    );
+    let endpoint = resolve_endpoint(
+        signal,
+        protocol.as_deref(),
+        signal_endpoint.as_deref(),
+        base_endpoint.as_deref(),
+    )?;
+    Ok((maybe_protocol, endpoint))
+}
+
+/// Read the compression of the exports of the `signal` from `OTEL_EXPORTER_OTLP_{SIGNAL}_COMPRESSION`
+/// fallback to `OTEL_EXPORTER_OTLP_COMPRESSION`: `gzip` (require the feature `gzip`) or `zstd` (require the feature `zstd`).
+///
+/// # Errors
+///
+/// Will return `Error::InvalidEnv` if the compression is unknown or its feature is not enabled.
+pub fn read_compression_from_env(signal: Signal) -> Result<Option<Compression>, Error> {
+    let Some((name, value)) = [
+        format!("OTEL_EXPORTER_OTLP_{}_COMPRESSION", signal.env_name()),
+        "OTEL_EXPORTER_OTLP_COMPRESSION".to_string(),
+    ]
+    .into_iter()
+    .find_map(|name| {
+        std::env::var(&name)
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(|value| (name, value))
+    }) else {
+        return Ok(None);
+    };
+    parse_compression(&value)
+        .map(Some)
+        .map_err(|reason| Error::InvalidEnv {
+            name,
+            value,
+            reason: reason.to_string(),
         })
-        .ok();
-    (maybe_protocol, maybe_endpoint)
+}
+
+fn parse_compression(value: &str) -> Result<Compression, &'static str> {
+    match value.trim().to_lowercase().as_str() {
+        #[cfg(feature = "gzip")]
+        "gzip" => Ok(Compression::Gzip),
+        #[cfg(not(feature = "gzip"))]
+        "gzip" => Err("the compression 'gzip' requires the feature 'gzip'"),
+        #[cfg(feature = "zstd")]
+        "zstd" => Ok(Compression::Zstd),
+        #[cfg(not(feature = "zstd"))]
+        "zstd" => Err("the compression 'zstd' requires the feature 'zstd'"),
+        _ => Err("the compression should be 'gzip' or 'zstd'"),
+    }
+}
+
+/// Apply the `endpoint` resolved by [`read_protocol_and_endpoint_from_env`] (if any) on the builder of an exporter,
+/// to not depend on the resolution of the environment by `opentelemetry-otlp`
+pub(crate) fn with_endpoint<B>(builder: B, endpoint: Option<&str>) -> B
+where
+    B: WithExportConfig,
+{
+    match endpoint {
+        Some(endpoint) => builder.with_endpoint(endpoint),
+        None => builder,
+    }
+}
+
+/// Apply the `compression` (if any) on the builder of a grpc exporter
+pub(crate) fn with_compression<B>(builder: B, compression: Option<Compression>) -> B
+where
+    B: WithTonicConfig,
+{
+    match compression {
+        Some(compression) => builder.with_compression(compression),
+        None => builder,
+    }
+}
+
+pub(crate) fn warn_compression_unsupported_by_http(compression: Option<Compression>) {
+    if let Some(compression) = compression {
+        tracing::warn!(target: "otel::setup", "the compression '{compression}' is not supported by the 'http/protobuf' exporter (only by 'grpc'); the data are exported uncompressed");
+    }
+}
+
+/// Resolve the endpoint of the `signal` according to the [OTLP Exporter specification](https://opentelemetry.io/docs/specs/otel/protocol/exporter/#endpoint-urls-for-otlphttp):
+///
+/// - the signal-specific endpoint (`OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`,...) is used as is
+/// - the base endpoint (`OTEL_EXPORTER_OTLP_ENDPOINT`) is used as is for grpc, and for http,
+///   the path of the signal (eg `v1/traces`) is appended to the path of the base endpoint
+///
+/// # Errors
+///
+/// Will return `Error::InvalidEnv` if the endpoint is not an `http` or `https` url.
+pub fn resolve_endpoint(
+    signal: Signal,
+    protocol: Option<&str>,
+    signal_endpoint: Option<&str>,
+    base_endpoint: Option<&str>,
+) -> Result<Option<String>, Error> {
+    let env_name = if signal_endpoint.is_some() {
+        format!("OTEL_EXPORTER_OTLP_{}_ENDPOINT", signal.env_name())
+    } else {
+        "OTEL_EXPORTER_OTLP_ENDPOINT".to_string()
+    };
+    let endpoint = match (signal_endpoint, base_endpoint) {
+        (None, Some(endpoint)) if protocol.is_some_and(|p| p.starts_with("http")) => {
+            let endpoint = endpoint.trim();
+            let separator = if endpoint.ends_with('/') { "" } else { "/" };
+            format!("{endpoint}{separator}{}", signal.http_path())
+        }
+        (Some(endpoint), _) | (None, Some(endpoint)) => endpoint.trim().to_string(),
+        (None, None) => return Ok(None),
+    };
+    validate_endpoint(&endpoint).map_err(|reason| Error::InvalidEnv {
+        name: env_name,
+        value: endpoint.clone(),
+        reason: reason.to_string(),
+    })?;
+    Ok(Some(endpoint))
+}
+
+fn validate_endpoint(endpoint: &str) -> Result<(), &'static str> {
+    let host = endpoint
+        .strip_prefix("http://") //Devskim: ignore DS137138
+        .or_else(|| endpoint.strip_prefix("https://"))
+        .ok_or("the scheme should be 'http' or 'https'")?;
+    if host.is_empty() || host.starts_with('/') {
+        return Err("the host is missing");
+    }
+    Ok(())
 }
 
 #[allow(unused_mut)]
-fn infer_protocol(maybe_protocol: Option<&str>, maybe_endpoint: Option<&str>) -> Option<String> {
+pub(crate) fn infer_protocol(
+    maybe_protocol: Option<&str>,
+    maybe_endpoint: Option<&str>,
+) -> Option<String> {
     let mut maybe_protocol = match (maybe_protocol, maybe_endpoint) {
         (Some(protocol), _) => Some(protocol.to_string()),
         (None, Some(endpoint)) => {
@@ -99,7 +567,7 @@ fn infer_protocol(maybe_protocol: Option<&str>, maybe_endpoint: Option<&str>) ->
 
 #[cfg(test)]
 mod tests {
-    use assert2::assert;
+    use assert2::{assert, let_assert};
     use rstest::rstest;
 
     use super::*;
@@ -139,4 +607,224 @@ mod tests {
     ) {
         assert!(infer_protocol(traces_protocol, traces_endpoint).as_deref() == expected_protocol);
     }
+
+    #[rstest]
+    #[case(Signal::Traces, None, None, None, None)]
+    #[case(
+        Signal::Traces,
+        Some("grpc"),
+        None,
+        Some("http://localhost:4317"),
+        Some("http://localhost:4317")
+    )] //Devskim: ignore DS137138
+    #[case(
+        Signal::Traces,
+        Some("http/protobuf"),
+        None,
+        Some("http://localhost:4318"),
+        Some("http://localhost:4318/v1/traces")
+    )] //Devskim: ignore DS137138
+    #[case(
+        Signal::Traces,
+        Some("http/protobuf"),
+        None,
+        Some("http://localhost:4318/"),
+        Some("http://localhost:4318/v1/traces")
+    )] //Devskim: ignore DS137138
+    #[case(
+        Signal::Metrics,
+        Some("http/protobuf"),
+        None,
+        Some("https://example.com/otlp"),
+        Some("https://example.com/otlp/v1/metrics")
+    )]
+    #[case(
+        Signal::Logs,
+        Some("http/protobuf"),
+        None,
+        Some("https://example.com/otlp/"),
+        Some("https://example.com/otlp/v1/logs")
+    )]
+    #[case(
+        Signal::Traces,
+        Some("http/protobuf"),
+        Some("https://example.com/custom"),
+        Some("https://example.com/otlp"),
+        Some("https://example.com/custom")
+    )]
+    #[case(
+        Signal::Traces,
+        Some("grpc"),
+        Some("https://example.com:4317"),
+        None,
+        Some("https://example.com:4317")
+    )]
+    fn test_resolve_endpoint(
+        #[case] signal: Signal,
+        #[case] protocol: Option<&str>,
+        #[case] signal_endpoint: Option<&str>,
+        #[case] base_endpoint: Option<&str>,
+        #[case] expected: Option<&str>,
+    ) {
+        let_assert!(
+            Ok(endpoint) = resolve_endpoint(signal, protocol, signal_endpoint, base_endpoint)
+        );
+        assert!(endpoint.as_deref() == expected);
+    }
+
+    #[rstest]
+    #[cfg_attr(feature = "gzip", case("gzip", Ok(Compression::Gzip)))]
+    #[cfg_attr(feature = "gzip", case(" GZIP ", Ok(Compression::Gzip)))]
+    #[cfg_attr(
+        not(feature = "gzip"),
+        case("gzip", Err("the compression 'gzip' requires the feature 'gzip'"))
+    )]
+    #[cfg_attr(feature = "zstd", case("zstd", Ok(Compression::Zstd)))]
+    #[case("brotli", Err("the compression should be 'gzip' or 'zstd'"))]
+    fn test_parse_compression(
+        #[case] value: &str,
+        #[case] expected: Result<Compression, &'static str>,
+    ) {
+        assert!(parse_compression(value) == expected);
+    }
+
+    #[rstest]
+    #[case("localhost:4317")]
+    #[case("ftp://localhost:4317")]
+    #[case("http://")] //Devskim: ignore DS137138
+    fn test_resolve_endpoint_invalid(#[case] endpoint: &str) {
+        let_assert!(
+            Err(Error::InvalidEnv { name, .. }) =
+                resolve_endpoint(Signal::Traces, Some("grpc"), Some(endpoint), None)
+        );
+        assert!(name == "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn wrap_the_batch_processor_with_the_recording_gate() {
+        use opentelemetry::trace::{Span as _, Status, Tracer, TracerProvider as _};
+        use opentelemetry::KeyValue;
+        use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+        use tracing_opentelemetry_instrumentation_sdk::RECORDING_GATE_ATTRIBUTE;
+
+        let exporter = InMemorySpanExporter::default();
+        let pipeline = PipelineOptions::default().with_recording_gate(true);
+        let tracer_provider = with_batch_exporter(
+            TracerProvider::builder(),
+            exporter.clone(),
+            opentelemetry_sdk::trace::BatchConfig::default(),
+            16,
+            &pipeline,
+            &mut None,
+        )
+        .build();
+        let tracer = tracer_provider.tracer("test");
+
+        for (name, status) in [("fast", Status::Unset), ("failed", Status::error("boom"))] {
+            tracer
+                .span_builder(name)
+                .with_attributes([KeyValue::new(RECORDING_GATE_ATTRIBUTE, 60_000)])
+                .with_status(status)
+                .start(&tracer)
+                .end();
+        }
+        for result in tracer_provider.force_flush() {
+            let_assert!(Ok(()) = result);
+        }
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        assert!(spans.len() == 1);
+        assert!(spans[0].name == "failed");
+        assert!(spans[0]
+            .attributes
+            .iter()
+            .all(|kv| kv.key.as_str() != RECORDING_GATE_ATTRIBUTE));
+    }
+
+    #[cfg(feature = "self_metrics")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn export_the_self_metrics() {
+        use opentelemetry::trace::{
+            Span as _, SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+            Tracer, TracerProvider as _,
+        };
+        use opentelemetry::Context;
+        use opentelemetry_sdk::metrics::data::Sum;
+        use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+        use opentelemetry_sdk::testing::metrics::InMemoryMetricExporter;
+        use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+        use tracing_opentelemetry_instrumentation_sdk::DroppedSpanReason;
+
+        let metric_exporter = InMemoryMetricExporter::default();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(
+                PeriodicReader::builder(metric_exporter.clone(), opentelemetry_sdk::runtime::Tokio)
+                    .build(),
+            )
+            .build();
+        let meter = crate::self_metrics::meter(&meter_provider);
+        let dropped_span_hook = crate::self_metrics::dropped_span_hook(&meter);
+        let pipeline = PipelineOptions::default().with_self_metrics(meter);
+        let mut health = None;
+        let tracer_provider = with_batch_exporter(
+            TracerProvider::builder(),
+            InMemorySpanExporter::default(),
+            opentelemetry_sdk::trace::BatchConfig::default(),
+            16,
+            &pipeline,
+            &mut health,
+        )
+        .build();
+        let tracer = tracer_provider.tracer("test");
+
+        tracer.start("sampled").end();
+        // dropped by the (parent based) sampler of the env
+        let unsampled_parent = Context::new().with_remote_span_context(SpanContext::new(
+            TraceId::from(1),
+            SpanId::from(1),
+            TraceFlags::default(),
+            true,
+            TraceState::default(),
+        ));
+        tracer
+            .start_with_context("unsampled", &unsampled_parent)
+            .end();
+        dropped_span_hook.record(DroppedSpanReason::Filter);
+        for result in tracer_provider.force_flush() {
+            let_assert!(Ok(()) = result);
+        }
+        let_assert!(Ok(()) = meter_provider.force_flush());
+
+        let_assert!(Ok(resource_metrics) = metric_exporter.get_finished_metrics());
+        let counters = resource_metrics
+            .iter()
+            .flat_map(|rm| rm.scope_metrics.iter())
+            .flat_map(|sm| sm.metrics.iter())
+            .filter_map(|metric| {
+                let sum = metric.data.as_any().downcast_ref::<Sum<u64>>()?;
+                Some(sum.data_points.iter().map(move |point| {
+                    let attributes = point
+                        .attributes
+                        .iter()
+                        .map(|kv| format!("{}={}", kv.key, kv.value))
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    (format!("{}{{{attributes}}}", metric.name), point.value)
+                }))
+            })
+            .flatten()
+            .collect::<std::collections::BTreeMap<_, _>>();
+        assert!(
+            counters.get("otel.sdk.span.started{}") == Some(&1),
+            "{counters:?}"
+        );
+        assert!(
+            counters.get("otel.sdk.span.ended{sampled=true}") == Some(&1),
+            "{counters:?}"
+        );
+        assert!(counters.get("otel.sdk.span.dropped{reason=sampler}") == Some(&1));
+        assert!(counters.get("otel.sdk.span.dropped{reason=filter}") == Some(&1));
+        assert!(counters.get("otel.sdk.exporter.span{outcome=success}") == Some(&1));
+        assert!(counters.get("otel.sdk.exporter.batch{outcome=success}") == Some(&1));
+    }
 }
@@ -0,0 +1,393 @@
+//! Export of the metrics via OTLP (eg the ones of `self_metrics` & `span_metrics`, or of the application).
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use opentelemetry::Key;
+use opentelemetry_otlp::MetricExporter;
+use opentelemetry_sdk::metrics::{
+    new_view, Instrument, MeterProviderBuilder, PeriodicReader, SdkMeterProvider, Stream,
+};
+use opentelemetry_sdk::Resource;
+#[cfg(feature = "tls")]
+use {opentelemetry_otlp::WithTonicConfig, tonic::transport::ClientTlsConfig};
+
+use super::{
+    infer_protocol, read_compression_from_env, read_protocol_and_endpoint_from_env,
+    warn_compression_unsupported_by_http, with_compression, with_endpoint, Signal,
+};
+use crate::batch_config::read_positive_env;
+use crate::{Error, ExporterHealth, HealthRecordingMetricExporter};
+
+#[must_use]
+pub fn identity(v: MeterProviderBuilder) -> MeterProviderBuilder {
+    v
+}
+
+/// The settings of the periodic reader that export the metrics, the unset values use the environment variables
+/// [`OTEL_METRIC_EXPORT_*`](https://opentelemetry.io/docs/specs/otel/configuration/sdk-environment-variables/#periodic-exporting-metricreader)
+/// or the default of the sdk (the environment variables keep the priority).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetricsConfig {
+    /// the delay between two exports (`OTEL_METRIC_EXPORT_INTERVAL` in milliseconds, default: 60s)
+    pub export_interval: Option<Duration>,
+    /// the maximum duration of an export (`OTEL_METRIC_EXPORT_TIMEOUT` in milliseconds, default: 30s)
+    pub export_timeout: Option<Duration>,
+    /// the attributes kept, per instrument name (glob, `*` matches any sequence of characters),
+    /// the other attributes are dropped (no env variable)
+    pub attribute_allowlists: BTreeMap<String, Vec<String>>,
+}
+
+impl MetricsConfig {
+    #[must_use]
+    pub fn with_export_interval(self, export_interval: Duration) -> Self {
+        MetricsConfig {
+            export_interval: Some(export_interval),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_export_timeout(self, export_timeout: Duration) -> Self {
+        MetricsConfig {
+            export_timeout: Some(export_timeout),
+            ..self
+        }
+    }
+
+    /// Keep only the attributes `keys` on the metrics of the instruments matching `instrument_glob`
+    /// (`*` matches any sequence of characters), to limit the cardinality,
+    /// eg of the metrics derived from the fields of the tracing's events.
+    ///
+    /// Every matching rule creates a stream, so the globs should not overlap.
+    ///
+    /// ```rust
+    /// use init_tracing_opentelemetry::otlp::metrics::MetricsConfig;
+    ///
+    /// let metrics_config = MetricsConfig::default()
+    ///     .with_attribute_allowlist("http.server.*", ["http.route", "http.request.method"]);
+    /// ```
+    #[must_use]
+    pub fn with_attribute_allowlist<K>(
+        mut self,
+        instrument_glob: impl Into<String>,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Self
+    where
+        K: Into<String>,
+    {
+        self.attribute_allowlists.insert(
+            instrument_glob.into(),
+            keys.into_iter().map(Into::into).collect(),
+        );
+        self
+    }
+
+    /// Read the settings from the environment variables `OTEL_METRIC_EXPORT_INTERVAL` & `OTEL_METRIC_EXPORT_TIMEOUT`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error::InvalidEnv` if a variable is not a positive integer.
+    pub fn from_env() -> Result<Self, Error> {
+        let read_millis = |name| {
+            read_positive_env(name).map(|v| v.map(|millis| Duration::from_millis(millis as u64)))
+        };
+        Ok(MetricsConfig {
+            export_interval: read_millis("OTEL_METRIC_EXPORT_INTERVAL")?,
+            export_timeout: read_millis("OTEL_METRIC_EXPORT_TIMEOUT")?,
+            attribute_allowlists: BTreeMap::new(),
+        })
+    }
+
+    /// The settings of `self`, completed by the ones of `other` for the unset values.
+    #[must_use]
+    pub fn or(self, other: MetricsConfig) -> Self {
+        MetricsConfig {
+            export_interval: self.export_interval.or(other.export_interval),
+            export_timeout: self.export_timeout.or(other.export_timeout),
+            attribute_allowlists: if self.attribute_allowlists.is_empty() {
+                other.attribute_allowlists
+            } else {
+                self.attribute_allowlists
+            },
+        }
+    }
+}
+
+/// Create the meter provider with a periodic reader exporting via OTLP
+/// (selected by `OTEL_METRICS_EXPORTER`: `otlp` (default) or `none`).
+///
+/// The `transform` can be used to register additional readers (or views), eg a `ManualReader` for the tests:
+///
+/// ```rust,ignore
+/// let reader = opentelemetry_sdk::metrics::ManualReader::builder().build();
+/// let meter_provider = otlp::metrics::init_meterprovider(resource, MetricsConfig::default(), |builder| {
+///     builder.with_reader(reader.clone())
+/// })?;
+/// ```
+///
+/// Should be called inside a Tokio runtime (the exports are done by a background task).
+pub fn init_meterprovider<F>(
+    resource: Resource,
+    metrics_config: MetricsConfig,
+    transform: F,
+) -> Result<SdkMeterProvider, Error>
+where
+    F: FnOnce(MeterProviderBuilder) -> MeterProviderBuilder,
+{
+    init_meterprovider_with_health(resource, metrics_config, transform)
+        .map(|(meter_provider, _)| meter_provider)
+}
+
+/// Like [`init_meterprovider`] but also returns the [`ExporterHealth`] of the OTLP exporter (if any),
+/// updated by each export (see [`HealthRecordingMetricExporter`]).
+pub fn init_meterprovider_with_health<F>(
+    resource: Resource,
+    metrics_config: MetricsConfig,
+    transform: F,
+) -> Result<(SdkMeterProvider, Option<ExporterHealth>), Error>
+where
+    F: FnOnce(MeterProviderBuilder) -> MeterProviderBuilder,
+{
+    let metrics_config = MetricsConfig::from_env()?.or(metrics_config);
+    let mut meter_provider = SdkMeterProvider::builder();
+    let mut health = None;
+    match read_metrics_exporter_from_env().as_str() {
+        "otlp" => {
+            if let Some(exporter) = build_metric_exporter()? {
+                let exporter_health = ExporterHealth::default();
+                health = Some(exporter_health.clone());
+                let mut reader = PeriodicReader::builder(
+                    HealthRecordingMetricExporter::new(exporter, exporter_health),
+                    opentelemetry_sdk::runtime::Tokio,
+                );
+                if let Some(export_interval) = metrics_config.export_interval {
+                    reader = reader.with_interval(export_interval);
+                }
+                if let Some(export_timeout) = metrics_config.export_timeout {
+                    reader = reader.with_timeout(export_timeout);
+                }
+                meter_provider = meter_provider.with_reader(reader.build());
+            }
+        }
+        "none" => {
+            tracing::debug!(target: "otel::setup", "OTEL_METRICS_EXPORTER is 'none'; no metric exporter will be created");
+        }
+        unknown => {
+            return Err(Error::UnsupportedExporter {
+                signal: "metrics",
+                name: unknown.to_string(),
+                required_feature: None,
+            });
+        }
+    }
+    for (instrument_glob, keys) in metrics_config.attribute_allowlists {
+        let view = new_view(
+            Instrument::new().name(instrument_glob),
+            Stream::new().allowed_attribute_keys(keys.into_iter().map(Key::new)),
+        )?;
+        meter_provider = meter_provider.with_view(view);
+    }
+    Ok((
+        transform(meter_provider.with_resource(resource)).build(),
+        health,
+    ))
+}
+
+/// Build the OTLP metric exporter configured by the environment variables (protocol & endpoint, compression, TLS),
+/// without the reader & the provider (see [`super::build_span_exporter`]), eg to compose a custom
+/// `SdkMeterProvider` with its own readers.
+///
+/// Returns `None` (with a warning) if no protocol is set or inferred, or if the protocol is unknown.
+/// Should be called inside a Tokio runtime (the grpc client is created on it).
+///
+/// # Errors
+///
+/// Will return `Err` if the environment variables are invalid or if the exporter can not be built.
+pub fn build_metric_exporter() -> Result<Option<MetricExporter>, Error> {
+    let (maybe_protocol, maybe_endpoint) = read_protocol_and_endpoint_from_env(Signal::Metrics)?;
+    let protocol = infer_protocol(maybe_protocol.as_deref(), maybe_endpoint.as_deref());
+    let build_error = |source| Error::ExporterBuild {
+        signal: "metrics",
+        endpoint: maybe_endpoint.clone(),
+        source: opentelemetry::trace::TraceError::Other(Box::new(source)),
+    };
+
+    let compression = read_compression_from_env(Signal::Metrics)?;
+
+    let exporter: Option<MetricExporter> = match protocol.as_deref() {
+        Some("http/protobuf") => {
+            warn_compression_unsupported_by_http(compression);
+            Some(
+                with_endpoint(
+                    MetricExporter::builder().with_http(),
+                    maybe_endpoint.as_deref(),
+                )
+                .build()
+                .map_err(build_error)?,
+            )
+        }
+        #[cfg(feature = "tls")]
+        Some("grpc/tls") => Some(
+            with_compression(
+                with_endpoint(
+                    MetricExporter::builder().with_tonic(),
+                    maybe_endpoint.as_deref(),
+                ),
+                compression,
+            )
+            .with_tls_config(ClientTlsConfig::new().with_native_roots())
+            .build()
+            .map_err(build_error)?,
+        ),
+        Some("grpc") => Some(
+            with_compression(
+                with_endpoint(
+                    MetricExporter::builder().with_tonic(),
+                    maybe_endpoint.as_deref(),
+                ),
+                compression,
+            )
+            .build()
+            .map_err(build_error)?,
+        ),
+        Some(x) => {
+            tracing::warn!("unknown '{x}' env var set or infered for OTEL_EXPORTER_OTLP_METRICS_PROTOCOL or OTEL_EXPORTER_OTLP_PROTOCOL; no metric exporter will be created");
+            None
+        }
+        None => {
+            tracing::warn!("no env var set or infered for OTEL_EXPORTER_OTLP_METRICS_PROTOCOL or OTEL_EXPORTER_OTLP_PROTOCOL; no metric exporter will be created");
+            None
+        }
+    };
+    Ok(exporter)
+}
+
+/// Read the exporter to use from [`OTEL_METRICS_EXPORTER`](https://opentelemetry.io/docs/specs/otel/configuration/sdk-environment-variables/#exporter-selection)
+/// Accepted values: "otlp" (default), "none"
+pub(crate) fn read_metrics_exporter_from_env() -> String {
+    std::env::var("OTEL_METRICS_EXPORTER")
+        .ok()
+        .map(|v| v.trim().to_lowercase())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "otlp".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::metrics::MeterProvider as _;
+    use opentelemetry_sdk::metrics::data::{ResourceMetrics, Sum};
+    use opentelemetry_sdk::metrics::reader::MetricReader;
+    use opentelemetry_sdk::metrics::ManualReader;
+
+    #[test]
+    fn or_completes_the_unset_values() {
+        let config = MetricsConfig::default()
+            .with_export_interval(Duration::from_secs(10))
+            .or(MetricsConfig::default()
+                .with_export_interval(Duration::from_secs(60))
+                .with_export_timeout(Duration::from_secs(5)));
+        assert!(config.export_interval == Some(Duration::from_secs(10)));
+        assert!(config.export_timeout == Some(Duration::from_secs(5)));
+    }
+
+    #[tokio::test]
+    async fn drop_the_attributes_not_allowed() {
+        use opentelemetry::KeyValue;
+
+        let reader = std::sync::Arc::new(ManualReader::builder().build());
+        let metrics_config = MetricsConfig::default().with_attribute_allowlist("call*", ["route"]);
+        let_assert!(
+            Ok(meter_provider) = init_meterprovider(Resource::empty(), metrics_config, |builder| {
+                builder.with_reader(SharedReader(reader.clone()))
+            })
+        );
+        let counter = meter_provider.meter("test").u64_counter("calls").build();
+        counter.add(
+            1,
+            &[
+                KeyValue::new("route", "/users/{id}"),
+                KeyValue::new("user_id", "42"),
+            ],
+        );
+        counter.add(
+            1,
+            &[
+                KeyValue::new("route", "/users/{id}"),
+                KeyValue::new("user_id", "43"),
+            ],
+        );
+
+        let mut metrics = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: vec![],
+        };
+        let_assert!(Ok(()) = reader.collect(&mut metrics));
+        let_assert!(Some(scope_metrics) = metrics.scope_metrics.first());
+        let_assert!(Some(metric) = scope_metrics.metrics.first());
+        let_assert!(Some(sum) = metric.data.as_any().downcast_ref::<Sum<u64>>());
+        assert!(sum.data_points.len() == 1);
+        assert!(sum.data_points[0].value == 2);
+        assert!(sum.data_points[0].attributes == vec![KeyValue::new("route", "/users/{id}")]);
+    }
+
+    #[tokio::test]
+    async fn register_additional_reader_via_transform() {
+        let reader = std::sync::Arc::new(ManualReader::builder().build());
+        let_assert!(
+            Ok(meter_provider) =
+                init_meterprovider(Resource::empty(), MetricsConfig::default(), |builder| {
+                    builder.with_reader(SharedReader(reader.clone()))
+                })
+        );
+        let counter = meter_provider.meter("test").u64_counter("calls").build();
+        counter.add(2, &[]);
+
+        let mut metrics = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: vec![],
+        };
+        let_assert!(Ok(()) = reader.collect(&mut metrics));
+        let_assert!(Some(scope_metrics) = metrics.scope_metrics.first());
+        let_assert!(Some(metric) = scope_metrics.metrics.first());
+        assert!(metric.name == "calls");
+        let_assert!(Some(sum) = metric.data.as_any().downcast_ref::<Sum<u64>>());
+        assert!(sum.data_points[0].value == 2);
+    }
+
+    /// The `ManualReader` is not `Clone`, share it with the meter provider to collect from the test
+    #[derive(Debug, Clone)]
+    struct SharedReader(std::sync::Arc<ManualReader>);
+
+    impl MetricReader for SharedReader {
+        fn register_pipeline(
+            &self,
+            pipeline: std::sync::Weak<opentelemetry_sdk::metrics::Pipeline>,
+        ) {
+            self.0.register_pipeline(pipeline);
+        }
+
+        fn collect(
+            &self,
+            rm: &mut ResourceMetrics,
+        ) -> opentelemetry_sdk::metrics::MetricResult<()> {
+            self.0.collect(rm)
+        }
+
+        fn force_flush(&self) -> opentelemetry_sdk::metrics::MetricResult<()> {
+            self.0.force_flush()
+        }
+
+        fn shutdown(&self) -> opentelemetry_sdk::metrics::MetricResult<()> {
+            self.0.shutdown()
+        }
+
+        fn temporality(
+            &self,
+            kind: opentelemetry_sdk::metrics::InstrumentKind,
+        ) -> opentelemetry_sdk::metrics::Temporality {
+            self.0.temporality(kind)
+        }
+    }
+}
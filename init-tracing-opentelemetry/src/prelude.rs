@@ -0,0 +1,19 @@
+//! Re-export of the items commonly combined to initialize the tracing, for a single import line:
+//!
+//! ```rust
+//! use init_tracing_opentelemetry::prelude::*;
+//! ```
+//!
+//! The items are available according to the enabled features (eg [`TracingConfig`] with `config_file`).
+
+pub use crate::{init_propagator, BatchConfig, EffectiveConfig, Error, Health, RuntimeMode};
+pub use tracing_opentelemetry_instrumentation_sdk::find_current_trace_id;
+
+#[cfg(feature = "config_file")]
+pub use crate::config_file::TracingConfig;
+#[cfg(feature = "shutdown")]
+pub use crate::shutdown::{with_telemetry_shutdown, TelemetryShutdown};
+#[cfg(all(feature = "tracing_subscriber_ext", feature = "config_file"))]
+pub use crate::tracing_subscriber_ext::init_subscribers_with_config;
+#[cfg(feature = "tracing_subscriber_ext")]
+pub use crate::tracing_subscriber_ext::{init_subscribers, OtelLayer, TracingGuard};
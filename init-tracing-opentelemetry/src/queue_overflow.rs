@@ -0,0 +1,283 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures_core::future::BoxFuture;
+use opentelemetry::trace::TraceResult;
+use opentelemetry::Context;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry_sdk::trace::{Span, SpanProcessor};
+use opentelemetry_sdk::Resource;
+
+/// The minimal delay between two warnings about the dropped spans
+const DROPPED_SPANS_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Shared handle to the number of spans waiting in the queue of the batch processor (and the number of spans dropped
+/// because the queue was full).
+///
+/// The sdk doesn't expose its own count of the dropped spans (it only logs it on shutdown), so the queue is bounded
+/// upstream by a [`QueueCapSpanProcessor`] (the spans enter the queue) and a [`QueueDrainExporter`] (the spans leave
+/// the queue), that share this handle.
+#[derive(Debug, Clone, Default)]
+pub struct SpanQueueUsage(Arc<SpanQueueState>);
+
+#[derive(Debug, Default)]
+struct SpanQueueState {
+    pending: AtomicUsize,
+    dropped: AtomicU64,
+    log_throttle: Mutex<DropLogThrottle>,
+}
+
+impl SpanQueueUsage {
+    /// The number of spans ended but not yet given to the exporter
+    #[must_use]
+    pub fn pending_spans(&self) -> usize {
+        self.0.pending.load(Ordering::Relaxed)
+    }
+
+    /// The number of spans dropped because the queue was full (since the creation of the handle)
+    #[must_use]
+    pub fn dropped_spans(&self) -> u64 {
+        self.0.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Reserve a place in the queue, `false` if the queue is full
+    fn try_enqueue(&self, max_queue_size: usize) -> bool {
+        self.0
+            .pending
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |pending| {
+                (pending < max_queue_size).then_some(pending + 1)
+            })
+            .is_ok()
+    }
+
+    fn dequeue(&self, count: usize) {
+        let _ = self
+            .0
+            .pending
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |pending| {
+                Some(pending.saturating_sub(count))
+            });
+    }
+
+    /// Record a dropped span, and log a warning via `tracing` under the target `otel::exporter`
+    /// (at most once per [`DROPPED_SPANS_LOG_INTERVAL`]).
+    fn record_drop(&self) {
+        let total = self.0.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+        let dropped = self
+            .0
+            .log_throttle
+            .lock()
+            .ok()
+            .and_then(|mut throttle| throttle.on_drop(Instant::now()));
+        // log outside of the lock
+        if let Some(dropped) = dropped {
+            log_dropped_spans(dropped, total);
+        }
+    }
+
+    /// Log the drops not reported yet (eg on shutdown)
+    fn flush_log(&self) {
+        let dropped = self
+            .0
+            .log_throttle
+            .lock()
+            .ok()
+            .map(|mut throttle| throttle.take_unreported())
+            .unwrap_or_default();
+        if dropped > 0 {
+            log_dropped_spans(dropped, self.dropped_spans());
+        }
+    }
+}
+
+fn log_dropped_spans(dropped: u64, total: u64) {
+    tracing::warn!(
+        target: "otel::exporter",
+        dropped,
+        total,
+        "spans dropped because the queue of the batch processor is full (increase OTEL_BSP_MAX_QUEUE_SIZE or reduce OTEL_BSP_SCHEDULE_DELAY)"
+    );
+}
+
+/// Rate-limit the warnings about the dropped spans
+#[derive(Debug, Default)]
+struct DropLogThrottle {
+    last_logged: Option<Instant>,
+    unreported: u64,
+}
+
+impl DropLogThrottle {
+    /// `Some(number of drops since the previous log)` if the drop should be logged
+    fn on_drop(&mut self, now: Instant) -> Option<u64> {
+        self.unreported += 1;
+        let should_log = self.last_logged.map_or(true, |at| {
+            now.duration_since(at) >= DROPPED_SPANS_LOG_INTERVAL
+        });
+        if should_log {
+            self.last_logged = Some(now);
+            Some(self.take_unreported())
+        } else {
+            None
+        }
+    }
+
+    fn take_unreported(&mut self) -> u64 {
+        std::mem::take(&mut self.unreported)
+    }
+}
+
+/// `SpanProcessor` that bounds the number of spans waiting in the queue of the `inner` processor
+/// (eg the batch processor of the exporter): when `max_queue_size` spans are waiting, the sampled spans are dropped
+/// (instead of being dropped silently by the `inner` processor), counted into the [`SpanQueueUsage`] and reported by
+/// a rate-limited warning (and by the counter `otel.sdk.span.dropped`, see `with_self_metrics`).
+///
+/// The spans leave the queue when they are given to the exporter wrapped into a [`QueueDrainExporter`] sharing the
+/// same [`SpanQueueUsage`].
+#[derive(Debug)]
+pub struct QueueCapSpanProcessor<P> {
+    inner: P,
+    max_queue_size: usize,
+    usage: SpanQueueUsage,
+    #[cfg(feature = "self_metrics")]
+    dropped: Option<opentelemetry::metrics::Counter<u64>>,
+}
+
+impl<P: SpanProcessor> QueueCapSpanProcessor<P> {
+    pub fn new(inner: P, max_queue_size: usize, usage: SpanQueueUsage) -> Self {
+        Self {
+            inner,
+            max_queue_size,
+            usage,
+            #[cfg(feature = "self_metrics")]
+            dropped: None,
+        }
+    }
+
+    /// Count the dropped spans into `otel.sdk.span.dropped` (with attribute `reason` = `queue_full`) of the `meter`
+    #[cfg(feature = "self_metrics")]
+    #[must_use]
+    pub fn with_self_metrics(self, meter: &opentelemetry::metrics::Meter) -> Self {
+        Self {
+            dropped: Some(crate::self_metrics::dropped_spans_counter(meter)),
+            ..self
+        }
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for QueueCapSpanProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        // the unsampled spans are not queued by the batch processor
+        if !span.span_context.is_sampled() || self.usage.try_enqueue(self.max_queue_size) {
+            self.inner.on_end(span);
+        } else {
+            self.usage.record_drop();
+            #[cfg(feature = "self_metrics")]
+            if let Some(dropped) = &self.dropped {
+                dropped.add(1, &[opentelemetry::KeyValue::new("reason", "queue_full")]);
+            }
+        }
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        self.inner.force_flush()
+    }
+
+    fn shutdown(&self) -> TraceResult<()> {
+        self.usage.flush_log();
+        self.inner.shutdown()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+/// Wrap a `SpanExporter` to remove the exported spans from the queue accounted by a [`QueueCapSpanProcessor`].
+#[derive(Debug)]
+pub struct QueueDrainExporter<E> {
+    inner: E,
+    usage: SpanQueueUsage,
+}
+
+impl<E> QueueDrainExporter<E> {
+    pub fn new(inner: E, usage: SpanQueueUsage) -> Self {
+        Self { inner, usage }
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for QueueDrainExporter<E> {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        self.usage.dequeue(batch.len());
+        self.inner.export(batch)
+    }
+
+    fn shutdown(&mut self) {
+        self.inner.shutdown();
+    }
+
+    fn force_flush(&mut self) -> BoxFuture<'static, ExportResult> {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::trace::{Span as _, Tracer, TracerProvider as _};
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use opentelemetry_sdk::trace::{SimpleSpanProcessor, TracerProvider};
+
+    #[tokio::test]
+    async fn drop_the_spans_when_the_queue_is_full() {
+        let usage = SpanQueueUsage::default();
+        let exporter = InMemorySpanExporter::default();
+        // the spans are not drained (the exporter is not wrapped), so the queue is full after 2 spans
+        let tracer_provider = TracerProvider::builder()
+            .with_span_processor(QueueCapSpanProcessor::new(
+                SimpleSpanProcessor::new(Box::new(exporter.clone())),
+                2,
+                usage.clone(),
+            ))
+            .build();
+        let tracer = tracer_provider.tracer("test");
+
+        for name in ["a", "b", "c"] {
+            tracer.start(name).end();
+        }
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        assert!(spans.len() == 2);
+        assert!(usage.pending_spans() == 2);
+        assert!(usage.dropped_spans() == 1);
+
+        let mut drain = QueueDrainExporter::new(InMemorySpanExporter::default(), usage.clone());
+        let_assert!(Ok(()) = drain.export(spans).await);
+        assert!(usage.pending_spans() == 0);
+
+        tracer.start("d").end();
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        assert!(spans.len() == 3);
+        assert!(usage.dropped_spans() == 1);
+    }
+
+    #[test]
+    fn throttle_the_logs_of_the_drops() {
+        let start = Instant::now();
+        let mut throttle = DropLogThrottle::default();
+        assert!(throttle.on_drop(start) == Some(1));
+        assert!(throttle.on_drop(start + Duration::from_secs(1)) == None);
+        assert!(throttle.on_drop(start + Duration::from_secs(2)) == None);
+        assert!(throttle.on_drop(start + DROPPED_SPANS_LOG_INTERVAL) == Some(3));
+        assert!(throttle.on_drop(start + DROPPED_SPANS_LOG_INTERVAL) == None);
+        assert!(throttle.take_unreported() == 1);
+    }
+}
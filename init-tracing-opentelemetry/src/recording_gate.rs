@@ -0,0 +1,452 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use opentelemetry::trace::{
+    Span as _, SpanId, SpanKind, Status, TraceContextExt, TraceId, TraceResult,
+};
+use opentelemetry::{Context, Value};
+use opentelemetry_sdk::export::trace::SpanData;
+use opentelemetry_sdk::trace::{Span, SpanProcessor};
+use opentelemetry_sdk::Resource;
+use tracing_opentelemetry_instrumentation_sdk::RECORDING_GATE_ATTRIBUTE;
+
+/// Default maximum number of spans waiting for the decision of the gate
+pub const DEFAULT_MAX_BUFFERED_SPANS: usize = 2048;
+
+/// `SpanProcessor` that applies the recording gate of the layers (eg `OtelAxumLayer::with_recording_gate`):
+/// the traces of the requests that end successfully & fast are dropped, the others are forwarded
+/// to the `inner` processor (eg the batch processor of the exporter).
+///
+/// The spans are buffered until the end of their local root (a span without parent, or with a remote parent,
+/// or with `SpanKind::Server` / `SpanKind::Consumer`, or marked by the gate), then:
+///
+/// - if the local root is marked with [`RECORDING_GATE_ATTRIBUTE`], the root & its buffered descendants are
+///   forwarded only if the root ends with an `ERROR` status or lasts at least the latency threshold
+/// - else the root & its buffered descendants are forwarded
+///
+/// So the local roots of a trace (eg 2 requests of the same trace handled by the process) are decided separately.
+///
+/// The decisions of the last `max_buffered_spans` decided spans are kept, so the late children (ended after
+/// their local root, eg a spawned task) are forwarded or dropped like their local root.
+///
+/// When more than `max_buffered_spans` are waiting, the buffered spans are forwarded without decision
+/// (to bound the memory), like on `force_flush` & `shutdown`.
+///
+/// It is installed around the batch processor of the exporter by `init_tracerprovider_with_pipeline` with
+/// `PipelineOptions::with_recording_gate` (`TracingConfig::with_recording_gate`).
+///
+/// ```rust
+/// use init_tracing_opentelemetry::RecordingGateSpanProcessor;
+/// use opentelemetry_sdk::trace::{SimpleSpanProcessor, TracerProvider};
+/// use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+///
+/// let processor = RecordingGateSpanProcessor::new(SimpleSpanProcessor::new(Box::new(
+///     InMemorySpanExporter::default(),
+/// )));
+/// let tracer_provider = TracerProvider::builder()
+///     .with_span_processor(processor)
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct RecordingGateSpanProcessor<P> {
+    inner: P,
+    max_buffered_spans: usize,
+    buffer: Mutex<Buffer>,
+}
+
+/// The ended spans waiting for the end of their local root, by parent
+#[derive(Debug, Default)]
+struct Buffer {
+    children: HashMap<(TraceId, SpanId), Vec<SpanData>>,
+    len: usize,
+    /// the started spans with a remote parent (the local roots not detectable from the `SpanData`)
+    remote_children: HashSet<(TraceId, SpanId)>,
+    /// the decision (kept or not) of the local root of the recently decided spans
+    decisions: HashMap<(TraceId, SpanId), bool>,
+    /// the recently decided spans, from the oldest
+    decided: VecDeque<(TraceId, SpanId)>,
+}
+
+impl Buffer {
+    /// Remember the decision for `span_id` (dropping the oldest decisions above `max_decisions`)
+    fn decide(&mut self, trace_id: TraceId, span_id: SpanId, kept: bool, max_decisions: usize) {
+        if self.decisions.insert((trace_id, span_id), kept).is_none() {
+            self.decided.push_back((trace_id, span_id));
+        }
+        while self.decided.len() > max_decisions {
+            if let Some(oldest) = self.decided.pop_front() {
+                self.decisions.remove(&oldest);
+            }
+        }
+    }
+
+    /// The decision of the local root of the parent of `span`, if the local root already ended
+    fn decision_of_parent(&self, span: &SpanData) -> Option<bool> {
+        self.decisions
+            .get(&(span.span_context.trace_id(), span.parent_span_id))
+            .copied()
+    }
+
+    fn push(&mut self, span: SpanData) {
+        self.len += 1;
+        self.children
+            .entry((span.span_context.trace_id(), span.parent_span_id))
+            .or_default()
+            .push(span);
+    }
+
+    /// Take the buffered descendants of `span`
+    fn take_descendants(&mut self, span: &SpanData) -> Vec<SpanData> {
+        let trace_id = span.span_context.trace_id();
+        let mut descendants = Vec::new();
+        let mut parents = vec![span.span_context.span_id()];
+        while let Some(parent) = parents.pop() {
+            if let Some(children) = self.children.remove(&(trace_id, parent)) {
+                parents.extend(children.iter().map(|child| child.span_context.span_id()));
+                descendants.extend(children);
+            }
+        }
+        self.len -= descendants.len();
+        descendants
+    }
+
+    fn take_all(&mut self) -> Vec<SpanData> {
+        self.len = 0;
+        self.children.drain().flat_map(|(_, spans)| spans).collect()
+    }
+}
+
+impl<P: SpanProcessor> RecordingGateSpanProcessor<P> {
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            max_buffered_spans: DEFAULT_MAX_BUFFERED_SPANS,
+            buffer: Mutex::default(),
+        }
+    }
+
+    /// Define the maximum number of spans waiting for the decision of the gate
+    /// (default: [`DEFAULT_MAX_BUFFERED_SPANS`])
+    #[must_use]
+    pub fn with_max_buffered_spans(self, max_buffered_spans: usize) -> Self {
+        Self {
+            max_buffered_spans,
+            ..self
+        }
+    }
+
+    fn forward(&self, spans: Vec<SpanData>) {
+        for span in spans {
+            self.inner.on_end(span);
+        }
+    }
+
+    fn flush_buffer(&self) {
+        let spans = match self.buffer.lock() {
+            Ok(mut buffer) => buffer.take_all(),
+            Err(_) => return,
+        };
+        self.forward(spans);
+    }
+}
+
+fn take_latency_threshold(span: &mut SpanData) -> Option<Duration> {
+    let index = span
+        .attributes
+        .iter()
+        .position(|kv| kv.key.as_str() == RECORDING_GATE_ATTRIBUTE)?;
+    match span.attributes.remove(index).value {
+        Value::I64(ms) => Some(Duration::from_millis(u64::try_from(ms).unwrap_or_default())),
+        _ => None,
+    }
+}
+
+fn is_local_root(span: &SpanData) -> bool {
+    span.parent_span_id == SpanId::INVALID
+        || matches!(span.span_kind, SpanKind::Server | SpanKind::Consumer)
+}
+
+fn is_kept(span: &SpanData, latency_threshold: Duration) -> bool {
+    matches!(span.status, Status::Error { .. })
+        || span
+            .end_time
+            .duration_since(span.start_time)
+            .is_ok_and(|duration| duration >= latency_threshold)
+}
+
+impl<P: SpanProcessor> SpanProcessor for RecordingGateSpanProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        if cx.has_active_span() && cx.span().span_context().is_remote() {
+            let span_context = span.span_context();
+            if let Ok(mut buffer) = self.buffer.lock() {
+                buffer
+                    .remote_children
+                    .insert((span_context.trace_id(), span_context.span_id()));
+            }
+        }
+        self.inner.on_start(span, cx);
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        let latency_threshold = take_latency_threshold(&mut span);
+        let Ok(mut buffer) = self.buffer.lock() else {
+            self.inner.on_end(span);
+            return;
+        };
+        let trace_id = span.span_context.trace_id();
+        let has_remote_parent = buffer
+            .remote_children
+            .remove(&(trace_id, span.span_context.span_id()));
+        let is_root = latency_threshold.is_some() || has_remote_parent || is_local_root(&span);
+        // a late child (ended after its local root) follows the decision of its local root
+        let late_child_decision = if is_root {
+            None
+        } else {
+            buffer.decision_of_parent(&span)
+        };
+        if is_root || late_child_decision.is_some() {
+            let spans = buffer.take_descendants(&span);
+            let kept = late_child_decision.unwrap_or_else(|| {
+                latency_threshold.map_or(true, |threshold| is_kept(&span, threshold))
+            });
+            for decided in spans.iter().chain(std::iter::once(&span)) {
+                buffer.decide(
+                    trace_id,
+                    decided.span_context.span_id(),
+                    kept,
+                    self.max_buffered_spans,
+                );
+            }
+            drop(buffer);
+            if kept {
+                self.forward(spans);
+                self.inner.on_end(span);
+            }
+        } else if buffer.len >= self.max_buffered_spans {
+            let mut spans = buffer.take_all();
+            drop(buffer);
+            spans.push(span);
+            self.forward(spans);
+        } else {
+            buffer.push(span);
+        }
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        self.flush_buffer();
+        self.inner.force_flush()
+    }
+
+    fn shutdown(&self) -> TraceResult<()> {
+        self.flush_buffer();
+        self.inner.shutdown()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::trace::{
+        SpanContext, TraceContextExt, TraceFlags, TraceState, Tracer, TracerProvider as _,
+    };
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use opentelemetry_sdk::trace::{SimpleSpanProcessor, TracerProvider};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Status::Unset, Duration::ZERO, false)]
+    #[case(Status::error("boom"), Duration::ZERO, true)]
+    #[case(Status::Unset, Duration::from_millis(20), true)]
+    fn apply_gate_to_the_trace(
+        #[case] status: Status,
+        #[case] latency: Duration,
+        #[case] expected_kept: bool,
+    ) {
+        let exporter = InMemorySpanExporter::default();
+        let tracer_provider = TracerProvider::builder()
+            .with_span_processor(RecordingGateSpanProcessor::new(SimpleSpanProcessor::new(
+                Box::new(exporter.clone()),
+            )))
+            .build();
+        let tracer = tracer_provider.tracer("test");
+
+        let root = tracer
+            .span_builder("GET /users/{id}")
+            .with_kind(SpanKind::Server)
+            .with_attributes([KeyValue::new(RECORDING_GATE_ATTRIBUTE, 10)])
+            .start(&tracer);
+        let cx = Context::current_with_span(root);
+        tracer.start_with_context("SELECT", &cx).end();
+        std::thread::sleep(latency);
+        cx.span().set_status(status);
+        cx.span().end();
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        if expected_kept {
+            assert!(spans.len() == 2);
+            assert!(spans.iter().all(|s| s
+                .attributes
+                .iter()
+                .all(|kv| kv.key.as_str() != RECORDING_GATE_ATTRIBUTE)));
+        } else {
+            assert!(spans.is_empty());
+        }
+    }
+
+    #[test]
+    fn decide_each_local_root_of_the_trace() {
+        let exporter = InMemorySpanExporter::default();
+        let tracer_provider = TracerProvider::builder()
+            .with_span_processor(RecordingGateSpanProcessor::new(SimpleSpanProcessor::new(
+                Box::new(exporter.clone()),
+            )))
+            .build();
+        let tracer = tracer_provider.tracer("test");
+        // 2 requests of the same trace (same remote parent)
+        let remote_cx = Context::new().with_remote_span_context(SpanContext::new(
+            TraceId::from(42),
+            SpanId::from(7),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        ));
+        let start_request = |name: &'static str, gated: bool| {
+            let builder = tracer.span_builder(name).with_kind(SpanKind::Server);
+            let builder = if gated {
+                builder.with_attributes([KeyValue::new(RECORDING_GATE_ATTRIBUTE, 1000)])
+            } else {
+                builder
+            };
+            remote_cx.with_span(builder.start_with_context(&tracer, &remote_cx))
+        };
+        let fast = start_request("fast", true);
+        let kept = start_request("kept", false);
+        tracer.start_with_context("fast child", &fast).end();
+        tracer.start_with_context("kept child", &kept).end();
+        kept.span().end();
+        fast.span().end();
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        let mut names = spans.iter().map(|s| s.name.as_ref()).collect::<Vec<_>>();
+        names.sort_unstable();
+        assert!(names == vec!["kept", "kept child"]);
+    }
+
+    #[rstest]
+    #[case(Status::Unset, false)]
+    #[case(Status::error("boom"), true)]
+    fn apply_gate_to_the_late_children(#[case] status: Status, #[case] expected_kept: bool) {
+        let exporter = InMemorySpanExporter::default();
+        let tracer_provider = TracerProvider::builder()
+            .with_span_processor(RecordingGateSpanProcessor::new(SimpleSpanProcessor::new(
+                Box::new(exporter.clone()),
+            )))
+            .build();
+        let tracer = tracer_provider.tracer("test");
+
+        let root = tracer
+            .span_builder("GET /users/{id}")
+            .with_kind(SpanKind::Server)
+            .with_attributes([KeyValue::new(RECORDING_GATE_ATTRIBUTE, 1000)])
+            .start(&tracer);
+        let cx = Context::current_with_span(root);
+        // eg a task spawned by the request, ended after the response
+        let late_child = cx.with_span(tracer.start_with_context("late child", &cx));
+        cx.span().set_status(status);
+        cx.span().end();
+        tracer
+            .start_with_context("late grandchild", &late_child)
+            .end();
+        late_child.span().end();
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        if expected_kept {
+            assert!(spans.len() == 3);
+        } else {
+            assert!(spans.is_empty());
+        }
+        // nothing leaks in the buffer
+        assert!(tracer_provider.force_flush().iter().all(Result::is_ok));
+        assert!(exporter.get_finished_spans().map(|s| s.len()).ok() == Some(spans.len()));
+    }
+
+    #[test]
+    fn forward_the_local_root_with_a_remote_parent() {
+        let exporter = InMemorySpanExporter::default();
+        let tracer_provider = TracerProvider::builder()
+            .with_span_processor(RecordingGateSpanProcessor::new(SimpleSpanProcessor::new(
+                Box::new(exporter.clone()),
+            )))
+            .build();
+        let tracer = tracer_provider.tracer("test");
+        let remote_cx = Context::new().with_remote_span_context(SpanContext::new(
+            TraceId::from(42),
+            SpanId::from(7),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        ));
+
+        let job = remote_cx.with_span(
+            tracer
+                .span_builder("job")
+                .with_kind(SpanKind::Client)
+                .start_with_context(&tracer, &remote_cx),
+        );
+        tracer.start_with_context("step", &job).end();
+        job.span().end();
+
+        // forwarded at the end of the local root, not on flush
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        assert!(spans.len() == 2);
+    }
+
+    #[test]
+    fn forward_the_trace_without_gate() {
+        let exporter = InMemorySpanExporter::default();
+        let tracer_provider = TracerProvider::builder()
+            .with_span_processor(RecordingGateSpanProcessor::new(SimpleSpanProcessor::new(
+                Box::new(exporter.clone()),
+            )))
+            .build();
+        let tracer = tracer_provider.tracer("test");
+
+        tracer.in_span("job", |cx| {
+            tracer.start_with_context("step", &cx).end();
+            // the child is buffered until the end of the root
+            assert!(exporter.get_finished_spans().map(|s| s.len()).ok() == Some(0));
+        });
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        assert!(spans.len() == 2);
+    }
+
+    #[test]
+    fn forward_the_spans_when_the_buffer_is_full() {
+        let exporter = InMemorySpanExporter::default();
+        let tracer_provider = TracerProvider::builder()
+            .with_span_processor(
+                RecordingGateSpanProcessor::new(SimpleSpanProcessor::new(Box::new(
+                    exporter.clone(),
+                )))
+                .with_max_buffered_spans(1),
+            )
+            .build();
+        let tracer = tracer_provider.tracer("test");
+
+        let root = tracer.start("job");
+        let cx = Context::current_with_span(root);
+        tracer.start_with_context("step 1", &cx).end();
+        tracer.start_with_context("step 2", &cx).end();
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        assert!(spans.len() == 2);
+    }
+}
@@ -1,7 +1,8 @@
-use opentelemetry::KeyValue;
-// use opentelemetry_resource_detectors::OsResourceDetector;
+use opentelemetry::{Key, KeyValue};
+use opentelemetry_sdk::trace::{IdGenerator, RandomIdGenerator};
 use opentelemetry_sdk::{resource::ResourceDetector, Resource};
 use opentelemetry_semantic_conventions::resource;
+use std::sync::OnceLock;
 use std::time::Duration;
 
 /// To log detected value set environement variable `RUST_LOG="...,otel::setup::resource=debug"`
@@ -19,6 +20,14 @@ use std::time::Duration;
 pub struct DetectResource {
     fallback_service_name: Option<&'static str>,
     fallback_service_version: Option<&'static str>,
+    fallback_environment: Option<&'static str>,
+    service_instance_id: Option<String>,
+    #[cfg(feature = "detector_process")]
+    process_detector: bool,
+    #[cfg(feature = "detector_host")]
+    host_detector: bool,
+    #[cfg(feature = "community_detectors")]
+    community_detectors: bool,
 }
 
 impl DetectResource {
@@ -40,26 +49,159 @@ impl DetectResource {
         self
     }
 
+    /// `deployment.environment.name` is first extracted from `OTEL_RESOURCE_ATTRIBUTES`,
+    /// then from environment variables (in this order) `DEPLOYMENT_ENVIRONMENT`, `ENV`, `APP_ENV`.
+    /// But a default value can be provided with this method.
     #[must_use]
-    pub fn build(mut self) -> Resource {
-        let base = Resource::default();
-        let fallback = Resource::from_detectors(
-            Duration::from_secs(0),
-            vec![
-                Box::new(ServiceInfoDetector {
-                    fallback_service_name: self.fallback_service_name.take(),
-                    fallback_service_version: self.fallback_service_version.take(),
-                }),
-                //Box::new(OsResourceDetector), //FIXME enable when available for opentelemetry >= 0.25
-                //Box::new(ProcessResourceDetector),
-            ],
-        );
+    pub fn with_fallback_environment(mut self, fallback_environment: &'static str) -> Self {
+        self.fallback_environment = Some(fallback_environment);
+        self
+    }
+
+    /// `service.instance.id` is first extracted from
+    /// - `OTEL_RESOURCE_ATTRIBUTES`
+    /// - environment variables (in this order) `OTEL_SERVICE_INSTANCE_ID`, `POD_NAME` (eg set via the Kubernetes downward API)
+    /// - else a random UUID (v4) is generated at startup.
+    ///
+    /// But the value can be overridden with this method.
+    #[must_use]
+    pub fn with_service_instance_id(mut self, service_instance_id: impl Into<String>) -> Self {
+        self.service_instance_id = Some(service_instance_id.into());
+        self
+    }
+
+    /// Enable the detection of `process.pid`, `process.executable.name`,
+    /// `process.runtime.name` & `process.runtime.version` (see [`ProcessResourceDetector`]).
+    #[cfg(feature = "detector_process")]
+    #[must_use]
+    pub fn with_process_detector(mut self, enabled: bool) -> Self {
+        self.process_detector = enabled;
+        self
+    }
+
+    /// Enable the detection of `host.name` & `host.arch` (see [`HostResourceDetector`]).
+    #[cfg(feature = "detector_host")]
+    #[must_use]
+    pub fn with_host_detector(mut self, enabled: bool) -> Self {
+        self.host_detector = enabled;
+        self
+    }
+
+    /// Enable the detectors of the crate `opentelemetry-resource-detectors`:
+    /// `os.type`, `process.command_args` & `process.pid`, `host.id` & `host.arch`.
+    ///
+    /// Warning: `process.command_args` contains the arguments of the command line, do not enable it if they
+    /// contain secrets.
+    ///
+    /// The attributes also detected by the detectors of this crate (enabled with `with_process_detector`,
+    /// `with_host_detector`) keep the value of this crate.
+    #[cfg(feature = "community_detectors")]
+    #[must_use]
+    pub fn with_community_detectors(mut self, enabled: bool) -> Self {
+        self.community_detectors = enabled;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> Resource {
+        self.build_with(&Resource::default(), |name| std::env::var(name).ok())
+    }
+
+    /// Build the resource over `base` (the resource of the sdk, from `OTEL_RESOURCE_ATTRIBUTES`,...),
+    /// with `env` to read the environment variables
+    fn build_with(mut self, base: &Resource, env: impl Fn(&str) -> Option<String>) -> Resource {
+        let mut detectors: Vec<Box<dyn ResourceDetector>> = Vec::new();
+        // the first detectors have the lowest priority
+        #[cfg(feature = "community_detectors")]
+        if self.community_detectors {
+            detectors.push(Box::new(
+                opentelemetry_resource_detectors::OsResourceDetector,
+            ));
+            detectors.push(Box::new(
+                opentelemetry_resource_detectors::ProcessResourceDetector,
+            ));
+            detectors.push(Box::new(
+                opentelemetry_resource_detectors::HostResourceDetector::default(),
+            ));
+        }
+        let service_info = ServiceInfoDetector {
+            fallback_service_name: self.fallback_service_name.take(),
+            fallback_service_version: self.fallback_service_version.take(),
+            fallback_environment: self.fallback_environment.take(),
+        }
+        .detect_with(base, &env);
+        let service_instance_id = self.service_instance_id.take().or_else(|| {
+            base.get(Key::from_static_str(SERVICE_INSTANCE_ID))
+                .is_none()
+                .then(read_or_generate_service_instance_id)
+        });
+        if let Some(service_instance_id) = service_instance_id {
+            detectors.push(Box::new(ServiceInstanceIdDetector(service_instance_id)));
+        }
+        #[cfg(feature = "detector_process")]
+        if self.process_detector {
+            detectors.push(Box::new(ProcessResourceDetector));
+        }
+        #[cfg(feature = "detector_host")]
+        if self.host_detector {
+            detectors.push(Box::new(HostResourceDetector));
+        }
+        // the keys of `service_info` are not detected by the other detectors
+        let fallback =
+            Resource::from_detectors(Duration::from_secs(0), detectors).merge(&service_info);
         let rsrc = base.merge(&fallback); // base has lower priority
         debug_resource(&rsrc);
         rsrc
     }
 }
 
+// semantic conventions of `service.instance.id` is only available with the feature `semconv_experimental`
+const SERVICE_INSTANCE_ID: &str = "service.instance.id";
+// semantic conventions of `deployment.environment.name` is only available with the feature `semconv_experimental`
+const DEPLOYMENT_ENVIRONMENT_NAME: &str = "deployment.environment.name";
+
+fn read_or_generate_service_instance_id() -> String {
+    std::env::var("OTEL_SERVICE_INSTANCE_ID")
+        .or_else(|_| std::env::var("POD_NAME"))
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(generated_service_instance_id)
+}
+
+/// The generated `service.instance.id`: once per process, to be the same on every resource
+/// (eg of the tracer provider & of the meter provider).
+fn generated_service_instance_id() -> String {
+    static SERVICE_INSTANCE_ID: OnceLock<String> = OnceLock::new();
+    SERVICE_INSTANCE_ID.get_or_init(generate_uuid_v4).clone()
+}
+
+/// Generate a random UUID (v4), formatted as `xxxxxxxx-xxxx-4xxx-yxxx-xxxxxxxxxxxx`
+fn generate_uuid_v4() -> String {
+    let bytes = RandomIdGenerator::default().new_trace_id().to_bytes();
+    let mut v = u128::from_be_bytes(bytes);
+    // set the version (4) & the variant (RFC 4122)
+    v = (v & !(0xf << 76)) | (0x4 << 76);
+    v = (v & !(0x3 << 62)) | (0x2 << 62);
+    let hex = format!("{v:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[derive(Debug)]
+struct ServiceInstanceIdDetector(String);
+
+impl ResourceDetector for ServiceInstanceIdDetector {
+    fn detect(&self, _timeout: Duration) -> Resource {
+        Resource::new(vec![KeyValue::new(SERVICE_INSTANCE_ID, self.0.clone())])
+    }
+}
+
 pub fn debug_resource(rsrc: &Resource) {
     rsrc.iter().for_each(
         |kv| tracing::debug!(target: "otel::setup::resource", key = %kv.0, value = %kv.1),
@@ -70,27 +212,225 @@ pub fn debug_resource(rsrc: &Resource) {
 pub struct ServiceInfoDetector {
     fallback_service_name: Option<&'static str>,
     fallback_service_version: Option<&'static str>,
+    fallback_environment: Option<&'static str>,
 }
 
 impl ResourceDetector for ServiceInfoDetector {
     fn detect(&self, _timeout: Duration) -> Resource {
-        let service_name = std::env::var("OTEL_SERVICE_NAME")
-            .or_else(|_| std::env::var("SERVICE_NAME"))
-            .or_else(|_| std::env::var("APP_NAME"))
-            .ok()
+        self.detect_with(&Resource::empty(), |name| std::env::var(name).ok())
+    }
+}
+
+impl ServiceInfoDetector {
+    /// Detect the attributes from `env`, the `deployment.environment.name` already set into `base`
+    /// (eg via `OTEL_RESOURCE_ATTRIBUTES`) is kept.
+    fn detect_with(&self, base: &Resource, env: impl Fn(&str) -> Option<String>) -> Resource {
+        let service_name = env("OTEL_SERVICE_NAME")
+            .or_else(|| env("SERVICE_NAME"))
+            .or_else(|| env("APP_NAME"))
             .or_else(|| {
                 self.fallback_service_name
                     .map(std::string::ToString::to_string)
             })
             .map(|v| KeyValue::new(resource::SERVICE_NAME, v));
-        let service_version = std::env::var("SERVICE_VERSION")
-            .or_else(|_| std::env::var("APP_VERSION"))
-            .ok()
+        let service_version = env("SERVICE_VERSION")
+            .or_else(|| env("APP_VERSION"))
             .or_else(|| {
                 self.fallback_service_version
                     .map(std::string::ToString::to_string)
             })
             .map(|v| KeyValue::new(resource::SERVICE_VERSION, v));
-        Resource::new(vec![service_name, service_version].into_iter().flatten())
+        let environment = base
+            .get(Key::from_static_str(DEPLOYMENT_ENVIRONMENT_NAME))
+            .is_none()
+            .then(|| {
+                env("DEPLOYMENT_ENVIRONMENT")
+                    .or_else(|| env("ENV"))
+                    .or_else(|| env("APP_ENV"))
+                    .or_else(|| {
+                        self.fallback_environment
+                            .map(std::string::ToString::to_string)
+                    })
+            })
+            .flatten()
+            .map(|v| KeyValue::new(DEPLOYMENT_ENVIRONMENT_NAME, v));
+        Resource::new(
+            vec![service_name, service_version, environment]
+                .into_iter()
+                .flatten(),
+        )
+    }
+}
+
+/// Detect `process.pid`, `process.executable.name`, `process.runtime.name` (`rustc`)
+/// & `process.runtime.version` (version of rustc used to compile).
+#[cfg(feature = "detector_process")]
+#[derive(Debug)]
+pub struct ProcessResourceDetector;
+
+#[cfg(feature = "detector_process")]
+impl ResourceDetector for ProcessResourceDetector {
+    fn detect(&self, _timeout: Duration) -> Resource {
+        // semantic conventions of `process.*` are only available with the feature `semconv_experimental`
+        let executable_name = std::env::current_exe()
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+            .map(|v| KeyValue::new("process.executable.name", v));
+        let runtime_version = Some(env!("INIT_TRACING_RUSTC_VERSION"))
+            .filter(|v| !v.is_empty())
+            .map(|v| KeyValue::new("process.runtime.version", v));
+        Resource::new(
+            vec![
+                Some(KeyValue::new("process.pid", i64::from(std::process::id()))),
+                executable_name,
+                Some(KeyValue::new("process.runtime.name", "rustc")),
+                runtime_version,
+            ]
+            .into_iter()
+            .flatten(),
+        )
+    }
+}
+
+/// Detect `host.name` (from env `HOSTNAME`, fallback to `/etc/hostname`) & `host.arch`.
+#[cfg(feature = "detector_host")]
+#[derive(Debug)]
+pub struct HostResourceDetector;
+
+#[cfg(feature = "detector_host")]
+impl ResourceDetector for HostResourceDetector {
+    fn detect(&self, _timeout: Duration) -> Resource {
+        // semantic conventions of `host.*` are only available with the feature `semconv_experimental`
+        let host_name = std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(|v| KeyValue::new("host.name", v));
+        let host_arch = KeyValue::new("host.arch", host_arch(std::env::consts::ARCH));
+        Resource::new(vec![host_name, Some(host_arch)].into_iter().flatten())
+    }
+}
+
+/// Convert the rust name of the architecture into the value defined by the semantic conventions
+#[cfg(feature = "detector_host")]
+fn host_arch(arch: &str) -> &str {
+    match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "x86",
+        "arm" => "arm32",
+        "powerpc" => "ppc32",
+        "powerpc64" => "ppc64",
+        "s390x" => "s390x",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use opentelemetry::Value;
+    use rstest::rstest;
+
+    #[test]
+    fn generate_uuid_v4_format() {
+        let uuid = generate_uuid_v4();
+        assert!(uuid.len() == 36);
+        assert!(uuid.chars().nth(14) == Some('4'));
+        assert!(matches!(uuid.chars().nth(19), Some('8' | '9' | 'a' | 'b')));
+        assert!(uuid != generate_uuid_v4());
+    }
+
+    #[test]
+    fn same_service_instance_id_on_every_build() {
+        let key = Key::from_static_str(SERVICE_INSTANCE_ID);
+        let first = DetectResource::default().build().get(key.clone());
+        assert!(first.is_some());
+        assert!(first == DetectResource::default().build().get(key));
+        assert!(generated_service_instance_id() == generated_service_instance_id());
+    }
+
+    #[test]
+    fn override_service_instance_id() {
+        let rsrc = DetectResource::default()
+            .with_service_instance_id("my-instance")
+            .build();
+        assert!(rsrc.get(Key::from_static_str(SERVICE_INSTANCE_ID)) == Some("my-instance".into()));
+    }
+
+    fn environment_of(
+        base: &[(&'static str, &'static str)],
+        env: &[(&str, &str)],
+    ) -> Option<Value> {
+        let base = Resource::new(base.iter().map(|(k, v)| KeyValue::new(*k, *v)));
+        DetectResource::default()
+            .with_fallback_environment("fallback")
+            .build_with(&base, |name| {
+                env.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| (*v).to_string())
+            })
+            .get(Key::from_static_str(DEPLOYMENT_ENVIRONMENT_NAME))
+    }
+
+    #[rstest]
+    #[case(&[], &[], "fallback")]
+    #[case(&[], &[("APP_ENV", "dev")], "dev")]
+    #[case(&[], &[("ENV", "staging"), ("APP_ENV", "dev")], "staging")]
+    #[case(&[], &[("DEPLOYMENT_ENVIRONMENT", "production"), ("ENV", "staging")], "production")]
+    // the value of `OTEL_RESOURCE_ATTRIBUTES` wins over the generic variables & the fallback
+    #[case(&[(DEPLOYMENT_ENVIRONMENT_NAME, "canary")], &[("ENV", "staging")], "canary")]
+    #[case(&[(DEPLOYMENT_ENVIRONMENT_NAME, "canary")], &[], "canary")]
+    fn detect_environment(
+        #[case] base: &[(&'static str, &'static str)],
+        #[case] env: &[(&str, &str)],
+        #[case] expected: &'static str,
+    ) {
+        assert!(environment_of(base, env) == Some(expected.into()));
+    }
+
+    #[cfg(feature = "detector_process")]
+    #[test]
+    fn detect_process() {
+        let rsrc = DetectResource::default()
+            .with_process_detector(true)
+            .build();
+        assert!(rsrc.get("process.pid".into()) == Some(i64::from(std::process::id()).into()));
+        assert!(rsrc.get("process.runtime.name".into()) == Some("rustc".into()));
+        assert!(rsrc.get("process.runtime.version".into()).is_some());
+    }
+
+    #[cfg(feature = "detector_host")]
+    #[test]
+    fn detect_host_arch() {
+        let rsrc = DetectResource::default().with_host_detector(true).build();
+        assert!(rsrc.get("host.arch".into()).is_some());
+        assert!(host_arch("x86_64") == "amd64");
+        assert!(host_arch("riscv64") == "riscv64");
+    }
+
+    #[cfg(feature = "community_detectors")]
+    #[test]
+    fn detect_with_community_detectors() {
+        let rsrc = DetectResource::default().build();
+        assert!(rsrc.get("os.type".into()).is_none());
+        let rsrc = DetectResource::default()
+            .with_community_detectors(true)
+            .build();
+        assert!(rsrc.get("os.type".into()) == Some(std::env::consts::OS.into()));
+        assert!(rsrc.get("process.pid".into()) == Some(i64::from(std::process::id()).into()));
+        assert!(rsrc.get("host.arch".into()).is_some());
+    }
+
+    #[cfg(all(feature = "community_detectors", feature = "detector_host"))]
+    #[test]
+    fn detectors_of_this_crate_have_priority_over_community_detectors() {
+        let rsrc = DetectResource::default()
+            .with_community_detectors(true)
+            .with_host_detector(true)
+            .build();
+        assert!(rsrc.get("host.arch".into()) == Some(host_arch(std::env::consts::ARCH).into()));
     }
 }
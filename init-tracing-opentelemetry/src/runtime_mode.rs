@@ -0,0 +1,146 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Error;
+
+/// The async runtime used by the exporters (batch processing, network calls).
+///
+/// ```rust,ignore
+/// // in a CLI, without `#[tokio::main]`
+/// let config = TracingConfig::default().with_runtime(RuntimeMode::OwnThread);
+/// let _guard = init_subscribers_with_config(&config)?;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RuntimeMode {
+    /// the Tokio runtime of the application (the setup should be called inside the runtime, eg `#[tokio::main]`)
+    #[default]
+    Tokio,
+    /// a runtime dedicated to the telemetry, on its own background thread (`otel-runtime`),
+    /// for the applications without Tokio runtime (eg CLI) (require feature `own_thread_runtime`)
+    OwnThread,
+}
+
+impl RuntimeMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            RuntimeMode::Tokio => "tokio",
+            RuntimeMode::OwnThread => "own_thread",
+        }
+    }
+
+    /// Enter the runtime (if not the one of the application), until the guard is dropped:
+    /// the tasks (batch processor, network clients) spawned by the setup of the exporters run on it.
+    #[cfg(feature = "otlp")]
+    pub(crate) fn enter(self) -> Result<RuntimeGuard, Error> {
+        match self {
+            RuntimeMode::Tokio => Ok(RuntimeGuard::default()),
+            #[cfg(feature = "own_thread_runtime")]
+            RuntimeMode::OwnThread => Ok(RuntimeGuard {
+                _enter: Some(own_thread_runtime()?.enter()),
+            }),
+            #[cfg(not(feature = "own_thread_runtime"))]
+            RuntimeMode::OwnThread => Err(Error::UnsupportedRuntime {
+                name: self.to_string(),
+                required_feature: Some("own_thread_runtime"),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+#[derive(Default)]
+pub(crate) struct RuntimeGuard {
+    #[cfg(feature = "own_thread_runtime")]
+    _enter: Option<tokio::runtime::EnterGuard<'static>>,
+}
+
+/// The runtime is created once and lives until the end of the process
+/// (the spans are flushed by the `TracingGuard` from the thread of the application).
+#[cfg(all(feature = "otlp", feature = "own_thread_runtime"))]
+fn own_thread_runtime() -> Result<&'static tokio::runtime::Runtime, Error> {
+    static RUNTIME: std::sync::OnceLock<tokio::runtime::Runtime> = std::sync::OnceLock::new();
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("otel-runtime")
+        .enable_all()
+        .build()?;
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
+impl fmt::Display for RuntimeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RuntimeMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "tokio" => Ok(RuntimeMode::Tokio),
+            "own_thread" => Ok(RuntimeMode::OwnThread),
+            _ => Err(Error::InvalidConfig(format!(
+                "unsupported runtime '{s}', expected 'tokio' or 'own_thread'"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("tokio", RuntimeMode::Tokio)]
+    #[case(" Own_Thread ", RuntimeMode::OwnThread)]
+    fn parse_runtime_mode(#[case] input: &str, #[case] expected: RuntimeMode) {
+        let_assert!(Ok(runtime_mode) = input.parse::<RuntimeMode>());
+        assert!(runtime_mode == expected);
+        assert!(runtime_mode.to_string().parse::<RuntimeMode>().ok() == Some(expected));
+    }
+
+    #[test]
+    fn parse_invalid_runtime_mode() {
+        let_assert!(Err(Error::InvalidConfig(_)) = "async-std".parse::<RuntimeMode>());
+    }
+
+    #[cfg(all(feature = "otlp", not(feature = "own_thread_runtime")))]
+    #[test]
+    fn own_thread_requires_feature() {
+        let_assert!(
+            Err(Error::UnsupportedRuntime {
+                required_feature: Some("own_thread_runtime"),
+                ..
+            }) = RuntimeMode::OwnThread.enter()
+        );
+    }
+
+    #[cfg(all(feature = "otlp", feature = "own_thread_runtime"))]
+    #[test]
+    fn export_without_tokio_runtime_of_the_application() {
+        use opentelemetry::trace::{Tracer, TracerProvider as _};
+        use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+        use opentelemetry_sdk::trace::TracerProvider;
+
+        assert!(tokio::runtime::Handle::try_current().is_err());
+        let exporter = InMemorySpanExporter::default();
+        let provider = {
+            let_assert!(Ok(_runtime) = RuntimeMode::OwnThread.enter());
+            TracerProvider::builder()
+                .with_batch_exporter(exporter.clone(), opentelemetry_sdk::runtime::Tokio)
+                .build()
+        };
+        assert!(tokio::runtime::Handle::try_current().is_err());
+        provider.tracer("test").in_span("cli", |_| {});
+        let flushed: Result<(), _> = provider.force_flush().into_iter().collect();
+        assert!(flushed.is_ok());
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        assert!(spans.len() == 1);
+    }
+}
@@ -0,0 +1,191 @@
+use futures_core::future::BoxFuture;
+use opentelemetry::metrics::{Counter, Meter, MeterProvider};
+use opentelemetry::trace::{Link, SamplingDecision, SamplingResult, Span as _, SpanKind, TraceId};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry_sdk::trace::{ShouldSample, Span, SpanProcessor};
+use opentelemetry_sdk::Resource;
+use tracing_opentelemetry_instrumentation_sdk::DroppedSpanHook;
+
+/// Name of the meter used to register the self-metrics
+pub const METER_NAME: &str = "init-tracing-opentelemetry";
+
+/// The meter of the self-metrics on the `meter_provider`.
+///
+/// The instruments are bound to the meter provider of the meter when they are created, so use the meter provider
+/// that exports the metrics (not the global one before its registration).
+pub fn meter(meter_provider: &(impl MeterProvider + ?Sized)) -> Meter {
+    meter_provider.meter(METER_NAME)
+}
+
+/// The counter of the spans dropped before the export (`otel.sdk.span.dropped` with attribute `reason`):
+/// `queue_full` (see [`QueueCapSpanProcessor`](crate::QueueCapSpanProcessor)), `sampler` (see
+/// [`SelfMetricsSampler`]), `filter` & `sampling` (by the middlewares, see [`dropped_span_hook`]).
+pub(crate) fn dropped_spans_counter(meter: &Meter) -> Counter<u64> {
+    meter
+        .u64_counter("otel.sdk.span.dropped")
+        .with_description("number of spans dropped before the export")
+        .build()
+}
+
+/// The hook to count the spans not created by the middlewares (rejected by their `filter` or their sampling)
+/// into `otel.sdk.span.dropped` of the `meter`, to define on the layers (eg `OtelAxumLayer::with_dropped_span_hook`).
+///
+/// ```rust
+/// use init_tracing_opentelemetry::self_metrics;
+///
+/// // after the setup (the global meter provider exports the metrics)
+/// let meter = self_metrics::meter(&*opentelemetry::global::meter_provider());
+/// let hook = self_metrics::dropped_span_hook(&meter);
+/// ```
+#[must_use]
+pub fn dropped_span_hook(meter: &Meter) -> DroppedSpanHook {
+    let counter = dropped_spans_counter(meter);
+    DroppedSpanHook::new(move |reason| {
+        counter.add(1, &[KeyValue::new("reason", reason.as_str())]);
+    })
+}
+
+/// Wrap the sampler of the tracer provider to count the spans dropped by the sampler
+/// (`otel.sdk.span.dropped` with attribute `reason` = `sampler`), eg the children of an unsampled parent.
+#[derive(Debug, Clone)]
+pub struct SelfMetricsSampler {
+    inner: Box<dyn ShouldSample>,
+    dropped: Counter<u64>,
+}
+
+impl SelfMetricsSampler {
+    #[must_use]
+    pub fn new(inner: Box<dyn ShouldSample>, meter: &Meter) -> Self {
+        Self {
+            inner,
+            dropped: dropped_spans_counter(meter),
+        }
+    }
+}
+
+impl ShouldSample for SelfMetricsSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        let result =
+            self.inner
+                .should_sample(parent_context, trace_id, name, span_kind, attributes, links);
+        if result.decision == SamplingDecision::Drop {
+            self.dropped.add(1, &[KeyValue::new("reason", "sampler")]);
+        }
+        result
+    }
+}
+
+/// `SpanProcessor` that counts the spans started & ended
+/// (`otel.sdk.span.started`, `otel.sdk.span.ended` with attribute `sampled`).
+///
+/// The spans dropped by the sampler are not counted (not recorded), they are counted by [`SelfMetricsSampler`].
+#[derive(Debug)]
+pub struct SelfMetricsSpanProcessor {
+    started: Counter<u64>,
+    ended: Counter<u64>,
+}
+
+impl SelfMetricsSpanProcessor {
+    #[must_use]
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            started: meter
+                .u64_counter("otel.sdk.span.started")
+                .with_description("number of spans started (recorded)")
+                .build(),
+            ended: meter
+                .u64_counter("otel.sdk.span.ended")
+                .with_description("number of spans ended (recorded)")
+                .build(),
+        }
+    }
+}
+
+impl SpanProcessor for SelfMetricsSpanProcessor {
+    fn on_start(&self, span: &mut Span, _cx: &Context) {
+        // the processors are also called for the spans dropped by the sampler
+        if span.is_recording() {
+            self.started.add(1, &[]);
+        }
+    }
+
+    fn on_end(&self, span: SpanData) {
+        self.ended.add(
+            1,
+            &[KeyValue::new("sampled", span.span_context.is_sampled())],
+        );
+    }
+
+    fn force_flush(&self) -> opentelemetry::trace::TraceResult<()> {
+        Ok(())
+    }
+
+    fn shutdown(&self) -> opentelemetry::trace::TraceResult<()> {
+        Ok(())
+    }
+}
+
+/// Wrap a `SpanExporter` to count the batches & spans exported
+/// (`otel.sdk.exporter.batch`, `otel.sdk.exporter.span` with attribute `outcome` = `success` | `failure`).
+#[derive(Debug)]
+pub struct SelfMetricsExporter<E> {
+    inner: E,
+    batches: Counter<u64>,
+    spans: Counter<u64>,
+}
+
+impl<E> SelfMetricsExporter<E> {
+    pub fn new(inner: E, meter: &Meter) -> Self {
+        Self {
+            inner,
+            batches: meter
+                .u64_counter("otel.sdk.exporter.batch")
+                .with_description("number of batches of spans exported")
+                .build(),
+            spans: meter
+                .u64_counter("otel.sdk.exporter.span")
+                .with_description("number of spans exported")
+                .build(),
+        }
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for SelfMetricsExporter<E> {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        let batches = self.batches.clone();
+        let spans = self.spans.clone();
+        let count = batch.len() as u64;
+        let export = self.inner.export(batch);
+        Box::pin(async move {
+            let result = export.await;
+            let attributes = [KeyValue::new(
+                "outcome",
+                if result.is_ok() { "success" } else { "failure" },
+            )];
+            batches.add(1, &attributes);
+            spans.add(count, &attributes);
+            result
+        })
+    }
+
+    fn shutdown(&mut self) {
+        self.inner.shutdown();
+    }
+
+    fn force_flush(&mut self) -> BoxFuture<'static, ExportResult> {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
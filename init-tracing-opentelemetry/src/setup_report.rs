@@ -0,0 +1,54 @@
+use std::time::{Duration, Instant};
+
+/// The duration of the steps of the setup, to diagnose a slow startup (eg a resource detector or a DNS resolution
+/// during the construction of the exporter).
+///
+/// Each step is also traced by a span `setup` (with the field `step`) and an event with its duration, at level
+/// `debug` on target `otel::setup`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SetupReport {
+    /// detection of the resource (service, host, process,... attributes)
+    pub resource_detection: Duration,
+    /// construction of the tracer provider (including the exporter of the spans)
+    pub tracer_provider_build: Duration,
+    /// initialization of the propagators (from `OTEL_PROPAGATORS`)
+    pub propagator_init: Duration,
+    /// construction of the meter provider (with the feature `metrics`)
+    pub meter_provider_build: Option<Duration>,
+    /// the whole setup
+    pub total: Duration,
+}
+
+impl SetupReport {
+    /// Log the report at level `debug` on target `otel::setup`
+    pub fn log(&self) {
+        tracing::debug!(target: "otel::setup", setup_report = ?self);
+    }
+}
+
+/// Run the `step` of the setup into a span `setup`, and return its result with its duration
+pub(crate) fn timed<T>(step: &'static str, f: impl FnOnce() -> T) -> (T, Duration) {
+    let span = tracing::debug_span!(target: "otel::setup", "setup", step);
+    let start = Instant::now();
+    let result = span.in_scope(f);
+    let duration = start.elapsed();
+    tracing::debug!(target: "otel::setup", parent: &span, step, ?duration, "setup step done");
+    (result, duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn time_the_step() {
+        let (result, duration) = timed("sleep", || {
+            std::thread::sleep(Duration::from_millis(10));
+            42
+        });
+        assert!(result == 42);
+        assert!(duration >= Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,125 @@
+//! Integrate the shutdown of the telemetry with the graceful shutdown of a server (eg `axum::serve`),
+//! in the right order: stop accepting requests, complete the pending requests, flush the spans (with a timeout), exit.
+//!
+//! ```rust,ignore
+//! use init_tracing_opentelemetry::shutdown::{shutdown_signal, with_telemetry_shutdown};
+//!
+//! let guard = init_tracing_opentelemetry::tracing_subscriber_ext::init_subscribers()?;
+//! let (signal, telemetry) = with_telemetry_shutdown(shutdown_signal(), guard);
+//! axum::serve(listener, app.into_make_service())
+//!     .with_graceful_shutdown(signal)
+//!     .await?;
+//! telemetry.shutdown()?;
+//! ```
+use std::future::Future;
+use std::time::Duration;
+
+use crate::tracing_subscriber_ext::TracingGuard;
+use crate::Error;
+
+/// Default maximum duration to flush the telemetry on shutdown
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Wait for `Ctrl+C` or (on unix) `SIGTERM` (the signal sent by kubernetes, docker,... to stop the container).
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::warn!(target: "otel::setup", error = %err, "failed to listen for Ctrl+C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                tracing::warn!(target: "otel::setup", error = %err, "failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+}
+
+/// Combine the shutdown `signal` with the shutdown of the telemetry held by the `guard`:
+///
+/// - the returned future completes when the `signal` is received (to pass to the graceful shutdown of the server)
+/// - the returned [`TelemetryShutdown`] should be called once the server is stopped (the pending requests completed)
+///   to flush the spans and shut down the providers (it is also done on drop, but without timeout)
+pub fn with_telemetry_shutdown<F>(
+    signal: F,
+    guard: TracingGuard,
+) -> (impl Future<Output = ()>, TelemetryShutdown)
+where
+    F: Future<Output = ()>,
+{
+    let signal = async move {
+        signal.await;
+        tracing::info!(target: "otel::setup", "shutdown signal received, stop accepting requests");
+    };
+    (
+        signal,
+        TelemetryShutdown {
+            guard,
+            timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        },
+    )
+}
+
+/// The shutdown of the telemetry, see [`with_telemetry_shutdown`]
+#[must_use = "call `shutdown()` after the graceful shutdown of the server to flush the telemetry"]
+pub struct TelemetryShutdown {
+    guard: TracingGuard,
+    timeout: Duration,
+}
+
+impl TelemetryShutdown {
+    /// Define the maximum duration to flush the telemetry (default: [`DEFAULT_SHUTDOWN_TIMEOUT`])
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Flush the pending spans and shut down the providers (see [`TracingGuard::shutdown`])
+    pub fn shutdown(self) -> Result<(), Error> {
+        tracing::info!(target: "otel::setup", timeout = ?self.timeout, "flush & shutdown the telemetry");
+        self.guard.shutdown(self.timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExporterHealth, HealthRecordingExporter};
+    use assert2::{assert, let_assert};
+    use opentelemetry::trace::{Tracer, TracerProvider as _};
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn flush_the_spans_after_the_signal() {
+        let health = ExporterHealth::default();
+        let tracer_provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_batch_exporter(
+                HealthRecordingExporter::new(InMemorySpanExporter::default(), health.clone()),
+                opentelemetry_sdk::runtime::Tokio,
+            )
+            .build();
+        tracer_provider.tracer("test").in_span("request", |_cx| {});
+        let guard = TracingGuard::from_tracerprovider(tracer_provider);
+
+        let (signal, telemetry) = with_telemetry_shutdown(async {}, guard);
+        signal.await;
+        assert!(health.status().last_success.is_none());
+        let_assert!(Ok(()) = telemetry.with_timeout(Duration::from_secs(1)).shutdown());
+        assert!(health.status().last_success.is_some());
+    }
+}
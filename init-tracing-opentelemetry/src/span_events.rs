@@ -0,0 +1,218 @@
+use tracing::Level;
+
+/// Which tracing's events (`tracing::event!`, `debug!`,...) are recorded as span events by the `OpenTelemetryLayer`,
+/// to not bloat the exported spans with noisy events (the other layers, eg the logs, are not impacted).
+///
+/// The selection is applied by filtering the events on the layer:
+///
+/// ```rust,ignore
+/// let span_events = SpanEventsConfig::default()
+///     .with_max_level(tracing::Level::INFO)
+///     .with_target_allowlist(["my_app", "axum_tracing_opentelemetry"])
+///     .with_max_events_per_span(64);
+/// let subscriber = tracing_subscriber::registry()
+///     .with(otel_layer.with_filter(span_events.filter()));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanEventsConfig {
+    /// the most verbose level recorded (eg `INFO` to drop the `DEBUG` & `TRACE` events), default: all the levels
+    pub max_level: Option<Level>,
+    /// the targets (and their sub-modules) recorded, default: all the targets
+    pub target_allowlist: Vec<String>,
+    /// the maximum number of events recorded per span (the following ones are dropped), default: unlimited
+    pub max_events_per_span: Option<usize>,
+}
+
+impl SpanEventsConfig {
+    #[must_use]
+    pub fn with_max_level(self, max_level: Level) -> Self {
+        SpanEventsConfig {
+            max_level: Some(max_level),
+            ..self
+        }
+    }
+
+    /// Record only the events of the `targets` (eg `my_app` for `my_app` & `my_app::handlers`)
+    #[must_use]
+    pub fn with_target_allowlist<T>(self, targets: impl IntoIterator<Item = T>) -> Self
+    where
+        T: Into<String>,
+    {
+        SpanEventsConfig {
+            target_allowlist: targets.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_max_events_per_span(self, max_events_per_span: usize) -> Self {
+        SpanEventsConfig {
+            max_events_per_span: Some(max_events_per_span),
+            ..self
+        }
+    }
+
+    /// `true` if the events of the callsite (level & target) are recorded
+    #[must_use]
+    pub fn is_recorded(&self, level: Level, target: &str) -> bool {
+        // `Level` is "greater" when more verbose
+        self.max_level.map_or(true, |max_level| level <= max_level)
+            && (self.target_allowlist.is_empty()
+                || self
+                    .target_allowlist
+                    .iter()
+                    .any(|allowed| is_same_or_sub_target(target, allowed)))
+    }
+}
+
+fn is_same_or_sub_target(target: &str, allowed: &str) -> bool {
+    target
+        .strip_prefix(allowed)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+#[cfg(any(feature = "logs_bridge", feature = "tracing_subscriber_ext"))]
+mod filter {
+    use tracing::{subscriber::Interest, Event, Metadata, Subscriber};
+    use tracing_subscriber::layer::{Context, Filter};
+    use tracing_subscriber::registry::LookupSpan;
+
+    use super::SpanEventsConfig;
+
+    impl SpanEventsConfig {
+        /// The filter for the `OpenTelemetryLayer` (the spans are always enabled)
+        #[must_use]
+        pub fn filter(&self) -> SpanEventsFilter {
+            SpanEventsFilter {
+                config: self.clone(),
+            }
+        }
+    }
+
+    /// The filter built by [`SpanEventsConfig::filter`]
+    #[derive(Debug, Clone)]
+    pub struct SpanEventsFilter {
+        config: SpanEventsConfig,
+    }
+
+    /// The number of events seen on the span (stored into the extensions of the span)
+    struct SpanEventsCount(usize);
+
+    impl<S> Filter<S> for SpanEventsFilter
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn enabled(&self, metadata: &Metadata<'_>, _cx: &Context<'_, S>) -> bool {
+            metadata.is_span()
+                || self
+                    .config
+                    .is_recorded(*metadata.level(), metadata.target())
+        }
+
+        fn callsite_enabled(&self, metadata: &'static Metadata<'static>) -> Interest {
+            if metadata.is_span() {
+                Interest::always()
+            } else if !self
+                .config
+                .is_recorded(*metadata.level(), metadata.target())
+            {
+                Interest::never()
+            } else if self.config.max_events_per_span.is_some() {
+                // the count depends on the span of the event
+                Interest::sometimes()
+            } else {
+                Interest::always()
+            }
+        }
+
+        fn event_enabled(&self, event: &Event<'_>, cx: &Context<'_, S>) -> bool {
+            let Some(max_events_per_span) = self.config.max_events_per_span else {
+                return true;
+            };
+            let Some(span) = cx.event_span(event) else {
+                return true;
+            };
+            let mut extensions = span.extensions_mut();
+            let count = if let Some(count) = extensions.get_mut::<SpanEventsCount>() {
+                count.0 += 1;
+                count.0
+            } else {
+                extensions.insert(SpanEventsCount(1));
+                1
+            };
+            count <= max_events_per_span
+        }
+    }
+}
+
+#[cfg(any(feature = "logs_bridge", feature = "tracing_subscriber_ext"))]
+pub use filter::SpanEventsFilter;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Level::INFO, "my_app", true)]
+    #[case(Level::WARN, "my_app::handlers", true)]
+    #[case(Level::DEBUG, "my_app", false)]
+    #[case(Level::INFO, "my_application", false)]
+    #[case(Level::ERROR, "hyper", false)]
+    fn test_is_recorded(#[case] level: Level, #[case] target: &str, #[case] expected: bool) {
+        let config = SpanEventsConfig::default()
+            .with_max_level(Level::INFO)
+            .with_target_allowlist(["my_app"]);
+        assert!(config.is_recorded(level, target) == expected);
+    }
+
+    #[test]
+    fn record_everything_by_default() {
+        assert!(SpanEventsConfig::default().is_recorded(Level::TRACE, "hyper"));
+    }
+
+    #[cfg(feature = "tracing_subscriber_ext")]
+    #[test]
+    fn drop_the_events_over_the_limit() {
+        use assert2::let_assert;
+        use opentelemetry::trace::TracerProvider as _;
+        use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+        use tracing_subscriber::layer::SubscriberExt;
+        use tracing_subscriber::Layer;
+
+        let exporter = InMemorySpanExporter::default();
+        let tracer_provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let config = SpanEventsConfig::default()
+            .with_max_level(Level::INFO)
+            .with_max_events_per_span(2);
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_opentelemetry::layer()
+                .with_tracer(tracer_provider.tracer("test"))
+                .with_filter(config.filter()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request").in_scope(|| {
+                tracing::debug!("too verbose");
+                for i in 0..5 {
+                    tracing::info!(i, "step");
+                }
+            });
+            tracing::info_span!("other").in_scope(|| {
+                tracing::info!("step");
+            });
+        });
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        assert!(spans.len() == 2);
+        let events = spans
+            .iter()
+            .map(|span| (span.name.as_ref(), span.events.len()))
+            .collect::<Vec<_>>();
+        assert!(events.contains(&("request", 2)));
+        assert!(events.contains(&("other", 1)));
+    }
+}
@@ -0,0 +1,199 @@
+//! Generate RED metrics (rate, errors, duration) from the finished server spans,
+//! like the [spanmetrics connector](https://github.com/open-telemetry/opentelemetry-collector-contrib/tree/main/connector/spanmetricsconnector)
+//! of the collector, for deployments without collector.
+//!
+//! ```rust,ignore
+//! // register the meter provider before the creation of the processor
+//! opentelemetry::global::set_meter_provider(meter_provider);
+//! let tracer_provider = TracerProvider::builder()
+//!     .with_span_processor(SpanMetricsSpanProcessor::default())
+//!     .with_batch_exporter(exporter, runtime::Tokio)
+//!     .build();
+//! ```
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::trace::{SpanKind, Status};
+use opentelemetry::{Context, Key, KeyValue, Value};
+use opentelemetry_sdk::export::trace::SpanData;
+use opentelemetry_sdk::trace::{Span, SpanProcessor};
+use opentelemetry_sdk::Resource;
+
+/// Name of the meter used to register the span metrics
+pub const METER_NAME: &str = "init-tracing-opentelemetry";
+
+/// The attributes of the span copied (if present) as dimensions of the metrics
+const DIMENSION_KEYS: &[&str] = &[
+    "http.request.method",
+    "http.route",
+    "http.response.status_code",
+    "rpc.system",
+    "rpc.service",
+    "rpc.method",
+    "rpc.grpc.status_code",
+];
+
+/// `SpanProcessor` that records, for every finished server span (`SpanKind::Server`):
+///
+/// - `traces.span.metrics.calls` (counter): the number of requests, the errors have the dimension `status.code` = `STATUS_CODE_ERROR`
+/// - `traces.span.metrics.duration` (histogram, in seconds): the duration of the requests
+///
+/// The dimensions are `service.name`, `span.name`, `status.code` and the http/rpc attributes
+/// (`http.request.method`, `http.route`, `http.response.status_code`, `rpc.service`, `rpc.method`,...)
+/// when they are defined on the span.
+///
+/// The instruments are created from the global meter provider by `default()`,
+/// so the meter provider should be registered before (or use [`SpanMetricsSpanProcessor::new`]).
+#[derive(Debug)]
+pub struct SpanMetricsSpanProcessor {
+    calls: Counter<u64>,
+    duration: Histogram<f64>,
+    service_name: Option<Value>,
+}
+
+impl Default for SpanMetricsSpanProcessor {
+    fn default() -> Self {
+        Self::new(&opentelemetry::global::meter(METER_NAME))
+    }
+}
+
+impl SpanMetricsSpanProcessor {
+    #[must_use]
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            calls: meter
+                .u64_counter("traces.span.metrics.calls")
+                .with_description("number of server spans (requests)")
+                .build(),
+            duration: meter
+                .f64_histogram("traces.span.metrics.duration")
+                .with_description("duration of the server spans (requests)")
+                .with_unit("s")
+                .build(),
+            service_name: None,
+        }
+    }
+
+    fn dimensions(&self, span: &SpanData) -> Vec<KeyValue> {
+        let mut dimensions = Vec::with_capacity(DIMENSION_KEYS.len() + 3);
+        if let Some(service_name) = &self.service_name {
+            dimensions.push(KeyValue::new("service.name", service_name.clone()));
+        }
+        dimensions.push(KeyValue::new("span.name", span.name.clone()));
+        dimensions.push(KeyValue::new("status.code", status_code(&span.status)));
+        dimensions.extend(
+            span.attributes
+                .iter()
+                .filter(|kv| DIMENSION_KEYS.contains(&kv.key.as_str()))
+                .cloned(),
+        );
+        dimensions
+    }
+}
+
+fn status_code(status: &Status) -> &'static str {
+    match status {
+        Status::Unset => "STATUS_CODE_UNSET",
+        Status::Error { .. } => "STATUS_CODE_ERROR",
+        Status::Ok => "STATUS_CODE_OK",
+    }
+}
+
+impl SpanProcessor for SpanMetricsSpanProcessor {
+    fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+    fn on_end(&self, span: SpanData) {
+        if span.span_kind != SpanKind::Server {
+            return;
+        }
+        let dimensions = self.dimensions(&span);
+        self.calls.add(1, &dimensions);
+        if let Ok(duration) = span.end_time.duration_since(span.start_time) {
+            self.duration.record(duration.as_secs_f64(), &dimensions);
+        }
+    }
+
+    fn force_flush(&self) -> opentelemetry::trace::TraceResult<()> {
+        Ok(())
+    }
+
+    fn shutdown(&self) -> opentelemetry::trace::TraceResult<()> {
+        Ok(())
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.service_name = resource.get(Key::from_static_str("service.name"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::metrics::MeterProvider as _;
+    use opentelemetry::trace::{Tracer, TracerProvider as _};
+    use opentelemetry_sdk::metrics::{data, PeriodicReader, SdkMeterProvider};
+    use opentelemetry_sdk::testing::metrics::InMemoryMetricExporter;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn record_metrics_of_server_spans() {
+        let exporter = InMemoryMetricExporter::default();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(
+                PeriodicReader::builder(exporter.clone(), opentelemetry_sdk::runtime::Tokio)
+                    .build(),
+            )
+            .build();
+        let tracer_provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_span_processor(SpanMetricsSpanProcessor::new(&meter_provider.meter("test")))
+            .with_resource(Resource::new([KeyValue::new("service.name", "my-service")]))
+            .build();
+        let tracer = tracer_provider.tracer("test");
+        for status in [Status::Unset, Status::error("boom")] {
+            tracer
+                .span_builder("GET /users/{id}")
+                .with_kind(SpanKind::Server)
+                .with_attributes([KeyValue::new("http.route", "/users/{id}")])
+                .with_status(status)
+                .start(&tracer);
+        }
+        tracer
+            .span_builder("SELECT")
+            .with_kind(SpanKind::Client)
+            .start(&tracer);
+        let_assert!(Ok(()) = meter_provider.force_flush());
+
+        let_assert!(Ok(metrics) = exporter.get_finished_metrics());
+        let_assert!(Some(resource_metrics) = metrics.last());
+        let metrics = &resource_metrics.scope_metrics[0].metrics;
+        let_assert!(
+            Some(calls) = metrics
+                .iter()
+                .find(|m| m.name == "traces.span.metrics.calls")
+        );
+        let_assert!(Some(calls) = calls.data.as_any().downcast_ref::<data::Sum<u64>>());
+        assert!(calls.data_points.len() == 2);
+        for data_point in &calls.data_points {
+            assert!(data_point.value == 1);
+            assert!(data_point
+                .attributes
+                .contains(&KeyValue::new("service.name", "my-service")));
+            assert!(data_point
+                .attributes
+                .contains(&KeyValue::new("http.route", "/users/{id}")));
+        }
+        assert!(calls.data_points.iter().any(|dp| dp
+            .attributes
+            .contains(&KeyValue::new("status.code", "STATUS_CODE_ERROR"))));
+        let_assert!(
+            Some(duration) = metrics
+                .iter()
+                .find(|m| m.name == "traces.span.metrics.duration")
+        );
+        let_assert!(
+            Some(duration) = duration
+                .data
+                .as_any()
+                .downcast_ref::<data::Histogram<f64>>()
+        );
+        assert!(duration.data_points.iter().map(|dp| dp.count).sum::<u64>() == 2);
+    }
+}
@@ -0,0 +1,71 @@
+use futures_core::future::BoxFuture;
+use opentelemetry::trace::FutureExt;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use tracing_opentelemetry_instrumentation_sdk::suppressed_instrumentation_context;
+
+/// Wrap a `SpanExporter` to suppress the instrumentation during the export,
+/// so the requests of the exporter (http, grpc) are not traced by the instrumented clients
+/// (and don't create a feedback loop).
+#[derive(Debug)]
+pub struct SuppressInstrumentationExporter<E> {
+    inner: E,
+}
+
+impl<E> SuppressInstrumentationExporter<E> {
+    pub fn new(inner: E) -> Self {
+        Self { inner }
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for SuppressInstrumentationExporter<E> {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        let context = suppressed_instrumentation_context();
+        let _guard = context.clone().attach();
+        Box::pin(self.inner.export(batch).with_context(context))
+    }
+
+    fn shutdown(&mut self) {
+        let _guard = suppressed_instrumentation_context().attach();
+        self.inner.shutdown();
+    }
+
+    fn force_flush(&mut self) -> BoxFuture<'static, ExportResult> {
+        let context = suppressed_instrumentation_context();
+        let _guard = context.clone().attach();
+        Box::pin(self.inner.force_flush().with_context(context))
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use tracing_opentelemetry_instrumentation_sdk::is_instrumentation_suppressed;
+
+    #[derive(Debug, Default)]
+    struct CheckSuppressedExporter;
+
+    impl SpanExporter for CheckSuppressedExporter {
+        fn export(&mut self, _batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            let in_call = is_instrumentation_suppressed();
+            Box::pin(async move {
+                assert!(in_call);
+                assert!(is_instrumentation_suppressed());
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn export_is_suppressed() {
+        let mut exporter = SuppressInstrumentationExporter::new(CheckSuppressedExporter);
+        let result = exporter.export(vec![]).await;
+        assert!(result.is_ok());
+        assert!(!is_instrumentation_suppressed());
+    }
+}
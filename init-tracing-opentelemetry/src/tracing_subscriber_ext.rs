@@ -1,80 +1,212 @@
+use std::time::Duration;
+
 use opentelemetry::trace::{TraceError, TracerProvider};
 use opentelemetry_sdk::trace::{self, Tracer};
 use tracing::{info, Subscriber};
 use tracing_opentelemetry::OpenTelemetryLayer;
 use tracing_subscriber::{filter::EnvFilter, layer::SubscriberExt, registry::LookupSpan, Layer};
 
-use crate::Error;
+use crate::otlp::PipelineOptions;
+use crate::setup_report::timed;
+use crate::{
+    BatchConfig, EffectiveConfig, Error, ExporterHealth, Health, LogFormat, LogWriter, RuntimeMode,
+    SetupReport, SpanEventsConfig,
+};
 
-#[cfg(not(feature = "logfmt"))]
 #[must_use]
 pub fn build_logger_text<S>() -> Box<dyn Layer<S> + Send + Sync + 'static>
 where
     S: Subscriber + for<'a> LookupSpan<'a>,
 {
-    use tracing_subscriber::fmt::format::FmtSpan;
-    if cfg!(debug_assertions) {
-        Box::new(
-            tracing_subscriber::fmt::layer()
-                .pretty()
-                .with_line_number(true)
-                .with_thread_names(true)
-                .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
-                .with_timer(tracing_subscriber::fmt::time::uptime()),
-        )
-    } else {
-        Box::new(
-            tracing_subscriber::fmt::layer()
-                .json()
-                //.with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
-                .with_timer(tracing_subscriber::fmt::time::uptime()),
-        )
-    }
+    build_logger_text_with(LogFormat::Auto, LogWriter::Stdout)
 }
 
-#[cfg(feature = "logfmt")]
+/// Build the layer of the logs with the format `format`, written to `writer`.
+///
+/// With the feature `logfmt`, `LogFormat::Auto` uses `tracing_logfmt` (always written to stdout).
 #[must_use]
-pub fn build_logger_text<S>() -> Box<dyn Layer<S> + Send + Sync + 'static>
+pub fn build_logger_text_with<S>(
+    format: LogFormat,
+    writer: LogWriter,
+) -> Box<dyn Layer<S> + Send + Sync + 'static>
 where
     S: Subscriber + for<'a> LookupSpan<'a>,
 {
-    //FIXME tracing_logfmt use an old version of crates, how to inject trace_id and span_id into log?
-    Box::new(tracing_logfmt::layer())
+    use tracing_subscriber::fmt::format::FmtSpan;
+    use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+    #[cfg(feature = "logfmt")]
+    if format == LogFormat::Auto {
+        //FIXME tracing_logfmt use an old version of crates, how to inject trace_id and span_id into log?
+        return Box::new(tracing_logfmt::layer());
+    }
+    let format = match format {
+        LogFormat::Auto if cfg!(debug_assertions) => LogFormat::Pretty,
+        LogFormat::Auto => LogFormat::Json,
+        format => format,
+    };
+    let writer = match writer {
+        LogWriter::Stdout => BoxMakeWriter::new(std::io::stdout),
+        LogWriter::Stderr => BoxMakeWriter::new(std::io::stderr),
+    };
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_timer(tracing_subscriber::fmt::time::uptime());
+    match format {
+        LogFormat::Pretty => Box::new(
+            layer
+                .pretty()
+                .with_line_number(true)
+                .with_thread_names(true)
+                .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE),
+        ),
+        LogFormat::Json => Box::new(layer.json()),
+        LogFormat::Compact => Box::new(layer.compact()),
+        LogFormat::Auto | LogFormat::Full => Box::new(layer),
+    }
 }
 
+/// Build the filter of the logs from `RUST_LOG` (default: `info`) for the application,
+/// and from [`OTEL_LOG_LEVEL`](https://opentelemetry.io/docs/specs/otel/configuration/sdk-environment-variables/#general-sdk-configuration)
+/// for the logs of the setup of the telemetry (`otel::setup`, `otel::setup::env`),
+/// so the configuration of the telemetry can be debugged without raising the level of the application
+/// (eg `OTEL_LOG_LEVEL=debug`).
 #[must_use]
 pub fn build_loglevel_filter_layer() -> tracing_subscriber::filter::EnvFilter {
     // filter what is output on log (fmt)
     // std::env::set_var("RUST_LOG", "warn,otel::tracing=info,otel=debug");
     std::env::set_var(
         "RUST_LOG",
-        format!(
-            // `otel::tracing` should be a level info to emit opentelemetry trace & span
-            // `otel::setup` set to debug to log detected resources, configuration read
-            "{},otel::tracing=trace",
-            std::env::var("RUST_LOG")
-                .or_else(|_| std::env::var("OTEL_LOG_LEVEL"))
-                .unwrap_or_else(|_| "info".to_string())
+        log_directives(
+            std::env::var("RUST_LOG").ok().as_deref(),
+            std::env::var("OTEL_LOG_LEVEL").ok().as_deref(),
         ),
     );
     EnvFilter::from_default_env()
 }
 
-pub fn build_otel_layer<S>() -> Result<(OpenTelemetryLayer<S, Tracer>, TracingGuard), TraceError>
+fn log_directives(rust_log: Option<&str>, otel_log_level: Option<&str>) -> String {
+    // `otel::tracing` should be a level info to emit opentelemetry trace & span
+    let mut directives = format!(
+        "{},otel::tracing=trace",
+        rust_log.filter(|v| !v.trim().is_empty()).unwrap_or("info")
+    );
+    // `otel::setup` set to debug to log detected resources, configuration read
+    if let Some(level) = otel_log_level.and_then(otel_setup_level) {
+        directives.push_str(",otel::setup=");
+        directives.push_str(&level.to_string().to_lowercase());
+    }
+    directives
+}
+
+/// The level of `OTEL_LOG_LEVEL` (`none` is `off`), `None` if invalid
+fn otel_setup_level(otel_log_level: &str) -> Option<tracing_subscriber::filter::LevelFilter> {
+    match otel_log_level.trim().to_lowercase().as_str() {
+        "" => None,
+        "none" => Some(tracing_subscriber::filter::LevelFilter::OFF),
+        level => level.parse().ok(),
+    }
+}
+
+/// The layer of [`tracing_opentelemetry`] built by [`build_otel_layer`] (with the tracer of the `opentelemetry_sdk`)
+pub type OtelLayer<S> = OpenTelemetryLayer<S, Tracer>;
+
+pub fn build_otel_layer<S>() -> Result<(OtelLayer<S>, TracingGuard), Error>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    build_otel_layer_with_runtime(RuntimeMode::Tokio)
+}
+
+/// Like [`build_otel_layer`] but with the exporter running on the runtime `runtime_mode`
+/// (eg [`RuntimeMode::OwnThread`] for a CLI without Tokio runtime).
+pub fn build_otel_layer_with_runtime<S>(
+    runtime_mode: RuntimeMode,
+) -> Result<(OtelLayer<S>, TracingGuard), Error>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    build_otel_layer_with_batch_config(runtime_mode, BatchConfig::default())
+}
+
+/// Like [`build_otel_layer_with_runtime`] but with the settings of the batch processor of the exporter
+/// (the environment variables `OTEL_BSP_*` keep the priority).
+pub fn build_otel_layer_with_batch_config<S>(
+    runtime_mode: RuntimeMode,
+    batch_config: BatchConfig,
+) -> Result<(OtelLayer<S>, TracingGuard), Error>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    build_otel_layer_with_pipeline(runtime_mode, batch_config, &PipelineOptions::default())
+}
+
+/// Like [`build_otel_layer_with_batch_config`] but with the optional processors of `pipeline`
+/// (eg the self-metrics, see [`PipelineOptions`]).
+pub fn build_otel_layer_with_pipeline<S>(
+    runtime_mode: RuntimeMode,
+    batch_config: BatchConfig,
+    pipeline: &PipelineOptions,
+) -> Result<(OtelLayer<S>, TracingGuard), Error>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let setup_start = std::time::Instant::now();
+    let (otel_rsrc, resource_detection) = timed("resource_detection", detect_resource);
+    build_otel_layer_with_resource(
+        runtime_mode,
+        batch_config,
+        pipeline,
+        otel_rsrc,
+        resource_detection,
+        setup_start,
+    )
+}
+
+fn detect_resource() -> opentelemetry_sdk::Resource {
+    crate::resource::DetectResource::default()
+        //.with_fallback_service_name(env!("CARGO_PKG_NAME"))
+        //.with_fallback_service_version(env!("CARGO_PKG_VERSION"))
+        .build()
+}
+
+/// Like [`build_otel_layer_with_pipeline`] but with the resource already detected
+/// (to share it with the meter provider, see [`init_subscribers_with_config`]).
+fn build_otel_layer_with_resource<S>(
+    runtime_mode: RuntimeMode,
+    batch_config: BatchConfig,
+    pipeline: &PipelineOptions,
+    otel_rsrc: opentelemetry_sdk::Resource,
+    resource_detection: Duration,
+    setup_start: std::time::Instant,
+) -> Result<(OtelLayer<S>, TracingGuard), Error>
 where
     S: Subscriber + for<'a> LookupSpan<'a>,
 {
     use crate::{
         init_propagator, //stdio,
         otlp,
-        resource::DetectResource,
     };
     use opentelemetry::global;
-    let otel_rsrc = DetectResource::default()
-        //.with_fallback_service_name(env!("CARGO_PKG_NAME"))
-        //.with_fallback_service_version(env!("CARGO_PKG_VERSION"))
-        .build();
-    let tracerprovider = otlp::init_tracerprovider(otel_rsrc, otlp::identity)?;
+    let effective_config = EffectiveConfig::from_env(&otel_rsrc);
+    effective_config.log();
+    for issue in crate::validate_env() {
+        tracing::warn!(target: "otel::setup", %issue, "invalid configuration");
+    }
+    let (tracerprovider, tracer_provider_build) = timed("tracer_provider_build", || {
+        if otlp::read_traces_exporter_from_env() == "none" {
+            Ok(build_propagation_only_tracerprovider(otel_rsrc))
+        } else {
+            otlp::init_tracerprovider_with_pipeline(
+                otel_rsrc,
+                runtime_mode,
+                batch_config,
+                pipeline,
+                otlp::identity,
+            )
+        }
+    });
+    let (tracerprovider, traces_health) = tracerprovider?;
     // to not send trace somewhere, but continue to create and propagate,...
     // then send them to `axum_tracing_opentelemetry::stdio::WriteNoWhere::default()`
     // or to `std::io::stdout()` to print
@@ -84,39 +216,445 @@ where
     //     stdio::identity::<stdio::WriteNoWhere>,
     //     stdio::WriteNoWhere::default(),
     // )?;
-    init_propagator()?;
+    let (propagator, propagator_init) = timed("propagator_init", init_propagator);
+    propagator?;
     let layer = tracing_opentelemetry::layer()
         .with_error_records_to_exceptions(true)
         .with_tracer(tracerprovider.tracer(""));
     global::set_tracer_provider(tracerprovider.clone());
-    Ok((layer, TracingGuard { tracerprovider }))
+    let setup_report = SetupReport {
+        resource_detection,
+        tracer_provider_build,
+        propagator_init,
+        meter_provider_build: None,
+        total: setup_start.elapsed(),
+    };
+    setup_report.log();
+    Ok((
+        layer,
+        TracingGuard {
+            tracerprovider,
+            effective_config,
+            setup_report,
+            traces_health,
+            #[cfg(feature = "metrics")]
+            meterprovider: None,
+            #[cfg(feature = "metrics")]
+            metrics_health: None,
+            #[cfg(feature = "heartbeat")]
+            heartbeat: None,
+            is_shut_down: false,
+            setup_error: None,
+        },
+    ))
+}
+
+/// The tracer provider of the propagation-only mode (`OTEL_TRACES_EXPORTER=none`): the spans get their context
+/// (from the sampler of the env, to propagate a consistent decision downstream) and the `trace_id` for the logs,
+/// but no processor, no exporter and no runtime are created.
+fn build_propagation_only_tracerprovider(
+    resource: opentelemetry_sdk::Resource,
+) -> (trace::TracerProvider, Option<ExporterHealth>) {
+    tracing::info!(target: "otel::setup", "OTEL_TRACES_EXPORTER is 'none': propagation-only, the spans are not exported");
+    (
+        trace::TracerProvider::builder()
+            .with_resource(resource)
+            .build(),
+        None,
+    )
+}
+
+/// Create the meter provider (see [`crate::otlp::metrics::init_meterprovider`]) and register it as the global one.
+#[cfg(feature = "metrics")]
+fn build_meterprovider(
+    runtime_mode: RuntimeMode,
+    metrics_config: crate::otlp::metrics::MetricsConfig,
+    otel_rsrc: opentelemetry_sdk::Resource,
+) -> Result<
+    (
+        opentelemetry_sdk::metrics::SdkMeterProvider,
+        Option<ExporterHealth>,
+    ),
+    Error,
+> {
+    let _runtime = runtime_mode.enter()?;
+    let (meterprovider, health) = crate::otlp::metrics::init_meterprovider_with_health(
+        otel_rsrc,
+        metrics_config,
+        crate::otlp::metrics::identity,
+    )?;
+    opentelemetry::global::set_meter_provider(meterprovider.clone());
+    Ok((meterprovider, health))
+}
+
+/// The fallback of [`build_otel_layer`] when the setup failed (see [`init_subscribers_with_config`]):
+/// the spans are created (the trace context is propagated, the `trace_id` is available for the logs)
+/// but not exported.
+fn build_otel_layer_without_export<S>(setup_error: Error) -> (OtelLayer<S>, TracingGuard)
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    if let Err(err) = crate::init_propagator() {
+        tracing::warn!(target: "otel::setup", error = %err, "failed to setup the propagators, keep the default one");
+    }
+    let tracerprovider = trace::TracerProvider::builder().build();
+    let layer = tracing_opentelemetry::layer()
+        .with_error_records_to_exceptions(true)
+        .with_tracer(tracerprovider.tracer(""));
+    opentelemetry::global::set_tracer_provider(tracerprovider.clone());
+    (
+        layer,
+        TracingGuard {
+            tracerprovider,
+            effective_config: EffectiveConfig::default(),
+            setup_report: SetupReport::default(),
+            traces_health: None,
+            #[cfg(feature = "metrics")]
+            meterprovider: None,
+            #[cfg(feature = "metrics")]
+            metrics_health: None,
+            #[cfg(feature = "heartbeat")]
+            heartbeat: None,
+            is_shut_down: false,
+            setup_error: Some(setup_error),
+        },
+    )
 }
 
 #[must_use = "Recommend holding with 'let _guard = ' pattern to ensure final traces are sent to the server"]
 pub struct TracingGuard {
     tracerprovider: trace::TracerProvider,
+    effective_config: EffectiveConfig,
+    setup_report: SetupReport,
+    traces_health: Option<ExporterHealth>,
+    #[cfg(feature = "metrics")]
+    meterprovider: Option<opentelemetry_sdk::metrics::SdkMeterProvider>,
+    #[cfg(feature = "metrics")]
+    metrics_health: Option<ExporterHealth>,
+    #[cfg(feature = "heartbeat")]
+    heartbeat: Option<crate::heartbeat::Heartbeat>,
+    is_shut_down: bool,
+    setup_error: Option<Error>,
+}
+
+impl TracingGuard {
+    #[cfg(all(test, feature = "shutdown"))]
+    pub(crate) fn from_tracerprovider(tracerprovider: trace::TracerProvider) -> Self {
+        TracingGuard {
+            tracerprovider,
+            effective_config: EffectiveConfig::default(),
+            setup_report: SetupReport::default(),
+            traces_health: None,
+            #[cfg(feature = "metrics")]
+            meterprovider: None,
+            #[cfg(feature = "metrics")]
+            metrics_health: None,
+            #[cfg(feature = "heartbeat")]
+            heartbeat: None,
+            is_shut_down: false,
+            setup_error: None,
+        }
+    }
+
+    /// The configuration resolved during the setup
+    #[must_use]
+    pub fn effective_config(&self) -> &EffectiveConfig {
+        &self.effective_config
+    }
+
+    /// The duration of the steps of the setup (see [`SetupReport`])
+    #[must_use]
+    pub fn setup_report(&self) -> &SetupReport {
+        &self.setup_report
+    }
+
+    /// Stop the heartbeat (see [`crate::heartbeat::build_heartbeat`]) when the guard is dropped
+    /// (before the flush of the pending spans).
+    #[cfg(feature = "heartbeat")]
+    pub fn with_heartbeat(mut self, heartbeat: crate::heartbeat::Heartbeat) -> Self {
+        self.heartbeat = Some(heartbeat);
+        self
+    }
+
+    /// The error of the setup of the exporter, when the initialization continued without export
+    /// (see [`init_subscribers_with_config`] with `fail_open`)
+    #[must_use]
+    pub fn setup_error(&self) -> Option<&Error> {
+        self.setup_error.as_ref()
+    }
+
+    /// Export the pending metrics now (eg before the exit of a short-lived process or in the tests),
+    /// instead of waiting the next export interval.
+    #[cfg(feature = "metrics")]
+    pub fn flush_metrics(&self) -> Result<(), Error> {
+        match &self.meterprovider {
+            Some(meterprovider) => meterprovider.force_flush().map_err(Error::from),
+            None => Ok(()),
+        }
+    }
+
+    /// The status of the exporters (last export success/failure time, last error), per signal
+    #[must_use]
+    pub fn health(&self) -> Health {
+        Health {
+            traces: self.traces_health.as_ref().map(ExporterHealth::status),
+            #[cfg(feature = "metrics")]
+            metrics: self.metrics_health.as_ref().map(ExporterHealth::status),
+            #[cfg(not(feature = "metrics"))]
+            metrics: None,
+        }
+    }
+
+    /// The error of the most recent failed export of the spans (kept after a recovery, see [`TracingGuard::health`]
+    /// for the current status).
+    ///
+    /// The failures are also logged via `tracing` under the target `otel::exporter` (rate-limited).
+    #[must_use]
+    pub fn last_export_error(&self) -> Option<String> {
+        self.traces_health
+            .as_ref()
+            .and_then(|health| health.status().last_error)
+    }
+
+    /// Flush the pending spans then shut down the tracer provider, waiting at most `timeout`
+    /// (the flush is done on a dedicated thread, the remaining work is abandoned after the timeout).
+    ///
+    /// Prefer it to the drop of the guard at the end of the application (eg after the graceful shutdown of the server),
+    /// to bound the time to exit when the collector is unreachable.
+    pub fn shutdown(mut self, timeout: Duration) -> Result<(), Error> {
+        #[cfg(feature = "heartbeat")]
+        drop(self.heartbeat.take());
+        self.is_shut_down = true;
+        let tracerprovider = self.tracerprovider.clone();
+        #[cfg(feature = "metrics")]
+        let meterprovider = self.meterprovider.clone();
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("otel-shutdown".to_string())
+            .spawn(move || {
+                #[cfg(feature = "metrics")]
+                if let Some(meterprovider) = meterprovider {
+                    if let Err(err) = meterprovider.shutdown() {
+                        tracing::warn!(target: "otel::setup", error = %err, "failed to shut down the meter provider");
+                    }
+                }
+                let flushed: Result<(), TraceError> =
+                    tracerprovider.force_flush().into_iter().collect();
+                let _ = tx.send(flushed.and_then(|()| tracerprovider.shutdown()));
+            })?;
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result.map_err(Error::from),
+            Err(_) => Err(TraceError::ExportTimedOut(timeout).into()),
+        }
+    }
 }
 
 impl Drop for TracingGuard {
     fn drop(&mut self) {
-        self.tracerprovider.force_flush();
+        #[cfg(feature = "heartbeat")]
+        drop(self.heartbeat.take());
+        if !self.is_shut_down {
+            #[cfg(feature = "metrics")]
+            let _ = self.flush_metrics();
+            self.tracerprovider.force_flush();
+        }
     }
 }
 
 pub fn init_subscribers() -> Result<TracingGuard, Error> {
+    init_subscribers_with_options(&SubscribersOptions::default())
+}
+
+/// Like [`init_subscribers`], but with the options of the `config` not applied via the environment variables
+/// (the configuration should already be applied to the env, eg with `TracingConfig::from_env_and_file`):
+///
+/// - `log_format` & `log_writer`: the format of the logs and where they are written (see [`build_logger_text_with`])
+/// - `fail_open`: if the setup of the exporter fails, log the error and continue without export
+///   (the log layers are installed, the error is available via [`TracingGuard::setup_error`])
+/// - `runtime`: the runtime of the exporter, [`RuntimeMode::OwnThread`] to not require to be called inside
+///   a Tokio runtime (eg for a CLI)
+/// - `batch`: the settings of the batch processor of the exporter (the `OTEL_BSP_*` env variables keep the priority)
+/// - `span_events`: the tracing's events recorded as span events (see [`SpanEventsConfig::filter`])
+/// - `metric_export_interval` & `metric_timeout`: the settings of the export of the metrics (require feature `metrics`,
+///   the `OTEL_METRIC_EXPORT_*` env variables keep the priority)
+/// - `metric_attribute_allowlists`: the attributes kept on the metrics (require feature `metrics`)
+/// - `recording_gate`: apply the recording gate of the layers (see [`crate::RecordingGateSpanProcessor`])
+/// - `self_metrics`: count the spans & the exports (require feature `self_metrics`, see [`crate::self_metrics`]),
+///   on the meter provider of the feature `metrics` (else on the global one, to register before)
+#[cfg(feature = "config_file")]
+pub fn init_subscribers_with_config(
+    config: &crate::config_file::TracingConfig,
+) -> Result<TracingGuard, Error> {
+    init_subscribers_with_options(&SubscribersOptions {
+        log_format: config.log_format(),
+        log_writer: config.log_writer(),
+        fail_open: config.fail_open(),
+        runtime_mode: config.runtime(),
+        batch_config: config.batch,
+        span_events: config.span_events.clone(),
+        self_metrics: config.self_metrics(),
+        recording_gate: config.recording_gate(),
+        #[cfg(feature = "metrics")]
+        metrics_config: crate::otlp::metrics::MetricsConfig {
+            export_interval: config.metric_export_interval,
+            export_timeout: config.metric_timeout,
+            attribute_allowlists: config.metric_attribute_allowlists.clone(),
+        },
+    })
+}
+
+/// The options of the setup not applied via the environment variables
+#[derive(Debug, Clone, Default)]
+struct SubscribersOptions {
+    log_format: LogFormat,
+    log_writer: LogWriter,
+    fail_open: bool,
+    runtime_mode: RuntimeMode,
+    batch_config: BatchConfig,
+    span_events: SpanEventsConfig,
+    self_metrics: bool,
+    recording_gate: bool,
+    #[cfg(feature = "metrics")]
+    metrics_config: crate::otlp::metrics::MetricsConfig,
+}
+
+fn init_subscribers_with_options(options: &SubscribersOptions) -> Result<TracingGuard, Error> {
+    let SubscribersOptions {
+        log_format,
+        log_writer,
+        fail_open,
+        runtime_mode,
+        batch_config,
+        ..
+    } = *options;
     //setup a temporary subscriber to log output during setup
     let subscriber = tracing_subscriber::registry()
         .with(build_loglevel_filter_layer())
-        .with(build_logger_text());
+        .with(build_logger_text_with(log_format, log_writer));
     let _guard = tracing::subscriber::set_default(subscriber);
     info!("init logging & tracing");
 
-    let (layer, guard) = build_otel_layer()?;
+    // the resource is detected once, to share it between the tracer & meter providers
+    let setup_start = std::time::Instant::now();
+    let (otel_rsrc, resource_detection) = timed("resource_detection", detect_resource);
+    // the meter provider is built first, to bind the self-metrics of the tracer provider to it
+    #[cfg(feature = "metrics")]
+    let (meterprovider, meter_provider_build) = timed("meter_provider_build", || {
+        build_meterprovider(
+            runtime_mode,
+            options.metrics_config.clone(),
+            otel_rsrc.clone(),
+        )
+    });
+    #[cfg(feature = "metrics")]
+    let (meterprovider, metrics_health) = match meterprovider {
+        Ok((meterprovider, metrics_health)) => (Some(meterprovider), metrics_health),
+        Err(err) if fail_open => {
+            tracing::error!(target: "otel::setup", error = %err, "failed to setup the export of metrics, continue without export");
+            (None, None)
+        }
+        Err(err) => return Err(err),
+    };
+    let pipeline = PipelineOptions::default().with_recording_gate(options.recording_gate);
+    #[cfg(feature = "self_metrics")]
+    let pipeline = if options.self_metrics {
+        #[cfg(feature = "metrics")]
+        let meter = match &meterprovider {
+            Some(meterprovider) => crate::self_metrics::meter(meterprovider),
+            None => crate::self_metrics::meter(&*opentelemetry::global::meter_provider()),
+        };
+        #[cfg(not(feature = "metrics"))]
+        let meter = crate::self_metrics::meter(&*opentelemetry::global::meter_provider());
+        pipeline.with_self_metrics(meter)
+    } else {
+        pipeline
+    };
+    #[cfg(not(feature = "self_metrics"))]
+    if options.self_metrics {
+        tracing::warn!(target: "otel::setup", "the self-metrics require the feature 'self_metrics' of init-tracing-opentelemetry, they are disabled");
+    }
+
+    let (layer, guard) = match build_otel_layer_with_resource(
+        runtime_mode,
+        batch_config,
+        &pipeline,
+        otel_rsrc,
+        resource_detection,
+        setup_start,
+    ) {
+        Ok(layer_and_guard) => layer_and_guard,
+        Err(err) if fail_open => {
+            tracing::error!(target: "otel::setup", error = %err, "failed to setup the export of traces, continue without export");
+            build_otel_layer_without_export(err)
+        }
+        Err(err) => return Err(err),
+    };
+    #[cfg(feature = "metrics")]
+    let mut guard = guard;
+    #[cfg(feature = "metrics")]
+    {
+        guard.setup_report.meter_provider_build = Some(meter_provider_build);
+        guard.setup_report.total += meter_provider_build;
+        guard.meterprovider = meterprovider;
+        guard.metrics_health = metrics_health;
+    }
 
     let subscriber = tracing_subscriber::registry()
-        .with(layer)
+        .with(layer.with_filter(options.span_events.filter()))
         .with(build_loglevel_filter_layer())
-        .with(build_logger_text());
+        .with(build_logger_text_with(log_format, log_writer));
     tracing::subscriber::set_global_default(subscriber)?;
     Ok(guard)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(None, None, "info,otel::tracing=trace")]
+    #[case(Some("warn"), None, "warn,otel::tracing=trace")]
+    #[case(None, Some("debug"), "info,otel::tracing=trace,otel::setup=debug")]
+    #[case(
+        Some("warn,my_app=debug"),
+        Some("ERROR"),
+        "warn,my_app=debug,otel::tracing=trace,otel::setup=error"
+    )]
+    #[case(None, Some("none"), "info,otel::tracing=trace,otel::setup=off")]
+    #[case(None, Some("verbose"), "info,otel::tracing=trace")]
+    fn test_log_directives(
+        #[case] rust_log: Option<&str>,
+        #[case] otel_log_level: Option<&str>,
+        #[case] expected: &str,
+    ) {
+        assert!(log_directives(rust_log, otel_log_level) == expected);
+    }
+
+    #[test]
+    fn propagation_only_creates_the_context_of_the_spans() {
+        use opentelemetry::trace::TraceContextExt;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let (tracerprovider, health) =
+            build_propagation_only_tracerprovider(opentelemetry_sdk::Resource::empty());
+        assert!(health.is_none());
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracerprovider.tracer("")));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request");
+            let context = span.context();
+            assert!(context.span().span_context().is_valid());
+        });
+    }
+
+    #[test]
+    fn build_layer_without_export_keeps_the_error() {
+        let (_layer, guard) = build_otel_layer_without_export::<tracing_subscriber::Registry>(
+            Error::InvalidConfig("boom".to_string()),
+        );
+        let_assert!(Some(Error::InvalidConfig(_)) = guard.setup_error());
+        assert!(guard.health().traces.is_none());
+    }
+}
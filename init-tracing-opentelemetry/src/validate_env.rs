@@ -0,0 +1,294 @@
+use std::fmt;
+
+use crate::Error;
+
+/// The samplers of `OTEL_TRACES_SAMPLER` supported by the sdk
+const KNOWN_SAMPLERS: [&str; 6] = [
+    "always_on",
+    "always_off",
+    "traceidratio",
+    "parentbased_always_on",
+    "parentbased_always_off",
+    "parentbased_traceidratio",
+];
+
+/// The protocols of `OTEL_EXPORTER_OTLP_PROTOCOL` (and of the per-signal variables)
+const KNOWN_PROTOCOLS: [&str; 3] = ["grpc", "http/protobuf", "http/json"];
+
+/// A (probable) mistake into the `OTEL_*` environment variables, found by [`validate_env`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ConfigIssue {
+    /// name of the environment variable
+    pub variable: String,
+    /// value of the environment variable
+    pub value: String,
+    /// what is wrong
+    pub problem: String,
+    /// how to fix it
+    pub suggestion: String,
+}
+
+impl ConfigIssue {
+    fn new(
+        variable: impl Into<String>,
+        value: impl Into<String>,
+        problem: impl Into<String>,
+        suggestion: impl Into<String>,
+    ) -> Self {
+        Self {
+            variable: variable.into(),
+            value: value.into(),
+            problem: problem.into(),
+            suggestion: suggestion.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}='{}': {}, {}",
+            self.variable, self.value, self.problem, self.suggestion
+        )
+    }
+}
+
+/// Check the `OTEL_*` environment variables for the common mistakes (endpoint without scheme, protocol not
+/// matching the port of the endpoint, unknown sampler, propagator unknown or requiring a disabled compile
+/// feature,...), eg to print them at the startup of the application.
+///
+/// The issues are also logged (at level `warn` on target `otel::setup`) by the setup of
+/// `tracing_subscriber_ext`.
+///
+/// ```rust
+/// for issue in init_tracing_opentelemetry::validate_env() {
+///     eprintln!("invalid configuration of OpenTelemetry: {issue}");
+/// }
+/// ```
+#[must_use]
+pub fn validate_env() -> Vec<ConfigIssue> {
+    validate(|name| std::env::var(name).ok())
+}
+
+fn validate(read_env: impl Fn(&str) -> Option<String>) -> Vec<ConfigIssue> {
+    let read_env = |name: &str| read_env(name).filter(|v| !v.trim().is_empty());
+    let mut issues = Vec::new();
+    check_endpoints(&read_env, &mut issues);
+    check_protocols(&read_env, &mut issues);
+    check_sampler(&read_env, &mut issues);
+    check_propagators(&read_env, &mut issues);
+    issues.dedup();
+    issues
+}
+
+fn check_endpoints(read_env: &dyn Fn(&str) -> Option<String>, issues: &mut Vec<ConfigIssue>) {
+    for signal in ["", "TRACES_", "METRICS_", "LOGS_"] {
+        let endpoint_var = format!("OTEL_EXPORTER_OTLP_{signal}ENDPOINT");
+        if let Some(endpoint) = read_env(&endpoint_var) {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                issues.push(ConfigIssue::new(
+                    &endpoint_var,
+                    &endpoint,
+                    "the endpoint has no scheme",
+                    format!("prefix it with 'http://' or 'https://' (eg 'http://{endpoint}')"),
+                ));
+            }
+        }
+    }
+}
+
+/// Check the protocol of each signal (the issues of the shared variables are repeated, then deduplicated)
+fn check_protocols(read_env: &dyn Fn(&str) -> Option<String>, issues: &mut Vec<ConfigIssue>) {
+    for signal in ["TRACES_", "METRICS_", "LOGS_"] {
+        let (protocol_var, protocol) = first_defined(
+            read_env,
+            &[
+                format!("OTEL_EXPORTER_OTLP_{signal}PROTOCOL"),
+                "OTEL_EXPORTER_OTLP_PROTOCOL".to_string(),
+            ],
+        );
+        let Some(protocol) = protocol else {
+            continue;
+        };
+        if !KNOWN_PROTOCOLS.contains(&protocol.trim()) {
+            issues.push(ConfigIssue::new(
+                &protocol_var,
+                &protocol,
+                "unknown protocol",
+                format!("use one of {}", KNOWN_PROTOCOLS.join(", ")),
+            ));
+            continue;
+        }
+        let (endpoint_var, endpoint) = first_defined(
+            read_env,
+            &[
+                format!("OTEL_EXPORTER_OTLP_{signal}ENDPOINT"),
+                "OTEL_EXPORTER_OTLP_ENDPOINT".to_string(),
+            ],
+        );
+        let expected_protocol = match endpoint.as_deref().and_then(endpoint_port) {
+            Some(4317) if protocol.starts_with("http/") => "grpc",
+            Some(4318) if protocol == "grpc" => "http/protobuf",
+            _ => continue,
+        };
+        issues.push(ConfigIssue::new(
+            &protocol_var,
+            &protocol,
+            format!(
+                "the protocol doesn't match the port of {endpoint_var} ('{}')",
+                endpoint.unwrap_or_default()
+            ),
+            format!(
+                "use the protocol '{expected_protocol}' or the port {} (the default ports of OTLP are 4317 for grpc & 4318 for http)",
+                if protocol == "grpc" { 4317 } else { 4318 }
+            ),
+        ));
+    }
+}
+
+fn check_sampler(read_env: &dyn Fn(&str) -> Option<String>, issues: &mut Vec<ConfigIssue>) {
+    let Some(sampler) = read_env("OTEL_TRACES_SAMPLER") else {
+        return;
+    };
+    let sampler = sampler.trim().to_lowercase();
+    if !KNOWN_SAMPLERS.contains(&sampler.as_str()) {
+        issues.push(ConfigIssue::new(
+            "OTEL_TRACES_SAMPLER",
+            &sampler,
+            "unknown sampler (the default 'parentbased_always_on' is used)",
+            format!("use one of {}", KNOWN_SAMPLERS.join(", ")),
+        ));
+    } else if sampler.ends_with("traceidratio") {
+        if let Some(arg) = read_env("OTEL_TRACES_SAMPLER_ARG") {
+            if !arg
+                .trim()
+                .parse::<f64>()
+                .is_ok_and(|ratio| (0.0..=1.0).contains(&ratio))
+            {
+                issues.push(ConfigIssue::new(
+                    "OTEL_TRACES_SAMPLER_ARG",
+                    &arg,
+                    "the ratio of the sampler is not a number between 0 and 1",
+                    "use a ratio like '0.25' (25% of the traces)",
+                ));
+            }
+        }
+    }
+}
+
+fn check_propagators(read_env: &dyn Fn(&str) -> Option<String>, issues: &mut Vec<ConfigIssue>) {
+    let Some(propagators) = read_env("OTEL_PROPAGATORS") else {
+        return;
+    };
+    for name in propagators.split(',').map(|s| s.trim().to_lowercase()) {
+        if let Err(Error::UnsupportedPropagator {
+            required_feature, ..
+        }) = crate::propagator_from_string(&name)
+        {
+            let suggestion = match required_feature {
+                Some(feature) => {
+                    format!("enable the compile feature '{feature}' of init-tracing-opentelemetry")
+                }
+                None => "use tracecontext, baggage, b3, b3multi, jaeger or none".to_string(),
+            };
+            issues.push(ConfigIssue::new(
+                "OTEL_PROPAGATORS",
+                &propagators,
+                format!("unsupported propagator '{name}'"),
+                suggestion,
+            ));
+        }
+    }
+}
+
+/// The first of the variables `names` with a value (or the last name)
+fn first_defined(
+    read_env: &dyn Fn(&str) -> Option<String>,
+    names: &[String],
+) -> (String, Option<String>) {
+    names
+        .iter()
+        .find_map(|name| read_env(name).map(|value| (name.clone(), Some(value))))
+        .unwrap_or_else(|| (names.last().cloned().unwrap_or_default(), None))
+}
+
+/// The explicit port of the `endpoint` (an url)
+fn endpoint_port(endpoint: &str) -> Option<u16> {
+    let authority = endpoint
+        .split_once("://")
+        .map_or(endpoint, |(_, rest)| rest)
+        .split(['/', '?'])
+        .next()?;
+    let (host, port) = authority.rsplit_once(':')?;
+    // ignore the ipv6 address without port (eg `[::1]`)
+    (!host.is_empty() && !port.contains(']'))
+        .then(|| port.parse().ok())
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use rstest::rstest;
+    use std::collections::HashMap;
+
+    fn validate_vars(vars: &[(&str, &str)]) -> Vec<ConfigIssue> {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        validate(|name| vars.get(name).map(ToString::to_string))
+    }
+
+    #[test]
+    fn no_issue_for_valid_or_default_config() {
+        assert!(validate_vars(&[]).is_empty());
+        assert!(validate_vars(&[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://localhost:4317"),
+            ("OTEL_EXPORTER_OTLP_PROTOCOL", "grpc"),
+            ("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL", "http/protobuf"),
+            (
+                "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+                "https://collector:4318/v1/traces"
+            ),
+            ("OTEL_TRACES_SAMPLER", "parentbased_traceidratio"),
+            ("OTEL_TRACES_SAMPLER_ARG", "0.5"),
+            ("OTEL_PROPAGATORS", "tracecontext, baggage"),
+        ])
+        .is_empty());
+    }
+
+    #[rstest]
+    #[case(&[("OTEL_EXPORTER_OTLP_ENDPOINT", "localhost:4317")], "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    #[case(&[("OTEL_EXPORTER_OTLP_LOGS_ENDPOINT", "collector/v1/logs")], "OTEL_EXPORTER_OTLP_LOGS_ENDPOINT")]
+    #[case(&[("OTEL_EXPORTER_OTLP_PROTOCOL", "http"), ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://localhost:4318")], "OTEL_EXPORTER_OTLP_PROTOCOL")]
+    #[case(&[("OTEL_EXPORTER_OTLP_PROTOCOL", "grpc"), ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://localhost:4318")], "OTEL_EXPORTER_OTLP_PROTOCOL")]
+    #[case(&[("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL", "http/protobuf"), ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://localhost:4317")], "OTEL_EXPORTER_OTLP_TRACES_PROTOCOL")]
+    #[case(&[("OTEL_TRACES_SAMPLER", "ratio")], "OTEL_TRACES_SAMPLER")]
+    #[case(&[("OTEL_TRACES_SAMPLER", "traceidratio"), ("OTEL_TRACES_SAMPLER_ARG", "50%")], "OTEL_TRACES_SAMPLER_ARG")]
+    #[case(&[("OTEL_PROPAGATORS", "tracecontext,w3c")], "OTEL_PROPAGATORS")]
+    fn report_one_issue(#[case] vars: &[(&str, &str)], #[case] variable: &str) {
+        let issues = validate_vars(vars);
+        assert!(issues.len() == 1, "{issues:?}");
+        assert!(issues[0].variable == variable);
+    }
+
+    #[cfg(not(feature = "jaeger"))]
+    #[test]
+    fn suggest_the_missing_feature_of_a_propagator() {
+        let issues = validate_vars(&[("OTEL_PROPAGATORS", "jaeger")]);
+        assert!(issues.len() == 1);
+        assert!(issues[0].suggestion.contains("'jaeger'"));
+    }
+
+    #[rstest]
+    #[case("http://localhost:4317", Some(4317))]
+    #[case("https://collector:4318/v1/traces", Some(4318))]
+    #[case("localhost:4317", Some(4317))]
+    #[case("http://[::1]:4317", Some(4317))]
+    #[case("http://[::1]", None)]
+    #[case("https://collector/v1/traces", None)]
+    fn read_the_port_of_the_endpoint(#[case] endpoint: &str, #[case] expected: Option<u16>) {
+        assert!(endpoint_port(endpoint) == expected);
+    }
+}
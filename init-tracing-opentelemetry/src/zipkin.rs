@@ -0,0 +1,17 @@
+use opentelemetry::trace::TraceError;
+use opentelemetry::Key;
+use opentelemetry_sdk::Resource;
+use opentelemetry_semantic_conventions::resource;
+
+/// Create a zipkin exporter, the url of the collector is read from the env variable
+/// `OTEL_EXPORTER_ZIPKIN_ENDPOINT` (default: `http://localhost:9411/api/v2/spans`),
+/// and the timeout from `OTEL_EXPORTER_ZIPKIN_TIMEOUT`.
+///
+/// The zipkin's local endpoint use the `service.name` of the resource.
+pub fn init_exporter(resource: &Resource) -> Result<opentelemetry_zipkin::Exporter, TraceError> {
+    let mut pipeline = opentelemetry_zipkin::new_pipeline();
+    if let Some(service_name) = resource.get(Key::from_static_str(resource::SERVICE_NAME)) {
+        pipeline = pipeline.with_service_name(service_name.as_str());
+    }
+    pipeline.init_exporter()
+}
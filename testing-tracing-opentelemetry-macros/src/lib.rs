@@ -0,0 +1,142 @@
+#![forbid(unsafe_code)]
+#![warn(clippy::perf)]
+#![warn(clippy::pedantic)]
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Expr, FnArg, ItemFn, PatType};
+
+/// Define an async test that collects the traces of an instrumented code:
+///
+/// 1. setup a `FakeEnvironment` (subscriber + fake collector)
+/// 2. run the async function defined by `run = ...` (the code to instrument), either a path to an async function
+///    without argument or a call (eg `run = call_service(uri)`, the arguments can be the parameters of the test)
+/// 3. call the annotated function with the collected `(tracing_events, otel_spans)`
+///
+/// ```ignore
+/// use testing_tracing_opentelemetry::{assert_trace, otel_test};
+///
+/// async fn call_service() {
+///     // ... call the instrumented service
+/// }
+///
+/// #[otel_test(run = call_service)]
+/// async fn check_trace(
+///     tracing_events: Vec<serde_json::Value>,
+///     otel_spans: Vec<fake_opentelemetry_collector::ExportedSpan>,
+/// ) {
+///     assert_trace("call_service", tracing_events, otel_spans, false);
+/// }
+/// ```
+///
+/// The parameters with an attribute (eg `#[case]` of `rstest`, with `#[otel_test]` declared before `#[rstest]`)
+/// are kept as parameters of the test, and an optional parameter before `(tracing_events, otel_spans)`
+/// receives the value returned by `run`.
+///
+/// ```ignore
+/// async fn call_service(uri: &str) -> String {
+///     // ... call the instrumented service, return the body of the response
+/// }
+///
+/// #[otel_test(run = call_service(uri))]
+/// #[rstest]
+/// #[case("/users/123")]
+/// async fn check_trace(
+///     #[case] uri: &str,
+///     body: String,
+///     _tracing_events: Vec<serde_json::Value>,
+///     otel_spans: Vec<fake_opentelemetry_collector::ExportedSpan>,
+/// ) {
+///     // ...
+/// }
+/// ```
+///
+/// The generated test uses `#[tokio::test(flavor = "multi_thread")]`, so `tokio` (with features `macros` & `rt-multi-thread`)
+/// should be a (dev-)dependency.
+#[proc_macro_attribute]
+pub fn otel_test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut run: Option<Expr> = None;
+    let args_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("run") {
+            run = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error(
+                "unsupported otel_test property, expected `run = path::to::async_fn` or `run = async_fn(args)`",
+            ))
+        }
+    });
+    parse_macro_input!(args with args_parser);
+    let input = parse_macro_input!(item as ItemFn);
+    match expand(run, &input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(run: Option<Expr>, input: &ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let Some(run) = run else {
+        return Err(syn::Error::new(
+            input.sig.span(),
+            "missing `run = path::to::async_fn` (the code to instrument)",
+        ));
+    };
+    if input.sig.asyncness.is_none() {
+        return Err(syn::Error::new(
+            input.sig.fn_token.span(),
+            "otel_test function should be async",
+        ));
+    }
+    let params = input
+        .sig
+        .inputs
+        .iter()
+        .map(|arg| match arg {
+            FnArg::Typed(pat_type) => Ok(pat_type),
+            FnArg::Receiver(r) => Err(syn::Error::new(r.span(), "unsupported `self` parameter")),
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    // the parameters with attributes are provided by an other macro (eg `#[case]` of `rstest`)
+    let (kept_params, params): (Vec<&PatType>, Vec<&PatType>) = params
+        .into_iter()
+        .partition(|param| !param.attrs.is_empty());
+    let (output, tracing_events, otel_spans) = match params.as_slice() {
+        [tracing_events, otel_spans] => (None, tracing_events, otel_spans),
+        [output, tracing_events, otel_spans] => (Some(output), tracing_events, otel_spans),
+        _ => {
+            return Err(syn::Error::new(
+                input.sig.inputs.span(),
+                "otel_test function should have 2 parameters: (tracing_events, otel_spans), optionally preceded by the output of `run`",
+            ))
+        }
+    };
+    let attrs = &input.attrs;
+    let vis = &input.vis;
+    let name = &input.sig.ident;
+    let body = &input.block;
+    let run = match run {
+        Expr::Path(path) => quote! { #path() },
+        run => quote! { #run },
+    };
+    let output = output.map_or_else(
+        || quote! { #run.await; },
+        |output| {
+            let (output_pat, output_ty) = (&output.pat, &output.ty);
+            quote! { let #output_pat: #output_ty = #run.await; }
+        },
+    );
+    let (tracing_events_pat, tracing_events_ty) = (&tracing_events.pat, &tracing_events.ty);
+    let (otel_spans_pat, otel_spans_ty) = (&otel_spans.pat, &otel_spans.ty);
+    Ok(quote! {
+        #(#attrs)*
+        #[::tokio::test(flavor = "multi_thread")]
+        #vis async fn #name(#(#kept_params),*) {
+            let mut fake_env = ::testing_tracing_opentelemetry::FakeEnvironment::setup().await;
+            #output
+            let (tracing_events, otel_spans) = fake_env.collect_traces().await;
+            let #tracing_events_pat: #tracing_events_ty = tracing_events;
+            let #otel_spans_pat: #otel_spans_ty = otel_spans;
+            #body
+        }
+    })
+}
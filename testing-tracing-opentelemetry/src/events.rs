@@ -0,0 +1,83 @@
+//! Helpers to query the tracing events (json lines of the fmt layer) collected by [`crate::FakeEnvironment`].
+
+use serde_json::Value;
+
+/// The events emitted with the `target` (eg `otel::tracing`)
+#[must_use]
+pub fn events_with_target<'a>(tracing_events: &'a [Value], target: &str) -> Vec<&'a Value> {
+    tracing_events
+        .iter()
+        .filter(|event| event.get("target").and_then(Value::as_str) == Some(target))
+        .collect()
+}
+
+/// The events emitted in the context of the span `name` (including the `new` / `close` events of the span)
+#[must_use]
+pub fn span_events<'a>(tracing_events: &'a [Value], name: &str) -> Vec<&'a Value> {
+    tracing_events
+        .iter()
+        .filter(|event| span_name(event) == Some(name))
+        .collect()
+}
+
+/// The events of the span `name` with the message `message` (eg `new`, `close` for the span events)
+#[must_use]
+pub fn span_events_with_message<'a>(
+    tracing_events: &'a [Value],
+    name: &str,
+    message: &str,
+) -> Vec<&'a Value> {
+    span_events(tracing_events, name)
+        .into_iter()
+        .filter(|event| field(event, "message").and_then(Value::as_str) == Some(message))
+        .collect()
+}
+
+/// The name of the span (current) of the event
+#[must_use]
+pub fn span_name(event: &Value) -> Option<&str> {
+    event
+        .get("span")
+        .and_then(|span| span.get("name"))
+        .and_then(Value::as_str)
+}
+
+/// The value of the field `key` of the event, fallback to the field of the span (current) of the event
+/// (eg `field(event, "http.route")`)
+#[must_use]
+pub fn field<'a>(event: &'a Value, key: &str) -> Option<&'a Value> {
+    event
+        .get("fields")
+        .and_then(|fields| fields.get(key))
+        .or_else(|| event.get("span").and_then(|span| span.get(key)))
+}
+
+/// Like [`field`] but only for string value
+#[must_use]
+pub fn field_str<'a>(event: &'a Value, key: &str) -> Option<&'a str> {
+    field(event, key).and_then(Value::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use serde_json::json;
+
+    #[test]
+    fn query_events() {
+        let tracing_events = vec![
+            json!({"fields": {"message": "new"}, "span": {"name": "HTTP request", "otel.name": "GET"}, "target": "otel::tracing"}),
+            json!({"fields": {"message": "hello"}, "span": {"name": "my child span"}, "target": "app"}),
+            json!({"fields": {"message": "close"}, "span": {"name": "HTTP request", "http.route": "/users/{id}", "http.response.status_code": 200}, "target": "otel::tracing"}),
+        ];
+        assert!(events_with_target(&tracing_events, "otel::tracing").len() == 2);
+        assert!(span_events(&tracing_events, "HTTP request").len() == 2);
+        let closes = span_events_with_message(&tracing_events, "HTTP request", "close");
+        let_assert!([close] = closes.as_slice());
+        assert!(field_str(close, "http.route") == Some("/users/{id}"));
+        assert!(field(close, "http.response.status_code") == Some(&json!(200)));
+        assert!(field_str(close, "message") == Some("close"));
+        assert!(field(close, "unknown").is_none());
+    }
+}
@@ -9,6 +9,10 @@ use tracing_subscriber::{
     EnvFilter,
 };
 
+pub mod events;
+pub mod process;
+pub use testing_tracing_opentelemetry_macros::otel_test;
+
 pub fn assert_trace(
     name: &str,
     tracing_events: Vec<Value>,
@@ -81,6 +85,8 @@ pub fn assert_trace(
         "[].attributes[\"code.lineno\"]" => "ignore",
         "[].attributes[\"code.filepath\"]" => "ignore",
         "[].attributes[\"thread.id\"]" => "ignore",
+        "[].events[].attributes[\"code.lineno\"]" => "ignore",
+        "[].events[].attributes[\"code.filepath\"]" => "ignore",
     });
 }
 
@@ -104,6 +110,8 @@ impl FakeEnvironment {
         let tracer_provider =
             fake_opentelemetry_collector::setup_tracer_provider(&fake_collector).await;
         //let (tracer, mut req_rx) = fake_opentelemetry_collector::setup_tracer().await;
+        // always the same (W3C) global propagator, so the tests running in parallel don't interfere:
+        // the tests of other formats use the explicit propagator of the layers (eg `with_propagator(...)`)
         opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
         let otel_layer = tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("fake"));
 
@@ -0,0 +1,136 @@
+//! Helpers to test an instrumented binary (an example, a service,...) as a black box: the spans are exported by
+//! the child process to a [`FakeCollectorServer`].
+
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use fake_opentelemetry_collector::{ExportedSpan, FakeCollectorServer};
+
+/// The delay between two polls of the spans received by the collector
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A child process with the environment variables `OTEL_EXPORTER_OTLP_*` pointed at a [`FakeCollectorServer`]
+/// (grpc), and `OTEL_BSP_SCHEDULE_DELAY` reduced to receive the spans quickly.
+///
+/// The child process is killed on drop.
+///
+/// ```rust,no_run
+/// use fake_opentelemetry_collector::ExportedSpansExt;
+/// use std::process::Command;
+/// use std::time::Duration;
+/// use testing_tracing_opentelemetry::process::ChildProcessEnvironment;
+///
+/// # async fn check() {
+/// // eg `env!("CARGO_BIN_EXE_<name>")` in the integration tests of the binary
+/// let mut process = ChildProcessEnvironment::spawn(Command::new("path/to/binary"))
+///     .await
+///     .unwrap();
+/// // call the process (eg http request),...
+/// let otel_spans = process
+///     .wait_for_spans(
+///         |spans| !spans.spans_named("GET /").is_empty(),
+///         Duration::from_secs(10),
+///     )
+///     .await;
+/// assert!(!otel_spans.spans_named("GET /").is_empty());
+/// # }
+/// ```
+pub struct ChildProcessEnvironment {
+    fake_collector: FakeCollectorServer,
+    child: tokio::process::Child,
+    otel_spans: Vec<ExportedSpan>,
+}
+
+impl ChildProcessEnvironment {
+    /// Start a [`FakeCollectorServer`], then spawn `command` with the environment to export to it
+    /// (the `OTEL_EXPORTER_OTLP_TRACES_*` inherited from the current process are removed).
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the collector can not be started or the command can not be spawned.
+    pub async fn spawn(command: Command) -> Result<Self, Box<dyn std::error::Error>> {
+        let fake_collector = FakeCollectorServer::start().await?;
+        let mut command = tokio::process::Command::from(command);
+        command
+            .env("OTEL_EXPORTER_OTLP_ENDPOINT", fake_collector.endpoint())
+            .env("OTEL_EXPORTER_OTLP_PROTOCOL", "grpc")
+            .env("OTEL_TRACES_EXPORTER", "otlp")
+            .env("OTEL_BSP_SCHEDULE_DELAY", "100")
+            .env_remove("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT")
+            .env_remove("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL")
+            .kill_on_drop(true);
+        let child = command.spawn()?;
+        Ok(Self {
+            fake_collector,
+            child,
+            otel_spans: Vec::new(),
+        })
+    }
+
+    /// The endpoint of the collector (the value of `OTEL_EXPORTER_OTLP_ENDPOINT` for the child process)
+    #[must_use]
+    pub fn endpoint(&self) -> String {
+        self.fake_collector.endpoint()
+    }
+
+    /// The child process, eg to read its output or to wait its exit
+    pub fn child_mut(&mut self) -> &mut tokio::process::Child {
+        &mut self.child
+    }
+
+    /// Wait (at most `timeout`) until the spans received since the spawn of the process match the `predicate`,
+    /// then return all of them (the returned spans don't match the `predicate` on timeout).
+    pub async fn wait_for_spans<P>(&mut self, predicate: P, timeout: Duration) -> Vec<ExportedSpan>
+    where
+        P: Fn(&[ExportedSpan]) -> bool,
+    {
+        let start = Instant::now();
+        loop {
+            self.otel_spans
+                .extend(self.fake_collector.exported_spans(0, Duration::ZERO).await);
+            if predicate(&self.otel_spans) || start.elapsed() >= timeout {
+                return self.otel_spans.clone();
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Kill the child process (and wait its exit), the spans not exported yet are lost.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the process can not be killed.
+    pub async fn kill(mut self) -> std::io::Result<()> {
+        self.child.kill().await
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use std::process::Stdio;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn spawn_with_the_env_of_the_collector() {
+        let mut command = Command::new("printenv");
+        command
+            .arg("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .stdout(Stdio::piped());
+        let_assert!(Ok(mut process) = ChildProcessEnvironment::spawn(command).await);
+
+        let_assert!(Some(mut stdout) = process.child_mut().stdout.take());
+        let mut output = String::new();
+        let_assert!(Ok(_) = stdout.read_to_string(&mut output).await);
+        assert!(output.trim() == process.endpoint());
+
+        // the process doesn't export spans
+        let otel_spans = process
+            .wait_for_spans(|spans| !spans.is_empty(), Duration::from_millis(100))
+            .await;
+        assert!(otel_spans.is_empty());
+        let_assert!(Ok(status) = process.child_mut().wait().await);
+        assert!(status.success());
+    }
+}
@@ -0,0 +1,331 @@
+use http_body::{Body, Frame, SizeHint};
+use hyper::body::Buf;
+use pin_project_lite::pin_project;
+use std::{
+    io::IoSlice,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tracing::Span;
+use tracing_opentelemetry_instrumentation_sdk::{
+    http::{self as otel_http, GrpcErrorCodes},
+    TRACING_LEVEL, TRACING_TARGET,
+};
+
+pin_project! {
+    /// Response body for [`super::server::OtelGrpcService`] and [`super::client::OtelGrpcService`].
+    ///
+    /// It holds the span (to keep it open until the end of the stream) and records
+    /// the `grpc-status` sent into the trailers (and optionally the size of the body & the messages).
+    pub struct ResponseBody<B> {
+        #[pin]
+        inner: B,
+        span: Span,
+        error_codes: GrpcErrorCodes,
+        // the size of the data sent so far, `None` if not recorded (or already recorded)
+        body_size: Option<u64>,
+        // the messages received so far, `None` if not recorded (or already recorded)
+        messages: Option<GrpcMessageCounter>,
+    }
+}
+
+impl<B> ResponseBody<B> {
+    pub(crate) fn new(inner: B, span: Span, error_codes: GrpcErrorCodes) -> Self {
+        Self {
+            inner,
+            span,
+            error_codes,
+            body_size: None,
+            messages: None,
+        }
+    }
+
+    /// Record the size of the body as `rpc.grpc.response.body.size` at the end of the stream
+    pub(crate) fn with_body_size(self) -> Self {
+        Self {
+            body_size: Some(0),
+            ..self
+        }
+    }
+
+    /// Add a span event `message` per message received (`rpc.message.type`, `rpc.message.id` & size) and
+    /// record the number of messages as `rpc.grpc.response.messages` at the end of the stream
+    pub(crate) fn with_message_events(self) -> Self {
+        Self {
+            messages: Some(GrpcMessageCounter::default()),
+            ..self
+        }
+    }
+}
+
+impl<B> Body for ResponseBody<B>
+where
+    B: Body,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let mut inner = this.inner;
+        let result = futures_util::ready!(inner.as_mut().poll_frame(cx));
+        let frame = result.as_ref().and_then(|r| r.as_ref().ok());
+        if let Some(trailers) = frame.and_then(Frame::trailers_ref) {
+            otel_http::grpc_update_span_from_trailers(this.span, trailers, *this.error_codes);
+        }
+        if let Some(body_size) = this.body_size.as_mut() {
+            if let Some(data) = frame.and_then(Frame::data_ref) {
+                *body_size += data.remaining() as u64;
+            }
+            if result.is_none() || frame.is_some_and(Frame::is_trailers) || inner.is_end_stream() {
+                super::record_body_size(this.span, "rpc.grpc.response.body.size", *body_size);
+                *this.body_size = None;
+            }
+        }
+        if let Some(messages) = this.messages.as_mut() {
+            if let Some(data) = frame.and_then(Frame::data_ref) {
+                let span = &*this.span;
+                messages.feed(data, |id, size, compressed| {
+                    let size = i64::try_from(size).unwrap_or(i64::MAX);
+                    if compressed {
+                        tracing::event!(target: TRACING_TARGET, parent: span, TRACING_LEVEL, "rpc.message.type" = "RECEIVED", "rpc.message.id" = id, "rpc.message.compressed_size" = size, "message");
+                    } else {
+                        tracing::event!(target: TRACING_TARGET, parent: span, TRACING_LEVEL, "rpc.message.type" = "RECEIVED", "rpc.message.id" = id, "rpc.message.uncompressed_size" = size, "message");
+                    }
+                });
+            }
+            if result.is_none() || frame.is_some_and(Frame::is_trailers) || inner.is_end_stream() {
+                super::record_body_size(this.span, "rpc.grpc.response.messages", messages.count);
+                *this.messages = None;
+            }
+        }
+        Poll::Ready(result)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+pin_project! {
+    /// Request body for [`super::server::OtelGrpcService`] (with `with_message_sizes`): it counts the size of the
+    /// data received and records it as `rpc.grpc.request.body.size` at the end of the stream (or when the body is
+    /// dropped before, eg by a handler that doesn't read the whole stream).
+    pub(crate) struct RequestBody<B> {
+        #[pin]
+        inner: B,
+        span: Span,
+        // the size of the data received so far, `None` once recorded
+        body_size: Option<u64>,
+    }
+
+    impl<B> PinnedDrop for RequestBody<B> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if let Some(body_size) = this.body_size.take() {
+                super::record_body_size(this.span, "rpc.grpc.request.body.size", body_size);
+            }
+        }
+    }
+}
+
+impl<B> RequestBody<B> {
+    pub(crate) fn new(inner: B, span: Span) -> Self {
+        Self {
+            inner,
+            span,
+            body_size: Some(0),
+        }
+    }
+}
+
+impl<B> Body for RequestBody<B>
+where
+    B: Body,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let mut inner = this.inner;
+        let result = futures_util::ready!(inner.as_mut().poll_frame(cx));
+        if let Some(body_size) = this.body_size.as_mut() {
+            let frame = result.as_ref().and_then(|r| r.as_ref().ok());
+            if let Some(data) = frame.and_then(Frame::data_ref) {
+                *body_size += data.remaining() as u64;
+            }
+            if result.is_none() || frame.is_some_and(Frame::is_trailers) || inner.is_end_stream() {
+                super::record_body_size(this.span, "rpc.grpc.request.body.size", *body_size);
+                *this.body_size = None;
+            }
+        }
+        Poll::Ready(result)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Count the grpc messages of a stream of data frames (each message is prefixed by 5 bytes: the compression flag
+/// & the length of the message), the frames can contain a part of a message or several messages.
+#[derive(Debug, Default, Clone, Copy)]
+struct GrpcMessageCounter {
+    count: u64,
+    prefix: [u8; 5],
+    prefix_len: usize,
+    // the bytes of the current message not received yet
+    remaining: u64,
+    // the counting is abandoned if a frame can not be read without consuming it
+    // (a `Buf` that doesn't expose all its chunks with `chunks_vectored`)
+    lost: bool,
+}
+
+impl GrpcMessageCounter {
+    /// Read the data, `on_message(id, size, compressed)` is called at the start of each message
+    fn feed(&mut self, data: &impl Buf, mut on_message: impl FnMut(u64, u64, bool)) {
+        if self.lost {
+            return;
+        }
+        let mut chunks = vec![IoSlice::new(&[]); 16];
+        let filled = loop {
+            let filled = data.chunks_vectored(&mut chunks);
+            if chunks[..filled].iter().map(|c| c.len()).sum::<usize>() == data.remaining() {
+                break filled;
+            }
+            if filled < chunks.len() {
+                self.lost = true;
+                return;
+            }
+            // more chunks than the slots
+            chunks.resize(chunks.len() * 2, IoSlice::new(&[]));
+        };
+        for chunk in &chunks[..filled] {
+            let mut bytes: &[u8] = chunk;
+            while !bytes.is_empty() {
+                if self.remaining > 0 {
+                    let n = bytes
+                        .len()
+                        .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+                    self.remaining -= n as u64;
+                    bytes = &bytes[n..];
+                } else {
+                    let n = bytes.len().min(self.prefix.len() - self.prefix_len);
+                    self.prefix[self.prefix_len..self.prefix_len + n].copy_from_slice(&bytes[..n]);
+                    self.prefix_len += n;
+                    bytes = &bytes[n..];
+                    if self.prefix_len == self.prefix.len() {
+                        let [flag, len @ ..] = self.prefix;
+                        self.count += 1;
+                        self.prefix_len = 0;
+                        self.remaining = u64::from(u32::from_be_bytes(len));
+                        on_message(self.count, self.remaining, flag == 1);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use bytes::Bytes;
+    use rstest::rstest;
+
+    fn message(flag: u8, content: &[u8]) -> Vec<u8> {
+        let len = u32::try_from(content.len()).unwrap();
+        let mut message = vec![flag];
+        message.extend_from_slice(&len.to_be_bytes());
+        message.extend_from_slice(content);
+        message
+    }
+
+    /// Feed the `frames`, return the messages notified `(id, size, compressed)`
+    fn feed_frames(counter: &mut GrpcMessageCounter, frames: &[&[u8]]) -> Vec<(u64, u64, bool)> {
+        let mut messages = Vec::new();
+        for frame in frames {
+            counter.feed(&Bytes::copy_from_slice(frame), |id, size, compressed| {
+                messages.push((id, size, compressed));
+            });
+        }
+        messages
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    #[case(3)]
+    #[case(4)]
+    #[case(6)]
+    fn count_a_message_with_the_prefix_split_across_frames(#[case] at: usize) {
+        let data = message(0, b"hello");
+        let mut counter = GrpcMessageCounter::default();
+
+        let messages = feed_frames(&mut counter, &[&data[..at], &data[at..]]);
+
+        assert!(messages == vec![(1, 5, false)]);
+        assert!(counter.count == 1);
+        assert!(counter.remaining == 0);
+    }
+
+    #[test]
+    fn count_several_messages_in_one_frame() {
+        let data = [message(0, b"hello"), message(0, b""), message(0, b"world!")].concat();
+        let mut counter = GrpcMessageCounter::default();
+
+        let messages = feed_frames(&mut counter, &[&data]);
+
+        assert!(messages == vec![(1, 5, false), (2, 0, false), (3, 6, false)]);
+        assert!(counter.count == 3);
+    }
+
+    #[test]
+    fn read_the_compressed_flag() {
+        let data = [message(1, b"zipped"), message(0, b"raw")].concat();
+        let mut counter = GrpcMessageCounter::default();
+
+        let messages = feed_frames(&mut counter, &[&data]);
+
+        assert!(messages == vec![(1, 6, true), (2, 3, false)]);
+    }
+
+    #[test]
+    fn count_the_messages_of_a_frame_with_more_than_16_chunks() {
+        // a frame made of 40 chunks (eg a `Chain` of buffers), with a message of 1 byte per 6 bytes
+        let data = (0..40u8).flat_map(|i| message(0, &[i])).collect::<Vec<_>>();
+        let frame = data
+            .chunks(6)
+            .map(Bytes::copy_from_slice)
+            .fold(Box::new(Bytes::new()) as Box<dyn Buf>, |frame, chunk| {
+                Box::new(frame.chain(chunk))
+            });
+        let mut counter = GrpcMessageCounter::default();
+        let mut messages = 0;
+
+        counter.feed(&frame, |_, size, _| {
+            assert!(size == 1);
+            messages += 1;
+        });
+
+        assert!(!counter.lost);
+        assert!(messages == 40);
+        assert!(counter.count == 40);
+    }
+}
@@ -1,16 +1,24 @@
 //! code based on [tonic/examples/src/tower/client.rs at master · hyperium/tonic · GitHub](https://github.com/hyperium/tonic/blob/master/examples/src/tower/client.rs)
-use http::{Request, Response};
+use http::{HeaderName, Request, Response};
+use opentelemetry::propagation::TextMapPropagator;
 use pin_project_lite::pin_project;
 use std::{
     error::Error,
     future::Future,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 use tonic::client::GrpcService;
 use tower::{Layer, Service};
 use tracing::Span;
-use tracing_opentelemetry_instrumentation_sdk::{find_context_from_tracing, http as otel_http};
+
+use super::ResponseBody;
+use tracing_opentelemetry_instrumentation_sdk::{
+    find_context_from_tracing,
+    http::{self as otel_http, GrpcCode, GrpcErrorCodes, RpcSpanNamer},
+    PrivacyMode, PrivacyPolicy,
+};
 
 /// layer for grpc (tonic client):
 ///
@@ -19,19 +27,162 @@ use tracing_opentelemetry_instrumentation_sdk::{find_context_from_tracing, http
 ///
 /// `OpenTelemetry` context are extracted frim tracing's span.
 #[derive(Default, Debug, Clone)]
-pub struct OtelGrpcLayer;
+pub struct OtelGrpcLayer {
+    error_codes: Option<GrpcErrorCodes>,
+    span_namer: Option<RpcSpanNamer>,
+    request_metadata: Arc<[HeaderName]>,
+    propagator: Option<Arc<dyn TextMapPropagator + Send + Sync>>,
+    message_events: bool,
+    privacy: Arc<PrivacyPolicy>,
+}
+
+// add a builder like api
+impl OtelGrpcLayer {
+    /// Define the grpc codes that set the status of the span to `ERROR`
+    /// (by default, every code except `Ok`, as defined by the semantic conventions for the client side).
+    #[must_use]
+    pub fn with_error_codes(self, error_codes: &[GrpcCode]) -> Self {
+        OtelGrpcLayer {
+            error_codes: Some(GrpcErrorCodes::new(error_codes)),
+            ..self
+        }
+    }
+
+    /// Name the span with `span_namer` (from the service & the method), instead of
+    /// the default `$package.$service/$method` (see [`otel_http::rpc_span_name`]).
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::client::OtelGrpcLayer;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_span_namer(|_service, method| method.to_string());
+    /// ```
+    #[must_use]
+    pub fn with_span_namer(self, span_namer: RpcSpanNamer) -> Self {
+        OtelGrpcLayer {
+            span_namer: Some(span_namer),
+            ..self
+        }
+    }
+
+    /// Capture the values of the request metadata `keys` as `rpc.grpc.request.metadata.<key>` attributes
+    /// (nothing is captured by default, select only the metadata without credentials or personal data).
+    ///
+    /// ```rust
+    /// use http::HeaderName;
+    /// use tonic_tracing_opentelemetry::middleware::client::OtelGrpcLayer;
+    ///
+    /// let layer = OtelGrpcLayer::default()
+    ///     .with_request_metadata([HeaderName::from_static("x-tenant-id")]);
+    /// ```
+    #[must_use]
+    pub fn with_request_metadata(self, keys: impl IntoIterator<Item = HeaderName>) -> Self {
+        let mut request_metadata = self.request_metadata.to_vec();
+        request_metadata.extend(keys);
+        OtelGrpcLayer {
+            request_metadata: request_metadata.into(),
+            ..self
+        }
+    }
+
+    /// Same as [`OtelGrpcLayer::with_request_metadata`] from the names of the metadata,
+    /// eg to debug the calls between services (`authorization` & `cookie` are captured as `REDACTED`).
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::client::OtelGrpcLayer;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_captured_metadata(&["x-request-id", "authorization"]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If a key is not a valid name of metadata.
+    #[must_use]
+    pub fn with_captured_metadata(self, keys: &[&str]) -> Self {
+        self.with_request_metadata(keys.iter().map(|key| {
+            HeaderName::try_from(*key)
+                .unwrap_or_else(|err| panic!("invalid metadata name '{key}': {err}"))
+        }))
+    }
+
+    /// Inject the context into the requests with the `propagator` instead of the global one
+    /// (eg the format expected by the called service).
+    ///
+    /// ```rust
+    /// use opentelemetry_sdk::propagation::TraceContextPropagator;
+    /// use std::sync::Arc;
+    /// use tonic_tracing_opentelemetry::middleware::client::OtelGrpcLayer;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_propagator(Arc::new(TraceContextPropagator::new()));
+    /// ```
+    #[must_use]
+    pub fn with_propagator(self, propagator: Arc<dyn TextMapPropagator + Send + Sync>) -> Self {
+        OtelGrpcLayer {
+            propagator: Some(propagator),
+            ..self
+        }
+    }
+
+    /// Add a span event `message` per message of the response (`rpc.message.type = RECEIVED`, `rpc.message.id`
+    /// and the size), and record the number of messages as `rpc.grpc.response.messages`, eg to follow the
+    /// progress of a server streaming call (the span ends with the stream, with the status of the trailers).
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::client::OtelGrpcLayer;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_message_events(true);
+    /// ```
+    #[must_use]
+    pub fn with_message_events(self, enabled: bool) -> Self {
+        OtelGrpcLayer {
+            message_events: enabled,
+            ..self
+        }
+    }
+
+    /// Protect the attributes identifying a person (`http.user_agent`, see
+    /// [`tracing_opentelemetry_instrumentation_sdk::DEFAULT_PROTECTED_KEYS`]) recorded by this layer:
+    /// hashed with a secret salt ([`PrivacyMode::Hash`]) or not recorded ([`PrivacyMode::Drop`]).
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::client::OtelGrpcLayer;
+    /// use tracing_opentelemetry_instrumentation_sdk::PrivacyMode;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_privacy_mode(PrivacyMode::Drop);
+    /// ```
+    #[must_use]
+    pub fn with_privacy_mode(self, mode: PrivacyMode) -> Self {
+        OtelGrpcLayer {
+            privacy: Arc::new(PrivacyPolicy::new(mode)),
+            ..self
+        }
+    }
+}
 
 impl<S> Layer<S> for OtelGrpcLayer {
     /// The wrapped service
     type Service = OtelGrpcService<S>;
     fn layer(&self, inner: S) -> Self::Service {
-        OtelGrpcService { inner }
+        OtelGrpcService {
+            inner,
+            error_codes: self.error_codes,
+            span_namer: self.span_namer,
+            request_metadata: self.request_metadata.clone(),
+            propagator: self.propagator.clone(),
+            message_events: self.message_events,
+            privacy: self.privacy.clone(),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct OtelGrpcService<S> {
     inner: S,
+    error_codes: Option<GrpcErrorCodes>,
+    span_namer: Option<RpcSpanNamer>,
+    request_metadata: Arc<[HeaderName]>,
+    propagator: Option<Arc<dyn TextMapPropagator + Send + Sync>>,
+    message_events: bool,
+    privacy: Arc<PrivacyPolicy>,
 }
 
 impl<S, B, B2> Service<Request<B>> for OtelGrpcService<S>
@@ -43,7 +194,7 @@ where
     // B2: tonic::codegen::Body,
     B2: http_body::Body,
 {
-    type Response = Response<B2>;
+    type Response = Response<ResponseBody<B2>>;
     type Error = S::Error;
     type Future = ResponseFuture<S::Future>;
     // #[allow(clippy::type_complexity)]
@@ -61,15 +212,35 @@ where
         // let clone = self.inner.clone();
         // let mut inner = std::mem::replace(&mut self.inner, clone);
         let mut req = req;
-        let span = otel_http::grpc_client::make_span_from_request(&req);
-        otel_http::inject_context(&find_context_from_tracing(&span), req.headers_mut());
+        let span = otel_http::grpc_client::make_span_from_request_with_rpc_system(
+            &req,
+            "grpc",
+            &self.privacy,
+        );
+        super::update_span_from_request(&span, &req, self.span_namer, &self.request_metadata);
+        if !span.is_disabled() {
+            let context = find_context_from_tracing(&span);
+            match &self.propagator {
+                Some(propagator) => otel_http::inject_context_with_propagator(
+                    propagator.as_ref(),
+                    &context,
+                    req.headers_mut(),
+                ),
+                None => otel_http::inject_context(&context, req.headers_mut()),
+            }
+        }
         let future = {
             let _enter = span.enter();
             self.inner.call(req)
         };
+        let message_events = self.message_events && !span.is_disabled();
         ResponseFuture {
             inner: future,
             span,
+            error_codes: self
+                .error_codes
+                .unwrap_or_else(|| GrpcErrorCodes::semconv(false)),
+            message_events,
         }
     }
 }
@@ -82,6 +253,8 @@ pin_project! {
         #[pin]
         pub(crate) inner: F,
         pub(crate) span: Span,
+        pub(crate) error_codes: GrpcErrorCodes,
+        pub(crate) message_events: bool,
         // pub(crate) start: Instant,
     }
 }
@@ -91,13 +264,154 @@ where
     Fut: Future<Output = Result<Response<ResBody>, E>>,
     E: std::error::Error + 'static,
 {
-    type Output = Result<Response<ResBody>, E>;
+    type Output = Result<Response<ResponseBody<ResBody>>, E>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
         let _guard = this.span.enter();
         let result = futures_util::ready!(this.inner.poll(cx));
-        otel_http::grpc_client::update_span_from_response_or_error(this.span, &result);
-        Poll::Ready(result)
+        otel_http::grpc_client::update_span_from_response_or_error_with_error_codes(
+            this.span,
+            &result,
+            *this.error_codes,
+        );
+        Poll::Ready(result.map(|response| {
+            response.map(|body| {
+                let body = ResponseBody::new(body, this.span.clone(), *this.error_codes);
+                if *this.message_events {
+                    body.with_message_events()
+                } else {
+                    body
+                }
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::testing::{Echo, RawCodec};
+    use assert2::{assert, let_assert};
+    use fake_opentelemetry_collector::{AttrValue, StatusCode};
+    use std::convert::Infallible;
+    use testing_tracing_opentelemetry::FakeEnvironment;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn record_the_messages_of_a_server_streaming_call() {
+        let mut fake_env = FakeEnvironment::setup().await;
+        {
+            let svc = OtelGrpcLayer::default()
+                .with_message_events(true)
+                .layer(Echo::<Infallible>::new());
+            let mut client = tonic::client::Grpc::new(svc);
+            client.ready().await.unwrap();
+            let_assert!(
+                Ok(response) = client
+                    .server_streaming(
+                        tonic::Request::new(vec![1u8, 2, 3]),
+                        http::uri::PathAndQuery::from_static("/test.Echo/Split"),
+                        RawCodec,
+                    )
+                    .await
+            );
+            let mut stream = response.into_inner();
+            let mut messages = Vec::new();
+            while let Some(message) = stream.message().await.unwrap() {
+                messages.extend(message);
+            }
+            assert!(messages == vec![1, 2, 3]);
+        }
+        let (_, otel_spans) = fake_env.collect_traces().await;
+        let_assert!([span] = otel_spans.as_slice());
+        assert!(span.name == "test.Echo/Split");
+        assert!(span.attributes.get("rpc.grpc.response.messages") == Some(&AttrValue::from(3_i64)));
+        // the status of the trailers, at the end of the stream
+        assert!(span.attributes.get("rpc.grpc.status_code") == Some(&AttrValue::from("0")));
+        let ids = span
+            .events
+            .iter()
+            .filter(|event| event.name == "message")
+            .map(|event| {
+                event
+                    .attributes
+                    .get("rpc.message.id")
+                    .and_then(AttrValue::as_str)
+            })
+            .collect::<Vec<_>>();
+        assert!(ids == vec![Some("1"), Some("2"), Some("3")]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn record_the_status_of_the_trailers() {
+        let mut fake_env = FakeEnvironment::setup().await;
+        {
+            let svc = OtelGrpcLayer::default().layer(Echo::<Infallible>::new());
+            let mut client = tonic::client::Grpc::new(svc);
+            client.ready().await.unwrap();
+            let_assert!(
+                Ok(response) = client
+                    .server_streaming(
+                        tonic::Request::new(vec![1u8, 0]),
+                        http::uri::PathAndQuery::from_static("/test.Echo/Split"),
+                        RawCodec,
+                    )
+                    .await
+            );
+            let mut stream = response.into_inner();
+            let_assert!(Ok(Some(_)) = stream.message().await);
+            let_assert!(Err(status) = stream.message().await);
+            assert!(status.code() == tonic::Code::Internal);
+        }
+        let (_, otel_spans) = fake_env.collect_traces().await;
+        let_assert!([span] = otel_spans.as_slice());
+        assert!(span.attributes.get("rpc.grpc.status_code") == Some(&AttrValue::from("13")));
+        assert!(span.attributes.get("exception.message") == Some(&AttrValue::from("zero")));
+        assert!(span.status_code() == StatusCode::Error);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn name_the_span_and_capture_the_metadata() {
+        let mut fake_env = FakeEnvironment::setup().await;
+        {
+            let svc = OtelGrpcLayer::default()
+                .with_span_namer(|service, method| format!("{method} of {service}"))
+                .with_captured_metadata(&["x-tenant-id", "authorization"])
+                .layer(Echo::<Infallible>::new());
+            let mut client = tonic::client::Grpc::new(svc);
+            client.ready().await.unwrap();
+            let mut request = tonic::Request::new(vec![1u8; 10]);
+            request
+                .metadata_mut()
+                .insert("x-tenant-id", "acme".parse().unwrap());
+            request
+                .metadata_mut()
+                .insert("authorization", "Bearer s3cr3t".parse().unwrap());
+            request
+                .metadata_mut()
+                .insert("x-not-captured", "value".parse().unwrap());
+            let_assert!(
+                Ok(_) = client
+                    .unary(
+                        request,
+                        http::uri::PathAndQuery::from_static("/test.Echo/Reverse"),
+                        RawCodec,
+                    )
+                    .await
+            );
+        }
+        let (_, otel_spans) = fake_env.collect_traces().await;
+        let_assert!([span] = otel_spans.as_slice());
+        assert!(span.name == "Reverse of test.Echo");
+        let metadata = |key: &str| {
+            span.attributes
+                .get(&format!("rpc.grpc.request.metadata.{key}"))
+                .cloned()
+        };
+        assert!(metadata("x-tenant-id") == Some(AttrValue::Array(vec![AttrValue::from("acme")])));
+        assert!(
+            metadata("authorization") == Some(AttrValue::Array(vec![AttrValue::from("REDACTED")]))
+        );
+        assert!(metadata("x-not-captured").is_none());
     }
 }
@@ -1,3 +1,48 @@
+mod body;
 pub mod client;
 pub mod filters;
 pub mod server;
+#[cfg(test)]
+mod testing;
+
+pub use body::ResponseBody;
+
+use http::HeaderName;
+use tracing_opentelemetry_instrumentation_sdk::http::{self as otel_http, RpcSpanNamer};
+
+/// Apply the options of the layers (common to server & client) on the span of the request
+fn update_span_from_request<B>(
+    span: &tracing::Span,
+    req: &http::Request<B>,
+    span_namer: Option<RpcSpanNamer>,
+    request_metadata: &[HeaderName],
+) {
+    if span.is_disabled() {
+        return;
+    }
+    if let Some(span_namer) = span_namer {
+        let (service, method) = otel_http::extract_rpc_service_method(req.uri());
+        span.record("otel.name", span_namer(service, method));
+    }
+    otel_http::grpc_record_request_metadata(span, req.headers(), request_metadata);
+}
+
+/// Record the time spent waiting for the readiness of the inner service (eg a buffer or a concurrency limit is full)
+/// before the call, as `service.ready_wait` (in seconds).
+fn record_ready_wait(span: &tracing::Span, ready_wait: std::time::Duration) {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    if !span.is_disabled() {
+        span.set_attribute("service.ready_wait", ready_wait.as_secs_f64());
+    }
+}
+
+/// Record the size (in bytes) of a body (or a number of messages) as the attribute `name`
+/// (eg `rpc.grpc.request.body.size`)
+fn record_body_size(span: &tracing::Span, name: &'static str, size: u64) {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    if let Ok(size) = i64::try_from(size) {
+        span.set_attribute(name, size);
+    }
+}
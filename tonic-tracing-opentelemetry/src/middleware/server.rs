@@ -1,14 +1,26 @@
 //! code based on [tonic/examples/src/tower/client.rs at master · hyperium/tonic · GitHub](https://github.com/hyperium/tonic/blob/master/examples/src/tower/client.rs)
-use http::{Request, Response};
+use http::{HeaderName, Request, Response};
+use opentelemetry::propagation::TextMapPropagator;
 use pin_project_lite::pin_project;
 use std::{
     future::Future,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
 use tower::{BoxError, Layer, Service};
 use tracing::Span;
-use tracing_opentelemetry_instrumentation_sdk::http as otel_http;
+
+use super::body::RequestBody;
+use super::ResponseBody;
+use tracing_opentelemetry_instrumentation_sdk::http::{
+    self as otel_http, ForceSamplingFor, ForceTraceConfig, GrpcCode, GrpcErrorCodes, RpcSpanNamer,
+};
+use tracing_opentelemetry_instrumentation_sdk::{
+    force_sampling, mark_span_for_recording_gate, DroppedSpanHook, DroppedSpanReason, PrivacyMode,
+    PrivacyPolicy,
+};
 
 pub type Filter = fn(&str) -> bool;
 
@@ -21,6 +33,17 @@ pub type Filter = fn(&str) -> bool;
 #[derive(Default, Debug, Clone)]
 pub struct OtelGrpcLayer {
     filter: Option<Filter>,
+    error_codes: Option<GrpcErrorCodes>,
+    span_namer: Option<RpcSpanNamer>,
+    request_metadata: Arc<[HeaderName]>,
+    recording_gate: Option<Duration>,
+    message_sizes: bool,
+    propagator: Option<Arc<dyn TextMapPropagator + Send + Sync>>,
+    minimal_span_services: Arc<[String]>,
+    force_sampling_for: Option<ForceSamplingFor>,
+    force_trace: Option<ForceTraceConfig>,
+    dropped_span_hook: Option<DroppedSpanHook>,
+    privacy: Arc<PrivacyPolicy>,
 }
 
 // add a builder like api
@@ -29,6 +52,222 @@ impl OtelGrpcLayer {
     pub fn filter(self, filter: Filter) -> Self {
         OtelGrpcLayer {
             filter: Some(filter),
+            ..self
+        }
+    }
+
+    /// Define the grpc codes that set the status of the span to `ERROR`
+    /// (by default, the codes defined by the semantic conventions for the server side).
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::server::OtelGrpcLayer;
+    /// use tracing_opentelemetry_instrumentation_sdk::http::GrpcCode;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_error_codes(&[
+    ///     GrpcCode::NotFound,
+    ///     GrpcCode::Internal,
+    ///     GrpcCode::Unavailable,
+    /// ]);
+    /// ```
+    #[must_use]
+    pub fn with_error_codes(self, error_codes: &[GrpcCode]) -> Self {
+        OtelGrpcLayer {
+            error_codes: Some(GrpcErrorCodes::new(error_codes)),
+            ..self
+        }
+    }
+
+    /// Name the span with `span_namer` (from the service & the method), instead of
+    /// the default `$package.$service/$method` (see [`otel_http::rpc_span_name`]).
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::server::OtelGrpcLayer;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_span_namer(|_service, method| method.to_string());
+    /// ```
+    #[must_use]
+    pub fn with_span_namer(self, span_namer: RpcSpanNamer) -> Self {
+        OtelGrpcLayer {
+            span_namer: Some(span_namer),
+            ..self
+        }
+    }
+
+    /// Capture the values of the request metadata `keys` as `rpc.grpc.request.metadata.<key>` attributes
+    /// (nothing is captured by default, select only the metadata without credentials or personal data).
+    ///
+    /// ```rust
+    /// use http::HeaderName;
+    /// use tonic_tracing_opentelemetry::middleware::server::OtelGrpcLayer;
+    ///
+    /// let layer = OtelGrpcLayer::default()
+    ///     .with_request_metadata([HeaderName::from_static("x-tenant-id")]);
+    /// ```
+    #[must_use]
+    pub fn with_request_metadata(self, keys: impl IntoIterator<Item = HeaderName>) -> Self {
+        let mut request_metadata = self.request_metadata.to_vec();
+        request_metadata.extend(keys);
+        OtelGrpcLayer {
+            request_metadata: request_metadata.into(),
+            ..self
+        }
+    }
+
+    /// Only export the traces of the requests that end with an error or last at least `latency_threshold`,
+    /// the decision is made by the `RecordingGateSpanProcessor` of `init-tracing-opentelemetry`
+    /// (see [`tracing_opentelemetry_instrumentation_sdk::RECORDING_GATE_ATTRIBUTE`]), enabled with
+    /// `TracingConfig::with_recording_gate` (else the span is exported as usual, with the mark).
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use tonic_tracing_opentelemetry::middleware::server::OtelGrpcLayer;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_recording_gate(Duration::from_millis(500));
+    /// ```
+    #[must_use]
+    pub fn with_recording_gate(self, latency_threshold: Duration) -> Self {
+        OtelGrpcLayer {
+            recording_gate: Some(latency_threshold),
+            ..self
+        }
+    }
+
+    /// Record the size (in bytes) of the messages as `rpc.grpc.request.body.size` & `rpc.grpc.response.body.size`
+    /// (for a streaming call, the total of the messages), to make the regressions of payload size visible.
+    ///
+    /// The size of the request is counted while it's received (the request body is wrapped, so the body of the
+    /// requests should be a `tonic::body::BoxBody`, like with `tonic::transport::Server`), the size of the response
+    /// is counted while it's sent.
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::server::OtelGrpcLayer;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_message_sizes(true);
+    /// ```
+    #[must_use]
+    pub fn with_message_sizes(self, enabled: bool) -> Self {
+        OtelGrpcLayer {
+            message_sizes: enabled,
+            ..self
+        }
+    }
+
+    /// Extract the context of the requests with the `propagator` instead of the global one.
+    ///
+    /// ```rust
+    /// use opentelemetry_sdk::propagation::TraceContextPropagator;
+    /// use std::sync::Arc;
+    /// use tonic_tracing_opentelemetry::middleware::server::OtelGrpcLayer;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_propagator(Arc::new(TraceContextPropagator::new()));
+    /// ```
+    #[must_use]
+    pub fn with_propagator(self, propagator: Arc<dyn TextMapPropagator + Send + Sync>) -> Self {
+        OtelGrpcLayer {
+            propagator: Some(propagator),
+            ..self
+        }
+    }
+
+    /// Create a minimal span for the calls of the `services` (eg health checks, reflection),
+    /// instead of suppressing them with [`OtelGrpcLayer::filter`]:
+    ///
+    /// - only `rpc.system`, `rpc.service`, `rpc.method` & the status are recorded
+    ///   (no metadata, user agent, message sizes,...)
+    /// - the span is created only when the parent (from the request) is sampled,
+    ///   so the calls are visible into the traces of the sampled upstream calls, but never start a trace
+    ///   (the calls without span are reported to the [`OtelGrpcLayer::with_dropped_span_hook`] as `Sampling`)
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::server::OtelGrpcLayer;
+    ///
+    /// let layer = OtelGrpcLayer::default()
+    ///     .with_minimal_spans_for(["grpc.health.v1.Health", "grpc.reflection.v1.ServerReflection"]);
+    /// ```
+    #[must_use]
+    pub fn with_minimal_spans_for<T>(self, services: impl IntoIterator<Item = T>) -> Self
+    where
+        T: Into<String>,
+    {
+        let mut minimal_span_services = self.minimal_span_services.to_vec();
+        minimal_span_services.extend(services.into_iter().map(Into::into));
+        OtelGrpcLayer {
+            minimal_span_services: minimal_span_services.into(),
+            ..self
+        }
+    }
+
+    /// Force the sampling of the calls selected by `force_sampling_for` (eg with the metadata `x-debug-trace: 1`),
+    /// whatever the decision of the sampler of the sdk, to debug-trace a call on demand without changing
+    /// the global sampling (see [`tracing_opentelemetry_instrumentation_sdk::force_sampling`]).
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::server::OtelGrpcLayer;
+    /// use tracing_opentelemetry_instrumentation_sdk::http::has_debug_trace_header;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_force_sampling_for(has_debug_trace_header);
+    /// ```
+    #[must_use]
+    pub fn with_force_sampling_for(self, force_sampling_for: ForceSamplingFor) -> Self {
+        OtelGrpcLayer {
+            force_sampling_for: Some(force_sampling_for),
+            ..self
+        }
+    }
+
+    /// Force the sampling of the calls with the force-trace metadata of `config` (with the secret),
+    /// like [`OtelGrpcLayer::with_force_sampling_for`], and propagate the metadata to the downstream services
+    /// of the trace (see [`ForceTraceConfig`]).
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::server::OtelGrpcLayer;
+    /// use tracing_opentelemetry_instrumentation_sdk::http::ForceTraceConfig;
+    ///
+    /// let config = ForceTraceConfig::new("s3cr3t").expect("a valid metadata value");
+    /// let layer = OtelGrpcLayer::default().with_force_trace(config);
+    /// ```
+    #[must_use]
+    pub fn with_force_trace(self, config: ForceTraceConfig) -> Self {
+        OtelGrpcLayer {
+            force_trace: Some(config),
+            ..self
+        }
+    }
+
+    /// Call the `hook` for each call without span (rejected by the [`OtelGrpcLayer::filter`],...),
+    /// eg to count them with the self-metrics of `init-tracing-opentelemetry` (`self_metrics::dropped_span_hook`).
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::server::OtelGrpcLayer;
+    /// use tracing_opentelemetry_instrumentation_sdk::DroppedSpanHook;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_dropped_span_hook(DroppedSpanHook::new(|reason| {
+    ///     tracing::debug!(reason = reason.as_str(), "span dropped");
+    /// }));
+    /// ```
+    #[must_use]
+    pub fn with_dropped_span_hook(self, hook: DroppedSpanHook) -> Self {
+        OtelGrpcLayer {
+            dropped_span_hook: Some(hook),
+            ..self
+        }
+    }
+
+    /// Protect the attributes identifying a person (`http.user_agent`, see
+    /// [`tracing_opentelemetry_instrumentation_sdk::DEFAULT_PROTECTED_KEYS`]) recorded by this layer:
+    /// hashed with a secret salt ([`PrivacyMode::Hash`]) or not recorded ([`PrivacyMode::Drop`]).
+    ///
+    /// ```rust
+    /// use tonic_tracing_opentelemetry::middleware::server::OtelGrpcLayer;
+    /// use tracing_opentelemetry_instrumentation_sdk::PrivacyMode;
+    ///
+    /// let layer = OtelGrpcLayer::default().with_privacy_mode(PrivacyMode::Drop);
+    /// ```
+    #[must_use]
+    pub fn with_privacy_mode(self, mode: PrivacyMode) -> Self {
+        OtelGrpcLayer {
+            privacy: Arc::new(PrivacyPolicy::new(mode)),
+            ..self
         }
     }
 }
@@ -40,6 +279,18 @@ impl<S> Layer<S> for OtelGrpcLayer {
         OtelGrpcService {
             inner,
             filter: self.filter,
+            error_codes: self.error_codes,
+            span_namer: self.span_namer,
+            request_metadata: self.request_metadata.clone(),
+            recording_gate: self.recording_gate,
+            message_sizes: self.message_sizes,
+            propagator: self.propagator.clone(),
+            minimal_span_services: self.minimal_span_services.clone(),
+            force_sampling_for: self.force_sampling_for,
+            force_trace: self.force_trace.clone(),
+            dropped_span_hook: self.dropped_span_hook.clone(),
+            privacy: self.privacy.clone(),
+            ready_wait_start: None,
         }
     }
 }
@@ -48,15 +299,37 @@ impl<S> Layer<S> for OtelGrpcLayer {
 pub struct OtelGrpcService<S> {
     inner: S,
     filter: Option<Filter>,
+    error_codes: Option<GrpcErrorCodes>,
+    span_namer: Option<RpcSpanNamer>,
+    request_metadata: Arc<[HeaderName]>,
+    recording_gate: Option<Duration>,
+    message_sizes: bool,
+    propagator: Option<Arc<dyn TextMapPropagator + Send + Sync>>,
+    minimal_span_services: Arc<[String]>,
+    force_sampling_for: Option<ForceSamplingFor>,
+    force_trace: Option<ForceTraceConfig>,
+    dropped_span_hook: Option<DroppedSpanHook>,
+    privacy: Arc<PrivacyPolicy>,
+    /// since when the inner service is not ready (backpressure), for the next call
+    ready_wait_start: Option<Instant>,
+}
+
+impl<S> OtelGrpcService<S> {
+    fn record_dropped_span(&self, reason: DroppedSpanReason) {
+        if let Some(hook) = &self.dropped_span_hook {
+            hook.record(reason);
+        }
+    }
 }
 
 impl<S, B, B2> Service<Request<B>> for OtelGrpcService<S>
 where
     S: Service<Request<B>, Response = Response<B2>, Error = BoxError> + Clone + Send + 'static,
     S::Future: Send + 'static,
-    B: Send + 'static,
+    B: http_body::Body<Data = hyper::body::Bytes> + From<tonic::body::BoxBody> + Send + 'static,
+    B::Error: Into<BoxError>,
 {
-    type Response = S::Response;
+    type Response = Response<ResponseBody<B2>>;
     type Error = S::Error;
     type Future = ResponseFuture<S::Future>;
     // #[allow(clippy::type_complexity)]
@@ -67,7 +340,11 @@ where
     //type Future = Inspect<S::Future, Box<dyn FnOnce(S::Response)>>;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.inner.poll_ready(cx).map_err(Into::into)
+        let poll = self.inner.poll_ready(cx);
+        if poll.is_pending() {
+            self.ready_wait_start.get_or_insert_with(Instant::now);
+        }
+        poll.map_err(Into::into)
     }
 
     fn call(&mut self, req: Request<B>) -> Self::Future {
@@ -77,13 +354,69 @@ where
         // for details on why this is necessary
         // let clone = self.inner.clone();
         // let mut inner = std::mem::replace(&mut self.inner, clone);
+        use opentelemetry::trace::TraceContextExt;
         let req = req;
-        let span = if self.filter.map_or(true, |f| f(req.uri().path())) {
-            let span = otel_http::grpc_server::make_span_from_request(&req);
-            span.set_parent(otel_http::extract_context(req.headers()));
+        let extract_context = |headers| match &self.propagator {
+            Some(propagator) => {
+                otel_http::extract_context_with_propagator(propagator.as_ref(), headers)
+            }
+            None => otel_http::extract_context(headers),
+        };
+        let minimal_span = !self.minimal_span_services.is_empty() && {
+            let (service, _) = otel_http::extract_rpc_service_method(req.uri());
+            self.minimal_span_services.iter().any(|s| s == service)
+        };
+        let span = if !self.filter.map_or(true, |f| f(req.uri().path())) {
+            self.record_dropped_span(DroppedSpanReason::Filter);
+            tracing::Span::none()
+        } else if minimal_span {
+            let context = extract_context(req.headers());
+            if context.span().span_context().is_sampled() {
+                let span = otel_http::grpc_server::make_minimal_span_from_request(&req);
+                span.set_parent(context);
+                span
+            } else {
+                self.record_dropped_span(DroppedSpanReason::Sampling);
+                tracing::Span::none()
+            }
+        } else {
+            let span = otel_http::grpc_server::make_span_from_request_with_rpc_system(
+                &req,
+                "grpc",
+                &self.privacy,
+            );
+            super::update_span_from_request(&span, &req, self.span_namer, &self.request_metadata);
+            span.set_parent(extract_context(req.headers()));
+            if let Some(config) = self
+                .force_trace
+                .as_ref()
+                .filter(|config| config.matches(req.headers()))
+            {
+                otel_http::force_trace(&span, config);
+            } else if self
+                .force_sampling_for
+                .is_some_and(|f| f(req.uri(), req.headers()))
+            {
+                force_sampling(&span);
+            }
+            if let Some(latency_threshold) = self.recording_gate {
+                mark_span_for_recording_gate(&span, latency_threshold);
+            }
             span
+        };
+        if let Some(ready_wait_start) = self.ready_wait_start.take() {
+            super::record_ready_wait(&span, ready_wait_start.elapsed());
+        }
+        let message_sizes = self.message_sizes && !minimal_span && !span.is_disabled();
+        let deadline = (!minimal_span && !span.is_disabled())
+            .then(|| otel_http::grpc_server::grpc_timeout(req.headers()))
+            .flatten()
+            .map(|timeout| (Instant::now(), timeout));
+        let req = if message_sizes {
+            let span = span.clone();
+            req.map(|body| B::from(tonic::body::boxed(RequestBody::new(body, span))))
         } else {
-            tracing::Span::none()
+            req
         };
         let future = {
             let _enter = span.enter();
@@ -92,6 +425,12 @@ where
         ResponseFuture {
             inner: future,
             span,
+            error_codes: self
+                .error_codes
+                .unwrap_or_else(|| GrpcErrorCodes::semconv(true)),
+            message_sizes,
+            deadline,
+            completed: false,
         }
     }
 }
@@ -104,21 +443,250 @@ pin_project! {
         #[pin]
         pub(crate) inner: F,
         pub(crate) span: Span,
+        pub(crate) error_codes: GrpcErrorCodes,
+        pub(crate) message_sizes: bool,
+        // the start of the processing & the timeout of the call (`grpc-timeout`)
+        pub(crate) deadline: Option<(Instant, Duration)>,
+        pub(crate) completed: bool,
         // pub(crate) start: Instant,
     }
+
+    impl<F> PinnedDrop for ResponseFuture<F> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if !*this.completed && !this.span.is_disabled() {
+                otel_http::grpc_server::update_span_from_cancellation(this.span, *this.error_codes);
+                record_deadline(this.span, *this.deadline);
+            }
+        }
+    }
 }
 
 impl<Fut, ResBody> Future for ResponseFuture<Fut>
 where
     Fut: Future<Output = Result<Response<ResBody>, BoxError>>,
 {
-    type Output = Result<Response<ResBody>, BoxError>;
+    type Output = Result<Response<ResponseBody<ResBody>>, BoxError>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
         let _guard = this.span.enter();
         let result = futures_util::ready!(this.inner.poll(cx));
-        otel_http::grpc_server::update_span_from_response_or_error(this.span, &result);
-        Poll::Ready(result)
+        *this.completed = true;
+        record_deadline(this.span, *this.deadline);
+        otel_http::grpc_server::update_span_from_response_or_error_with_error_codes(
+            this.span,
+            &result,
+            *this.error_codes,
+        );
+        Poll::Ready(result.map(|response| {
+            response.map(|body| {
+                let body = ResponseBody::new(body, this.span.clone(), *this.error_codes);
+                if *this.message_sizes {
+                    body.with_body_size()
+                } else {
+                    body
+                }
+            })
+        }))
+    }
+}
+
+fn record_deadline(span: &Span, deadline: Option<(Instant, Duration)>) {
+    if let Some((start, timeout)) = deadline {
+        otel_http::grpc_server::update_span_from_deadline(span, timeout, start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::testing::{Echo, RawCodec};
+    use assert2::{assert, let_assert};
+    use fake_opentelemetry_collector::{AttrValue, StatusCode};
+    use testing_tracing_opentelemetry::FakeEnvironment;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn record_the_size_of_the_messages_without_content_length() {
+        let mut fake_env = FakeEnvironment::setup().await;
+        {
+            // the client sends the request as a stream (without `content-length`)
+            let svc = OtelGrpcLayer::default()
+                .with_message_sizes(true)
+                .layer(Echo::<BoxError>::new());
+            let mut client = tonic::client::Grpc::new(svc);
+            client.ready().await.unwrap();
+            let_assert!(
+                Ok(response) = client
+                    .unary(
+                        tonic::Request::new(vec![1u8; 100]),
+                        http::uri::PathAndQuery::from_static("/test.Echo/Reverse"),
+                        RawCodec,
+                    )
+                    .await
+            );
+            assert!(response.into_inner().len() == 100);
+        }
+        let (_, otel_spans) = fake_env.collect_traces().await;
+        let_assert!([span] = otel_spans.as_slice());
+        assert!(span.name == "test.Echo/Reverse");
+        // the message (100 bytes) & its prefix (5 bytes)
+        assert!(
+            span.attributes.get("rpc.grpc.request.body.size") == Some(&AttrValue::from(105_i64))
+        );
+        assert!(
+            span.attributes.get("rpc.grpc.response.body.size") == Some(&AttrValue::from(105_i64))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn record_the_status_of_the_trailers() {
+        let mut fake_env = FakeEnvironment::setup().await;
+        {
+            let svc = OtelGrpcLayer::default().layer(Echo::<BoxError>::new());
+            let mut client = tonic::client::Grpc::new(svc);
+            client.ready().await.unwrap();
+            let_assert!(
+                Ok(response) = client
+                    .server_streaming(
+                        tonic::Request::new(vec![1u8, 0]),
+                        http::uri::PathAndQuery::from_static("/test.Echo/Split"),
+                        RawCodec,
+                    )
+                    .await
+            );
+            let mut stream = response.into_inner();
+            let_assert!(Ok(Some(_)) = stream.message().await);
+            let_assert!(Err(status) = stream.message().await);
+            assert!(status.code() == tonic::Code::Internal);
+        }
+        let (_, otel_spans) = fake_env.collect_traces().await;
+        let_assert!([span] = otel_spans.as_slice());
+        // the response (headers) is OK, the status is sent into the trailers
+        assert!(span.attributes.get("rpc.grpc.status_code") == Some(&AttrValue::from("13")));
+        assert!(span.attributes.get("exception.message") == Some(&AttrValue::from("zero")));
+        assert!(span.status_code() == StatusCode::Error);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn create_a_minimal_span_only_under_a_sampled_parent() {
+        let mut fake_env = FakeEnvironment::setup().await;
+        let dropped = Arc::new(std::sync::Mutex::new(Vec::new()));
+        {
+            let hook = {
+                let dropped = dropped.clone();
+                DroppedSpanHook::new(move |reason| dropped.lock().unwrap().push(reason))
+            };
+            let svc = OtelGrpcLayer::default()
+                .with_minimal_spans_for(["test.Echo"])
+                .with_message_sizes(true)
+                .with_request_metadata([HeaderName::from_static("x-tenant-id")])
+                .with_dropped_span_hook(hook)
+                .layer(Echo::<BoxError>::new());
+            let mut client = tonic::client::Grpc::new(svc);
+            for traceparent in [
+                Some("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"),
+                Some("00-0af7651916cd43dd8448eb211c80319d-b7ad6b7169203331-00"),
+                None,
+            ] {
+                let mut request = tonic::Request::new(vec![1u8; 100]);
+                request
+                    .metadata_mut()
+                    .insert("x-tenant-id", "acme".parse().unwrap());
+                if let Some(traceparent) = traceparent {
+                    request
+                        .metadata_mut()
+                        .insert("traceparent", traceparent.parse().unwrap());
+                }
+                client.ready().await.unwrap();
+                let_assert!(
+                    Ok(_) = client
+                        .unary(
+                            request,
+                            http::uri::PathAndQuery::from_static("/test.Echo/Reverse"),
+                            RawCodec,
+                        )
+                        .await
+                );
+            }
+        }
+        let (_, otel_spans) = fake_env.collect_traces().await;
+        let_assert!([span] = otel_spans.as_slice());
+        assert!(span.trace_id == "0af7651916cd43dd8448eb211c80319c");
+        assert!(span.parent_span_id == "b7ad6b7169203331");
+        assert!(span.name == "test.Echo/Reverse");
+        assert!(span.attributes.get("rpc.grpc.status_code") == Some(&AttrValue::from("0")));
+        // only the reduced attributes (+ the attributes of `tracing-opentelemetry`): no metadata,
+        // no user agent, no message sizes
+        let unexpected = span
+            .attributes
+            .keys()
+            .filter(|key| {
+                !key.starts_with("code.")
+                    && !key.starts_with("thread.")
+                    && !matches!(
+                        key.as_str(),
+                        "busy_ns"
+                            | "idle_ns"
+                            | "rpc.system"
+                            | "rpc.service"
+                            | "rpc.method"
+                            | "rpc.grpc.status_code"
+                    )
+            })
+            .collect::<Vec<_>>();
+        assert!(unexpected.is_empty());
+        assert!(
+            *dropped.lock().unwrap()
+                == vec![DroppedSpanReason::Sampling, DroppedSpanReason::Sampling]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn name_the_span_and_capture_the_metadata() {
+        let mut fake_env = FakeEnvironment::setup().await;
+        {
+            let svc = OtelGrpcLayer::default()
+                .with_span_namer(|service, method| format!("{method} of {service}"))
+                .with_request_metadata([
+                    HeaderName::from_static("x-tenant-id"),
+                    HeaderName::from_static("authorization"),
+                ])
+                .layer(Echo::<BoxError>::new());
+            let mut client = tonic::client::Grpc::new(svc);
+            client.ready().await.unwrap();
+            let mut request = tonic::Request::new(vec![1u8; 10]);
+            request
+                .metadata_mut()
+                .insert("x-tenant-id", "acme".parse().unwrap());
+            request
+                .metadata_mut()
+                .insert("authorization", "Bearer s3cr3t".parse().unwrap());
+            request
+                .metadata_mut()
+                .insert("x-not-captured", "value".parse().unwrap());
+            let_assert!(
+                Ok(_) = client
+                    .unary(
+                        request,
+                        http::uri::PathAndQuery::from_static("/test.Echo/Reverse"),
+                        RawCodec,
+                    )
+                    .await
+            );
+        }
+        let (_, otel_spans) = fake_env.collect_traces().await;
+        let_assert!([span] = otel_spans.as_slice());
+        assert!(span.name == "Reverse of test.Echo");
+        let metadata = |key: &str| {
+            span.attributes
+                .get(&format!("rpc.grpc.request.metadata.{key}"))
+                .cloned()
+        };
+        assert!(metadata("x-tenant-id") == Some(AttrValue::Array(vec![AttrValue::from("acme")])));
+        assert!(
+            metadata("authorization") == Some(AttrValue::Array(vec![AttrValue::from("REDACTED")]))
+        );
+        assert!(metadata("x-not-captured").is_none());
     }
 }
@@ -0,0 +1,131 @@
+//! A grpc service to test the layers (server & client) without generated code.
+use bytes::{Buf, BufMut};
+use http::{Request, Response};
+use std::marker::PhantomData;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::server::{NamedService, ServerStreamingService, UnaryService};
+use tonic::Status;
+use tower::Service;
+
+/// A codec of raw bytes (no protobuf), to call a service without generated code
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RawCodec;
+
+impl Codec for RawCodec {
+    type Encode = Vec<u8>;
+    type Decode = Vec<u8>;
+    type Encoder = RawCodec;
+    type Decoder = RawCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        RawCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        RawCodec
+    }
+}
+
+impl Encoder for RawCodec {
+    type Item = Vec<u8>;
+    type Error = Status;
+
+    fn encode(&mut self, item: Vec<u8>, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        dst.put_slice(&item);
+        Ok(())
+    }
+}
+
+impl Decoder for RawCodec {
+    type Item = Vec<u8>;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Vec<u8>>, Status> {
+        Ok(Some(src.copy_to_bytes(src.remaining()).to_vec()))
+    }
+}
+
+/// The service `test.Echo`, with the methods:
+///
+/// - `Reverse` (unary): the response has the size of the request
+/// - `Split` (server streaming): a message per byte of the request, the stream ends with
+///   the status `INTERNAL` (into the trailers) at the first byte `0`
+///
+/// The error type `E` of the service is `BoxError` for the server layer, a `std::error::Error` for the client layer.
+#[derive(Debug)]
+pub(crate) struct Echo<E>(PhantomData<fn() -> E>);
+
+impl<E> Echo<E> {
+    pub(crate) fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E> Clone for Echo<E> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<E> NamedService for Echo<E> {
+    const NAME: &'static str = "test.Echo";
+}
+
+struct Reverse;
+
+impl UnaryService<Vec<u8>> for Reverse {
+    type Response = Vec<u8>;
+    type Future = std::future::Ready<Result<tonic::Response<Vec<u8>>, Status>>;
+
+    fn call(&mut self, request: tonic::Request<Vec<u8>>) -> Self::Future {
+        let mut message = request.into_inner();
+        message.reverse();
+        std::future::ready(Ok(tonic::Response::new(message)))
+    }
+}
+
+struct Split;
+
+impl ServerStreamingService<Vec<u8>> for Split {
+    type Response = Vec<u8>;
+    type ResponseStream = tokio_stream::Iter<std::vec::IntoIter<Result<Vec<u8>, Status>>>;
+    type Future = std::future::Ready<Result<tonic::Response<Self::ResponseStream>, Status>>;
+
+    fn call(&mut self, request: tonic::Request<Vec<u8>>) -> Self::Future {
+        let message = request.into_inner();
+        let end = message.iter().position(|b| *b == 0);
+        let mut items = message[..end.unwrap_or(message.len())]
+            .iter()
+            .map(|b| Ok(vec![*b]))
+            .collect::<Vec<_>>();
+        if end.is_some() {
+            items.push(Err(Status::internal("zero")));
+        }
+        std::future::ready(Ok(tonic::Response::new(tokio_stream::iter(items))))
+    }
+}
+
+impl<E> Service<Request<BoxBody>> for Echo<E>
+where
+    E: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = E;
+    type Future = futures_util::future::BoxFuture<'static, Result<Self::Response, E>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<BoxBody>) -> Self::Future {
+        Box::pin(async move {
+            let mut grpc = tonic::server::Grpc::new(RawCodec);
+            Ok(match req.uri().path() {
+                "/test.Echo/Split" => grpc.server_streaming(Split, req).await,
+                _ => grpc.unary(Reverse, req).await,
+            })
+        })
+    }
+}
@@ -0,0 +1,65 @@
+//! Benchmark of `http::extract_context` with a growing number of headers,
+//! for the propagators that only read their headers (`tracecontext,baggage`)
+//! and for a propagator that scans all the keys (`jaeger`, for the `uberctx-*` baggage).
+//!
+//! Without dependency on a benchmark framework (std only), run it with:
+//! `cargo bench -p tracing-opentelemetry-instrumentation-sdk --features http`
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::propagation::{TextMapCompositePropagator, TextMapPropagator};
+use opentelemetry_sdk::propagation::{BaggagePropagator, TraceContextPropagator};
+use tracing_opentelemetry_instrumentation_sdk::http::extract_context;
+
+const ITERATIONS: u32 = 100_000;
+
+fn headers(count: usize) -> HeaderMap {
+    let mut headers = HeaderMap::with_capacity(count + 2);
+    headers.insert(
+        "traceparent",
+        HeaderValue::from_static("00-b2611246a58fd7ea623d2264c5a1e226-b2c9b811f2f424af-01"),
+    );
+    headers.insert("baggage", HeaderValue::from_static("tenant=acme"));
+    for i in 0..count {
+        if let Ok(name) = HeaderName::try_from(format!("x-custom-{i}")) {
+            headers.insert(name, HeaderValue::from_static("some value"));
+        }
+    }
+    headers
+}
+
+fn bench(name: &str, headers: &HeaderMap) -> Duration {
+    // warm up
+    for _ in 0..ITERATIONS / 10 {
+        black_box(extract_context(black_box(headers)));
+    }
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(extract_context(black_box(headers)));
+    }
+    let per_call = start.elapsed() / ITERATIONS;
+    println!("{name:<40} {per_call:>10.2?} / call");
+    per_call
+}
+
+fn run(propagator_name: &str, propagator: impl TextMapPropagator + Send + Sync + 'static) {
+    opentelemetry::global::set_text_map_propagator(propagator);
+    for count in [0, 10, 50, 100] {
+        bench(
+            &format!("{propagator_name} with {count} extra headers"),
+            &headers(count),
+        );
+    }
+}
+
+fn main() {
+    run(
+        "tracecontext,baggage",
+        TextMapCompositePropagator::new(vec![
+            Box::new(TraceContextPropagator::new()),
+            Box::new(BaggagePropagator::new()),
+        ]),
+    );
+    run("jaeger", opentelemetry_jaeger_propagator::Propagator::new());
+}
@@ -0,0 +1,83 @@
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const NOT_INITIALIZED: usize = 0;
+const UNLIMITED: usize = usize::MAX;
+
+static MAX_ATTRIBUTE_LEN: AtomicUsize = AtomicUsize::new(NOT_INITIALIZED);
+
+/// Define the max length (in bytes) of the string values recorded by the span helpers
+/// (`user_agent.original`, `url.*`, `exception.message`, ...), `None` to not truncate.
+///
+/// Long user agents, urls or error messages blow up the size of the spans.
+pub fn set_max_attribute_len(max_len: Option<usize>) {
+    let value = match max_len {
+        Some(0) => 1,
+        Some(n) => n,
+        None => UNLIMITED,
+    };
+    MAX_ATTRIBUTE_LEN.store(value, Ordering::Relaxed);
+}
+
+/// The max length (in bytes) of the string values recorded by the span helpers.
+///
+/// If not defined by [`set_max_attribute_len`], the value is read (once) from the environment variables
+/// `OTEL_SPAN_ATTRIBUTE_VALUE_LENGTH_LIMIT` fallback to `OTEL_ATTRIBUTE_VALUE_LENGTH_LIMIT`
+/// (see [Attribute Limits](https://opentelemetry.io/docs/specs/otel/configuration/sdk-environment-variables/#attribute-limits)).
+/// Default: no limit
+#[must_use]
+pub fn max_attribute_len() -> Option<usize> {
+    let mut value = MAX_ATTRIBUTE_LEN.load(Ordering::Relaxed);
+    if value == NOT_INITIALIZED {
+        set_max_attribute_len(max_attribute_len_from_env());
+        value = MAX_ATTRIBUTE_LEN.load(Ordering::Relaxed);
+    }
+    (value != UNLIMITED).then_some(value)
+}
+
+fn max_attribute_len_from_env() -> Option<usize> {
+    std::env::var("OTEL_SPAN_ATTRIBUTE_VALUE_LENGTH_LIMIT")
+        .or_else(|_| std::env::var("OTEL_ATTRIBUTE_VALUE_LENGTH_LIMIT"))
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+}
+
+/// Truncate the value to the [`max_attribute_len`].
+#[inline]
+#[must_use]
+pub fn truncate_attribute_value(value: &str) -> Cow<'_, str> {
+    match max_attribute_len() {
+        Some(max_len) => truncate_to(value, max_len),
+        None => Cow::Borrowed(value),
+    }
+}
+
+/// Truncate the value to at most `max_len` bytes (without splitting a character).
+#[must_use]
+pub fn truncate_to(value: &str, max_len: usize) -> Cow<'_, str> {
+    if value.len() <= max_len {
+        return Cow::Borrowed(value);
+    }
+    let mut end = max_len;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    Cow::Owned(value[..end].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("hello", 10, "hello")]
+    #[case("hello", 5, "hello")]
+    #[case("hello", 3, "hel")]
+    #[case("héllo", 2, "h")]
+    #[case("héllo", 3, "hé")]
+    fn test_truncate_to(#[case] input: &str, #[case] max_len: usize, #[case] expected: &str) {
+        assert!(truncate_to(input, max_len) == expected);
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+/// The reason of a span not created by a middleware for a request (see [`DroppedSpanHook`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DroppedSpanReason {
+    /// rejected by the `filter` of the layer (eg the health checks)
+    Filter,
+    /// not sampled by the layer (eg `with_sampling_rate_for` of axum)
+    Sampling,
+}
+
+impl DroppedSpanReason {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DroppedSpanReason::Filter => "filter",
+            DroppedSpanReason::Sampling => "sampling",
+        }
+    }
+}
+
+/// The hook called for each span dropped by a middleware configured with it (eg `OtelAxumLayer::with_dropped_span_hook`),
+/// eg to count them (`init-tracing-opentelemetry` provides one with its self-metrics).
+///
+/// ```rust
+/// use tracing_opentelemetry_instrumentation_sdk::{DroppedSpanHook, DroppedSpanReason};
+///
+/// let hook = DroppedSpanHook::new(|reason: DroppedSpanReason| {
+///     println!("span dropped by the middleware: {}", reason.as_str());
+/// });
+/// hook.record(DroppedSpanReason::Filter);
+/// ```
+#[derive(Clone)]
+pub struct DroppedSpanHook(Arc<dyn Fn(DroppedSpanReason) + Send + Sync>);
+
+impl DroppedSpanHook {
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(DroppedSpanReason) + Send + Sync + 'static,
+    {
+        Self(Arc::new(hook))
+    }
+
+    /// Notify the hook that the span of a request was not created.
+    pub fn record(&self, reason: DroppedSpanReason) {
+        (self.0)(reason);
+    }
+}
+
+impl std::fmt::Debug for DroppedSpanHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DroppedSpanHook")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn call_the_hook() {
+        let filtered = Arc::new(AtomicUsize::new(0));
+        let hook = DroppedSpanHook::new({
+            let filtered = filtered.clone();
+            move |reason| {
+                if reason == DroppedSpanReason::Filter {
+                    filtered.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+        hook.record(DroppedSpanReason::Filter);
+        hook.clone().record(DroppedSpanReason::Filter);
+        hook.record(DroppedSpanReason::Sampling);
+        assert!(filtered.load(Ordering::Relaxed) == 2);
+    }
+}
@@ -0,0 +1,118 @@
+use opentelemetry::trace::{SamplingDecision, SamplingResult, TraceContextExt};
+use opentelemetry::Context;
+use tracing_opentelemetry::{OpenTelemetrySpanExt, OtelData};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+
+/// The attribute set on the spans sampled by [`force_sampling`] (the `sampling.priority` of `OpenTracing`,
+/// understood by some backends to keep the trace).
+pub const FORCED_SAMPLING_ATTRIBUTE: &str = "sampling.priority";
+
+/// Marker stored into the context of a span sampled by [`force_sampling`] (and inherited by its descendants)
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ForcedSampling;
+
+/// Force the `span` to be sampled (recorded & exported), whatever the decision of the sampler of the sdk,
+/// eg to debug-trace a request on demand. The descendants (local & remote, via the sampled flag of the propagated
+/// context) follow the decision with a parent-based sampler.
+///
+/// The decision is set in advance (the sampler is not called), so it should be done just after the creation
+/// of the span (and the definition of its parent): returns `false` if the sampling decision was already made
+/// (eg by a call of `span.context()`) or if the span is not handled by `tracing-opentelemetry`.
+///
+/// ```rust
+/// use tracing_opentelemetry_instrumentation_sdk::{force_sampling, otel_trace_span};
+///
+/// let span = otel_trace_span!("debug job");
+/// force_sampling(&span);
+/// ```
+#[allow(clippy::must_use_candidate)] // the result is informative
+pub fn force_sampling(span: &tracing::Span) -> bool {
+    force_sampling_and_mark(span, |context| context)
+}
+
+/// Like [`force_sampling`], with the context (inherited by the descendants) completed by `mark`
+pub(crate) fn force_sampling_and_mark(
+    span: &tracing::Span,
+    mark: impl FnOnce(Context) -> Context,
+) -> bool {
+    let forced = span
+        .with_subscriber(|(id, dispatch)| {
+            let registry = dispatch.downcast_ref::<Registry>()?;
+            let span = registry.span(id)?;
+            let mut extensions = span.extensions_mut();
+            let otel_data = extensions.get_mut::<OtelData>()?;
+            if otel_data.builder.sampling_result.is_some() {
+                return Some(false);
+            }
+            let trace_state = otel_data
+                .parent_cx
+                .span()
+                .span_context()
+                .trace_state()
+                .clone();
+            otel_data.builder.sampling_result = Some(SamplingResult {
+                decision: SamplingDecision::RecordAndSample,
+                attributes: Vec::new(),
+                trace_state,
+            });
+            otel_data.parent_cx = mark(otel_data.parent_cx.with_value(ForcedSampling));
+            Some(true)
+        })
+        .flatten()
+        .unwrap_or(false);
+    if forced {
+        span.set_attribute(FORCED_SAMPLING_ATTRIBUTE, 1);
+    }
+    forced
+}
+
+/// `true` if the sampling of the span of the `context` (or of one of its local ancestors) was forced
+/// by [`force_sampling`].
+#[must_use]
+pub fn is_sampling_forced(context: &Context) -> bool {
+    context.get::<ForcedSampling>().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn force_the_sampling_of_the_span() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::AlwaysOff)))
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            let dropped = tracing::info_span!("dropped");
+            drop(dropped);
+
+            let forced = tracing::info_span!("forced");
+            assert!(force_sampling(&forced));
+            assert!(is_sampling_forced(&forced.context()));
+            forced.in_scope(|| {
+                // the child follows the decision of the parent
+                let child = tracing::info_span!("child").entered();
+                assert!(is_sampling_forced(&child.context()));
+            });
+            drop(forced);
+
+            let decided = tracing::info_span!("decided");
+            assert!(!is_sampling_forced(&decided.context()));
+            assert!(!force_sampling(&decided));
+        });
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        let mut names = spans.iter().map(|s| s.name.as_ref()).collect::<Vec<_>>();
+        names.sort_unstable();
+        assert!(names == ["child", "forced"]);
+    }
+}
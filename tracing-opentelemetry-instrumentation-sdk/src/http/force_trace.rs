@@ -0,0 +1,163 @@
+use http::header::InvalidHeaderValue;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::Context;
+
+use crate::force_sampling::force_sampling_and_mark;
+
+/// The default name of the header to force the sampling of a request (see [`ForceTraceConfig`])
+pub const DEFAULT_FORCE_TRACE_HEADER: &str = "x-otel-force-trace";
+
+/// The configuration of the force-trace header: a request with the `header` set to the `secret` is sampled
+/// (recorded & exported) by the server layers configured with it (eg `OtelAxumLayer::with_force_trace`),
+/// whatever the decision of the sampler, and the header is injected into the outgoing requests of the forced trace
+/// (with [`super::inject_context`]), so the downstream services (with the same configuration) sample it too,
+/// eg to trace a single production request on demand.
+///
+/// The secret is sent to the downstream services like the propagation headers: don't use it with the clients to
+/// third parties.
+///
+/// ```rust
+/// use tracing_opentelemetry_instrumentation_sdk::http::ForceTraceConfig;
+///
+/// let config = ForceTraceConfig::new("s3cr3t").expect("a valid header value");
+/// // then `curl -H 'x-otel-force-trace: s3cr3t' ...`
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForceTraceConfig {
+    header: HeaderName,
+    secret: HeaderValue,
+}
+
+impl ForceTraceConfig {
+    /// The config with the header [`DEFAULT_FORCE_TRACE_HEADER`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the `secret` is not a valid header value.
+    pub fn new(secret: impl AsRef<str>) -> Result<Self, InvalidHeaderValue> {
+        let mut secret = HeaderValue::from_str(secret.as_ref())?;
+        secret.set_sensitive(true);
+        Ok(Self {
+            header: HeaderName::from_static(DEFAULT_FORCE_TRACE_HEADER),
+            secret,
+        })
+    }
+
+    #[must_use]
+    pub fn with_header(self, header: HeaderName) -> Self {
+        ForceTraceConfig { header, ..self }
+    }
+
+    #[must_use]
+    pub fn header(&self) -> &HeaderName {
+        &self.header
+    }
+
+    /// `true` if the `headers` contain the header with the secret (compared in constant time).
+    /// An empty secret never matches.
+    #[must_use]
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        let secret = self.secret.as_bytes();
+        !secret.is_empty()
+            && headers
+                .get_all(&self.header)
+                .iter()
+                .any(|value| constant_time_eq(value.as_bytes(), secret))
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The force-trace header to inject into the outgoing requests, stored into the context of the forced span
+/// (and inherited by its descendants).
+#[derive(Debug, Clone)]
+struct ForceTraceHeader(ForceTraceConfig);
+
+/// Force the sampling of the `span` (see [`crate::force_sampling`]) requested by the force-trace header
+/// (see [`ForceTraceConfig::matches`]), and propagate the header to the downstream services of the trace.
+#[allow(clippy::must_use_candidate)] // the result is informative
+pub fn force_trace(span: &tracing::Span, config: &ForceTraceConfig) -> bool {
+    force_sampling_and_mark(span, |context| {
+        context.with_value(ForceTraceHeader(config.clone()))
+    })
+}
+
+/// Insert the force-trace header into `headers` if the sampling of the `context` was forced by [`force_trace`].
+pub(crate) fn inject_force_trace_header(context: &Context, headers: &mut HeaderMap) {
+    if let Some(ForceTraceHeader(config)) = context.get::<ForceTraceHeader>() {
+        headers.insert(config.header.clone(), config.secret.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+    use rstest::rstest;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[rstest]
+    #[case(&[], false)]
+    #[case(&[("x-otel-force-trace", "s3cr3t")], true)]
+    #[case(&[("x-otel-force-trace", "secret")], false)]
+    #[case(&[("x-otel-force-trace", "s3cr3t!")], false)]
+    #[case(&[("x-otel-force-trace", "other"), ("x-otel-force-trace", "s3cr3t")], true)]
+    #[case(&[("x-debug", "s3cr3t")], false)]
+    fn match_the_header(#[case] headers: &[(&str, &str)], #[case] expected: bool) {
+        let headers = headers
+            .iter()
+            .map(|(k, v)| (k.parse().unwrap(), v.parse().unwrap()))
+            .collect::<HeaderMap>();
+        let_assert!(Ok(config) = ForceTraceConfig::new("s3cr3t"));
+        assert!(config.matches(&headers) == expected);
+    }
+
+    #[test]
+    fn never_match_an_empty_secret() {
+        let mut headers = HeaderMap::new();
+        headers.insert(DEFAULT_FORCE_TRACE_HEADER, HeaderValue::from_static(""));
+        let_assert!(Ok(config) = ForceTraceConfig::new(""));
+        assert!(!config.matches(&headers));
+    }
+
+    #[test]
+    fn reject_an_invalid_secret() {
+        assert!(ForceTraceConfig::new("s3cr3t\n").is_err());
+    }
+
+    #[test]
+    fn inject_the_header_of_the_forced_trace() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter)
+            .with_sampler(Sampler::AlwaysOff)
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let_assert!(Ok(config) = ForceTraceConfig::new("s3cr3t"));
+        let config = config.with_header(HeaderName::from_static("x-force"));
+        tracing::subscriber::with_default(subscriber, || {
+            let forced = tracing::info_span!("forced");
+            assert!(force_trace(&forced, &config));
+            forced.in_scope(|| {
+                let child = tracing::info_span!("child");
+                let mut injected = HeaderMap::new();
+                inject_force_trace_header(&child.context(), &mut injected);
+                assert!(injected.get("x-force").is_some_and(|v| v == "s3cr3t"));
+            });
+
+            // forced without the header (eg by `with_force_sampling_for`): the header is not injected
+            let other = tracing::info_span!("other");
+            assert!(crate::force_sampling(&other));
+            let mut injected = HeaderMap::new();
+            inject_force_trace_header(&other.context(), &mut injected);
+            assert!(injected.is_empty());
+        });
+    }
+}
@@ -0,0 +1,517 @@
+//! Parser for the `Forwarded` header ([RFC 7239](https://www.rfc-editor.org/rfc/rfc7239)).
+//!
+//! It provides the information added by the proxies (client address & port, original host & scheme)
+//! to record `client.address`, `network.peer.port`, `url.scheme`,... behind proxies.
+//!
+//! ```
+//! use tracing_opentelemetry_instrumentation_sdk::http::forwarded::{self, NodeName};
+//!
+//! let elements = forwarded::parse(r#"for="[2001:db8::1]:4711";proto=https, for=192.0.2.43"#).unwrap();
+//! assert_eq!(elements.len(), 2);
+//! assert_eq!(elements[0].proto.as_deref(), Some("https"));
+//! let client = elements[0].for_node.as_ref().unwrap();
+//! assert_eq!(client.name, NodeName::Ip("2001:db8::1".parse().unwrap()));
+//! assert_eq!(client.port(), Some(4711));
+//! ```
+use std::borrow::Cow;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use http::HeaderMap;
+
+/// The identifier of a node (client, proxy), see [RFC 7239 section 6](https://www.rfc-editor.org/rfc/rfc7239#section-6)
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NodeName {
+    Ip(IpAddr),
+    /// the node is not known (eg the proxy doesn't want to disclose it)
+    Unknown,
+    /// an obfuscated identifier (starts with `_`)
+    Obfuscated(String),
+}
+
+/// The port of a node, see [RFC 7239 section 6](https://www.rfc-editor.org/rfc/rfc7239#section-6)
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NodePort {
+    Port(u16),
+    /// an obfuscated port (starts with `_`)
+    Obfuscated(String),
+}
+
+/// The value of the `for` and `by` parameters
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Node {
+    pub name: NodeName,
+    pub port: Option<NodePort>,
+}
+
+impl Node {
+    #[must_use]
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self.name {
+            NodeName::Ip(ip) => Some(ip),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn port(&self) -> Option<u16> {
+        match self.port {
+            Some(NodePort::Port(port)) => Some(port),
+            _ => None,
+        }
+    }
+}
+
+/// One element of the `Forwarded` header (the information added by one proxy)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ForwardedElement {
+    /// the interface where the request came in to the proxy
+    pub by: Option<Node>,
+    /// the client that initiated the request (or the previous proxy)
+    pub for_node: Option<Node>,
+    /// the `Host` header as received by the proxy
+    pub host: Option<String>,
+    /// the scheme (lowercase) used to make the request to the proxy
+    pub proto: Option<String>,
+}
+
+/// Parse the value of a `Forwarded` header, `None` if the value is malformed.
+///
+/// The parameters `by`, `for`, `host`, `proto` are case-insensitive,
+/// the extension parameters are ignored.
+#[must_use]
+pub fn parse(value: &str) -> Option<Vec<ForwardedElement>> {
+    let mut parser = Parser {
+        input: value,
+        pos: 0,
+    };
+    let mut elements = Vec::new();
+    loop {
+        let mut element = ForwardedElement::default();
+        let mut has_pair = false;
+        loop {
+            parser.skip_ows();
+            match parser.peek() {
+                None | Some(b',') => break,
+                Some(b';') => {
+                    parser.pos += 1;
+                    continue;
+                }
+                Some(_) => {}
+            }
+            let name = parser.token()?;
+            parser.expect(b'=')?;
+            let value = if parser.peek() == Some(b'"') {
+                parser.quoted_string()?
+            } else {
+                Cow::Borrowed(parser.token()?)
+            };
+            let duplicated = if name.eq_ignore_ascii_case("for") {
+                element.for_node.replace(parse_node(&value)?).is_some()
+            } else if name.eq_ignore_ascii_case("by") {
+                element.by.replace(parse_node(&value)?).is_some()
+            } else if name.eq_ignore_ascii_case("host") {
+                element.host.replace(value.into_owned()).is_some()
+            } else if name.eq_ignore_ascii_case("proto") {
+                if !is_scheme(&value) {
+                    return None;
+                }
+                element.proto.replace(value.to_ascii_lowercase()).is_some()
+            } else {
+                false
+            };
+            // each parameter MUST NOT occur more than once per element
+            if duplicated {
+                return None;
+            }
+            has_pair = true;
+            parser.skip_ows();
+            if !matches!(parser.peek(), None | Some(b',' | b';')) {
+                return None;
+            }
+        }
+        if has_pair {
+            elements.push(element);
+        }
+        if parser.peek().is_none() {
+            break;
+        }
+        parser.expect(b',')?;
+    }
+    Some(elements)
+}
+
+/// The elements of every `Forwarded` header of `headers` (in order), the malformed headers are ignored.
+#[must_use]
+pub fn elements_from_headers(headers: &HeaderMap) -> Vec<ForwardedElement> {
+    headers
+        .get_all(http::header::FORWARDED)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(parse)
+        .flatten()
+        .collect()
+}
+
+/// The client as defined by the first proxy (the `for` of the first element of the `Forwarded` headers).
+#[must_use]
+pub fn client_node(headers: &HeaderMap) -> Option<Node> {
+    elements_from_headers(headers)
+        .into_iter()
+        .next()
+        .and_then(|element| element.for_node)
+}
+
+fn parse_node(value: &str) -> Option<Node> {
+    let (name, port) = if let Some(rest) = value.strip_prefix('[') {
+        let (ip, rest) = rest.split_once(']')?;
+        let port = match rest {
+            "" => None,
+            _ => Some(rest.strip_prefix(':')?),
+        };
+        (NodeName::Ip(IpAddr::V6(ip.parse::<Ipv6Addr>().ok()?)), port)
+    } else {
+        let (name, port) = match value.split_once(':') {
+            Some((name, port)) => (name, Some(port)),
+            None => (value, None),
+        };
+        let name = if name.eq_ignore_ascii_case("unknown") {
+            NodeName::Unknown
+        } else if is_obfuscated(name) {
+            NodeName::Obfuscated(name.to_string())
+        } else {
+            NodeName::Ip(IpAddr::V4(name.parse::<Ipv4Addr>().ok()?))
+        };
+        (name, port)
+    };
+    let port = match port {
+        None => None,
+        Some(port) if is_obfuscated(port) => Some(NodePort::Obfuscated(port.to_string())),
+        Some(port) if (1..=5).contains(&port.len()) && port.bytes().all(|b| b.is_ascii_digit()) => {
+            Some(NodePort::Port(port.parse().ok()?))
+        }
+        Some(_) => return None,
+    };
+    Some(Node { name, port })
+}
+
+fn is_obfuscated(value: &str) -> bool {
+    value.len() > 1
+        && value.starts_with('_')
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+}
+
+fn is_scheme(value: &str) -> bool {
+    value
+        .bytes()
+        .next()
+        .is_some_and(|b| b.is_ascii_alphabetic())
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.'))
+}
+
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_ows(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, b: u8) -> Option<()> {
+        (self.peek() == Some(b)).then(|| self.pos += 1)
+    }
+
+    fn token(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        while self.peek().is_some_and(is_tchar) {
+            self.pos += 1;
+        }
+        (self.pos > start).then(|| &self.input[start..self.pos])
+    }
+
+    fn quoted_string(&mut self) -> Option<Cow<'a, str>> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        let mut unescaped: Option<String> = None;
+        loop {
+            match self.peek()? {
+                b'"' => {
+                    let value = match unescaped {
+                        Some(s) => Cow::Owned(s),
+                        None => Cow::Borrowed(&self.input[start..self.pos]),
+                    };
+                    self.pos += 1;
+                    return Some(value);
+                }
+                b'\\' => {
+                    let s =
+                        unescaped.get_or_insert_with(|| self.input[start..self.pos].to_string());
+                    self.pos += 1;
+                    let c = self.input[self.pos..].chars().next()?;
+                    s.push(c);
+                    self.pos += c.len_utf8();
+                }
+                _ => {
+                    let c = self.input[self.pos..].chars().next()?;
+                    if let Some(s) = unescaped.as_mut() {
+                        s.push(c);
+                    }
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for NodeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeName::Ip(IpAddr::V4(ip)) => write!(f, "{ip}"),
+            NodeName::Ip(IpAddr::V6(ip)) => write!(f, "[{ip}]"),
+            NodeName::Unknown => f.write_str("unknown"),
+            NodeName::Obfuscated(name) => f.write_str(name),
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        match &self.port {
+            Some(NodePort::Port(port)) => write!(f, ":{port}"),
+            Some(NodePort::Obfuscated(port)) => write!(f, ":{port}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Format the element as a value for the `Forwarded` header (eg to append the information of a proxy)
+impl fmt::Display for ForwardedElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs = [
+            ("by", self.by.as_ref().map(ToString::to_string)),
+            ("for", self.for_node.as_ref().map(ToString::to_string)),
+            ("host", self.host.clone()),
+            ("proto", self.proto.clone()),
+        ];
+        let mut first = true;
+        for (name, value) in pairs {
+            let Some(value) = value else { continue };
+            if !first {
+                f.write_str(";")?;
+            }
+            first = false;
+            if !value.is_empty() && value.bytes().all(is_tchar) {
+                write!(f, "{name}={value}")?;
+            } else {
+                write!(f, "{name}=\"")?;
+                for c in value.chars() {
+                    if matches!(c, '"' | '\\') {
+                        f.write_str("\\")?;
+                    }
+                    write!(f, "{c}")?;
+                }
+                f.write_str("\"")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use rstest::rstest;
+
+    fn node(name: NodeName, port: Option<NodePort>) -> Node {
+        Node { name, port }
+    }
+
+    fn ip(s: &str) -> NodeName {
+        NodeName::Ip(s.parse().unwrap())
+    }
+
+    #[rstest]
+    #[case("for=192.0.2.60", node(ip("192.0.2.60"), None))]
+    #[case(
+        "For=\"192.0.2.60:8080\"",
+        node(ip("192.0.2.60"), Some(NodePort::Port(8080)))
+    )]
+    #[case("for=\"[2001:db8:cafe::17]\"", node(ip("2001:db8:cafe::17"), None))]
+    #[case(
+        "for=\"[2001:db8:cafe::17]:4711\"",
+        node(ip("2001:db8:cafe::17"), Some(NodePort::Port(4711)))
+    )]
+    #[case("for=unknown", node(NodeName::Unknown, None))]
+    #[case("for=_hidden", node(NodeName::Obfuscated("_hidden".into()), None))]
+    #[case("for=\"_gazonk:_p0rt\"", node(NodeName::Obfuscated("_gazonk".into()), Some(NodePort::Obfuscated("_p0rt".into()))))]
+    #[case("for=\"\\_hid\\den\"", node(NodeName::Obfuscated("_hidden".into()), None))]
+    fn test_parse_for_node(#[case] input: &str, #[case] expected: Node) {
+        let_assert!(Some(elements) = parse(input));
+        assert!(elements.len() == 1);
+        assert!(elements[0].for_node == Some(expected));
+    }
+
+    #[test]
+    fn test_parse_every_parameter() {
+        let_assert!(
+            Some(elements) =
+                parse("for=192.0.2.43;by=203.0.113.60;proto=HTTPS;host=\"example.com:8443\"")
+        );
+        assert!(
+            elements
+                == vec![ForwardedElement {
+                    by: Some(node(ip("203.0.113.60"), None)),
+                    for_node: Some(node(ip("192.0.2.43"), None)),
+                    host: Some("example.com:8443".to_string()),
+                    proto: Some("https".to_string()),
+                }]
+        );
+    }
+
+    #[test]
+    fn test_parse_multiple_elements() {
+        let_assert!(
+            Some(elements) =
+                parse("for=192.0.2.43, ,for=\"[2001:db8:cafe::17]\";ext=\"a,b\" , for=unknown")
+        );
+        let fors = elements
+            .iter()
+            .map(|e| e.for_node.clone().map(|n| n.name))
+            .collect::<Vec<_>>();
+        assert!(
+            fors == vec![
+                Some(ip("192.0.2.43")),
+                Some(ip("2001:db8:cafe::17")),
+                Some(NodeName::Unknown)
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("for=2001:db8::1")] // ipv6 must be quoted (and between brackets)
+    #[case("for=\"2001:db8::1\"")]
+    #[case("for=\"[2001:db8::1\"")]
+    #[case("for=300.0.0.1")]
+    #[case("for=192.0.2.43:123456")]
+    #[case("for=192.0.2.43:")]
+    #[case("for=_")]
+    #[case("for=localhost")]
+    #[case("for=192.0.2.43;for=192.0.2.44")]
+    #[case("for=192.0.2.43 for=192.0.2.44")]
+    #[case("proto=\"1http\"")]
+    #[case("for")]
+    #[case("for=")]
+    #[case("for=\"192.0.2.43")]
+    #[case("=192.0.2.43")]
+    fn test_parse_malformed(#[case] input: &str) {
+        assert!(parse(input).is_none());
+    }
+
+    #[test]
+    fn test_elements_from_multiple_headers() {
+        let mut headers = HeaderMap::new();
+        headers.append(http::header::FORWARDED, "for=192.0.2.43".parse().unwrap());
+        headers.append(http::header::FORWARDED, "for=\"oops".parse().unwrap());
+        headers.append(
+            http::header::FORWARDED,
+            "for=198.51.100.17;proto=http".parse().unwrap(),
+        );
+        let elements = elements_from_headers(&headers);
+        assert!(elements.len() == 2);
+        assert!(elements[1].proto.as_deref() == Some("http"));
+        assert!(client_node(&headers) == Some(node(ip("192.0.2.43"), None)));
+    }
+
+    // minimal deterministic generator (xorshift), to not depend on a property testing framework
+    struct Gen(u64);
+
+    impl Gen {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn string(&mut self, alphabet: &str, max_len: usize) -> String {
+            let alphabet = alphabet.chars().collect::<Vec<_>>();
+            (0..self.below(max_len))
+                .map(|_| alphabet[self.below(alphabet.len())])
+                .collect()
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        fn node(&mut self) -> Node {
+            let name = match self.below(4) {
+                0 => NodeName::Ip(IpAddr::V4(Ipv4Addr::from(self.next() as u32))),
+                1 => NodeName::Ip(IpAddr::V6(Ipv6Addr::from(
+                    u128::from(self.next()) << 64 | u128::from(self.next()),
+                ))),
+                2 => NodeName::Unknown,
+                _ => NodeName::Obfuscated(format!("_{}", self.string("aZ09._-", 8)) + "x"),
+            };
+            let port = match self.below(3) {
+                0 => None,
+                1 => Some(NodePort::Port(self.next() as u16)),
+                _ => Some(NodePort::Obfuscated(format!(
+                    "_p{}",
+                    self.string("aZ09._-", 4)
+                ))),
+            };
+            Node { name, port }
+        }
+    }
+
+    #[test]
+    fn test_display_then_parse_roundtrip() {
+        let mut gen = Gen(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2_000 {
+            let element = ForwardedElement {
+                by: (gen.below(2) == 0).then(|| gen.node()),
+                for_node: (gen.below(2) == 0).then(|| gen.node()),
+                host: (gen.below(2) == 0).then(|| gen.string("ab.:\"\\ 9", 12)),
+                proto: (gen.below(2) == 0).then(|| format!("h{}", gen.string("t+-.s", 5))),
+            };
+            let formatted = element.to_string();
+            let_assert!(Some(parsed) = parse(&formatted), "{formatted}");
+            let expected = if formatted.is_empty() {
+                vec![]
+            } else {
+                vec![element]
+            };
+            assert!(parsed == expected, "{formatted}");
+        }
+    }
+
+    #[test]
+    fn test_parse_arbitrary_input_does_not_panic() {
+        let mut gen = Gen(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..20_000 {
+            let input = gen.string("fForbyhstp=;,\" \t\\[]:_.0129aunkwé", 40);
+            let _ = parse(&input);
+        }
+    }
+}
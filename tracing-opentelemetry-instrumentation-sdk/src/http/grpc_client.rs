@@ -1,26 +1,53 @@
 use std::error::Error;
 
-use crate::http::{extract_service_method, http_host, user_agent};
-use crate::otel_trace_span;
+use crate::http::{
+    extract_rpc_service_method, http_host, rpc_span_name, url_full, user_agent, QueryRedaction,
+};
+use crate::{
+    is_instrumentation_suppressed, otel_trace_span, truncate_attribute_value, PrivacyPolicy,
+};
 use tracing::field::Empty;
 
-use super::grpc_update_span_from_response;
+#[cfg(feature = "tonic")]
+use super::{grpc_status_is_error, GrpcCode};
+use super::{grpc_update_span_from_response_with_error_codes, GrpcErrorCodes};
 
 // [opentelemetry-specification/.../rpc.md](https://github.com/open-telemetry/opentelemetry-specification/blob/main/specification/trace/semantic_conventions/rpc.md)
 //TODO create similar but with tonic::Request<B> ?
 pub fn make_span_from_request<B>(req: &http::Request<B>) -> tracing::Span {
-    let (service, method) = extract_service_method(req.uri());
+    make_span_from_request_with_rpc_system(req, "grpc", &PrivacyPolicy::default())
+}
+
+/// Like [`make_span_from_request`] but for other RPC systems over http (eg `connect_rpc`, `jsonrpc`, `twirp`),
+/// the span is named `{service}/{method}` from the last 2 segments of the path,
+/// the user agent is recorded according to the `privacy` policy.
+///
+/// No span is created when the instrumentation is suppressed (see [`crate::with_suppressed_instrumentation`]).
+pub fn make_span_from_request_with_rpc_system<B>(
+    req: &http::Request<B>,
+    rpc_system: &str,
+    privacy: &PrivacyPolicy,
+) -> tracing::Span {
+    if is_instrumentation_suppressed() {
+        return tracing::Span::none();
+    }
+    let (service, method) = extract_rpc_service_method(req.uri());
     otel_trace_span!(
         "GRPC request",
-        http.user_agent = %user_agent(req),
-        otel.name = format!("{service}/{method}"),
+        http.user_agent = privacy.protect("http.user_agent", user_agent(req))
+            .as_deref()
+            .map(truncate_attribute_value)
+            .as_deref(),
+        otel.name = rpc_span_name(service, method),
         otel.kind = ?opentelemetry::trace::SpanKind::Client,
         otel.status_code = Empty,
-        rpc.system ="grpc",
+        rpc.system = rpc_system,
         rpc.service = %service,
         rpc.method = %method,
         rpc.grpc.status_code = Empty, // to set on response
-        server.address = %http_host(req),
+        server.address = %truncate_attribute_value(http_host(req)),
+        // gRPC does not use query string, so it is always stripped
+        url.full = url_full(req.uri(), &QueryRedaction::Strip),
         exception.message = Empty, // to set on response
         exception.details = Empty, // to set on response
     )
@@ -32,10 +59,16 @@ where
 {
     span.record("otel.status_code", "ERROR");
     span.record("rpc.grpc.status_code", 2);
-    span.record("exception.message", error.to_string());
-    error
-        .source()
-        .map(|s| span.record("exception.message", s.to_string()));
+    span.record(
+        "exception.message",
+        truncate_attribute_value(&error.to_string()).as_ref(),
+    );
+    error.source().map(|s| {
+        span.record(
+            "exception.message",
+            truncate_attribute_value(&s.to_string()).as_ref(),
+        )
+    });
 }
 
 pub fn update_span_from_response_or_error<B, E>(
@@ -43,13 +76,363 @@ pub fn update_span_from_response_or_error<B, E>(
     response: &Result<http::Response<B>, E>,
 ) where
     E: Error,
+{
+    update_span_from_response_or_error_with_error_codes(
+        span,
+        response,
+        GrpcErrorCodes::semconv(false),
+    );
+}
+
+/// Like [`update_span_from_response_or_error`] but with a custom set of codes considered as error
+pub fn update_span_from_response_or_error_with_error_codes<B, E>(
+    span: &tracing::Span,
+    response: &Result<http::Response<B>, E>,
+    error_codes: GrpcErrorCodes,
+) where
+    E: Error,
 {
     match response {
         Ok(response) => {
-            grpc_update_span_from_response(span, response, false);
+            grpc_update_span_from_response_with_error_codes(span, response, error_codes);
         }
         Err(err) => {
             update_span_from_error(span, err);
         }
     }
 }
+
+/// Update the span from the result of a tonic client call.
+/// It records the full status (code, message and details) instead of the http's headers
+/// (`grpc-status`,...) used by [`update_span_from_response_or_error`].
+#[cfg(feature = "tonic")]
+pub fn update_span_from_tonic_result<T>(
+    span: &tracing::Span,
+    result: &Result<tonic::Response<T>, tonic::Status>,
+) {
+    match result {
+        Ok(_) => {
+            span.record("rpc.grpc.status_code", GrpcCode::Ok as u16);
+            span.record("otel.status_code", "OK");
+        }
+        Err(status) => update_span_from_tonic_status(span, status),
+    }
+}
+
+#[cfg(feature = "tonic")]
+pub fn update_span_from_tonic_status(span: &tracing::Span, status: &tonic::Status) {
+    let code = status.code() as u16;
+    span.record("rpc.grpc.status_code", code);
+    if grpc_status_is_error(code, false) {
+        span.record("otel.status_code", "ERROR");
+    } else {
+        span.record("otel.status_code", "OK");
+    }
+    if !status.message().is_empty() {
+        span.record(
+            "exception.message",
+            truncate_attribute_value(status.message()).as_ref(),
+        );
+    }
+    record_grpc_status_details(span, status.details());
+}
+
+/// Record the details of the status (the encoded `google.rpc.Status`) as:
+///
+/// - `rpc.grpc.status.details.type_urls`: the type url of every detail (eg `type.googleapis.com/google.rpc.ErrorInfo`)
+/// - `rpc.grpc.status.details.messages`: the human readable part of the well known details
+///   (`ErrorInfo`, `BadRequest`, `DebugInfo`, `LocalizedMessage`)
+///
+/// When the details are not a `google.rpc.Status`, they are recorded (truncated) as
+/// `rpc.grpc.status.details.base64` (standard base64 encoding).
+///
+/// `exception.details` keeps a text of the details: the messages joined by `; ` (or the base64).
+#[cfg(feature = "tonic")]
+fn record_grpc_status_details(span: &tracing::Span, details: &[u8]) {
+    use opentelemetry::{Array, StringValue, Value};
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    match decode_grpc_status_details(details) {
+        GrpcStatusDetails::Empty => {}
+        GrpcStatusDetails::Decoded {
+            type_urls,
+            messages,
+        } => {
+            let to_array = |values: Vec<String>| {
+                Value::Array(Array::String(
+                    values
+                        .iter()
+                        .map(|v| StringValue::from(truncate_attribute_value(v).into_owned()))
+                        .collect(),
+                ))
+            };
+            span.set_attribute("rpc.grpc.status.details.type_urls", to_array(type_urls));
+            if !messages.is_empty() {
+                span.record(
+                    "exception.details",
+                    truncate_attribute_value(&messages.join("; ")).as_ref(),
+                );
+                span.set_attribute("rpc.grpc.status.details.messages", to_array(messages));
+            }
+        }
+        GrpcStatusDetails::Base64(encoded) => {
+            span.record(
+                "exception.details",
+                truncate_attribute_value(&encoded).as_ref(),
+            );
+            span.set_attribute(
+                "rpc.grpc.status.details.base64",
+                truncate_attribute_value(&encoded).into_owned(),
+            );
+        }
+    }
+}
+
+#[cfg(feature = "tonic")]
+#[derive(Debug, PartialEq)]
+enum GrpcStatusDetails {
+    Empty,
+    Decoded {
+        type_urls: Vec<String>,
+        messages: Vec<String>,
+    },
+    Base64(String),
+}
+
+#[cfg(feature = "tonic")]
+fn decode_grpc_status_details(details: &[u8]) -> GrpcStatusDetails {
+    use base64::Engine;
+    use prost::Message;
+
+    if details.is_empty() {
+        return GrpcStatusDetails::Empty;
+    }
+    match google_rpc::Status::decode(details) {
+        Ok(status) if !status.details.is_empty() => GrpcStatusDetails::Decoded {
+            type_urls: status.details.iter().map(|d| d.type_url.clone()).collect(),
+            messages: status
+                .details
+                .iter()
+                .filter_map(google_rpc::Any::message)
+                .collect(),
+        },
+        _ => GrpcStatusDetails::Base64(base64::engine::general_purpose::STANDARD.encode(details)),
+    }
+}
+
+/// The subset of the messages of [googleapis/google/rpc](https://github.com/googleapis/googleapis/tree/master/google/rpc)
+/// used to describe the details of a status.
+#[cfg(feature = "tonic")]
+mod google_rpc {
+    use prost::Message;
+
+    #[derive(Clone, PartialEq, Message)]
+    pub(super) struct Status {
+        #[prost(int32, tag = "1")]
+        pub code: i32,
+        #[prost(string, tag = "2")]
+        pub message: String,
+        #[prost(message, repeated, tag = "3")]
+        pub details: Vec<Any>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub(super) struct Any {
+        #[prost(string, tag = "1")]
+        pub type_url: String,
+        #[prost(bytes = "vec", tag = "2")]
+        pub value: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub(super) struct ErrorInfo {
+        #[prost(string, tag = "1")]
+        pub reason: String,
+        #[prost(string, tag = "2")]
+        pub domain: String,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub(super) struct DebugInfo {
+        #[prost(string, repeated, tag = "1")]
+        pub stack_entries: Vec<String>,
+        #[prost(string, tag = "2")]
+        pub detail: String,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub(super) struct BadRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub field_violations: Vec<FieldViolation>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub(super) struct FieldViolation {
+        #[prost(string, tag = "1")]
+        pub field: String,
+        #[prost(string, tag = "2")]
+        pub description: String,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub(super) struct LocalizedMessage {
+        #[prost(string, tag = "1")]
+        pub locale: String,
+        #[prost(string, tag = "2")]
+        pub message: String,
+    }
+
+    impl Any {
+        /// The human readable part of the well known details, `None` for the others.
+        pub(super) fn message(&self) -> Option<String> {
+            let name = self.type_url.rsplit('/').next().unwrap_or_default();
+            let value = self.value.as_slice();
+            let message = match name {
+                "google.rpc.ErrorInfo" => ErrorInfo::decode(value)
+                    .ok()
+                    .map(|e| format!("{} ({})", e.reason, e.domain)),
+                "google.rpc.DebugInfo" => DebugInfo::decode(value).ok().map(|d| d.detail),
+                "google.rpc.LocalizedMessage" => {
+                    LocalizedMessage::decode(value).ok().map(|m| m.message)
+                }
+                "google.rpc.BadRequest" => BadRequest::decode(value).ok().map(|b| {
+                    b.field_violations
+                        .iter()
+                        .map(|v| format!("{}: {}", v.field, v.description))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                }),
+                _ => None,
+            };
+            message.filter(|m| !m.is_empty())
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "tonic")]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use prost::Message;
+
+    fn any<M: Message>(name: &str, message: &M) -> google_rpc::Any {
+        google_rpc::Any {
+            type_url: format!("type.googleapis.com/{name}"),
+            value: message.encode_to_vec(),
+        }
+    }
+
+    #[test]
+    fn decode_the_empty_details() {
+        assert!(decode_grpc_status_details(b"") == GrpcStatusDetails::Empty);
+    }
+
+    #[test]
+    fn decode_the_details_of_google_rpc_status() {
+        let status = google_rpc::Status {
+            code: 3,
+            message: "invalid argument".to_string(),
+            details: vec![
+                any(
+                    "google.rpc.ErrorInfo",
+                    &google_rpc::ErrorInfo {
+                        reason: "API_DISABLED".to_string(),
+                        domain: "example.com".to_string(),
+                    },
+                ),
+                any(
+                    "google.rpc.BadRequest",
+                    &google_rpc::BadRequest {
+                        field_violations: vec![google_rpc::FieldViolation {
+                            field: "name".to_string(),
+                            description: "is required".to_string(),
+                        }],
+                    },
+                ),
+                any(
+                    "my.package.Custom",
+                    &google_rpc::LocalizedMessage::default(),
+                ),
+            ],
+        };
+        assert!(
+            decode_grpc_status_details(&status.encode_to_vec())
+                == GrpcStatusDetails::Decoded {
+                    type_urls: vec![
+                        "type.googleapis.com/google.rpc.ErrorInfo".to_string(),
+                        "type.googleapis.com/google.rpc.BadRequest".to_string(),
+                        "type.googleapis.com/my.package.Custom".to_string(),
+                    ],
+                    messages: vec![
+                        "API_DISABLED (example.com)".to_string(),
+                        "name: is required".to_string(),
+                    ],
+                }
+        );
+    }
+
+    #[test]
+    fn encode_as_base64_the_details_not_google_rpc_status() {
+        assert!(
+            decode_grpc_status_details(&[0x08, 0xff, 0x01, 0xff])
+                == GrpcStatusDetails::Base64("CP8B/w==".to_string())
+        );
+        assert!(
+            decode_grpc_status_details(b"not found")
+                == GrpcStatusDetails::Base64("bm90IGZvdW5k".to_string())
+        );
+    }
+
+    #[test]
+    fn record_the_details_on_the_span() {
+        use opentelemetry::trace::TracerProvider as _;
+        use opentelemetry::Value;
+        use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let details = google_rpc::Status {
+            code: 3,
+            message: "invalid argument".to_string(),
+            details: vec![any(
+                "google.rpc.ErrorInfo",
+                &google_rpc::ErrorInfo {
+                    reason: "API_DISABLED".to_string(),
+                    domain: "example.com".to_string(),
+                },
+            )],
+        }
+        .encode_to_vec();
+        tracing::subscriber::with_default(subscriber, || {
+            let req = http::Request::builder()
+                .uri("http://example.com/my.Service/Call")
+                .body(())
+                .unwrap();
+            let span = make_span_from_request(&req);
+            let status = tonic::Status::with_details(
+                tonic::Code::InvalidArgument,
+                "invalid argument",
+                details.into(),
+            );
+            update_span_from_tonic_status(&span, &status);
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert!(spans.len() == 1);
+        let attribute = |key: &str| {
+            spans[0]
+                .attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.clone())
+        };
+        assert!(attribute("exception.details") == Some(Value::from("API_DISABLED (example.com)")));
+        assert!(attribute("rpc.grpc.status.details.type_urls").is_some());
+    }
+}
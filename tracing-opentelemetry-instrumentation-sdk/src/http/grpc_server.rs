@@ -1,48 +1,180 @@
-use crate::http::{extract_service_method, http_host, user_agent};
-use crate::{otel_trace_span, BoxError};
+use crate::http::{extract_rpc_service_method, http_host, rpc_span_name, user_agent};
+use crate::{otel_trace_span, truncate_attribute_value, BoxError, PrivacyPolicy};
+use std::time::Duration;
 use tracing::field::Empty;
 
-use super::grpc_update_span_from_response;
+use super::{grpc_update_span_from_response_with_error_codes, GrpcCode, GrpcErrorCodes};
 
 //TODO create similar but with tonic::Request<B> ?
 /// see [Semantic Conventions for gRPC | OpenTelemetry](https://opentelemetry.io/docs/specs/semconv/rpc/grpc/#grpc-status)
 pub fn make_span_from_request<B>(req: &http::Request<B>) -> tracing::Span {
-    let (service, method) = extract_service_method(req.uri());
+    make_span_from_request_with_rpc_system(req, "grpc", &PrivacyPolicy::default())
+}
+
+/// Like [`make_span_from_request`] but for other RPC systems over http (eg `connect_rpc`, `jsonrpc`, `twirp`),
+/// the span is named `{service}/{method}` from the last 2 segments of the path,
+/// the user agent is recorded according to the `privacy` policy.
+/// see [Semantic Conventions for RPC](https://opentelemetry.io/docs/specs/semconv/rpc/rpc-spans/)
+pub fn make_span_from_request_with_rpc_system<B>(
+    req: &http::Request<B>,
+    rpc_system: &str,
+    privacy: &PrivacyPolicy,
+) -> tracing::Span {
+    let (service, method) = extract_rpc_service_method(req.uri());
     otel_trace_span!(
         "GRPC request",
-        http.user_agent = %user_agent(req),
-        otel.name = format!("{service}/{method}"),
+        http.user_agent = privacy.protect("http.user_agent", user_agent(req))
+            .as_deref()
+            .map(truncate_attribute_value)
+            .as_deref(),
+        otel.name = rpc_span_name(service, method),
         otel.kind = ?opentelemetry::trace::SpanKind::Server,
         otel.status_code = Empty,
-        rpc.system ="grpc",
+        rpc.system = rpc_system,
         rpc.service = %service,
         rpc.method = %method,
         rpc.grpc.status_code = Empty, // to set on response
-        server.address = %http_host(req),
+        rpc.grpc.timeout_ms = grpc_timeout(req.headers()).map(duration_ms),
+        rpc.grpc.deadline_exceeded = Empty, // to set on response or cancellation
+        server.address = %truncate_attribute_value(http_host(req)),
         exception.message = Empty, // to set on response
         exception.details = Empty, // to set on response
+        error.type = Empty, // to set on cancellation
+    )
+}
+
+/// Like [`make_span_from_request`] but with only `rpc.system`, `rpc.service`, `rpc.method` & the status
+/// (no headers, user agent, address,...), for the infrastructure endpoints (eg health checks, reflection)
+/// that should stay visible into the traces but cheap.
+pub fn make_minimal_span_from_request<B>(req: &http::Request<B>) -> tracing::Span {
+    let (service, method) = extract_rpc_service_method(req.uri());
+    otel_trace_span!(
+        "GRPC request",
+        otel.name = rpc_span_name(service, method),
+        otel.kind = ?opentelemetry::trace::SpanKind::Server,
+        otel.status_code = Empty,
+        rpc.system = "grpc",
+        rpc.service = %service,
+        rpc.method = %method,
+        rpc.grpc.status_code = Empty, // to set on response
+        error.type = Empty, // to set on cancellation
     )
 }
 
+/// The timeout of the call set by the client, read from the header `grpc-timeout`
+/// (see [gRPC over HTTP2](https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-HTTP2.md#requests)).
+#[must_use]
+pub fn grpc_timeout(headers: &http::HeaderMap) -> Option<Duration> {
+    headers
+        .get("grpc-timeout")
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_grpc_timeout)
+}
+
+/// `TimeoutValue` (at most 8 digits) followed by `TimeoutUnit` (`H`, `M`, `S`, `m`, `u`, `n`)
+fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if !value.is_ascii() || value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    if !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount = amount.parse::<u64>().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 3600)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+fn duration_ms(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Record `rpc.grpc.deadline_exceeded` (the processing lasted more than the `timeout` of the call,
+/// see [`grpc_timeout`]), on response or cancellation, to diagnose the calls abandoned by the clients.
+pub fn update_span_from_deadline(span: &tracing::Span, timeout: Duration, elapsed: Duration) {
+    span.record("rpc.grpc.deadline_exceeded", elapsed > timeout);
+}
+
+/// Update the span when the processing of the request is cancelled (the future is dropped before completion),
+/// eg when the client cancels the call or when a timeout fires: the status is `CANCELLED`.
+pub fn update_span_from_cancellation(span: &tracing::Span, error_codes: GrpcErrorCodes) {
+    let status = GrpcCode::Cancelled as u16;
+    span.record("rpc.grpc.status_code", status);
+    if error_codes.contains(status) {
+        span.record("otel.status_code", "ERROR");
+        span.record("error.type", "cancelled");
+    }
+}
+
 fn update_span_from_error(span: &tracing::Span, error: &BoxError) {
     span.record("otel.status_code", "ERROR");
     span.record("rpc.grpc.status_code", 2);
-    span.record("exception.message", error.to_string());
-    error
-        .source()
-        .map(|s| span.record("exception.message", s.to_string()));
+    span.record(
+        "exception.message",
+        truncate_attribute_value(&error.to_string()).as_ref(),
+    );
+    error.source().map(|s| {
+        span.record(
+            "exception.message",
+            truncate_attribute_value(&s.to_string()).as_ref(),
+        )
+    });
 }
 
 pub fn update_span_from_response_or_error<B>(
     span: &tracing::Span,
     response: &Result<http::Response<B>, BoxError>,
+) {
+    update_span_from_response_or_error_with_error_codes(
+        span,
+        response,
+        GrpcErrorCodes::semconv(true),
+    );
+}
+
+/// Like [`update_span_from_response_or_error`] but with a custom set of codes considered as error
+pub fn update_span_from_response_or_error_with_error_codes<B>(
+    span: &tracing::Span,
+    response: &Result<http::Response<B>, BoxError>,
+    error_codes: GrpcErrorCodes,
 ) {
     match response {
         Ok(response) => {
-            grpc_update_span_from_response(span, response, true);
+            grpc_update_span_from_response_with_error_codes(span, response, error_codes);
         }
         Err(err) => {
             update_span_from_error(span, err);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("1H", Some(Duration::from_secs(3600)))]
+    #[case("2M", Some(Duration::from_secs(120)))]
+    #[case("5S", Some(Duration::from_secs(5)))]
+    #[case("250m", Some(Duration::from_millis(250)))]
+    #[case("99999999u", Some(Duration::from_micros(99_999_999)))]
+    #[case("10n", Some(Duration::from_nanos(10)))]
+    #[case("123456789m", None)]
+    #[case("5s", None)]
+    #[case("m", None)]
+    #[case("-5S", None)]
+    #[case("5é", None)]
+    fn test_parse_grpc_timeout(#[case] input: &str, #[case] expected: Option<Duration>) {
+        assert!(parse_grpc_timeout(input) == expected);
+    }
+}
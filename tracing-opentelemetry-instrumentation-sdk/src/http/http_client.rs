@@ -0,0 +1,179 @@
+use std::error::Error;
+
+use crate::http::{
+    http_flavor, http_host, http_method_for_span_name, http_method_with_extra_known,
+    inject_context, url_full, user_agent, QueryRedaction, HTTP_METHOD_OTHER,
+};
+use crate::{
+    find_context_from_tracing, is_instrumentation_suppressed, otel_trace_span,
+    truncate_attribute_value, PrivacyPolicy,
+};
+use tracing::field::Empty;
+
+/// Create the span for an outgoing http request (`SpanKind::Client`).
+///
+/// `url.full` is recorded after processing the query string with `query_redaction`
+/// (use `QueryRedaction::default()` to strip the query).
+/// see [semantic-conventions/.../http-spans.md#http-client](https://github.com/open-telemetry/semantic-conventions/blob/v1.25.0/docs/http/http-spans.md#http-client)
+///
+/// No span is created when the instrumentation is suppressed (see [`crate::with_suppressed_instrumentation`]).
+pub fn make_span_from_request<B>(
+    req: &http::Request<B>,
+    query_redaction: &QueryRedaction,
+) -> tracing::Span {
+    make_span_from_request_with_extra_known_methods(
+        req,
+        query_redaction,
+        &[],
+        &PrivacyPolicy::default(),
+    )
+}
+
+/// Like [`make_span_from_request`], but the `extra_known_methods` (eg `PURGE`, `REPORT`) are recorded as is
+/// into `http.request.method` (instead of `_OTHER`) and the user agent is recorded according to the `privacy` policy.
+pub fn make_span_from_request_with_extra_known_methods<B>(
+    req: &http::Request<B>,
+    query_redaction: &QueryRedaction,
+    extra_known_methods: &[http::Method],
+    privacy: &PrivacyPolicy,
+) -> tracing::Span {
+    if is_instrumentation_suppressed() {
+        return tracing::Span::none();
+    }
+    let http_method = http_method_with_extra_known(req.method(), extra_known_methods);
+    let http_method_original =
+        (http_method == HTTP_METHOD_OTHER).then(|| truncate_attribute_value(req.method().as_str()));
+    otel_trace_span!(
+        "HTTP request",
+        http.request.method = %http_method,
+        http.request.method_original = http_method_original.as_deref(),
+        network.protocol.version = %http_flavor(req.version()),
+        server.address = truncate_attribute_value(http_host(req)).as_ref(),
+        server.port = req.uri().port_u16(),
+        url.full = truncate_attribute_value(&url_full(req.uri(), query_redaction)).as_ref(),
+        user_agent.original = privacy.protect("user_agent.original", user_agent(req))
+            .as_deref()
+            .map(truncate_attribute_value)
+            .as_deref(),
+        http.response.status_code = Empty, // to set on response
+        otel.name = http_method_for_span_name(&http_method),
+        otel.kind = ?opentelemetry::trace::SpanKind::Client,
+        otel.status_code = Empty, // to set on response
+        exception.message = Empty, // to set on response
+    )
+}
+
+/// Create the span for an outgoing http request (see [`make_span_from_request`]) and inject its context
+/// into the headers of the request, so the called service continues the trace.
+///
+/// It's the recommended pattern for outbound calls (eg from a handler behind `OtelAxumLayer`, or a reverse proxy):
+///
+/// ```rust,ignore
+/// let span = http_client::make_span_and_inject_context(&mut request, &QueryRedaction::default());
+/// let response = send(request).instrument(span.clone()).await;
+/// http_client::update_span_from_response_or_error(&span, &response);
+/// ```
+pub fn make_span_and_inject_context<B>(
+    req: &mut http::Request<B>,
+    query_redaction: &QueryRedaction,
+) -> tracing::Span {
+    let span = make_span_from_request(req, query_redaction);
+    if !span.is_disabled() {
+        inject_context(&find_context_from_tracing(&span), req.headers_mut());
+    }
+    span
+}
+
+pub fn update_span_from_response<B>(span: &tracing::Span, response: &http::Response<B>) {
+    let status = response.status();
+    span.record("http.response.status_code", status.as_u16());
+
+    // see [http-spans.md#status](https://github.com/open-telemetry/semantic-conventions/blob/v1.25.0/docs/http/http-spans.md#status)
+    // For HTTP status codes in the 4xx range span status MUST be set to Error for SpanKind.CLIENT
+    if status.is_client_error() || status.is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+pub fn update_span_from_error<E>(span: &tracing::Span, error: &E)
+where
+    E: Error,
+{
+    span.record("otel.status_code", "ERROR");
+    span.record(
+        "exception.message",
+        truncate_attribute_value(&error.to_string()).as_ref(),
+    );
+    error.source().map(|s| {
+        span.record(
+            "exception.message",
+            truncate_attribute_value(&s.to_string()).as_ref(),
+        )
+    });
+}
+
+pub fn update_span_from_response_or_error<B, E>(
+    span: &tracing::Span,
+    response: &Result<http::Response<B>, E>,
+) where
+    E: Error,
+{
+    match response {
+        Ok(response) => {
+            update_span_from_response(span, response);
+        }
+        Err(err) => {
+            update_span_from_error(span, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn inject_the_context_of_the_client_span() {
+        // the same (W3C) global propagator as the other tests
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            let server = tracing::info_span!("server");
+            let _guard = server.enter();
+            let mut req = http::Request::get("http://example.org/users/42")
+                .body(())
+                .unwrap();
+
+            let span = make_span_and_inject_context(&mut req, &QueryRedaction::default());
+
+            let span_context = span.context().span().span_context().clone();
+            let parent_context = server.context().span().span_context().clone();
+            assert!(span_context.span_id() != parent_context.span_id());
+            assert!(span_context.trace_id() == parent_context.trace_id());
+            let_assert!(Some(traceparent) = req.headers().get("traceparent"));
+            assert!(
+                traceparent.to_str().ok()
+                    == Some(
+                        format!(
+                            "00-{}-{}-01",
+                            span_context.trace_id(),
+                            span_context.span_id()
+                        )
+                        .as_str()
+                    )
+            );
+        });
+    }
+}
@@ -1,7 +1,11 @@
+pub mod forwarded;
 pub mod grpc_client;
 pub mod grpc_server;
+pub mod http_client;
 pub mod http_server;
 mod opentelemety_http;
 
+mod force_trace;
 mod tools;
+pub use force_trace::*;
 pub use tools::*;
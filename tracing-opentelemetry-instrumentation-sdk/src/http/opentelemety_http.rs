@@ -23,10 +23,12 @@ impl<'a> Extractor for HeaderExtractor<'a> {
     }
 
     /// Collect all the keys from the `HeaderMap`.
+    ///
+    /// Only called by the propagators that scan the keys (eg the baggage of jaeger `uberctx-*`),
+    /// the `Vec` (required by the trait) is allocated once with the number of distinct keys.
     fn keys(&self) -> Vec<&str> {
-        self.0
-            .keys()
-            .map(http::HeaderName::as_str)
-            .collect::<Vec<_>>()
+        let mut keys = Vec::with_capacity(self.0.keys_len());
+        keys.extend(self.0.keys().map(http::HeaderName::as_str));
+        keys
     }
 }
@@ -0,0 +1,63 @@
+//! Helpers to trace the runs of scheduled jobs (cron, periodic tasks), with the same conventions
+//! across the services.
+//!
+//! ```rust
+//! use tracing::Instrument;
+//! use tracing_opentelemetry_instrumentation_sdk::job;
+//!
+//! async fn purge_sessions() -> Result<u64, std::io::Error> {
+//!     Ok(0)
+//! }
+//!
+//! # async fn run() {
+//! let span = job::make_scheduled_job_span("purge_sessions", "0 */5 * * * *");
+//! span.record("job.run_id", "2024-01-01T00:05:00Z");
+//! let result = purge_sessions().instrument(span.clone()).await;
+//! job::update_span_from_job_result(&span, &result);
+//! # }
+//! ```
+use std::error::Error;
+
+use tracing::field::Empty;
+
+use crate::{otel_trace_span, truncate_attribute_value};
+
+/// Create the span of a run of the scheduled job `job_name` (triggered by `schedule`, eg a cron expression).
+///
+/// The span is a root span (a new trace per run, not a child of the current span), named `job_name`, with:
+/// - `job.name` & `code.function` = `job_name`
+/// - `job.schedule` = `schedule`
+/// - `job.run_id` to record by the caller (eg the id of the run provided by the scheduler)
+/// - `error.type`, `exception.message` & `otel.status_code` recorded by [`update_span_from_job_result`]
+#[must_use]
+pub fn make_scheduled_job_span(job_name: &str, schedule: &str) -> tracing::Span {
+    otel_trace_span!(
+        parent: None,
+        "scheduled_job",
+        otel.name = job_name,
+        otel.kind = ?opentelemetry::trace::SpanKind::Internal,
+        otel.status_code = Empty,
+        job.name = job_name,
+        job.schedule = schedule,
+        job.run_id = Empty, // to set by the caller
+        code.function = job_name,
+        error.type = Empty,
+        exception.message = Empty,
+    )
+}
+
+/// Update the span (created by [`make_scheduled_job_span`]) from the result of the run:
+/// on error, the status is `ERROR` with `error.type` (the type of the error) & `exception.message`.
+pub fn update_span_from_job_result<T, E>(span: &tracing::Span, result: &Result<T, E>)
+where
+    E: Error,
+{
+    if let Err(err) = result {
+        span.record("otel.status_code", "ERROR");
+        span.record("error.type", std::any::type_name::<E>());
+        span.record(
+            "exception.message",
+            truncate_attribute_value(&err.to_string()).as_ref(),
+        );
+    }
+}
@@ -5,9 +5,31 @@
 #![allow(clippy::module_name_repetitions)]
 #![doc = include_str!("../README.md")]
 
+mod attribute_limit;
+mod dropped_spans;
+mod force_sampling;
 #[cfg(feature = "http")]
 pub mod http;
+pub mod job;
+mod privacy;
+mod recording_gate;
 mod span_type;
+#[cfg(feature = "tokio")]
+pub mod task;
+mod trace_id_layer;
+#[cfg(feature = "valuable")]
+mod valuable_attribute;
+
+pub use attribute_limit::{
+    max_attribute_len, set_max_attribute_len, truncate_attribute_value, truncate_to,
+};
+pub use dropped_spans::{DroppedSpanHook, DroppedSpanReason};
+pub use force_sampling::{force_sampling, is_sampling_forced, FORCED_SAMPLING_ATTRIBUTE};
+pub use privacy::{PrivacyMode, PrivacyPolicy, DEFAULT_PROTECTED_KEYS};
+pub use recording_gate::{mark_span_for_recording_gate, RECORDING_GATE_ATTRIBUTE};
+pub use trace_id_layer::{find_recorded_trace_id, TraceIdLayer, TRACE_ID_FIELD};
+#[cfg(feature = "valuable")]
+pub use valuable_attribute::{record_valuable, valuable_to_attributes};
 
 use opentelemetry::Context;
 
@@ -60,6 +82,61 @@ macro_rules! otel_trace_span {
     };
 }
 
+/// Constructs a span for the target `TRACING_TARGET` with an explicit level
+/// (instead of the compile-time `TRACING_LEVEL` used by [`otel_trace_span!`]).
+///
+/// Useful for the spans created by the application, to be enabled by the same filter
+/// configuration as its logs (eg `otel::tracing=info`), without toggling the feature `tracing_level_info`.
+///
+/// ```rust
+/// use tracing_opentelemetry_instrumentation_sdk::otel_span;
+///
+/// let span = otel_span!(tracing::Level::INFO, "DB request", db.system = "postgresql");
+/// let child = otel_span!(tracing::Level::DEBUG, parent: &span, "DB fetch");
+/// ```
+#[macro_export]
+macro_rules! otel_span {
+    ($lvl:expr, parent: $parent:expr, $name:expr, $($field:tt)*) => {
+        tracing::span!(
+            target: $crate::TRACING_TARGET,
+            parent: $parent,
+            $lvl,
+            $name,
+            $($field)*
+        )
+    };
+    ($lvl:expr, parent: $parent:expr, $name:expr) => {
+        $crate::otel_span!($lvl, parent: $parent, $name,)
+    };
+    ($lvl:expr, $name:expr, $($field:tt)*) => {
+        tracing::span!(
+            target: $crate::TRACING_TARGET,
+            $lvl,
+            $name,
+            $($field)*
+        )
+    };
+    ($lvl:expr, $name:expr) => {
+        $crate::otel_span!($lvl, $name,)
+    };
+}
+
+/// Constructs a span for the target `TRACING_TARGET` with the level `INFO` (see [`otel_span!`]).
+#[macro_export]
+macro_rules! otel_info_span {
+    ($($arg:tt)*) => {
+        $crate::otel_span!(tracing::Level::INFO, $($arg)*)
+    };
+}
+
+/// Constructs a span for the target `TRACING_TARGET` with the level `DEBUG` (see [`otel_span!`]).
+#[macro_export]
+macro_rules! otel_debug_span {
+    ($($arg:tt)*) => {
+        $crate::otel_span!(tracing::Level::DEBUG, $($arg)*)
+    };
+}
+
 #[inline]
 #[must_use]
 pub fn find_current_context() -> Context {
@@ -69,9 +146,65 @@ pub fn find_current_context() -> Context {
     tracing::Span::current().context()
 }
 
+/// The context to inject into an outgoing call (eg with [`http::inject_context`]):
+/// the context of the current tracing's span, completed with the active baggage
+/// (from the current opentelemetry `Context`) and the suppression flag
+/// (see [`with_suppressed_instrumentation`]).
+#[must_use]
+pub fn find_current_context_for_export() -> Context {
+    use opentelemetry::baggage::BaggageExt;
+
+    let current = Context::current();
+    let mut context = find_current_context();
+    if context.baggage().is_empty() && !current.baggage().is_empty() {
+        context = context.with_baggage(
+            current
+                .baggage()
+                .iter()
+                .map(|(k, (v, _))| opentelemetry::KeyValue::new(k.clone(), v.clone())),
+        );
+    }
+    if is_instrumentation_suppressed_in(&current) {
+        context = context.with_value(SuppressInstrumentation);
+    }
+    context
+}
+
+#[derive(Clone, Copy, Debug)]
+struct SuppressInstrumentation;
+
+/// Run `f` with the instrumentation suppressed: the helpers & layers for outgoing calls
+/// don't create span (nor propagate the context) while `f` runs.
+///
+/// It's used to prevent the internal calls of the exporters to be traced recursively.
+pub fn with_suppressed_instrumentation<T>(f: impl FnOnce() -> T) -> T {
+    let _guard = suppressed_instrumentation_context().attach();
+    f()
+}
+
+/// The current opentelemetry `Context` with the instrumentation suppressed,
+/// to attach to a future (eg with `opentelemetry::trace::FutureExt::with_context`).
+#[must_use]
+pub fn suppressed_instrumentation_context() -> Context {
+    Context::current().with_value(SuppressInstrumentation)
+}
+
+/// Is the instrumentation suppressed in the current opentelemetry `Context` (see [`with_suppressed_instrumentation`])
+#[must_use]
+pub fn is_instrumentation_suppressed() -> bool {
+    is_instrumentation_suppressed_in(&Context::current())
+}
+
+fn is_instrumentation_suppressed_in(context: &Context) -> bool {
+    context.get::<SuppressInstrumentation>().is_some()
+}
+
 /// Search the current opentelemetry trace id into the Context from the current tracing'span.
 /// This function can be used to report the trace id into the error message send back to user.
 ///
+/// Without the opentelemetry layer (eg in the tests, or with a minimal setup), fallback to the field `trace_id`
+/// recorded on the current span (or an ancestor), when the [`TraceIdLayer`] is installed.
+///
 /// ```rust
 /// let trace_id = tracing_opentelemetry_instrumentation_sdk::find_current_trace_id();
 /// // json!({ "error" :  "xxxxxx", "trace_id": trace_id})
@@ -81,6 +214,7 @@ pub fn find_current_context() -> Context {
 #[must_use]
 pub fn find_current_trace_id() -> Option<String> {
     find_trace_id(&find_current_context())
+        .or_else(|| find_recorded_trace_id(&tracing::Span::current()))
 }
 
 #[inline]
@@ -159,3 +293,39 @@ pub fn find_span_id(context: &Context) -> Option<String> {
 // }
 
 pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use opentelemetry::baggage::BaggageExt;
+    use opentelemetry::KeyValue;
+
+    #[test]
+    fn suppressed_instrumentation_is_scoped() {
+        assert!(!is_instrumentation_suppressed());
+        with_suppressed_instrumentation(|| {
+            assert!(is_instrumentation_suppressed());
+            assert!(is_instrumentation_suppressed_in(
+                &find_current_context_for_export()
+            ));
+        });
+        assert!(!is_instrumentation_suppressed());
+    }
+
+    #[test]
+    fn otel_span_with_explicit_level() {
+        // without subscriber, the spans are disabled, it only checks the syntax supported by the macros
+        let span = otel_info_span!("parent", db.system = "postgresql", otel.name = "SELECT");
+        let _child = otel_debug_span!(parent: &span, "child");
+        let _other = otel_span!(tracing::Level::WARN, "other");
+        assert!(span.is_disabled());
+    }
+
+    #[test]
+    fn context_for_export_includes_baggage() {
+        let _guard = Context::current_with_baggage([KeyValue::new("tenant", "acme")]).attach();
+        let context = find_current_context_for_export();
+        assert!(context.baggage().get("tenant") == Some(&"acme".into()));
+    }
+}
@@ -0,0 +1,141 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use sha2::{Digest, Sha256};
+
+/// The attributes (identifying a person) protected by default by the [`PrivacyPolicy`]
+pub const DEFAULT_PROTECTED_KEYS: &[&str] =
+    &["enduser.id", "http.user_agent", "user_agent.original"];
+
+/// How the protected attributes are recorded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PrivacyMode {
+    /// recorded as is
+    #[default]
+    Off,
+    /// replaced by the hex of the SHA-256 of the `salt` + the value, to correlate without storing the raw value
+    /// (the salt should be kept secret, to prevent dictionary attacks)
+    Hash { salt: String },
+    /// not recorded
+    Drop,
+}
+
+/// Policy to sanitize the attributes identifying a person (`enduser.id`, user agent)
+/// recorded by the layers (eg `OtelAxumLayer::with_privacy_mode`) & the span helpers.
+///
+/// ```rust
+/// use tracing_opentelemetry_instrumentation_sdk::{PrivacyMode, PrivacyPolicy};
+///
+/// let policy = PrivacyPolicy::new(PrivacyMode::Hash {
+///     salt: "a-secret-salt".to_string(),
+/// });
+/// assert_ne!(policy.protect("enduser.id", "alice").as_deref(), Some("alice"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivacyPolicy {
+    mode: PrivacyMode,
+    keys: Vec<Cow<'static, str>>,
+}
+
+impl Default for PrivacyPolicy {
+    fn default() -> Self {
+        Self::new(PrivacyMode::default())
+    }
+}
+
+impl PrivacyPolicy {
+    /// A policy applied to the [`DEFAULT_PROTECTED_KEYS`]
+    #[must_use]
+    pub fn new(mode: PrivacyMode) -> Self {
+        Self {
+            mode,
+            keys: DEFAULT_PROTECTED_KEYS
+                .iter()
+                .map(|k| Cow::Borrowed(*k))
+                .collect(),
+        }
+    }
+
+    /// Replace the keys of the protected attributes
+    #[must_use]
+    pub fn with_keys<K>(self, keys: impl IntoIterator<Item = K>) -> Self
+    where
+        K: Into<Cow<'static, str>>,
+    {
+        Self {
+            keys: keys.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn mode(&self) -> &PrivacyMode {
+        &self.mode
+    }
+
+    /// The value to record for the attribute `key`, `None` if it should not be recorded
+    #[must_use]
+    pub fn protect<'a>(&self, key: &str, value: &'a str) -> Option<Cow<'a, str>> {
+        if !self.keys.iter().any(|k| k == key) {
+            return Some(Cow::Borrowed(value));
+        }
+        match &self.mode {
+            PrivacyMode::Off => Some(Cow::Borrowed(value)),
+            PrivacyMode::Hash { salt } => Some(Cow::Owned(salted_sha256_hex(salt, value))),
+            PrivacyMode::Drop => None,
+        }
+    }
+}
+
+/// The hex of the SHA-256 of the `salt` + the `value`
+pub(crate) fn salted_sha256_hex(salt: &str, value: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(salt.as_bytes())
+        .chain_update(value.as_bytes())
+        .finalize();
+    digest
+        .iter()
+        .fold(String::with_capacity(digest.len() * 2), |mut hex, b| {
+            let _ = write!(hex, "{b:02x}");
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(PrivacyMode::Off, "enduser.id", Some("alice"))]
+    #[case(PrivacyMode::Drop, "enduser.id", None)]
+    #[case(PrivacyMode::Drop, "http.route", Some("alice"))]
+    #[case(PrivacyMode::Hash { salt: "salt".to_string() }, "http.route", Some("alice"))]
+    fn protect_value(#[case] mode: PrivacyMode, #[case] key: &str, #[case] expected: Option<&str>) {
+        let policy = PrivacyPolicy::new(mode);
+        assert!(policy.protect(key, "alice").as_deref() == expected);
+    }
+
+    #[test]
+    fn hash_value_with_salt() {
+        let hash = |salt: &str| {
+            PrivacyPolicy::new(PrivacyMode::Hash {
+                salt: salt.to_string(),
+            })
+        };
+        let policy = hash("salt");
+        let hashed = policy.protect("enduser.id", "alice");
+        assert!(hashed == policy.protect("enduser.id", "alice"));
+        assert!(hashed.as_deref().map(str::len) == Some(64));
+        assert!(hashed.as_deref() != Some("alice"));
+        assert!(hashed != hash("pepper").protect("enduser.id", "alice"));
+    }
+
+    #[test]
+    fn protect_custom_keys() {
+        let policy = PrivacyPolicy::new(PrivacyMode::Drop).with_keys(["user.email"]);
+        assert!(policy.protect("user.email", "alice@example.com").is_none());
+        assert!(policy.protect("enduser.id", "alice").is_some());
+    }
+}
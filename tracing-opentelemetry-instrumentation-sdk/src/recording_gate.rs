@@ -0,0 +1,26 @@
+use std::time::Duration;
+
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// The attribute set on the (local root) spans submitted to the recording gate, with the latency threshold
+/// in milliseconds.
+///
+/// It is consumed (and removed) by the `RecordingGateSpanProcessor` of `init-tracing-opentelemetry`:
+/// the spans of the trace are buffered until the end of the marked span, and exported only if it
+/// ends with an error or lasts at least the threshold.
+pub const RECORDING_GATE_ATTRIBUTE: &str = "otel.recording_gate.latency_threshold_ms";
+
+/// Submit the `span` (and its descendants) to the recording gate, see [`RECORDING_GATE_ATTRIBUTE`].
+///
+/// The span is created & recorded as usual (so the context is propagated), the decision to export
+/// is made by the span processor when the span ends. Without the processor (not enabled by the
+/// `TracingConfig` of `init-tracing-opentelemetry`), the span is exported as usual, with the attribute.
+pub fn mark_span_for_recording_gate(span: &tracing::Span, latency_threshold: Duration) {
+    if span.is_disabled() {
+        return;
+    }
+    span.set_attribute(
+        RECORDING_GATE_ATTRIBUTE,
+        i64::try_from(latency_threshold.as_millis()).unwrap_or(i64::MAX),
+    );
+}
@@ -0,0 +1,139 @@
+//! Helpers to keep the trace across the boundaries of the tasks (eg `tokio::task::spawn_blocking`).
+use std::thread::ThreadId;
+
+use tracing::field::Empty;
+
+use crate::{find_context_from_tracing, otel_trace_span};
+
+/// Like [`tokio::task::spawn_blocking`], but `f` runs into a child span (of the current span) named `name`,
+/// with the `OpenTelemetry` context attached (so [`opentelemetry::Context::current`] and the baggage are available).
+///
+/// The span records `code.function` (= `name`), `thread.id` & `thread.name` of the blocking thread
+/// (`thread.id` is the id of the thread into the process, the same as the one recorded by `tracing-opentelemetry`
+/// on the spans created by the thread, not the id of the OS).
+///
+/// ```rust,ignore
+/// let digest = spawn_blocking_instrumented("compute_digest", move || sha256(&content)).await?;
+/// ```
+pub fn spawn_blocking_instrumented<F, R>(name: &'static str, f: F) -> tokio::task::JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let span = otel_trace_span!(
+        "spawn_blocking",
+        otel.name = name,
+        code.function = name,
+        thread.id = Empty,   // to set on the blocking thread
+        thread.name = Empty, // to set on the blocking thread
+    );
+    tokio::task::spawn_blocking(move || {
+        let thread = std::thread::current();
+        span.record("thread.id", thread_id());
+        if let Some(thread_name) = thread.name() {
+            span.record("thread.name", thread_name);
+        }
+        let _cx_guard = find_context_from_tracing(&span).attach();
+        span.in_scope(f)
+    })
+}
+
+/// The numeric id of the current thread, read like `tracing-opentelemetry` (for its `thread.id`)
+/// from the `Debug` of `ThreadId` (`ThreadId::as_u64` is unstable), once per thread
+fn thread_id() -> i64 {
+    thread_local! {
+        static THREAD_ID: i64 = thread_id_integer(std::thread::current().id());
+    }
+    THREAD_ID.with(|id| *id)
+}
+
+fn thread_id_integer(id: ThreadId) -> i64 {
+    format!("{id:?}")
+        .trim_start_matches("ThreadId(")
+        .trim_end_matches(')')
+        .parse()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{assert, let_assert};
+    use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn assign_an_id_per_thread() {
+        let id = thread_id();
+        assert!(thread_id() == id);
+        let_assert!(Ok(other) = std::thread::spawn(thread_id).join());
+        assert!(other != id);
+    }
+
+    #[test]
+    fn run_closure_into_child_span_with_context_attached() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let_assert!(Ok(runtime) = tokio::runtime::Builder::new_multi_thread().build());
+        let (caller_cx, (closure_cx, closure_thread_id)) =
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info_span!("caller").in_scope(|| {
+                    let caller_cx = crate::find_current_context().span().span_context().clone();
+                    let_assert!(
+                        Ok(closure_cx) = runtime.block_on(async {
+                            spawn_blocking_instrumented("on_blocking_thread", || {
+                                let cx = opentelemetry::Context::current()
+                                    .span()
+                                    .span_context()
+                                    .clone();
+                                (cx, thread_id())
+                            })
+                            .await
+                        })
+                    );
+                    (caller_cx, closure_cx)
+                })
+            });
+
+        let_assert!(Ok(spans) = exporter.get_finished_spans());
+        let_assert!(Some(child) = spans.iter().find(|s| s.name == "on_blocking_thread"));
+        assert!(child.parent_span_id == caller_cx.span_id());
+        assert!(child.span_context.trace_id() == caller_cx.trace_id());
+        // the context of the child span is the current one into the closure
+        assert!(closure_cx.span_id() == child.span_context.span_id());
+        assert!(closure_cx.trace_id() == caller_cx.trace_id());
+        // the same `thread.id` as the one of `tracing-opentelemetry`: recorded by `tracing-opentelemetry`
+        // at the creation of the span (on the caller thread), then recorded from the blocking thread
+        let thread_ids = child
+            .attributes
+            .iter()
+            .filter(|kv| kv.key.as_str() == "thread.id")
+            .map(|kv| kv.value.clone())
+            .collect::<Vec<_>>();
+        assert!(
+            thread_ids
+                == vec![
+                    opentelemetry::Value::I64(thread_id()),
+                    opentelemetry::Value::I64(closure_thread_id)
+                ]
+        );
+        assert!(closure_thread_id != thread_id());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn run_closure_on_blocking_thread() {
+        let caller = std::thread::current().id();
+        let_assert!(
+            Ok(thread) =
+                spawn_blocking_instrumented("on_blocking_thread", || std::thread::current().id())
+                    .await
+        );
+        assert!(thread != caller);
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use tracing_subscriber::Layer;
+
+/// Name of the span's field read by [`TraceIdLayer`]
+pub const TRACE_ID_FIELD: &str = "trace_id";
+
+/// The value of the field `trace_id` of a span, stored into its extensions by [`TraceIdLayer`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecordedTraceId(String);
+
+/// Lightweight `tracing_subscriber::Layer` that stores the value of the field `trace_id` (when recorded on a span)
+/// into the extensions of the span, so [`find_current_trace_id`](crate::find_current_trace_id) can find it without
+/// the opentelemetry layer (eg in the tests, or with a minimal setup that only logs).
+///
+/// ```rust
+/// use tracing_opentelemetry_instrumentation_sdk::{find_current_trace_id, TraceIdLayer};
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let subscriber = tracing_subscriber::registry().with(TraceIdLayer);
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("request", trace_id = "4bf92f3577b34da6a3ce929d0e0e4736");
+///     let _guard = span.enter();
+///     assert_eq!(
+///         find_current_trace_id().as_deref(),
+///         Some("4bf92f3577b34da6a3ce929d0e0e4736")
+///     );
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceIdLayer;
+
+impl TraceIdLayer {
+    fn store<S>(id: &Id, trace_id: Option<String>, ctx: &Context<'_, S>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        if let (Some(trace_id), Some(span)) = (trace_id, ctx.span(id)) {
+            span.extensions_mut().replace(RecordedTraceId(trace_id));
+        }
+    }
+}
+
+impl<S> Layer<S> for TraceIdLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = TraceIdVisitor(None);
+        attrs.record(&mut visitor);
+        Self::store(id, visitor.0, &ctx);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = TraceIdVisitor(None);
+        values.record(&mut visitor);
+        Self::store(id, visitor.0, &ctx);
+    }
+}
+
+struct TraceIdVisitor(Option<String>);
+
+impl Visit for TraceIdVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == TRACE_ID_FIELD && !value.is_empty() {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == TRACE_ID_FIELD {
+            self.0 = Some(format!("{value:?}")).filter(|v| !v.is_empty());
+        }
+    }
+}
+
+/// The `trace_id` recorded on the `span` (or on its nearest ancestor), stored by [`TraceIdLayer`].
+///
+/// `None` if the `TraceIdLayer` is not installed (on a subscriber based on `tracing_subscriber::Registry`).
+#[must_use]
+pub fn find_recorded_trace_id(span: &tracing::Span) -> Option<String> {
+    let id = span.id()?;
+    tracing::dispatcher::get_default(|dispatch| {
+        let registry = dispatch.downcast_ref::<Registry>()?;
+        registry.span(&id)?.scope().find_map(|span| {
+            span.extensions()
+                .get::<RecordedTraceId>()
+                .map(|t| t.0.clone())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use tracing::field::Empty;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn find_the_trace_id_recorded_on_an_ancestor() {
+        let subscriber = tracing_subscriber::registry().with(TraceIdLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            let root = tracing::info_span!("request", trace_id = Empty);
+            assert!(find_recorded_trace_id(&root).is_none());
+            root.record("trace_id", "4bf92f3577b34da6a3ce929d0e0e4736");
+            let child = tracing::info_span!(parent: &root, "db");
+            assert!(
+                find_recorded_trace_id(&child).as_deref()
+                    == Some("4bf92f3577b34da6a3ce929d0e0e4736")
+            );
+            let _guard = child.enter();
+            assert!(
+                crate::find_current_trace_id().as_deref()
+                    == Some("4bf92f3577b34da6a3ce929d0e0e4736")
+            );
+        });
+    }
+
+    #[test]
+    fn find_nothing_without_the_layer() {
+        tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+            let span =
+                tracing::info_span!("request", trace_id = "4bf92f3577b34da6a3ce929d0e0e4736");
+            assert!(find_recorded_trace_id(&span).is_none());
+        });
+    }
+}
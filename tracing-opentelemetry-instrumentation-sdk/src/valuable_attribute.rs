@@ -0,0 +1,313 @@
+use opentelemetry::{Array, KeyValue, StringValue, Value};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use valuable::{NamedValues, Valuable, Visit};
+
+use crate::truncate_attribute_value;
+
+/// Record a structured value (struct, map, list, enum,...) as attributes of the `span`,
+/// see [`valuable_to_attributes`] for the conversion.
+///
+/// Useful into the hooks of the middlewares (that receive the span), to not be limited to the
+/// strings & numbers of the `tracing`'s fields.
+///
+/// ```rust
+/// use std::collections::BTreeMap;
+/// use tracing_opentelemetry_instrumentation_sdk::{otel_trace_span, record_valuable};
+///
+/// let span = otel_trace_span!("HTTP request");
+/// let features = BTreeMap::from([("beta", true), ("dark_mode", false)]);
+/// // record `app.features.beta = true` & `app.features.dark_mode = false`
+/// record_valuable(&span, "app.features", &features);
+/// // record `app.roles = ["admin", "dev"]`
+/// record_valuable(&span, "app.roles", &vec!["admin", "dev"]);
+/// ```
+pub fn record_valuable(span: &tracing::Span, key: &str, value: &dyn Valuable) {
+    if span.is_disabled() {
+        return;
+    }
+    for attribute in valuable_to_attributes(key, value) {
+        span.set_attribute(attribute.key, attribute.value);
+    }
+}
+
+/// Convert a structured value into OpenTelemetry's attributes (prefixed by `key`).
+///
+/// Span's attributes can not be nested, so:
+///
+/// - the primitives are recorded as is (`key = value`), the unsigned integers that don't fit into `i64`
+///   and the chars, paths & errors as strings (truncated to the [`crate::max_attribute_len`])
+/// - the lists (& tuples) of primitives of the same type as an array (`key = [v0, v1]`),
+///   the others are flattened by index (`key.0 = v0`, `key.1.field = v1.field`)
+/// - the maps & structs are flattened by name of entry / field (`key.name = value`),
+///   the entries with a key that is not a primitive are ignored
+/// - the enums as the name of the variant (`key = "Variant"`) + the flattened fields of the variant
+/// - the unit, `None` & empty collections are not recorded
+#[must_use]
+pub fn valuable_to_attributes(key: &str, value: &dyn Valuable) -> Vec<KeyValue> {
+    let mut attributes = Vec::new();
+    flatten(key, value.as_value(), &mut attributes);
+    attributes
+}
+
+fn flatten(key: &str, value: valuable::Value<'_>, attributes: &mut Vec<KeyValue>) {
+    if let Some(value) = to_primitive(value) {
+        attributes.push(KeyValue::new(key.to_owned(), value));
+        return;
+    }
+    match value {
+        valuable::Value::Listable(listable) => {
+            let mut items = Items::new(key, attributes);
+            listable.visit(&mut items);
+            items.finish();
+        }
+        valuable::Value::Tuplable(tuplable) => {
+            let mut items = Items::new(key, attributes);
+            tuplable.visit(&mut items);
+            items.finish();
+        }
+        valuable::Value::Mappable(mappable) => mappable.visit(&mut Fields { key, attributes }),
+        valuable::Value::Structable(structable) => {
+            structable.visit(&mut Fields { key, attributes });
+        }
+        valuable::Value::Enumerable(enumerable) => {
+            attributes.push(KeyValue::new(
+                key.to_owned(),
+                enumerable.variant().name().to_owned(),
+            ));
+            enumerable.visit(&mut Fields { key, attributes });
+        }
+        _ => {}
+    }
+}
+
+fn to_primitive(value: valuable::Value<'_>) -> Option<Value> {
+    use valuable::Value as V;
+
+    let value = match value {
+        V::Bool(v) => Value::Bool(v),
+        V::F32(v) => Value::F64(f64::from(v)),
+        V::F64(v) => Value::F64(v),
+        V::I8(v) => Value::I64(i64::from(v)),
+        V::I16(v) => Value::I64(i64::from(v)),
+        V::I32(v) => Value::I64(i64::from(v)),
+        V::I64(v) => Value::I64(v),
+        V::U8(v) => Value::I64(i64::from(v)),
+        V::U16(v) => Value::I64(i64::from(v)),
+        V::U32(v) => Value::I64(i64::from(v)),
+        V::I128(v) => integer_or_string(v),
+        V::Isize(v) => integer_or_string(v),
+        V::U64(v) => integer_or_string(v),
+        V::U128(v) => integer_or_string(v),
+        V::Usize(v) => integer_or_string(v),
+        V::Char(v) => Value::from(v.to_string()),
+        V::String(v) => Value::from(truncate_attribute_value(v).into_owned()),
+        V::Path(v) => Value::from(truncate_attribute_value(&v.display().to_string()).into_owned()),
+        V::Error(v) => Value::from(truncate_attribute_value(&v.to_string()).into_owned()),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn integer_or_string<T>(v: T) -> Value
+where
+    T: TryInto<i64> + ToString + Copy,
+{
+    v.try_into()
+        .map_or_else(|_| Value::from(v.to_string()), Value::I64)
+}
+
+/// Visit the items of a list or a tuple, to record them as an array when possible.
+struct Items<'a> {
+    key: &'a str,
+    attributes: &'a mut Vec<KeyValue>,
+    index: usize,
+    /// the items while they are all primitives (candidates for an array)
+    primitives: Option<Vec<Value>>,
+}
+
+impl<'a> Items<'a> {
+    fn new(key: &'a str, attributes: &'a mut Vec<KeyValue>) -> Self {
+        Items {
+            key,
+            attributes,
+            index: 0,
+            primitives: Some(Vec::new()),
+        }
+    }
+
+    fn push_indexed(&mut self, index: usize, value: Value) {
+        self.attributes
+            .push(KeyValue::new(format!("{}.{index}", self.key), value));
+    }
+
+    /// Record the primitives collected so far by index, the list is not an array.
+    fn flush_indexed(&mut self) {
+        for (index, value) in self
+            .primitives
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+        {
+            self.push_indexed(index, value);
+        }
+    }
+
+    fn finish(mut self) {
+        let Some(values) = self.primitives.take() else {
+            return;
+        };
+        if let Some(array) = to_array(&values) {
+            self.attributes
+                .push(KeyValue::new(self.key.to_owned(), Value::Array(array)));
+        } else {
+            self.primitives = Some(values);
+            self.flush_indexed();
+        }
+    }
+}
+
+impl Visit for Items<'_> {
+    fn visit_value(&mut self, value: valuable::Value<'_>) {
+        match (to_primitive(value), self.primitives.as_mut()) {
+            (Some(primitive), Some(primitives)) => primitives.push(primitive),
+            (Some(primitive), None) => self.push_indexed(self.index, primitive),
+            (None, _) => {
+                self.flush_indexed();
+                flatten(
+                    &format!("{}.{}", self.key, self.index),
+                    value,
+                    self.attributes,
+                );
+            }
+        }
+        self.index += 1;
+    }
+
+    fn visit_unnamed_fields(&mut self, values: &[valuable::Value<'_>]) {
+        for value in values {
+            self.visit_value(*value);
+        }
+    }
+}
+
+/// An array if all the values have the same type.
+fn to_array(values: &[Value]) -> Option<Array> {
+    fn collect<T>(values: &[Value], f: impl Fn(&Value) -> Option<T>) -> Option<Vec<T>> {
+        values.iter().map(f).collect()
+    }
+
+    let array = match values.first()? {
+        Value::Bool(_) => Array::Bool(collect(values, |v| match v {
+            Value::Bool(v) => Some(*v),
+            _ => None,
+        })?),
+        Value::I64(_) => Array::I64(collect(values, |v| match v {
+            Value::I64(v) => Some(*v),
+            _ => None,
+        })?),
+        Value::F64(_) => Array::F64(collect(values, |v| match v {
+            Value::F64(v) => Some(*v),
+            _ => None,
+        })?),
+        Value::String(_) => Array::String(collect(values, |v| match v {
+            Value::String(v) => Some(StringValue::from(v.as_str().to_owned())),
+            _ => None,
+        })?),
+        _ => return None,
+    };
+    Some(array)
+}
+
+/// Visit the fields of a struct, an enum's variant or the entries of a map, to flatten them under the `key`.
+struct Fields<'a> {
+    key: &'a str,
+    attributes: &'a mut Vec<KeyValue>,
+}
+
+impl Visit for Fields<'_> {
+    fn visit_value(&mut self, _value: valuable::Value<'_>) {}
+
+    fn visit_named_fields(&mut self, named_values: &NamedValues<'_>) {
+        for (field, value) in named_values {
+            flatten(
+                &format!("{}.{}", self.key, field.name()),
+                *value,
+                self.attributes,
+            );
+        }
+    }
+
+    fn visit_unnamed_fields(&mut self, values: &[valuable::Value<'_>]) {
+        for (index, value) in values.iter().enumerate() {
+            flatten(&format!("{}.{index}", self.key), *value, self.attributes);
+        }
+    }
+
+    fn visit_entry(&mut self, key: valuable::Value<'_>, value: valuable::Value<'_>) {
+        if let Some(name) = to_primitive(key) {
+            flatten(
+                &format!("{}.{}", self.key, name.as_str()),
+                value,
+                self.attributes,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use std::collections::BTreeMap;
+
+    fn attributes(value: &dyn Valuable) -> Vec<(String, String)> {
+        valuable_to_attributes("app", value)
+            .into_iter()
+            .map(|kv| (kv.key.to_string(), format!("{:?}", kv.value)))
+            .collect()
+    }
+
+    fn expected(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn primitives_are_recorded_as_is() {
+        check!(attributes(&42_u8) == expected(&[("app", "I64(42)")]));
+        check!(attributes(&true) == expected(&[("app", "Bool(true)")]));
+        check!(attributes(&"hello") == expected(&[("app", "String(Owned(\"hello\"))")]));
+        check!(
+            attributes(&u64::MAX)
+                == expected(&[("app", "String(Owned(\"18446744073709551615\"))")])
+        );
+    }
+
+    #[test]
+    fn list_of_same_primitives_is_an_array() {
+        check!(attributes(&vec![1_i32, 2, 3]) == expected(&[("app", "Array(I64([1, 2, 3]))")]));
+        check!(attributes(&Vec::<i32>::new()).is_empty());
+    }
+
+    #[test]
+    fn list_of_mixed_values_is_flattened_by_index() {
+        let value = (1_i32, "two", vec![3.0_f64]);
+        check!(
+            attributes(&value)
+                == expected(&[
+                    ("app.0", "I64(1)"),
+                    ("app.1", "String(Owned(\"two\"))"),
+                    ("app.2", "Array(F64([3.0]))")
+                ])
+        );
+    }
+
+    #[test]
+    fn map_is_flattened_by_key() {
+        let value = BTreeMap::from([("a", Some(1_i64)), ("b", None), ("c", Some(3))]);
+        check!(attributes(&value) == expected(&[("app.a", "I64(1)"), ("app.c", "I64(3)")]));
+    }
+}